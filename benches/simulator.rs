@@ -0,0 +1,71 @@
+//! Wall-clock benchmarks: `cargo bench` (add `--features parallel` for the parallel step).
+//! Each case runs a few times and reports its fastest run.
+
+use multicore_simulator::cache::CacheConfig;
+use multicore_simulator::memory::MemoryConfig;
+use multicore_simulator::simulator::Simulator;
+use multicore_simulator::workload::{build_workload, WorkloadConfig};
+use std::time::{Duration, Instant};
+
+const RUNS: usize = 5;
+
+/// Fastest of `RUNS` runs of `build` then `run_to_completion`.
+fn time(mut build: impl FnMut() -> Simulator) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let mut sim = build();
+            let started = Instant::now();
+            sim.run_to_completion();
+            started.elapsed()
+        })
+        .min()
+        .expect("RUNS is not zero")
+}
+
+fn report(name: &str, base: Duration, other: Duration) {
+    let speedup = base.as_secs_f64() / other.as_secs_f64();
+    println!("{name:<28} {base:>12.2?} -> {other:>12.2?}  ({speedup:.1}x)");
+}
+
+/// Long memory latencies leave most cycles quiet; fast-forwarding jumps over them.
+fn fast_forward() {
+    let build = |fast_forward: bool| {
+        let memory_config = MemoryConfig {
+            read_latency_cycles: 2000,
+            write_latency_cycles: 2000,
+            ..MemoryConfig::default()
+        };
+        let mut sim = Simulator::new(2, 4, CacheConfig::default(), memory_config, 2);
+        sim.set_fast_forward(fast_forward);
+        let config = WorkloadConfig {
+            instructions_per_thread: 2000,
+            memory_fraction: 0.5,
+            ..WorkloadConfig::default()
+        };
+        sim.load_workload(build_workload(4, config)).unwrap();
+        sim
+    };
+    report("fast forward", time(|| build(false)), time(|| build(true)));
+}
+
+/// Many cores: each cycle's per-core stage work is spread across host threads.
+#[cfg(feature = "parallel")]
+fn parallel() {
+    let build = |parallel: bool| {
+        let mut sim = Simulator::new(64, 128, CacheConfig::default(), MemoryConfig::default(), 4);
+        sim.set_parallel(parallel);
+        let config = WorkloadConfig {
+            instructions_per_thread: 2000,
+            ..WorkloadConfig::default()
+        };
+        sim.load_workload(build_workload(128, config)).unwrap();
+        sim
+    };
+    report("parallel step (64 cores)", time(|| build(false)), time(|| build(true)));
+}
+
+fn main() {
+    fast_forward();
+    #[cfg(feature = "parallel")]
+    parallel();
+}
@@ -0,0 +1,25 @@
+//! Prints a pipeline diagram of a short single-core run: a few computes around cache misses.
+
+use multicore_simulator::cache::CacheConfig;
+use multicore_simulator::core::{CoreId, Instruction, InstructionKind};
+use multicore_simulator::memory::MemoryConfig;
+use multicore_simulator::simulator::Simulator;
+use multicore_simulator::trace::render_pipeline_diagram;
+
+fn main() {
+    let memory = MemoryConfig {
+        read_latency_cycles: 12,
+        ..MemoryConfig::default()
+    };
+    let mut sim = Simulator::new(1, 1, CacheConfig::default(), memory, 2);
+    sim.enable_instruction_trace();
+    let mut instructions = Vec::new();
+    for i in 0..4 {
+        instructions.push(Instruction::new_compute(0));
+        instructions.push(Instruction::new_memory(InstructionKind::Load, 0x1000 + i * 64, 0));
+    }
+    sim.load_workload(vec![instructions]).unwrap();
+    sim.run_to_completion();
+    let cycles = 0..sim.current_cycle();
+    print!("{}", render_pipeline_diagram(&sim.instruction_trace(), CoreId(0), cycles));
+}
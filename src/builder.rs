@@ -0,0 +1,339 @@
+//! Checked construction of a [`Simulator`] (see [`Simulator::builder`]).
+
+use crate::cache::CacheConfig;
+use crate::memory::MemoryConfig;
+use crate::simulator::{CoreConfig, Simulator};
+use crate::topology::Topology;
+use crate::workload::{build_workload, WorkloadConfig};
+use std::fmt;
+
+/// Why [`SimulatorBuilder::build`] rejected a configuration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    NoCores,
+    NoThreads,
+    NoPipelineWidth,
+    NoCommitWidth,
+    /// Per-core pipeline widths were given for a different number of cores.
+    PipelineWidthCount { cores: usize, widths: usize },
+    /// The cache geometry cannot be modeled; says which part is wrong.
+    InvalidCache(&'static str),
+    /// The memory system cannot be modeled; says which part is wrong.
+    InvalidMemory(&'static str),
+    /// The topology places a core on a memory node that does not exist.
+    UnknownMemoryNode(usize),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::NoCores => write!(f, "at least one core is required"),
+            ConfigError::NoThreads => write!(f, "at least one thread is required"),
+            ConfigError::NoPipelineWidth => write!(f, "pipeline width must be at least 1"),
+            ConfigError::NoCommitWidth => write!(f, "commit width must be at least 1"),
+            ConfigError::PipelineWidthCount { cores, widths } => {
+                write!(f, "{widths} pipeline widths given for {cores} cores")
+            }
+            ConfigError::InvalidCache(reason) => write!(f, "invalid cache: {reason}"),
+            ConfigError::InvalidMemory(reason) => write!(f, "invalid memory: {reason}"),
+            ConfigError::UnknownMemoryNode(node) => {
+                write!(f, "topology references memory node {node}, which does not exist")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Builds a [`Simulator`], checking that the pieces fit together. Defaults: one core, one
+/// thread, default cache and memory, pipeline width 4, uniform topology, no workload.
+#[derive(Clone, Debug)]
+pub struct SimulatorBuilder {
+    cores: usize,
+    threads: usize,
+    cache: CacheConfig,
+    memory: MemoryConfig,
+    pipeline_width: usize,
+    /// Per-core widths, overriding `pipeline_width`.
+    pipeline_widths: Option<Vec<usize>>,
+    topology: Topology,
+    workload: Option<WorkloadConfig>,
+}
+
+impl Default for SimulatorBuilder {
+    fn default() -> Self {
+        Self {
+            cores: 1,
+            threads: 1,
+            cache: CacheConfig::default(),
+            memory: MemoryConfig::default(),
+            pipeline_width: 4,
+            pipeline_widths: None,
+            topology: Topology::uniform(),
+            workload: None,
+        }
+    }
+}
+
+impl SimulatorBuilder {
+    pub fn cores(mut self, cores: usize) -> Self {
+        self.cores = cores;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    pub fn cache(mut self, cache: CacheConfig) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    pub fn memory(mut self, memory: MemoryConfig) -> Self {
+        self.memory = memory;
+        self
+    }
+
+    pub fn pipeline_width(mut self, width: usize) -> Self {
+        self.pipeline_width = width;
+        self
+    }
+
+    /// A pipeline width per core (`widths[i]` for core i) instead of one for all.
+    pub fn pipeline_widths(mut self, widths: Vec<usize>) -> Self {
+        self.pipeline_widths = Some(widths);
+        self
+    }
+
+    /// Places cores on memory nodes and sets the interconnect; `build` rejects a core placed
+    /// on a node the memory config does not have.
+    pub fn topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    /// Generates a workload for every thread; its `line_size` and `cache_num_sets` are taken
+    /// from the cache config.
+    pub fn workload(mut self, workload: WorkloadConfig) -> Self {
+        self.workload = Some(workload);
+        self
+    }
+
+    /// Validates the configuration and builds the simulator, with the workload loaded.
+    pub fn build(self) -> Result<Simulator, ConfigError> {
+        if self.cores == 0 {
+            return Err(ConfigError::NoCores);
+        }
+        if self.threads == 0 {
+            return Err(ConfigError::NoThreads);
+        }
+        if self.pipeline_width == 0 {
+            return Err(ConfigError::NoPipelineWidth);
+        }
+        if let Some(widths) = &self.pipeline_widths {
+            check_pipeline_widths(self.cores, widths)?;
+        }
+        validate_cache(&self.cache)?;
+        validate_memory(&self.memory)?;
+        if !self.memory.nodes.is_empty() {
+            let nodes = self.memory.nodes.len();
+            if let Some(&node) = self.topology.core_nodes.iter().find(|&&n| n >= nodes) {
+                return Err(ConfigError::UnknownMemoryNode(node));
+            }
+        }
+        let workload = self.derived_workload();
+        let mut sim = Simulator::with_topology(
+            self.cores,
+            self.threads,
+            self.cache,
+            self.memory,
+            self.pipeline_width,
+            self.topology,
+        );
+        if let Some(widths) = &self.pipeline_widths {
+            sim.set_pipeline_widths(widths)?;
+        }
+        if let Some(workload) = workload {
+            sim.load_workload(build_workload(self.threads, workload))
+                .expect("one workload per thread");
+        }
+        Ok(sim)
+    }
+
+    /// The workload config with its cache geometry matched to the cache.
+    fn derived_workload(&self) -> Option<WorkloadConfig> {
+        self.workload.clone().map(|workload| WorkloadConfig {
+            line_size: self.cache.line_size,
+            cache_num_sets: self.cache.num_sets(),
+            ..workload
+        })
+    }
+}
+
+/// Checks what each core of [`Simulator::new_heterogeneous`] needs: a pipeline and commit
+/// width of at least 1 and a cache that can be modeled.
+pub(crate) fn validate_core(core: &CoreConfig) -> Result<(), ConfigError> {
+    if core.pipeline_width == 0 {
+        return Err(ConfigError::NoPipelineWidth);
+    }
+    if core.commit_width == Some(0) {
+        return Err(ConfigError::NoCommitWidth);
+    }
+    validate_cache(&core.cache)
+}
+
+/// Checks that there is one width per core and that each is at least 1.
+pub(crate) fn check_pipeline_widths(cores: usize, widths: &[usize]) -> Result<(), ConfigError> {
+    if widths.len() != cores {
+        return Err(ConfigError::PipelineWidthCount {
+            cores,
+            widths: widths.len(),
+        });
+    }
+    if widths.contains(&0) {
+        return Err(ConfigError::NoPipelineWidth);
+    }
+    Ok(())
+}
+
+/// Checks the geometry `Cache::new` relies on: power-of-two lines and sets, and a size that
+/// is exactly sets x ways x line.
+pub(crate) fn validate_cache(cache: &CacheConfig) -> Result<(), ConfigError> {
+    if cache.line_size == 0 || !cache.line_size.is_power_of_two() {
+        return Err(ConfigError::InvalidCache("line size must be a power of two"));
+    }
+    if cache.associativity == 0 {
+        return Err(ConfigError::InvalidCache("associativity must be at least 1"));
+    }
+    let sets = cache.num_sets();
+    if sets == 0 || sets * cache.associativity * cache.line_size != cache.size_bytes {
+        return Err(ConfigError::InvalidCache("size must be a multiple of ways x line size"));
+    }
+    if !sets.is_power_of_two() {
+        return Err(ConfigError::InvalidCache("number of sets must be a power of two"));
+    }
+    Ok(())
+}
+
+/// Checks the parts of the memory config the model divides by. A `bytes_per_cycle` of 0 is
+/// valid: it means unlimited bandwidth.
+pub(crate) fn validate_memory(memory: &MemoryConfig) -> Result<(), ConfigError> {
+    if memory.num_channels == 0 {
+        return Err(ConfigError::InvalidMemory("at least one channel is required"));
+    }
+    if memory.max_bytes_per_cycle > 0 && memory.bandwidth_window_cycles == 0 {
+        return Err(ConfigError::InvalidMemory("a bandwidth cap needs a non-empty window"));
+    }
+    if let Some(dram) = &memory.dram {
+        if dram.num_banks == 0 {
+            return Err(ConfigError::InvalidMemory("DRAM needs at least one bank"));
+        }
+        if dram.row_size_bytes == 0 {
+            return Err(ConfigError::InvalidMemory("DRAM rows must be at least one byte"));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{DramConfig, NodeConfig};
+
+    #[test]
+    fn builder_rejects_inconsistent_configs() {
+        let err = |builder: SimulatorBuilder| builder.build().err().unwrap();
+        assert_eq!(err(Simulator::builder().cores(0)), ConfigError::NoCores);
+        assert_eq!(err(Simulator::builder().threads(0)), ConfigError::NoThreads);
+        assert_eq!(err(Simulator::builder().pipeline_width(0)), ConfigError::NoPipelineWidth);
+        assert_eq!(
+            err(Simulator::builder().cores(2).pipeline_widths(vec![4])),
+            ConfigError::PipelineWidthCount { cores: 2, widths: 1 }
+        );
+        assert_eq!(
+            err(Simulator::builder().cores(2).pipeline_widths(vec![4, 0])),
+            ConfigError::NoPipelineWidth
+        );
+        let cache = |size_bytes, line_size, associativity| CacheConfig {
+            size_bytes,
+            line_size,
+            associativity,
+            ..CacheConfig::default()
+        };
+        // Odd line size, no ways, ragged size, three sets.
+        for bad in [
+            cache(4096, 48, 2),
+            cache(4096, 64, 0),
+            cache(4000, 64, 2),
+            cache(3 * 64 * 2, 64, 2),
+        ] {
+            assert!(matches!(
+                err(Simulator::builder().cache(bad)),
+                ConfigError::InvalidCache(_)
+            ));
+        }
+        let dram = |num_banks, row_size_bytes| MemoryConfig {
+            dram: Some(DramConfig {
+                num_banks,
+                row_size_bytes,
+                ..DramConfig::default()
+            }),
+            ..MemoryConfig::default()
+        };
+        // No channels, a cap without a window, no banks, empty rows.
+        for bad in [
+            MemoryConfig {
+                num_channels: 0,
+                ..MemoryConfig::default()
+            },
+            MemoryConfig {
+                max_bytes_per_cycle: 8,
+                bandwidth_window_cycles: 0,
+                ..MemoryConfig::default()
+            },
+            dram(0, 2048),
+            dram(8, 0),
+        ] {
+            assert!(matches!(
+                err(Simulator::builder().memory(bad)),
+                ConfigError::InvalidMemory(_)
+            ));
+        }
+        let memory = MemoryConfig {
+            nodes: vec![NodeConfig {
+                latency_cycles: 100,
+                address_range: 0..1 << 30,
+            }],
+            ..MemoryConfig::default()
+        };
+        let numa = Simulator::builder()
+            .cores(2)
+            .memory(memory)
+            .topology(Topology::new(vec![0, 1]));
+        assert_eq!(err(numa), ConfigError::UnknownMemoryNode(1));
+    }
+
+    #[test]
+    fn builder_matches_workload_to_cache() {
+        let cache = CacheConfig {
+            size_bytes: 128 * 32 * 4,
+            line_size: 128,
+            associativity: 4,
+            ..CacheConfig::default()
+        };
+        let builder = Simulator::builder()
+            .threads(2)
+            .cache(cache)
+            .workload(WorkloadConfig {
+                instructions_per_thread: 50,
+                ..WorkloadConfig::default()
+            });
+        let workload = builder.derived_workload().unwrap();
+        assert_eq!((workload.line_size, workload.cache_num_sets), (128, 32));
+        let mut sim = builder.build().unwrap();
+        sim.run_to_completion();
+        assert_eq!(sim.metrics().instructions_committed, 100);
+    }
+}
@@ -1,6 +1,5 @@
 //! L1 cache model: set-associative with configurable size, line size, and LRU replacement.
 
-use crate::core::Cycle;
 use std::collections::VecDeque;
 
 /// Result of a cache access.
@@ -21,6 +20,11 @@ pub struct CacheConfig {
     pub associativity: usize,
     /// Latency in cycles for a hit.
     pub hit_latency_cycles: u32,
+    /// Energy per access (hit or miss), in picojoules.
+    pub access_energy_pj: f64,
+    /// Extra cycles a miss waits when the line it replaces is dirty and must be written back
+    /// first. 0 = writebacks cost no time.
+    pub writeback_latency_cycles: u32,
 }
 
 impl Default for CacheConfig {
@@ -30,6 +34,8 @@ impl Default for CacheConfig {
             line_size: 64,
             associativity: 2,
             hit_latency_cycles: 1,
+            access_energy_pj: 0.0,
+            writeback_latency_cycles: 0,
         }
     }
 }
@@ -40,14 +46,25 @@ impl CacheConfig {
     }
 }
 
+/// A line a miss displaced (see [`Cache::access_with_eviction`]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Eviction {
+    /// Address of the first byte of the evicted line.
+    pub address: u64,
+    /// Whether it had been written since it was filled (and so must be written back).
+    pub dirty: bool,
+}
+
 /// One cache line (tag + optional LRU ordering).
 #[derive(Clone, Debug)]
 struct CacheLine {
     tag: u64,
     valid: bool,
+    dirty: bool,
 }
 
 /// One set: multiple ways with LRU ordering (index 0 = MRU, last = LRU).
+#[derive(Clone)]
 struct CacheSet {
     lines: Vec<CacheLine>,
     /// FIFO/LRU order: front = most recently used, back = least recently used.
@@ -60,15 +77,17 @@ impl CacheSet {
             .map(|_| CacheLine {
                 tag: 0,
                 valid: false,
+                dirty: false,
             })
             .collect();
         let lru_order = (0..associativity).collect();
         Self { lines, lru_order }
     }
 
-    fn access(&mut self, tag: u64) -> CacheAccessResult {
-        for (i, line) in self.lines.iter().enumerate() {
+    fn access(&mut self, tag: u64, is_write: bool) -> CacheAccessResult {
+        for (i, line) in self.lines.iter_mut().enumerate() {
             if line.valid && line.tag == tag {
+                line.dirty |= is_write;
                 self.touch(i);
                 return CacheAccessResult::Hit;
             }
@@ -76,12 +95,17 @@ impl CacheSet {
         CacheAccessResult::Miss
     }
 
-    fn allocate(&mut self, tag: u64) {
-        if let Some(&victim_way) = self.lru_order.back() {
-            self.lines[victim_way].tag = tag;
-            self.lines[victim_way].valid = true;
-            self.touch(victim_way);
-        }
+    /// Fills the LRU way with `tag`; returns the tag and dirtiness of the valid line it
+    /// replaced, if any.
+    fn allocate(&mut self, tag: u64, is_write: bool) -> Option<(u64, bool)> {
+        let &victim_way = self.lru_order.back()?;
+        let victim = &mut self.lines[victim_way];
+        let evicted = victim.valid.then_some((victim.tag, victim.dirty));
+        victim.tag = tag;
+        victim.valid = true;
+        victim.dirty = is_write;
+        self.touch(victim_way);
+        evicted
     }
 
     fn touch(&mut self, way: usize) {
@@ -93,6 +117,7 @@ impl CacheSet {
 }
 
 /// Private L1 cache for one core.
+#[derive(Clone)]
 pub struct Cache {
     config: CacheConfig,
     sets: Vec<CacheSet>,
@@ -131,13 +156,69 @@ impl Cache {
     /// Access the cache (read or write). Returns Hit or Miss.
     /// On miss, the line is allocated (after victim is evicted in real HW; we model that as allocation).
     pub fn access(&mut self, address: u64) -> CacheAccessResult {
+        self.access_with_eviction(address, false).0
+    }
+
+    /// Like [`Cache::access`], but a write marks the line dirty, and a miss also reports the
+    /// valid line it evicted.
+    pub fn access_with_eviction(
+        &mut self,
+        address: u64,
+        is_write: bool,
+    ) -> (CacheAccessResult, Option<Eviction>) {
         let (set_idx, tag) = self.address_to_set_and_tag(address);
+        let set_bits = self.set_mask.count_ones();
+        let line_bits = self.line_bits;
         let set = &mut self.sets[set_idx];
-        let result = set.access(tag);
-        if result == CacheAccessResult::Miss {
-            set.allocate(tag);
+        let result = set.access(tag, is_write);
+        if result == CacheAccessResult::Hit {
+            return (result, None);
+        }
+        let evicted = set.allocate(tag, is_write).map(|(victim, dirty)| Eviction {
+            address: ((victim << set_bits) | set_idx as u64) << line_bits,
+            dirty,
+        });
+        (result, evicted)
+    }
+
+    /// Whether `address` is currently cached, without touching LRU state or allocating.
+    pub fn probe(&self, address: u64) -> bool {
+        let (set_idx, tag) = self.address_to_set_and_tag(address);
+        self.sets[set_idx]
+            .lines
+            .iter()
+            .any(|line| line.valid && line.tag == tag)
+    }
+
+    /// Address of the line containing `address` (offset bits removed).
+    pub fn line_address(&self, address: u64) -> u64 {
+        address >> self.line_bits
+    }
+
+    /// Invalidates the line holding `address` (another core took ownership of it). Returns
+    /// whether it was cached.
+    pub fn invalidate(&mut self, address: u64) -> bool {
+        let (set_idx, tag) = self.address_to_set_and_tag(address);
+        let line = self.sets[set_idx]
+            .lines
+            .iter_mut()
+            .find(|line| line.valid && line.tag == tag);
+        match line {
+            Some(line) => {
+                line.valid = false;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Invalidates every line (e.g. another thread's working set displaced this one).
+    pub fn flush(&mut self) {
+        for set in &mut self.sets {
+            for line in &mut set.lines {
+                line.valid = false;
+            }
         }
-        result
     }
 
     pub fn hit_latency_cycles(&self) -> u32 {
@@ -170,6 +251,7 @@ mod tests {
             line_size: 32,
             associativity: 2,
             hit_latency_cycles: 1,
+            ..CacheConfig::default()
         };
         assert_eq!(c.num_sets(), 4);
     }
@@ -181,6 +263,7 @@ mod tests {
             line_size: 64,
             associativity: 2,
             hit_latency_cycles: 1,
+            ..CacheConfig::default()
         };
         let mut cache = Cache::new(config);
         let addr = 0u64;
@@ -188,6 +271,35 @@ mod tests {
         assert_eq!(cache.access(addr), CacheAccessResult::Hit);
     }
 
+    #[test]
+    fn cache_probe_has_no_side_effects() {
+        let mut cache = Cache::new(CacheConfig::default());
+        assert!(!cache.probe(0x40));
+        assert_eq!(cache.access(0x40), CacheAccessResult::Miss);
+        assert!(cache.probe(0x40));
+        assert!(cache.probe(0x7f));
+        assert_eq!(cache.line_address(0x7f), 1);
+    }
+
+    #[test]
+    fn cache_invalidate_drops_one_line() {
+        let mut cache = Cache::new(CacheConfig::default());
+        cache.access(0x1000);
+        cache.access(0x2000);
+        assert!(cache.invalidate(0x1008));
+        assert!(!cache.invalidate(0x1000));
+        assert!(!cache.probe(0x1000));
+        assert!(cache.probe(0x2000));
+    }
+
+    #[test]
+    fn cache_flush_invalidates_lines() {
+        let mut cache = Cache::new(CacheConfig::default());
+        cache.access(0x40);
+        cache.flush();
+        assert!(!cache.probe(0x40));
+    }
+
     #[test]
     fn cache_conflict_same_set() {
         // Direct-mapped (1 way), 4 sets: set_index = line_addr % 4.
@@ -196,6 +308,7 @@ mod tests {
             line_size: 32,
             associativity: 1,
             hit_latency_cycles: 1,
+            ..CacheConfig::default()
         };
         let mut cache = Cache::new(config);
         let addr0 = 0u64;      // line_addr 0 -> set 0
@@ -205,6 +318,22 @@ mod tests {
         assert_eq!(cache.access(addr0), CacheAccessResult::Miss);
     }
 
+    #[test]
+    fn cache_reports_dirty_evictions() {
+        let mut cache = Cache::new(CacheConfig::default());
+        let stride = (cache.num_sets() * cache.line_size()) as u64;
+        assert_eq!(cache.access_with_eviction(0x40, true), (CacheAccessResult::Miss, None));
+        cache.access(0x40 + stride);
+        let dirty = Eviction {
+            address: 0x40,
+            dirty: true,
+        };
+        let (result, evicted) = cache.access_with_eviction(0x40 + 2 * stride, false);
+        assert_eq!((result, evicted), (CacheAccessResult::Miss, Some(dirty)));
+        let (_, evicted) = cache.access_with_eviction(0x40 + 3 * stride, false);
+        assert_eq!(evicted.map(|e| (e.address, e.dirty)), Some((0x40 + stride, false)));
+    }
+
     #[test]
     fn cache_different_sets_hit() {
         let config = CacheConfig {
@@ -212,6 +341,7 @@ mod tests {
             line_size: 64,
             associativity: 2,
             hit_latency_cycles: 1,
+            ..CacheConfig::default()
         };
         let mut cache = Cache::new(config);
         // 4 sets. Addresses 0, 256, 512, ... map to different sets.
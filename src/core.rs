@@ -1,36 +1,116 @@
 //! Core architecture model: cycles, cores, pipeline stages, and instruction representation.
 
+use crate::memory::RequestId;
 use std::fmt;
 
 /// Global simulation cycle counter (discrete time).
 pub type Cycle = u64;
 
 /// Identifies a core (0..N).
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct CoreId(pub usize);
 
 /// Identifies a thread (for scheduling).
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct ThreadId(pub usize);
 
+/// Identifies an architectural register (for data dependencies).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RegId(pub usize);
+
 /// Pipeline stage for instruction-level parallelism modeling.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum PipelineStage {
     Fetch,
+    /// Front-end stages after Fetch (skipped when `StageCycles::decode_cycles` is 0).
+    Decode,
     Execute,
     Memory,
     Commit,
 }
 
 /// Kind of operation an instruction performs (for latency modeling).
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum InstructionKind {
     /// Compute (execute stage only).
     Compute,
+    /// Integer multiply (a compute with its own execute latency, see `StageCycles`).
+    Mul,
+    /// Divide (a compute with its own execute latency; the divider is not pipelined by default).
+    Div,
+    /// Fused multiply-add (a compute with its own execute latency).
+    Fma,
     /// Load: may hit L1 or miss to memory.
     Load,
     /// Store: may hit L1 or miss to memory.
     Store,
+    /// Makes `child`'s workload runnable when this instruction commits.
+    Spawn { child: ThreadId },
+    /// Waits in Execute until `child`'s last instruction commits; the thread fetches nothing
+    /// past it meanwhile.
+    Join { child: ThreadId },
+    /// Waits in Execute until `participants` threads have reached barrier `id`, then all of
+    /// them move on in the same cycle; the thread fetches nothing past it meanwhile.
+    Barrier { id: u64, participants: usize },
+    /// Takes lock `lock_id`, spinning in Execute while another thread holds it; the thread
+    /// fetches nothing past it until it commits.
+    LockAcquire { lock_id: u64 },
+    /// Frees lock `lock_id` once the thread's older instructions have completed.
+    LockRelease { lock_id: u64 },
+    /// Atomic fetch-and-add: reads and writes `address` with the line held exclusively (other
+    /// cores' copies are invalidated), and does not overlap other memory operations of its core.
+    AtomicRmw,
+    /// Conditional branch at `address`, resolved in Execute; a misprediction flushes the
+    /// thread's younger instructions.
+    Branch { taken: bool },
+    /// Memory fence: waits in Execute until the thread's older instructions have completed
+    /// and the core's write buffer has drained; the thread's younger memory operations wait
+    /// for it.
+    Fence,
+    /// Starts a background copy of `bytes` from `src` to `dst` on the DMA engine and moves on
+    /// without waiting for it.
+    DmaStart { src: u64, dst: u64, bytes: u64 },
+    /// Waits in Execute until the thread's DMA transfer `id` has finished (transfers are
+    /// numbered from 0 in the order the thread starts them); the thread fetches nothing past
+    /// it meanwhile.
+    DmaWait { id: u64 },
+    /// m×k by k×n matrix multiply on the core's matrix unit: waits in Execute while the unit
+    /// is busy with an older op, then holds it for the op's latency (see
+    /// `MatrixUnitConfig::latency`).
+    MatrixOp { m: u32, n: u32, k: u32 },
+    /// Gives up the rest of the thread's quantum when fetched, so another thread can run
+    /// (time slicing only; otherwise a plain compute).
+    Yield,
+    /// Parks the thread for `cycles` cycles from when it is fetched; its core runs other
+    /// threads or idles meanwhile.
+    Sleep { cycles: u64 },
+}
+
+impl InstructionKind {
+    /// Short lowercase name, without operands (keys `Metrics::committed_by_kind`).
+    pub fn name(&self) -> &'static str {
+        match self {
+            InstructionKind::Compute => "compute",
+            InstructionKind::Mul => "mul",
+            InstructionKind::Div => "div",
+            InstructionKind::Fma => "fma",
+            InstructionKind::Load => "load",
+            InstructionKind::Store => "store",
+            InstructionKind::Spawn { .. } => "spawn",
+            InstructionKind::Join { .. } => "join",
+            InstructionKind::Barrier { .. } => "barrier",
+            InstructionKind::LockAcquire { .. } => "lock_acquire",
+            InstructionKind::LockRelease { .. } => "lock_release",
+            InstructionKind::AtomicRmw => "atomic_rmw",
+            InstructionKind::Branch { .. } => "branch",
+            InstructionKind::Fence => "fence",
+            InstructionKind::DmaStart { .. } => "dma_start",
+            InstructionKind::DmaWait { .. } => "dma_wait",
+            InstructionKind::MatrixOp { .. } => "matrix_op",
+            InstructionKind::Yield => "yield",
+            InstructionKind::Sleep { .. } => "sleep",
+        }
+    }
 }
 
 /// A single instruction in the pipeline.
@@ -47,8 +127,31 @@ pub struct Instruction {
     pub stage: PipelineStage,
     /// Whether this instruction is stalled (e.g. cache miss, structural hazard).
     pub stalled: bool,
-    /// If stalled, cycles remaining until stall ends.
-    pub stall_cycles_left: u32,
+    /// Outstanding memory request this instruction is waiting on (cache miss).
+    pub mem_request: Option<RequestId>,
+    /// Cycles its cache miss also waits for the dirty line it evicted to be written back.
+    pub writeback_cycles: u32,
+    /// Data value (functional memory only): written by a store, filled in by a load.
+    pub value: Option<u64>,
+    /// Thread the instruction belongs to (set by `Simulator::load_workload`).
+    pub thread: ThreadId,
+    /// Register this instruction writes, if any.
+    pub dest: Option<RegId>,
+    /// Registers this instruction reads: it leaves Execute only once the older instructions
+    /// of its thread writing them have completed.
+    pub srcs: Vec<RegId>,
+    /// Unique id, assigned when the instruction is fetched (0 until then). An instruction
+    /// flushed and fetched again gets a new one.
+    pub id: u64,
+    /// Fetched down a mispredicted path: squashed when its branch resolves, never committed.
+    pub wrong_path: bool,
+    /// Position in its thread's program, assigned when the workload is loaded: dense from 0
+    /// per thread (a workload appended after `Simulator::clear_workload` continues past the
+    /// dropped instructions). Unlike `id`, it survives a flush and refetch.
+    pub seq: u64,
+    /// Position across all threads' instructions in the order they were loaded (from 0; a
+    /// spawned thread's are numbered when its Spawn commits).
+    pub global_seq: u64,
 }
 
 impl Instruction {
@@ -60,7 +163,16 @@ impl Instruction {
             stage_cycles_left: 1,
             stage: PipelineStage::Fetch,
             stalled: false,
-            stall_cycles_left: 0,
+            mem_request: None,
+            writeback_cycles: 0,
+            value: None,
+            thread: ThreadId(0),
+            dest: None,
+            srcs: Vec::new(),
+            id: 0,
+            wrong_path: false,
+            seq: 0,
+            global_seq: 0,
         }
     }
 
@@ -72,19 +184,106 @@ impl Instruction {
             stage_cycles_left: 1,
             stage: PipelineStage::Fetch,
             stalled: false,
-            stall_cycles_left: 0,
+            mem_request: None,
+            writeback_cycles: 0,
+            value: None,
+            thread: ThreadId(0),
+            dest: None,
+            srcs: Vec::new(),
+            id: 0,
+            wrong_path: false,
+            seq: 0,
+            global_seq: 0,
         }
     }
 
+    /// An arithmetic instruction of the given kind (`Compute`, `Mul`, `Div` or `Fma`).
+    pub fn new_op(kind: InstructionKind, issue_cycle: Cycle) -> Self {
+        Self {
+            kind,
+            ..Self::new_compute(issue_cycle)
+        }
+    }
+
+    /// A conditional branch at program address `address`.
+    pub fn new_branch(address: u64, taken: bool) -> Self {
+        Self {
+            kind: InstructionKind::Branch { taken },
+            address,
+            ..Self::new_compute(0)
+        }
+    }
+
+    /// A thread-control instruction (spawn or join): no memory access, one cycle per stage.
+    pub fn new_control(kind: InstructionKind) -> Self {
+        Self {
+            kind,
+            ..Self::new_compute(0)
+        }
+    }
+
+    /// Sets the data value a store writes (functional memory only).
+    pub fn with_value(mut self, value: u64) -> Self {
+        self.value = Some(value);
+        self
+    }
+
+    /// Sets the register this instruction writes.
+    pub fn with_dest(mut self, dest: RegId) -> Self {
+        self.dest = Some(dest);
+        self
+    }
+
+    /// Sets the registers this instruction reads.
+    pub fn with_srcs(mut self, srcs: Vec<RegId>) -> Self {
+        self.srcs = srcs;
+        self
+    }
+
+    /// What fetch brings in down a mispredicted path where this instruction would have come
+    /// from: a load from another address for a memory op, the same unit for arithmetic, a
+    /// compute for anything else. It reads and writes no registers.
+    pub fn wrong_path_copy(&self, address_offset: u64) -> Self {
+        let mut copy = match self.kind {
+            _ if self.is_memory_op() => Self::new_memory(
+                InstructionKind::Load,
+                self.address.wrapping_add(address_offset),
+                0,
+            ),
+            InstructionKind::Mul | InstructionKind::Div | InstructionKind::Fma => {
+                Self::new_op(self.kind, 0)
+            }
+            _ => Self::new_compute(0),
+        };
+        copy.thread = self.thread;
+        copy.wrong_path = true;
+        copy
+    }
+
     pub fn is_memory_op(&self) -> bool {
-        matches!(self.kind, InstructionKind::Load | InstructionKind::Store)
+        matches!(
+            self.kind,
+            InstructionKind::Load | InstructionKind::Store | InstructionKind::AtomicRmw
+        )
     }
 }
 
+impl PipelineStage {
+    /// Every stage, in pipeline order.
+    pub const ALL: [PipelineStage; 5] = [
+        PipelineStage::Fetch,
+        PipelineStage::Decode,
+        PipelineStage::Execute,
+        PipelineStage::Memory,
+        PipelineStage::Commit,
+    ];
+}
+
 impl fmt::Display for PipelineStage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             PipelineStage::Fetch => write!(f, "Fetch"),
+            PipelineStage::Decode => write!(f, "Decode"),
             PipelineStage::Execute => write!(f, "Execute"),
             PipelineStage::Memory => write!(f, "Memory"),
             PipelineStage::Commit => write!(f, "Commit"),
@@ -0,0 +1,184 @@
+//! Asynchronous DMA engine: background copies that overlap with the cores' compute.
+
+use crate::core::{CoreId, Cycle, ThreadId};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// DMA engine parameters (see `Simulator::set_dma`).
+#[derive(Clone, Debug)]
+pub struct DmaConfig {
+    /// Bytes moved per memory request.
+    pub chunk_bytes: u64,
+    /// Chunks the engine sends to memory per cycle (reads and writes both use bandwidth);
+    /// 0 is treated as 1.
+    pub chunks_per_cycle: usize,
+    /// Whether copied destination lines are also filled into the issuing core's cache.
+    pub prefetch_into_cache: bool,
+}
+
+impl Default for DmaConfig {
+    fn default() -> Self {
+        Self {
+            chunk_bytes: 64,
+            chunks_per_cycle: 1,
+            prefetch_into_cache: false,
+        }
+    }
+}
+
+/// One piece of a transfer, handed to memory as a read of `src` and a write of `dst`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DmaChunk {
+    /// Core whose `DmaStart` began the transfer.
+    pub core_id: CoreId,
+    pub src: u64,
+    pub dst: u64,
+    pub bytes: u64,
+}
+
+#[derive(Clone, Debug)]
+struct Transfer {
+    core_id: CoreId,
+    thread: ThreadId,
+    id: u64,
+    src: u64,
+    dst: u64,
+    bytes: u64,
+    /// Bytes already sent to memory.
+    sent: u64,
+    /// Cycle the last chunk sent so far completes.
+    done_at: Cycle,
+}
+
+/// Transfers in flight, served in the order they started.
+#[derive(Clone, Debug, Default)]
+pub struct DmaEngine {
+    config: DmaConfig,
+    transfers: VecDeque<Transfer>,
+    /// Id the thread's next transfer gets.
+    next_id: HashMap<ThreadId, u64>,
+    completed: HashSet<(ThreadId, u64)>,
+}
+
+impl DmaEngine {
+    pub fn new(config: DmaConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    pub fn config(&self) -> &DmaConfig {
+        &self.config
+    }
+
+    /// Queues a copy for `thread` and returns its id: the thread's transfers are numbered
+    /// from 0 in the order they start.
+    pub fn start(
+        &mut self,
+        core_id: CoreId,
+        thread: ThreadId,
+        src: u64,
+        dst: u64,
+        bytes: u64,
+    ) -> u64 {
+        let next = self.next_id.entry(thread).or_default();
+        let id = *next;
+        *next += 1;
+        self.transfers.push_back(Transfer {
+            core_id,
+            thread,
+            id,
+            src,
+            dst,
+            bytes,
+            sent: 0,
+            done_at: 0,
+        });
+        id
+    }
+
+    /// Sends up to `chunks_per_cycle` chunks; `send` issues one to memory and returns when it
+    /// completes. Then retires every transfer whose last chunk has completed by `now`.
+    pub fn tick(&mut self, now: Cycle, mut send: impl FnMut(DmaChunk) -> Cycle) {
+        let chunk_bytes = self.config.chunk_bytes.max(1);
+        let mut budget = self.config.chunks_per_cycle.max(1);
+        for transfer in self.transfers.iter_mut() {
+            while budget > 0 && transfer.sent < transfer.bytes {
+                let bytes = chunk_bytes.min(transfer.bytes - transfer.sent);
+                let done_at = send(DmaChunk {
+                    core_id: transfer.core_id,
+                    src: transfer.src + transfer.sent,
+                    dst: transfer.dst + transfer.sent,
+                    bytes,
+                });
+                transfer.done_at = transfer.done_at.max(done_at);
+                transfer.sent += bytes;
+                budget -= 1;
+            }
+        }
+        let completed = &mut self.completed;
+        self.transfers.retain(|t| {
+            let done = t.sent == t.bytes && t.done_at <= now;
+            if done {
+                completed.insert((t.thread, t.id));
+            }
+            !done
+        });
+    }
+
+    /// Whether `thread`'s transfer `id` has finished. An id the thread has not started counts
+    /// as done: a thread starts its transfers before it waits on them, so nothing would ever
+    /// finish it.
+    pub fn is_done(&self, thread: ThreadId, id: u64) -> bool {
+        let started = self.next_id.get(&thread).copied().unwrap_or(0);
+        id >= started || self.completed.contains(&(thread, id))
+    }
+
+    /// Whether any transfer is still in flight.
+    pub fn is_active(&self) -> bool {
+        !self.transfers.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dma_sends_chunks_in_order_and_finishes_after_the_last() {
+        let mut dma = DmaEngine::new(DmaConfig::default());
+        let first = dma.start(CoreId(0), ThreadId(0), 0x1000, 0x8000, 100);
+        let second = dma.start(CoreId(0), ThreadId(0), 0x2000, 0x9000, 64);
+        assert_eq!((first, second), (0, 1));
+        let mut sent = Vec::new();
+        for now in 0..3 {
+            dma.tick(now, |chunk| {
+                sent.push(chunk);
+                now + 10
+            });
+        }
+        assert_eq!(sent.len(), 3);
+        assert_eq!((sent[1].src, sent[1].dst, sent[1].bytes), (0x1040, 0x8040, 36));
+        assert_eq!(sent[2].src, 0x2000);
+        assert!(dma.is_active() && !dma.is_done(ThreadId(0), 0));
+        dma.tick(11, |_| unreachable!());
+        assert!(dma.is_done(ThreadId(0), 0));
+        assert!(!dma.is_done(ThreadId(0), 1));
+        dma.tick(12, |_| unreachable!());
+        assert!(!dma.is_active() && dma.is_done(ThreadId(0), 1));
+    }
+
+    #[test]
+    fn dma_without_chunk_budget_still_progresses() {
+        let mut dma = DmaEngine::new(DmaConfig {
+            chunks_per_cycle: 0,
+            ..DmaConfig::default()
+        });
+        assert!(dma.is_done(ThreadId(0), 0));
+        dma.start(CoreId(0), ThreadId(0), 0, 0x8000, 64);
+        assert!(!dma.is_done(ThreadId(0), 0) && dma.is_done(ThreadId(0), 1));
+        dma.tick(0, |_| 5);
+        dma.tick(5, |_| unreachable!());
+        assert!(dma.is_done(ThreadId(0), 0));
+    }
+}
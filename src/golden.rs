@@ -0,0 +1,263 @@
+//! Golden-run regression checks: capture a run's key metrics and a fingerprint of its timing,
+//! save them, and later report which of them drifted.
+
+use crate::builder::{ConfigError, SimulatorBuilder};
+use crate::workload::WorkloadConfig;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Cycles per window of the committed-count fingerprint.
+pub const FINGERPRINT_WINDOW: u64 = 1000;
+
+/// Key metrics of one run plus a fingerprint of when its instructions committed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GoldenRun {
+    /// Metric values by name.
+    pub values: BTreeMap<String, u64>,
+    /// Relative drift allowed per metric before [`GoldenRun::compare`] reports it (0 when
+    /// absent: the value must match exactly).
+    pub tolerances: BTreeMap<String, f64>,
+    /// Hash of the instructions committed in each [`FINGERPRINT_WINDOW`]-cycle window.
+    pub fingerprint: u64,
+}
+
+/// A metric that differs from the golden by more than its tolerance.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Divergence {
+    pub field: String,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+impl Divergence {
+    /// Drift relative to the golden value (infinite if the golden value is 0 and the actual
+    /// one is not).
+    pub fn relative(&self) -> f64 {
+        if self.expected == 0 {
+            return if self.actual == 0 { 0.0 } else { f64::INFINITY };
+        }
+        (self.actual as f64 - self.expected as f64).abs() / self.expected as f64
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: expected {}, got {}", self.field, self.expected, self.actual)?;
+        if self.expected == 0 {
+            // No percentage of nothing.
+            return Ok(());
+        }
+        let change = (self.actual as f64 / self.expected as f64 - 1.0) * 100.0;
+        write!(f, " ({change:+.2}%)")
+    }
+}
+
+/// Builds the simulator with `workload` loaded, runs it to completion, and records the result.
+pub fn capture(sim: SimulatorBuilder, workload: WorkloadConfig) -> Result<GoldenRun, ConfigError> {
+    let mut sim = sim.workload(workload).build()?;
+    let mut windows = Vec::new();
+    let mut committed = 0u64;
+    for summary in sim.iter_cycles() {
+        committed += u64::from(summary.committed);
+        windows.push(committed);
+    }
+    let metrics = sim.metrics();
+    let values = [
+        ("total_cycles", metrics.total_cycles),
+        ("instructions_committed", metrics.instructions_committed),
+        ("cache_hits", metrics.cache_hits),
+        ("cache_misses", metrics.cache_misses),
+        ("memory_stall_cycles", metrics.memory_stall_cycles),
+        ("miss_latency_cycles", metrics.miss_latency_cycles),
+    ];
+    Ok(GoldenRun {
+        values: values.iter().map(|&(k, v)| (k.to_string(), v)).collect(),
+        tolerances: BTreeMap::new(),
+        fingerprint: fingerprint(&windows),
+    })
+}
+
+/// FNV-1a over the instructions committed in each window, given the running committed count
+/// at every cycle. Stable across builds so saved goldens stay comparable.
+fn fingerprint(committed_by_cycle: &[u64]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut previous = 0;
+    for chunk in committed_by_cycle.chunks(FINGERPRINT_WINDOW as usize) {
+        let total = chunk[chunk.len() - 1];
+        for byte in (total - previous).to_le_bytes() {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        previous = total;
+    }
+    hash
+}
+
+impl GoldenRun {
+    /// Allows `field` to drift by `relative` (e.g. 0.01 for 1%) before it is reported.
+    pub fn with_tolerance(mut self, field: &str, relative: f64) -> Self {
+        self.tolerances.insert(field.to_string(), relative);
+        self
+    }
+
+    /// Fields of `actual` that drifted from this golden by more than their tolerance. The
+    /// fingerprint is reported (as field "fingerprint") only if every value matches exactly,
+    /// since any timing change alters it.
+    pub fn compare(&self, actual: &GoldenRun) -> Vec<Divergence> {
+        let mut divergences = Vec::new();
+        let mut exact = true;
+        for (field, &expected) in &self.values {
+            let actual = actual.values.get(field).copied().unwrap_or(0);
+            exact &= actual == expected;
+            let tolerance = self.tolerances.get(field).copied().unwrap_or(0.0);
+            if actual.abs_diff(expected) as f64 > tolerance * expected as f64 {
+                divergences.push(Divergence {
+                    field: field.clone(),
+                    expected,
+                    actual,
+                });
+            }
+        }
+        if exact && divergences.is_empty() && actual.fingerprint != self.fingerprint {
+            divergences.push(Divergence {
+                field: "fingerprint".to_string(),
+                expected: self.fingerprint,
+                actual: actual.fingerprint,
+            });
+        }
+        divergences
+    }
+
+    /// Writes the run as text: a `name value [tolerance]` line per metric and a
+    /// `fingerprint <hex>` line.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut text = String::new();
+        for (field, value) in &self.values {
+            match self.tolerances.get(field) {
+                Some(tolerance) => text.push_str(&format!("{field} {value} {tolerance}\n")),
+                None => text.push_str(&format!("{field} {value}\n")),
+            }
+        }
+        text.push_str(&format!("fingerprint {:016x}\n", self.fingerprint));
+        fs::write(path, text)
+    }
+
+    /// Reads a run written by [`GoldenRun::save`].
+    pub fn load(path: impl AsRef<Path>) -> io::Result<GoldenRun> {
+        let invalid = |line: &str| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("bad golden line: {line:?}"))
+        };
+        let mut run = GoldenRun::default();
+        for line in fs::read_to_string(path)?.lines().filter(|l| !l.trim().is_empty()) {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match parts[..] {
+                ["fingerprint", hex] => {
+                    run.fingerprint = u64::from_str_radix(hex, 16).map_err(|_| invalid(line))?;
+                }
+                [field, value] | [field, value, _] => {
+                    let value = value.parse().map_err(|_| invalid(line))?;
+                    run.values.insert(field.to_string(), value);
+                    if let [_, _, tolerance] = parts[..] {
+                        let tolerance = tolerance.parse().map_err(|_| invalid(line))?;
+                        run.tolerances.insert(field.to_string(), tolerance);
+                    }
+                }
+                _ => return Err(invalid(line)),
+            }
+        }
+        Ok(run)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CacheConfig;
+    use crate::memory::MemoryConfig;
+    use crate::workload::AccessPattern;
+
+    /// The example binary's scenarios: sequential ("baseline") or conflict-heavy ("adverse")
+    /// accesses on two cores with a small L1 and DDR4.
+    fn scenario(
+        pattern: AccessPattern,
+        hit_latency_cycles: u32,
+    ) -> (SimulatorBuilder, WorkloadConfig) {
+        let cache = CacheConfig {
+            size_bytes: 32 * 64 * 2,
+            line_size: 64,
+            associativity: 2,
+            hit_latency_cycles,
+            access_energy_pj: 20.0,
+            writeback_latency_cycles: 0,
+        };
+        let sim = SimulatorBuilder::default()
+            .cores(2)
+            .threads(2)
+            .cache(cache)
+            .memory(MemoryConfig::ddr4());
+        let workload = WorkloadConfig {
+            instructions_per_thread: 2000,
+            memory_fraction: 0.5,
+            access_pattern: pattern,
+            working_set_lines: if pattern == AccessPattern::Sequential { 64 } else { 0 },
+            ..WorkloadConfig::default()
+        };
+        (sim, workload)
+    }
+
+    /// Compares against the shipped goldens; run with `UPDATE_GOLDENS=1` to rewrite them after
+    /// an intended timing change.
+    #[test]
+    fn golden_runs_match_the_shipped_goldens() {
+        for (name, pattern) in [
+            ("baseline", AccessPattern::Sequential),
+            ("adverse", AccessPattern::ConflictHeavy),
+        ] {
+            let path = format!("{}/goldens/{name}.golden", env!("CARGO_MANIFEST_DIR"));
+            let (sim, workload) = scenario(pattern, 1);
+            let run = capture(sim, workload).unwrap();
+            if std::env::var_os("UPDATE_GOLDENS").is_some() {
+                run.save(&path).unwrap();
+            }
+            let golden = GoldenRun::load(&path).unwrap();
+            assert_eq!(golden.compare(&run), Vec::new(), "{name}");
+        }
+    }
+
+    #[test]
+    fn golden_compare_flags_a_perturbed_cache_latency() {
+        let (sim, workload) = scenario(AccessPattern::Sequential, 1);
+        let golden = capture(sim, workload).unwrap();
+        let path = std::env::temp_dir().join(format!("golden-{}.golden", std::process::id()));
+        golden.clone().with_tolerance("total_cycles", 0.001).save(&path).unwrap();
+        let loaded = GoldenRun::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.tolerances["total_cycles"], 0.001);
+        assert_eq!(loaded.values, golden.values);
+
+        let (sim, workload) = scenario(AccessPattern::Sequential, 1);
+        assert!(loaded.compare(&capture(sim, workload).unwrap()).is_empty());
+
+        let (sim, workload) = scenario(AccessPattern::Sequential, 3);
+        let drifted = loaded.compare(&capture(sim, workload).unwrap());
+        let cycles = drifted.iter().find(|d| d.field == "total_cycles").unwrap();
+        assert!(cycles.actual > cycles.expected && cycles.relative() > 0.001);
+        assert!(drifted.iter().all(|d| d.field != "instructions_committed"));
+    }
+
+    #[test]
+    fn golden_divergence_from_zero_has_no_percentage() {
+        let divergence = |expected, actual| Divergence {
+            field: "cache_hits".to_string(),
+            expected,
+            actual,
+        };
+        assert_eq!(divergence(0, 0).relative(), 0.0);
+        assert_eq!(divergence(0, 3).relative(), f64::INFINITY);
+        assert_eq!(divergence(0, 3).to_string(), "cache_hits: expected 0, got 3");
+        assert_eq!(divergence(200, 210).to_string(), "cache_hits: expected 200, got 210 (+5.00%)");
+    }
+}
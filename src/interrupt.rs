@@ -0,0 +1,68 @@
+//! Interrupts injected into a core: a handler's instructions plus entry and exit overhead.
+
+use crate::core::{CoreId, Cycle, Instruction, ThreadId};
+use std::collections::VecDeque;
+
+/// Thread id handler instructions run under; they count toward no thread's metrics.
+pub const HANDLER_THREAD: ThreadId = ThreadId(usize::MAX);
+
+/// What happens to the interrupted core's in-flight instructions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InterruptMode {
+    /// Those not yet past Execute go back to their threads and are fetched again afterwards.
+    #[default]
+    Squash,
+    /// The pipeline drains completely before the handler starts.
+    Drain,
+}
+
+/// How interrupts are taken (see `Simulator::inject_interrupt`).
+#[derive(Clone, Debug)]
+pub struct InterruptConfig {
+    pub mode: InterruptMode,
+    /// Cycles between taking the interrupt and fetching the handler.
+    pub entry_cycles: u32,
+    /// Cycles between the handler's last instruction committing and the thread resuming.
+    pub exit_cycles: u32,
+}
+
+impl Default for InterruptConfig {
+    fn default() -> Self {
+        Self {
+            mode: InterruptMode::Squash,
+            entry_cycles: 10,
+            exit_cycles: 10,
+        }
+    }
+}
+
+/// An interrupt waiting for its cycle.
+#[derive(Clone, Debug)]
+pub(crate) struct PendingInterrupt {
+    pub core_id: CoreId,
+    pub at: Cycle,
+    pub handler: Vec<Instruction>,
+}
+
+/// Where a core is in servicing an interrupt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum InterruptPhase {
+    /// Waiting for the pipeline to empty (drain mode).
+    Draining,
+    /// Cycles of entry overhead left.
+    Entry(u32),
+    /// Fetching and running the handler.
+    Handler,
+    /// Cycles of exit overhead left.
+    Exit(u32),
+}
+
+/// The interrupt a core is servicing.
+#[derive(Clone, Debug)]
+pub(crate) struct ActiveInterrupt {
+    pub phase: InterruptPhase,
+    /// Handler instructions not fetched yet.
+    pub handler: VecDeque<Instruction>,
+    /// Thread that was running when the interrupt arrived.
+    pub victim: Option<ThreadId>,
+}
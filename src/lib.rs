@@ -1,9 +1,23 @@
 //! Multicore execution simulator: thread scheduling, cache contention, memory latency.
 
+pub mod branch;
+pub mod builder;
 pub mod cache;
 pub mod core;
+pub mod dma;
+pub mod golden;
+pub mod interrupt;
+pub mod matrix;
 pub mod memory;
 pub mod metrics;
+pub mod observer;
+pub mod power;
+pub mod replay;
+pub mod rng;
 pub mod scheduler;
 pub mod simulator;
+pub mod sweep;
+pub mod topology;
+pub mod trace;
 pub mod workload;
+pub mod write_buffer;
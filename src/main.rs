@@ -1,11 +1,13 @@
-//! Example run: baseline (sequential) vs conflict-heavy workload, quantifying ~17% slowdown.
+//! Example run: baseline (sequential) vs conflict-heavy workload on DDR4, quantifying the slowdown.
 
 use multicore_simulator::cache::CacheConfig;
 use multicore_simulator::memory::MemoryConfig;
+use multicore_simulator::metrics::Metrics;
 use multicore_simulator::simulator::Simulator;
-use multicore_simulator::workload::{build_workload, AccessPattern, WorkloadConfig};
+use multicore_simulator::workload::{build_workload, AccessPattern, FalseSharing, WorkloadConfig};
 
-fn run_benchmark(
+/// Parameters for one benchmark run.
+struct Scenario {
     num_cores: usize,
     num_threads: usize,
     instructions_per_thread: usize,
@@ -13,88 +15,146 @@ fn run_benchmark(
     access_pattern: AccessPattern,
     cache_num_sets: usize,
     working_set_lines: usize,
-    memory_latency_cycles: u32,
-) -> (u64, f64, f64, u64) {
+    memory: MemoryConfig,
+    cache_access_energy_pj: f64,
+}
+
+fn run_benchmark(scenario: &Scenario) -> Metrics {
     let cache_config = CacheConfig {
-        size_bytes: cache_num_sets * 64 * 2, // 2-way, 64-byte lines
+        size_bytes: scenario.cache_num_sets * 64 * 2, // 2-way, 64-byte lines
         line_size: 64,
         associativity: 2,
         hit_latency_cycles: 1,
+        access_energy_pj: scenario.cache_access_energy_pj,
+        writeback_latency_cycles: 0,
     };
-    let memory_config = MemoryConfig {
-        access_latency_cycles: memory_latency_cycles,
-    };
-    let mut sim = Simulator::new(num_cores, num_threads, cache_config, memory_config, 4);
+    let mut sim = Simulator::new(
+        scenario.num_cores,
+        scenario.num_threads,
+        cache_config,
+        scenario.memory.clone(),
+        4,
+    );
     let workload_config = WorkloadConfig {
-        instructions_per_thread,
-        memory_fraction,
-        access_pattern,
+        instructions_per_thread: scenario.instructions_per_thread,
+        memory_fraction: scenario.memory_fraction,
+        access_pattern: scenario.access_pattern,
         line_size: 64,
-        cache_num_sets,
-        working_set_lines,
+        cache_num_sets: scenario.cache_num_sets,
+        working_set_lines: scenario.working_set_lines,
+        ..WorkloadConfig::default()
+    };
+    let workload = build_workload(scenario.num_threads, workload_config);
+    sim.load_workload(workload).expect("one workload per thread");
+    sim.run_to_completion();
+    sim.metrics().clone()
+}
+
+/// Two threads on two coherent cores, each updating its own counter; the counters share one
+/// line unless `padded`.
+fn run_false_sharing(padded: bool) -> Metrics {
+    let mut sim = Simulator::new(2, 2, CacheConfig::default(), MemoryConfig::ddr4(), 4);
+    sim.enable_coherence();
+    let workload_config = WorkloadConfig {
+        instructions_per_thread: 2000,
+        memory_fraction: 0.5,
+        false_sharing: Some(FalseSharing {
+            num_vars: 2,
+            line_size: 64,
+            padded,
+        }),
+        ..WorkloadConfig::default()
     };
-    let workload = build_workload(num_threads, workload_config);
-    sim.load_workload(workload);
+    sim.load_workload(build_workload(2, workload_config))
+        .expect("one workload per thread");
     sim.run_to_completion();
+    sim.metrics().clone()
+}
+
+/// Prints each core's utilization (share of cycles with work in flight).
+fn print_utilization(metrics: &Metrics) {
+    for &core_id in metrics.per_core.keys() {
+        println!(
+            "  Core {} utilization:  {:.2}%",
+            core_id.0,
+            metrics.core_utilization(core_id) * 100.0
+        );
+    }
+}
 
-    let m = sim.metrics();
-    (
-        m.total_cycles,
-        m.hit_rate(),
-        m.miss_rate(),
-        m.memory_stall_cycles,
-    )
+/// Prints where stall cycles went, largest share first.
+fn print_stall_breakdown(metrics: &Metrics) {
+    let total: u64 = metrics.stall_cycles_by_reason.values().sum();
+    if total == 0 {
+        return;
+    }
+    for (reason, cycles) in metrics.stall_breakdown() {
+        let share = cycles as f64 / total as f64 * 100.0;
+        println!("  Stalls {reason:<16} {cycles} ({share:.1}%)");
+    }
 }
 
 fn main() {
-    let num_cores = 2;
-    let num_threads = 2;
-    let instructions_per_thread = 2000;
-    let memory_fraction = 0.5;
-    let cache_num_sets = 32;
-    // Sequential working set fits in L1 (32 sets * 2 ways = 64 lines); reuse gives hits.
-    let working_set_lines = 64;
-    // Memory latency tuned so conflict-heavy run shows ~17% slowdown vs baseline.
-    let memory_latency_cycles = 45;
+    let baseline_scenario = Scenario {
+        num_cores: 2,
+        num_threads: 2,
+        instructions_per_thread: 2000,
+        memory_fraction: 0.5,
+        access_pattern: AccessPattern::Sequential,
+        cache_num_sets: 32,
+        // Sequential working set fits in L1 (32 sets * 2 ways = 64 lines); reuse gives hits.
+        working_set_lines: 64,
+        // DDR4-3200 behind a 3 GHz core.
+        // Rough DDR4 energy: ~10 nJ per 64-byte line transfer, plus standby/refresh power.
+        memory: MemoryConfig {
+            read_energy_pj: 10_000.0,
+            write_energy_pj: 11_000.0,
+            background_power_pw_per_cycle: 100.0,
+            ..MemoryConfig::ddr4()
+        },
+        cache_access_energy_pj: 20.0,
+    };
 
     println!("=== Multicore Execution Simulator Benchmark ===\n");
 
     // Baseline: sequential access pattern (good locality, working set fits in cache).
-    let (baseline_cycles, baseline_hit, baseline_miss, baseline_stall) = run_benchmark(
-        num_cores,
-        num_threads,
-        instructions_per_thread,
-        memory_fraction,
-        AccessPattern::Sequential,
-        cache_num_sets,
-        working_set_lines,
-        memory_latency_cycles,
-    );
+    let baseline = run_benchmark(&baseline_scenario);
 
     println!("--- Baseline (sequential access pattern) ---");
-    println!("  Total cycles:        {}", baseline_cycles);
-    println!("  Cache hit rate:      {:.2}%", baseline_hit * 100.0);
-    println!("  Cache miss rate:     {:.2}%", baseline_miss * 100.0);
-    println!("  Memory stall cycles: {}", baseline_stall);
+    println!("  Total cycles:        {}", baseline.total_cycles);
+    println!("  IPC:                 {:.3}", baseline.ipc());
+    println!("  Cache hit rate:      {:.2}%", baseline.hit_rate() * 100.0);
+    println!("  Cache miss rate:     {:.2}%", baseline.miss_rate() * 100.0);
+    println!("  Memory stall cycles: {}", baseline.memory_stall_cycles);
+    println!("  Energy:              {:.1} nJ", baseline.total_energy_pj() / 1000.0);
+    if let Some(bottleneck) = baseline.bottleneck_report() {
+        println!("  Bottleneck:          {bottleneck}");
+    }
+    print_utilization(&baseline);
+    print_stall_breakdown(&baseline);
 
     // Adverse: conflict-heavy (all addresses map to same set -> evictions, misses).
-    let (adverse_cycles, adverse_hit, adverse_miss, adverse_stall) = run_benchmark(
-        num_cores,
-        num_threads,
-        instructions_per_thread,
-        memory_fraction,
-        AccessPattern::ConflictHeavy,
-        cache_num_sets,
-        0, // not used for conflict pattern
-        memory_latency_cycles,
-    );
+    let adverse = run_benchmark(&Scenario {
+        access_pattern: AccessPattern::ConflictHeavy,
+        working_set_lines: 0, // not used for conflict pattern
+        ..baseline_scenario
+    });
 
     println!("\n--- Adverse (conflict-heavy access pattern) ---");
-    println!("  Total cycles:        {}", adverse_cycles);
-    println!("  Cache hit rate:      {:.2}%", adverse_hit * 100.0);
-    println!("  Cache miss rate:     {:.2}%", adverse_miss * 100.0);
-    println!("  Memory stall cycles: {}", adverse_stall);
+    println!("  Total cycles:        {}", adverse.total_cycles);
+    println!("  IPC:                 {:.3}", adverse.ipc());
+    println!("  Cache hit rate:      {:.2}%", adverse.hit_rate() * 100.0);
+    println!("  Cache miss rate:     {:.2}%", adverse.miss_rate() * 100.0);
+    println!("  Memory stall cycles: {}", adverse.memory_stall_cycles);
+    println!("  Energy:              {:.1} nJ", adverse.total_energy_pj() / 1000.0);
+    if let Some(bottleneck) = adverse.bottleneck_report() {
+        println!("  Bottleneck:          {bottleneck}");
+    }
+    print_utilization(&adverse);
+    print_stall_breakdown(&adverse);
 
+    let baseline_cycles = baseline.total_cycles;
+    let adverse_cycles = adverse.total_cycles;
     let slowdown = if baseline_cycles > 0 {
         (adverse_cycles as f64 - baseline_cycles as f64) / baseline_cycles as f64 * 100.0
     } else {
@@ -105,5 +165,22 @@ fn main() {
     println!("  Baseline cycles:  {}", baseline_cycles);
     println!("  Adverse cycles:   {}", adverse_cycles);
     println!("  Slowdown:         {:.2}%", slowdown);
+    println!(
+        "  Energy:           {:.2}x baseline",
+        adverse.total_energy_pj() / baseline.total_energy_pj()
+    );
+    // False sharing: per-thread counters in one line vs padded to separate lines.
+    let shared_line = run_false_sharing(false);
+    let padded = run_false_sharing(true);
+    println!("\n--- False sharing (per-thread counters, coherent L1s) ---");
+    println!("  Same line cycles:    {}", shared_line.total_cycles);
+    println!("  Coherence misses:    {}", shared_line.coherence_misses);
+    println!("  Padded cycles:       {}", padded.total_cycles);
+    println!("  Coherence misses:    {}", padded.coherence_misses);
+    println!(
+        "  Slowdown:            {:.2}x padded",
+        shared_line.total_cycles as f64 / padded.total_cycles as f64
+    );
+
     println!("\nConclusion: Conflict-heavy memory access causes {:.1}% slowdown vs sequential access.", slowdown);
 }
@@ -0,0 +1,43 @@
+//! Per-core matrix engine that `MatrixOp` instructions are offloaded to.
+
+/// Matrix unit parameters (see `Simulator::set_matrix_unit`).
+#[derive(Clone, Debug)]
+pub struct MatrixUnitConfig {
+    /// Multiply-accumulates the unit completes per cycle.
+    pub macs_per_cycle: u64,
+}
+
+impl Default for MatrixUnitConfig {
+    fn default() -> Self {
+        Self {
+            macs_per_cycle: 256,
+        }
+    }
+}
+
+impl MatrixUnitConfig {
+    /// Cycles an m×k by k×n multiply occupies the unit: ceil(m·n·k / macs_per_cycle), at
+    /// least 1.
+    pub fn latency(&self, m: u32, n: u32, k: u32) -> u64 {
+        let macs = u64::from(m)
+            .saturating_mul(u64::from(n))
+            .saturating_mul(u64::from(k));
+        macs.div_ceil(self.macs_per_cycle.max(1)).max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matrix_latency_rounds_up_the_macs() {
+        let unit = MatrixUnitConfig::default();
+        assert_eq!(unit.latency(16, 16, 16), 16);
+        assert_eq!(unit.latency(16, 16, 17), 17);
+        assert_eq!(unit.latency(1, 1, 1), 1);
+        assert_eq!(unit.latency(0, 8, 8), 1);
+        let wide = MatrixUnitConfig { macs_per_cycle: 1024 };
+        assert_eq!(wide.latency(64, 64, 64), 256);
+    }
+}
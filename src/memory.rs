@@ -1,40 +1,990 @@
 //! Shared memory with configurable access latency (modeling DRAM).
 
-use crate::core::Cycle;
+use crate::core::{CoreId, Cycle};
+use crate::replay::{Decision, DecisionLog};
+use crate::rng::Rng;
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Write};
+use std::ops::Range;
 
 /// Configuration for shared memory.
 #[derive(Clone, Debug)]
 pub struct MemoryConfig {
-    /// Latency in cycles for a memory access (miss penalty).
-    pub access_latency_cycles: u32,
+    /// Latency in cycles for a read (load miss penalty).
+    pub read_latency_cycles: u32,
+    /// Latency in cycles for a write (store miss or write-buffer drain).
+    pub write_latency_cycles: u32,
+    /// Distribution each access's latency is drawn from, for reads and writes alike.
+    /// None = always `read_latency_cycles` / `write_latency_cycles`. With NUMA nodes, each
+    /// node's latency stands in for the model's mean and the model supplies the spread.
+    pub latency_model: Option<LatencyModel>,
+    /// Address regions with their own latency (e.g. scratchpad, HBM), checked in order; the
+    /// first match wins. Unmapped addresses fall back to the NUMA nodes / default latency.
+    pub regions: Vec<MemoryRegion>,
+    /// Bandwidth of each channel in bytes per cycle. 0 = unlimited (no bus contention).
+    pub bytes_per_cycle: usize,
+    /// Independent memory channels; lines are interleaved across them (line % channels).
+    pub num_channels: usize,
+    /// Requests a channel can have in flight at once; more wait for one to finish.
+    /// 0 = unlimited.
+    pub channel_max_in_flight: usize,
+    /// Hard cap on line-fill bandwidth, averaged over `bandwidth_window_cycles`. 0 = no cap.
+    /// The controller will not start a fill that would exceed the window's byte budget.
+    pub max_bytes_per_cycle: usize,
+    /// Length of the rolling window the bandwidth cap is enforced over.
+    pub bandwidth_window_cycles: u64,
+    /// NUMA memory nodes. Empty = one uniform memory with the read / write latencies.
+    pub nodes: Vec<NodeConfig>,
+    /// Extra latency in cycles for an access from a core to a node other than its home node.
+    pub remote_hop_cycles: u32,
+    /// Extra latency per miss already outstanding when a request is issued (contention knee):
+    /// effective latency = base + alpha * outstanding. 0 = no contention effect.
+    pub contention_alpha: u32,
+    /// Bank / row-buffer timing. When set, it replaces the flat (or NUMA node) device latency
+    /// for addresses outside `regions`.
+    pub dram: Option<DramConfig>,
+    /// Energy per line read from memory (load miss), in picojoules.
+    pub read_energy_pj: f64,
+    /// Energy per line written to memory (store miss or write-buffer drain), in picojoules.
+    pub write_energy_pj: f64,
+    /// Background (standby and refresh) energy charged every cycle, in picojoules.
+    pub background_power_pw_per_cycle: f64,
+    /// How the controller orders queued requests to each bank. None = no controller queue:
+    /// every request starts as soon as it arrives, regardless of bank occupancy.
+    pub scheduling_policy: Option<SchedulingPolicy>,
+    /// Store and return data values (sparse, per 8-byte word). Off = timing only.
+    pub functional: bool,
+    /// A DRAM refresh starts every this many cycles. 0 = refresh disabled.
+    pub refresh_interval_cycles: u64,
+    /// Cycles each refresh lasts; requests arriving during a refresh wait for it to finish.
+    pub refresh_duration_cycles: u32,
+    /// Core clock (GHz) the cycle counts above are expressed at; `scaled_to_ghz` rescales
+    /// from it.
+    pub core_clock_ghz: f64,
+}
+
+/// Core clock the technology presets are expressed at.
+pub const PRESET_CORE_GHZ: f64 = 3.0;
+
+/// Wall-clock characteristics of a memory technology, converted to cycles by the presets.
+struct TechnologyTimings {
+    /// Loaded end-to-end latency of a miss.
+    latency_ns: f64,
+    num_channels: usize,
+    /// Peak bandwidth of one channel.
+    channel_gbytes_per_sec: f64,
+    /// Average refresh interval (tREFI).
+    refresh_interval_ns: f64,
+    /// All-bank refresh time (tRFC).
+    refresh_ns: f64,
+}
+
+impl TechnologyTimings {
+    fn to_config(&self, ghz: f64) -> MemoryConfig {
+        MemoryConfig {
+            read_latency_cycles: (self.latency_ns * ghz).round() as u32,
+            write_latency_cycles: (self.latency_ns * ghz).round() as u32,
+            num_channels: self.num_channels,
+            bytes_per_cycle: ((self.channel_gbytes_per_sec / ghz).round() as usize).max(1),
+            refresh_interval_cycles: (self.refresh_interval_ns * ghz).round() as u64,
+            refresh_duration_cycles: (self.refresh_ns * ghz).round() as u32,
+            core_clock_ghz: ghz,
+            ..MemoryConfig::default()
+        }
+    }
+}
+
+impl MemoryConfig {
+    /// Default config with reads and writes both taking `cycles`.
+    pub fn with_access_latency(cycles: u32) -> Self {
+        Self {
+            read_latency_cycles: cycles,
+            write_latency_cycles: cycles,
+            ..Self::default()
+        }
+    }
+
+    /// DDR4-3200: two 64-bit channels, ~80 ns loaded latency, at `PRESET_CORE_GHZ`.
+    pub fn ddr4() -> Self {
+        TechnologyTimings {
+            latency_ns: 80.0,
+            num_channels: 2,
+            channel_gbytes_per_sec: 25.6,
+            refresh_interval_ns: 7800.0,
+            refresh_ns: 350.0,
+        }
+        .to_config(PRESET_CORE_GHZ)
+    }
+
+    /// LPDDR5-6400: four 16-bit channels, ~110 ns loaded latency, at `PRESET_CORE_GHZ`.
+    pub fn lpddr5() -> Self {
+        TechnologyTimings {
+            latency_ns: 110.0,
+            num_channels: 4,
+            channel_gbytes_per_sec: 12.8,
+            refresh_interval_ns: 3900.0,
+            refresh_ns: 280.0,
+        }
+        .to_config(PRESET_CORE_GHZ)
+    }
+
+    /// HBM2 (one stack): eight 128-bit channels, ~100 ns loaded latency, at `PRESET_CORE_GHZ`.
+    pub fn hbm2() -> Self {
+        TechnologyTimings {
+            latency_ns: 100.0,
+            num_channels: 8,
+            channel_gbytes_per_sec: 32.0,
+            refresh_interval_ns: 3900.0,
+            refresh_ns: 260.0,
+        }
+        .to_config(PRESET_CORE_GHZ)
+    }
+
+    /// Re-expresses every cycle count for a core running at `ghz`: latencies stretch and
+    /// per-cycle bandwidth shrinks as the clock gets faster.
+    pub fn scaled_to_ghz(mut self, ghz: f64) -> Self {
+        let factor = ghz / self.core_clock_ghz;
+        let cycles = |c: u32| (c as f64 * factor).round() as u32;
+        let bytes = |b: usize| {
+            if b == 0 {
+                0
+            } else {
+                ((b as f64 / factor).round() as usize).max(1)
+            }
+        };
+        self.read_latency_cycles = cycles(self.read_latency_cycles);
+        self.write_latency_cycles = cycles(self.write_latency_cycles);
+        self.latency_model = self.latency_model.map(|model| match model {
+            LatencyModel::Fixed(c) => LatencyModel::Fixed(cycles(c)),
+            LatencyModel::Uniform { min, max, seed } => LatencyModel::Uniform {
+                min: cycles(min),
+                max: cycles(max),
+                seed,
+            },
+            LatencyModel::Normal { mean, stddev, seed } => LatencyModel::Normal {
+                mean: mean * factor,
+                stddev: stddev * factor,
+                seed,
+            },
+        });
+        for region in &mut self.regions {
+            region.latency_cycles = cycles(region.latency_cycles);
+        }
+        for node in &mut self.nodes {
+            node.latency_cycles = cycles(node.latency_cycles);
+        }
+        self.remote_hop_cycles = cycles(self.remote_hop_cycles);
+        self.contention_alpha = cycles(self.contention_alpha);
+        self.bytes_per_cycle = bytes(self.bytes_per_cycle);
+        self.max_bytes_per_cycle = bytes(self.max_bytes_per_cycle);
+        if let Some(dram) = self.dram.as_mut() {
+            dram.t_cas = cycles(dram.t_cas);
+            dram.t_rcd = cycles(dram.t_rcd);
+            dram.t_rp = cycles(dram.t_rp);
+        }
+        self.refresh_interval_cycles =
+            (self.refresh_interval_cycles as f64 * factor).round() as u64;
+        self.refresh_duration_cycles = cycles(self.refresh_duration_cycles);
+        self.core_clock_ghz = ghz;
+        self
+    }
+
+    /// Upper bound on the cycles one uncontended access can take: the slowest device latency,
+    /// plus the latency model's largest sample and a refresh in the way. A normal model is
+    /// bounded at six standard deviations above its mean.
+    pub fn slowest_access_cycles(&self) -> u64 {
+        let regions = self.regions.iter().map(|r| r.latency_cycles);
+        let nodes = self.nodes.iter().map(|n| n.latency_cycles + self.remote_hop_cycles);
+        let dram = self.dram.as_ref().map(|d| d.t_rp + d.t_rcd + d.t_cas);
+        let device = [self.read_latency_cycles, self.write_latency_cycles]
+            .into_iter()
+            .chain(regions)
+            .chain(nodes)
+            .chain(dram)
+            .max()
+            .unwrap_or(0);
+        let model = match self.latency_model {
+            None => 0,
+            Some(LatencyModel::Fixed(cycles)) => u64::from(cycles),
+            Some(LatencyModel::Uniform { max, .. }) => u64::from(max),
+            Some(LatencyModel::Normal { mean, stddev, .. }) => (mean + 6.0 * stddev).ceil() as u64,
+        };
+        u64::from(device) + model + u64::from(self.refresh_duration_cycles)
+    }
+}
+
+/// How the latency of each memory access is chosen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LatencyModel {
+    /// Every access takes the same number of cycles.
+    Fixed(u32),
+    /// Uniformly distributed in [min, max] cycles.
+    Uniform { min: u32, max: u32, seed: u64 },
+    /// Normally distributed (rounded, clamped at 0).
+    Normal { mean: f64, stddev: f64, seed: u64 },
+}
+
+/// What the controller does with a row after accessing it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PagePolicy {
+    /// Leave the row open: a later access to the same row is a cheap row-buffer hit, but an
+    /// access to a different row in the bank must precharge first.
+    Open,
+    /// Precharge right away: every access pays activate + column access, never precharge.
+    Closed,
+}
+
+/// Order in which the memory controller serves requests queued for a bank.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SchedulingPolicy {
+    /// Oldest request first.
+    Fcfs,
+    /// First-ready FCFS: oldest row-buffer hit first, then oldest request.
+    FrFcfs,
+}
+
+/// DRAM bank and row-buffer timing.
+#[derive(Clone, Debug)]
+pub struct DramConfig {
+    pub num_banks: usize,
+    /// Bytes per DRAM row. Consecutive rows are interleaved across banks.
+    pub row_size_bytes: usize,
+    /// Column access latency (row-buffer hit).
+    pub t_cas: u32,
+    /// Row activate latency.
+    pub t_rcd: u32,
+    /// Precharge latency (closing an open row).
+    pub t_rp: u32,
+    pub page_policy: PagePolicy,
+}
+
+impl Default for DramConfig {
+    fn default() -> Self {
+        Self {
+            num_banks: 8,
+            row_size_bytes: 2048,
+            t_cas: 30,
+            t_rcd: 30,
+            t_rp: 30,
+            page_policy: PagePolicy::Open,
+        }
+    }
+}
+
+impl DramConfig {
+    /// Returns (bank, row) for the given address.
+    pub fn bank_and_row(&self, address: u64) -> (usize, u64) {
+        let global_row = address / self.row_size_bytes as u64;
+        let bank = (global_row % self.num_banks as u64) as usize;
+        (bank, global_row / self.num_banks as u64)
+    }
+}
+
+/// A range of addresses served at a fixed latency (e.g. on-chip scratchpad SRAM).
+#[derive(Clone, Debug)]
+pub struct MemoryRegion {
+    pub address_range: Range<u64>,
+    pub latency_cycles: u32,
+}
+
+/// One NUMA memory node and the addresses placed on it.
+#[derive(Clone, Debug)]
+pub struct NodeConfig {
+    /// Access latency in cycles as seen by a core local to this node.
+    pub latency_cycles: u32,
+    /// Addresses homed on this node. Addresses outside every node's range live on node 0.
+    pub address_range: Range<u64>,
 }
 
 impl Default for MemoryConfig {
     fn default() -> Self {
         Self {
-            access_latency_cycles: 100,
+            read_latency_cycles: 100,
+            write_latency_cycles: 100,
+            latency_model: None,
+            regions: Vec::new(),
+            bytes_per_cycle: 0,
+            num_channels: 1,
+            channel_max_in_flight: 0,
+            max_bytes_per_cycle: 0,
+            bandwidth_window_cycles: 64,
+            nodes: Vec::new(),
+            remote_hop_cycles: 0,
+            contention_alpha: 0,
+            dram: None,
+            read_energy_pj: 0.0,
+            write_energy_pj: 0.0,
+            background_power_pw_per_cycle: 0.0,
+            scheduling_policy: None,
+            functional: false,
+            refresh_interval_cycles: 0,
+            refresh_duration_cycles: 0,
+            core_clock_ghz: PRESET_CORE_GHZ,
         }
     }
 }
 
-/// Shared memory subsystem. Models latency only (no actual data storage for the simulator).
+/// Handle for a request in flight in the memory system.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RequestId(u64);
+
+/// A line-sized request sent to memory on a cache miss or write-buffer drain.
+#[derive(Clone, Copy, Debug)]
+pub struct MemRequest {
+    pub address: u64,
+    /// Bytes moved over the channel (one cache line).
+    pub bytes: usize,
+    /// Home memory node of the requesting core.
+    pub home_node: usize,
+    /// Store miss or write-buffer drain (charged write latency) rather than a load.
+    pub is_write: bool,
+}
+
+/// Timing breakdown of an issued request.
+#[derive(Clone, Copy, Debug)]
+pub struct IssuedRequest {
+    pub id: RequestId,
+    /// Cycle at which the request's data is available.
+    pub completion_cycle: Cycle,
+    /// Cycles the request was held back by a DRAM refresh.
+    pub refresh_delay: u32,
+    /// Cycles the request was held back by the bandwidth cap.
+    pub throttle_delay: u32,
+    /// Channel that served the request.
+    pub channel: usize,
+    /// Cycles the request waited for a free in-flight slot on its channel.
+    pub channel_wait: u32,
+    /// Cycles this request added to its channel's busy time (overlap with earlier requests
+    /// on the same channel is not double counted).
+    pub channel_busy_cycles: u64,
+    pub bus: BusGrant,
+    /// Cycles the request sat in the controller queue before its bank took it.
+    pub queue_wait: u32,
+    /// Whether the DRAM access hit the open row (None without a DRAM model or for regions).
+    pub row_hit: Option<bool>,
+    /// DRAM bank that served the request (None without a DRAM model or for regions).
+    pub bank: Option<usize>,
+    /// Index of the configured region holding the address, if any.
+    pub region: Option<usize>,
+}
+
+/// Traffic seen by one bank or region, for spotting hotspots.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HotspotStats {
+    pub accesses: u64,
+    /// Accesses that had to activate a row (always 0 for regions).
+    pub row_misses: u64,
+    /// Cycles accesses waited in the controller queue.
+    pub queue_wait_cycles: u64,
+}
+
+impl HotspotStats {
+    /// Records one access served here.
+    pub fn record(&mut self, row_hit: Option<bool>, queue_wait: u64) {
+        self.accesses += 1;
+        if row_hit == Some(false) {
+            self.row_misses += 1;
+        }
+        self.queue_wait_cycles += queue_wait;
+    }
+
+    pub fn average_queue_wait(&self) -> f64 {
+        if self.accesses == 0 {
+            return 0.0;
+        }
+        self.queue_wait_cycles as f64 / self.accesses as f64
+    }
+}
+
+/// Per-bank and per-region hotspot report.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemoryStats {
+    /// Indexed by bank (empty without a DRAM model).
+    pub per_bank: Vec<HotspotStats>,
+    /// Indexed like `MemoryConfig::regions`.
+    pub per_region: Vec<HotspotStats>,
+}
+
+impl MemoryStats {
+    /// Share of all DRAM bank accesses that went to the busiest bank (0 if none).
+    pub fn hottest_bank_share(&self) -> f64 {
+        let total: u64 = self.per_bank.iter().map(|b| b.accesses).sum();
+        let max = self.per_bank.iter().map(|b| b.accesses).max().unwrap_or(0);
+        if total == 0 {
+            return 0.0;
+        }
+        max as f64 / total as f64
+    }
+}
+
+/// One access that missed the cache and went to memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryTraceEntry {
+    /// Cycle the request was sent to memory.
+    pub cycle: Cycle,
+    pub core_id: CoreId,
+    pub address: u64,
+    pub is_write: bool,
+    /// Cycles from issue until memory completed the request.
+    pub latency_paid: u64,
+}
+
+/// Writes `entries` one per line as `0x<address> <READ|WRITE> <cycle>`, the plain-text
+/// format accepted by DRAMSim-style DRAM simulators.
+pub fn write_dram_trace<W: Write>(entries: &[MemoryTraceEntry], mut w: W) -> io::Result<()> {
+    for e in entries {
+        let op = if e.is_write { "WRITE" } else { "READ" };
+        writeln!(w, "0x{:x} {} {}", e.address, op, e.cycle)?;
+    }
+    Ok(())
+}
+
+/// Outcome of arbitrating for the shared memory channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BusGrant {
+    /// Cycles spent waiting for the channel to become free.
+    pub wait_cycles: u32,
+    /// Cycles the channel is occupied by this transfer.
+    pub transfer_cycles: u32,
+}
+
+/// Occupancy of one memory channel.
+#[derive(Clone, Debug, Default)]
+struct Channel {
+    /// First cycle at which the channel's data bus is free.
+    bus_free_at: Cycle,
+    /// Completion cycles of requests occupying an in-flight slot.
+    in_flight: Vec<Cycle>,
+    /// Latest completion cycle of any request on this channel.
+    busy_until: Cycle,
+}
+
+/// Shared memory subsystem. Models latency, and optionally data values (`functional`).
+#[derive(Clone)]
 pub struct Memory {
     config: MemoryConfig,
+    channels: Vec<Channel>,
+    /// Core that has priority in the next round-robin arbitration.
+    next_core: usize,
+    /// Source of latency samples for randomized latency models.
+    rng: Rng,
+    /// Records or replays the sampled latencies (see [`Memory::record_decisions`]).
+    decisions: DecisionLog,
+    /// Completion cycle of each tracked in-flight request.
+    in_flight: HashMap<RequestId, Cycle>,
+    next_request_id: u64,
+    /// Row currently held in each bank's row buffer (DRAM model only).
+    open_rows: Vec<Option<u64>>,
+    /// (start cycle, bytes) of fills inside the current bandwidth-cap window, oldest first.
+    recent_fills: VecDeque<(Cycle, usize)>,
+    /// Misses currently outstanding across all cores (see `begin_access` / `end_access`).
+    outstanding: usize,
+    /// Word-aligned address -> value, for functional mode.
+    data: HashMap<u64, u64>,
+    /// Requests waiting for their bank, in arrival order (scheduling policy only).
+    queue: Vec<QueuedRequest>,
+    /// Cycle each bank finishes its current request.
+    bank_free_at: Vec<Cycle>,
+    /// Hotspot counters (see [`Memory::stats`]).
+    stats: MemoryStats,
+}
+
+/// A request waiting in the controller queue.
+#[derive(Clone, Copy, Debug)]
+struct QueuedRequest {
+    id: RequestId,
+    request: MemRequest,
+    arrival: Cycle,
 }
 
 impl Memory {
-    pub fn new(config: MemoryConfig) -> Self {
-        Self { config }
+    /// A DRAM config with no banks or zero-byte rows is given one bank and one-byte rows, as
+    /// `bank_and_row` divides by both.
+    pub fn new(mut config: MemoryConfig) -> Self {
+        if let Some(dram) = &mut config.dram {
+            dram.num_banks = dram.num_banks.max(1);
+            dram.row_size_bytes = dram.row_size_bytes.max(1);
+        }
+        let seed = match config.latency_model {
+            Some(LatencyModel::Uniform { seed, .. }) | Some(LatencyModel::Normal { seed, .. }) => {
+                seed
+            }
+            _ => 0,
+        };
+        let num_banks = config.dram.as_ref().map_or(0, |d| d.num_banks);
+        let num_channels = config.num_channels.max(1);
+        let num_regions = config.regions.len();
+        Self {
+            config,
+            channels: vec![Channel::default(); num_channels],
+            next_core: 0,
+            rng: Rng::new(seed),
+            decisions: DecisionLog::Off,
+            in_flight: HashMap::new(),
+            next_request_id: 0,
+            open_rows: vec![None; num_banks],
+            recent_fills: VecDeque::new(),
+            outstanding: 0,
+            data: HashMap::new(),
+            queue: Vec::new(),
+            bank_free_at: vec![0; num_banks.max(1)],
+            stats: MemoryStats {
+                per_bank: vec![HotspotStats::default(); num_banks],
+                per_region: vec![HotspotStats::default(); num_regions],
+            },
+        }
+    }
+
+    /// Back to its state when built from its config: nothing in flight, no data, banks and
+    /// channels idle, the latency sampler reseeded and decision logging off.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.config.clone());
+    }
+
+    /// Returns the number of cycles an access to `address` takes (stall duration): the latency
+    /// of the region containing it, else the default read or write latency (sampled if a model
+    /// is configured).
+    pub fn access_latency_cycles(&mut self, address: u64, is_write: bool) -> u32 {
+        match self.region_of(address) {
+            Some(region) => region.latency_cycles,
+            None => self.default_latency_cycles(is_write),
+        }
+    }
+
+    /// Index of the first configured region containing `address`.
+    pub fn region_index(&self, address: u64) -> Option<usize> {
+        self.config
+            .regions
+            .iter()
+            .position(|r| r.address_range.contains(&address))
+    }
+
+    /// Returns the first configured region containing `address`.
+    pub fn region_of(&self, address: u64) -> Option<&MemoryRegion> {
+        self.config
+            .regions
+            .iter()
+            .find(|r| r.address_range.contains(&address))
+    }
+
+    fn default_latency_cycles(&mut self, is_write: bool) -> u32 {
+        match self.config.latency_model {
+            None if is_write => self.config.write_latency_cycles,
+            None => self.config.read_latency_cycles,
+            Some(model) => self.sample_latency(model),
+        }
     }
 
-    /// Returns the number of cycles a memory access takes (stall duration).
-    pub fn access_latency_cycles(&self) -> u32 {
-        self.config.access_latency_cycles
+    /// Latency of an access served by a NUMA node: the node's latency, spread by the latency
+    /// model around it (the node's latency takes the place of the model's mean).
+    fn node_latency_cycles(&mut self, node_latency: u32) -> u32 {
+        let Some(model) = self.config.latency_model else {
+            return node_latency;
+        };
+        let mean = match model {
+            LatencyModel::Fixed(cycles) => f64::from(cycles),
+            LatencyModel::Uniform { min, max, .. } => (f64::from(min) + f64::from(max)) / 2.0,
+            LatencyModel::Normal { mean, .. } => mean,
+        };
+        let offset = f64::from(self.sample_latency(model)) - mean;
+        (f64::from(node_latency) + offset).round().max(0.0) as u32
+    }
+
+    fn sample_latency(&mut self, model: LatencyModel) -> u32 {
+        match model {
+            LatencyModel::Fixed(cycles) => cycles,
+            LatencyModel::Uniform { min, max, .. } => {
+                let rng = &mut self.rng;
+                self.decisions
+                    .memory_latency(|| rng.range_inclusive(min as u64, max as u64) as u32)
+            }
+            LatencyModel::Normal { mean, stddev, .. } => {
+                let rng = &mut self.rng;
+                self.decisions.memory_latency(|| {
+                    (mean + stddev * rng.next_normal()).round().max(0.0) as u32
+                })
+            }
+        }
+    }
+
+    /// Starts logging every sampled latency, appending to `log`.
+    pub fn record_decisions(&mut self, log: Vec<Decision>) {
+        self.decisions = DecisionLog::Record(log);
+    }
+
+    /// Takes sampled latencies from `log`, in order, instead of the PRNG.
+    pub fn replay_decisions(&mut self, log: &[Decision]) {
+        self.decisions = DecisionLog::Replay(log.iter().copied().collect());
+    }
+
+    /// Decisions logged since [`Memory::record_decisions`].
+    pub fn recorded_decisions(&self) -> &[Decision] {
+        self.decisions.recorded()
+    }
+
+    /// Returns the node the given address is placed on (0 when no nodes are configured).
+    pub fn node_of(&self, address: u64) -> usize {
+        self.config
+            .nodes
+            .iter()
+            .position(|n| n.address_range.contains(&address))
+            .unwrap_or(0)
+    }
+
+    /// Latency of an access to `address` from a core whose home node is `home_node`: a mapped
+    /// region's latency, else the owning node's latency plus the hop penalty if it is remote.
+    pub fn latency_from(&mut self, home_node: usize, address: u64, is_write: bool) -> u32 {
+        if let Some(region) = self.region_of(address) {
+            return region.latency_cycles;
+        }
+        let node_id = self.node_of(address);
+        let device_latency = if self.config.dram.is_some() {
+            self.dram_latency(address, is_write)
+        } else {
+            match self.config.nodes.get(node_id).map(|n| n.latency_cycles) {
+                Some(latency) => self.node_latency_cycles(latency),
+                None => self.default_latency_cycles(is_write),
+            }
+        };
+        if self.config.nodes.is_empty() || node_id == home_node {
+            device_latency
+        } else {
+            device_latency + self.config.remote_hop_cycles
+        }
+    }
+
+    /// Latency of a DRAM access under the configured page policy, updating row-buffer state.
+    fn dram_latency(&mut self, address: u64, is_write: bool) -> u32 {
+        let Some(dram) = self.config.dram.as_ref() else {
+            return self.default_latency_cycles(is_write);
+        };
+        let (bank, row) = dram.bank_and_row(address);
+        match dram.page_policy {
+            PagePolicy::Closed => dram.t_rcd + dram.t_cas,
+            PagePolicy::Open => {
+                let latency = match self.open_rows[bank] {
+                    Some(open) if open == row => dram.t_cas,
+                    Some(_) => dram.t_rp + dram.t_rcd + dram.t_cas,
+                    None => dram.t_rcd + dram.t_cas,
+                };
+                self.open_rows[bank] = Some(row);
+                latency
+            }
+        }
     }
 
     pub fn config(&self) -> &MemoryConfig {
         &self.config
     }
+
+    /// Cycles the channel needs to move `bytes` (0 if bandwidth is unlimited).
+    pub fn transfer_cycles(&self, bytes: usize) -> u32 {
+        if self.config.bytes_per_cycle == 0 {
+            return 0;
+        }
+        bytes.div_ceil(self.config.bytes_per_cycle) as u32
+    }
+
+    /// Orders the cores requesting the channel this cycle round-robin, starting from the core
+    /// after the one served first last time. `cores` must be sorted ascending.
+    pub fn arbitrate(&mut self, cores: &mut [usize], num_cores: usize) {
+        if cores.is_empty() || num_cores == 0 {
+            return;
+        }
+        let start = self.next_core;
+        cores.sort_by_key(|&c| (c + num_cores - start) % num_cores);
+        self.next_core = (cores[0] + 1) % num_cores;
+    }
+
+    /// Cycles a request arriving at `now` must wait for an in-progress refresh to finish.
+    /// Refreshes occupy [k * interval, k * interval + duration) for k >= 1.
+    pub fn refresh_delay(&self, now: Cycle) -> u32 {
+        let interval = self.config.refresh_interval_cycles;
+        if interval == 0 || now < interval {
+            return 0;
+        }
+        let phase = now % interval;
+        let duration = self.config.refresh_duration_cycles as u64;
+        if phase < duration {
+            (duration - phase) as u32
+        } else {
+            0
+        }
+    }
+
+    /// Sends a request to memory at cycle `now`. The caller polls
+    /// [`Memory::completion_cycle`] to learn when it finishes and then calls [`Memory::retire`].
+    pub fn issue(&mut self, request: MemRequest, now: Cycle) -> IssuedRequest {
+        let issued = self.schedule(request, now);
+        self.in_flight.insert(issued.id, issued.completion_cycle);
+        self.record_stats(&issued);
+        issued
+    }
+
+    /// Queues a request at the controller (requires a `scheduling_policy`). It starts when
+    /// [`Memory::tick`] picks it; until then [`Memory::completion_cycle`] returns None.
+    pub fn enqueue(&mut self, request: MemRequest, now: Cycle) -> RequestId {
+        let id = self.next_id();
+        self.queue.push(QueuedRequest {
+            id,
+            request,
+            arrival: now,
+        });
+        id
+    }
+
+    /// Whether requests go through the controller queue ([`Memory::enqueue`] / [`Memory::tick`])
+    /// rather than starting on arrival.
+    pub fn is_queued(&self) -> bool {
+        self.config.scheduling_policy.is_some()
+    }
+
+    /// Starts queued requests whose bank is free at `now`, one per bank, chosen by the
+    /// scheduling policy. Returns the requests started this cycle.
+    pub fn tick(&mut self, now: Cycle) -> Vec<IssuedRequest> {
+        let Some(policy) = self.config.scheduling_policy else {
+            return Vec::new();
+        };
+        let mut started = Vec::new();
+        loop {
+            let ready = |q: &QueuedRequest| self.bank_free_at[self.bank_of(q.request.address)] <= now;
+            let oldest = self.queue.iter().position(ready);
+            let pick = match policy {
+                SchedulingPolicy::Fcfs => oldest,
+                SchedulingPolicy::FrFcfs => self
+                    .queue
+                    .iter()
+                    .position(|q| ready(q) && self.row_hit(q.request.address) == Some(true))
+                    .or(oldest),
+            };
+            let Some(idx) = pick else {
+                break;
+            };
+            let queued = self.queue.remove(idx);
+            let mut issued = self.schedule_as(queued.id, queued.request, now);
+            issued.queue_wait = (now - queued.arrival) as u32;
+            self.record_stats(&issued);
+            let bank = self.bank_of(queued.request.address);
+            self.bank_free_at[bank] = issued.completion_cycle.max(now + 1);
+            self.in_flight.insert(issued.id, issued.completion_cycle);
+            started.push(issued);
+        }
+        started
+    }
+
+    /// Accesses, row misses and queue wait per bank and per region so far.
+    pub fn stats(&self) -> &MemoryStats {
+        &self.stats
+    }
+
+    fn record_stats(&mut self, issued: &IssuedRequest) {
+        let queue_wait = issued.queue_wait as u64;
+        if let Some(bank) = issued.bank {
+            self.stats.per_bank[bank].record(issued.row_hit, queue_wait);
+        }
+        if let Some(region) = issued.region {
+            self.stats.per_region[region].record(None, queue_wait);
+        }
+    }
+
+    /// Requests waiting in the controller queue.
+    pub fn queue_len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Bank holding `address` (0 without a DRAM model).
+    fn bank_of(&self, address: u64) -> usize {
+        self.config
+            .dram
+            .as_ref()
+            .map_or(0, |d| d.bank_and_row(address).0)
+    }
+
+    /// Whether an access to `address` would hit its bank's open row right now.
+    fn row_hit(&self, address: u64) -> Option<bool> {
+        let dram = self.config.dram.as_ref()?;
+        if self.region_of(address).is_some() {
+            return None;
+        }
+        let (bank, row) = dram.bank_and_row(address);
+        Some(dram.page_policy == PagePolicy::Open && self.open_rows[bank] == Some(row))
+    }
+
+    /// Sends a request nobody waits on (e.g. a write-buffer drain). It still occupies the
+    /// channel and is delayed by refresh like any other request, but bypasses the controller
+    /// queue.
+    pub fn post(&mut self, request: MemRequest, now: Cycle) -> IssuedRequest {
+        let issued = self.schedule(request, now);
+        self.record_stats(&issued);
+        issued
+    }
+
+    /// Energy of one line transfer to or from memory, in picojoules.
+    pub fn access_energy_pj(&self, is_write: bool) -> f64 {
+        if is_write {
+            self.config.write_energy_pj
+        } else {
+            self.config.read_energy_pj
+        }
+    }
+
+    pub fn is_functional(&self) -> bool {
+        self.config.functional
+    }
+
+    /// Reads the 8-byte word containing `address` (0 if never written).
+    pub fn read(&self, address: u64) -> u64 {
+        self.data.get(&(address & !7)).copied().unwrap_or(0)
+    }
+
+    /// Writes the 8-byte word containing `address`.
+    pub fn write(&mut self, address: u64, value: u64) {
+        self.data.insert(address & !7, value);
+    }
+
+    /// Notes that a miss has started waiting on memory.
+    pub fn begin_access(&mut self) {
+        self.outstanding += 1;
+    }
+
+    /// Notes that an outstanding miss has completed.
+    pub fn end_access(&mut self) {
+        self.outstanding = self.outstanding.saturating_sub(1);
+    }
+
+    /// Misses currently outstanding across all cores.
+    pub fn outstanding(&self) -> usize {
+        self.outstanding
+    }
+
+    /// Cycle at which the request completes, or None if it is unknown or not yet scheduled.
+    pub fn completion_cycle(&self, id: RequestId) -> Option<Cycle> {
+        self.in_flight.get(&id).copied()
+    }
+
+    /// Stops tracking a completed request.
+    pub fn retire(&mut self, id: RequestId) {
+        self.in_flight.remove(&id);
+    }
+
+    fn next_id(&mut self) -> RequestId {
+        let id = RequestId(self.next_request_id);
+        self.next_request_id += 1;
+        id
+    }
+
+    fn schedule(&mut self, request: MemRequest, now: Cycle) -> IssuedRequest {
+        let id = self.next_id();
+        self.schedule_as(id, request, now)
+    }
+
+    fn schedule_as(&mut self, id: RequestId, request: MemRequest, now: Cycle) -> IssuedRequest {
+        let refresh_delay = self.refresh_delay(now);
+        let arrival = now + refresh_delay as Cycle;
+        let admitted = self.throttle(request.bytes, arrival);
+        let channel = self.channel_of(request.address, request.bytes);
+        let start = self.acquire_slot(channel, admitted);
+        let bus = self.reserve_bus(channel, request.bytes, start);
+        let row_hit = self.row_hit(request.address);
+        let region = self.region_index(request.address);
+        let bank = row_hit.map(|_| self.bank_of(request.address));
+        let latency = self.latency_from(request.home_node, request.address, request.is_write)
+            + self.config.contention_alpha * self.outstanding as u32;
+        let completion_cycle =
+            start + bus.wait_cycles as Cycle + bus.transfer_cycles as Cycle + latency as Cycle;
+
+        let ch = &mut self.channels[channel];
+        ch.in_flight.push(completion_cycle);
+        let channel_busy_cycles = completion_cycle.saturating_sub(start.max(ch.busy_until));
+        ch.busy_until = ch.busy_until.max(completion_cycle);
+        IssuedRequest {
+            id,
+            completion_cycle,
+            refresh_delay,
+            throttle_delay: (admitted - arrival) as u32,
+            channel,
+            channel_wait: (start - admitted) as u32,
+            channel_busy_cycles,
+            bus,
+            queue_wait: 0,
+            row_hit,
+            bank,
+            region,
+        }
+    }
+
+    /// Channel serving `address`: consecutive lines of `line_size` bytes rotate across channels.
+    pub fn channel_of(&self, address: u64, line_size: usize) -> usize {
+        let line_addr = address / line_size.max(1) as u64;
+        (line_addr % self.channels.len() as u64) as usize
+    }
+
+    pub fn num_channels(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Earliest cycle >= `at` with a free in-flight slot on `channel`.
+    fn acquire_slot(&mut self, channel: usize, at: Cycle) -> Cycle {
+        let limit = self.config.channel_max_in_flight;
+        let in_flight = &mut self.channels[channel].in_flight;
+        let mut start = at;
+        loop {
+            in_flight.retain(|&done| done > start);
+            if limit == 0 || in_flight.len() < limit {
+                return start;
+            }
+            start = in_flight.iter().copied().min().unwrap_or(start);
+        }
+    }
+
+    /// Earliest cycle >= `arrival` at which a fill of `bytes` fits the bandwidth cap. Fills
+    /// start in request order; a fill is always allowed into an otherwise empty window.
+    fn throttle(&mut self, bytes: usize, arrival: Cycle) -> Cycle {
+        if self.config.max_bytes_per_cycle == 0 {
+            return arrival;
+        }
+        let window = self.config.bandwidth_window_cycles.max(1);
+        let budget = self.config.max_bytes_per_cycle as u64 * window;
+        let mut start = self
+            .recent_fills
+            .back()
+            .map_or(arrival, |&(last, _)| arrival.max(last));
+        loop {
+            while let Some(&(oldest, _)) = self.recent_fills.front() {
+                if oldest + window <= start {
+                    self.recent_fills.pop_front();
+                } else {
+                    break;
+                }
+            }
+            let used: u64 = self.recent_fills.iter().map(|&(_, b)| b as u64).sum();
+            match self.recent_fills.front() {
+                Some(&(oldest, _)) if used + bytes as u64 > budget => start = oldest + window,
+                _ => break,
+            }
+        }
+        self.recent_fills.push_back((start, bytes));
+        start
+    }
+
+    /// Reserves `channel`'s data bus for a transfer of `bytes` requested at cycle `now`.
+    /// Requests serialize: a transfer starts once all earlier reservations have finished.
+    pub fn reserve_bus(&mut self, channel: usize, bytes: usize, now: Cycle) -> BusGrant {
+        let transfer_cycles = self.transfer_cycles(bytes);
+        if transfer_cycles == 0 {
+            return BusGrant {
+                wait_cycles: 0,
+                transfer_cycles: 0,
+            };
+        }
+        let ch = &mut self.channels[channel];
+        let start = ch.bus_free_at.max(now);
+        ch.bus_free_at = start + transfer_cycles as Cycle;
+        BusGrant {
+            wait_cycles: (start - now) as u32,
+            transfer_cycles,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -43,15 +993,380 @@ mod tests {
 
     #[test]
     fn memory_default_latency() {
-        let mem = Memory::new(MemoryConfig::default());
-        assert_eq!(mem.access_latency_cycles(), 100);
+        let mut mem = Memory::new(MemoryConfig::default());
+        assert_eq!(mem.access_latency_cycles(0, false), 100);
     }
 
     #[test]
     fn memory_custom_latency() {
-        let mem = Memory::new(MemoryConfig {
-            access_latency_cycles: 50,
+        let mut mem = Memory::new(MemoryConfig {
+            read_latency_cycles: 50,
+            write_latency_cycles: 70,
+            ..MemoryConfig::default()
+        });
+        assert_eq!(mem.access_latency_cycles(0, false), 50);
+        assert_eq!(mem.access_latency_cycles(0, true), 70);
+    }
+
+    fn mean_latency(model: LatencyModel, samples: usize) -> f64 {
+        let mut mem = Memory::new(MemoryConfig {
+            latency_model: Some(model),
+            ..MemoryConfig::default()
         });
-        assert_eq!(mem.access_latency_cycles(), 50);
+        let total: u64 = (0..samples)
+            .map(|_| mem.access_latency_cycles(0, false) as u64)
+            .sum();
+        total as f64 / samples as f64
+    }
+
+    #[test]
+    fn memory_jitter_mean_matches_model() {
+        let uniform = mean_latency(
+            LatencyModel::Uniform {
+                min: 80,
+                max: 120,
+                seed: 3,
+            },
+            20_000,
+        );
+        assert!((uniform - 100.0).abs() < 1.0, "uniform mean {uniform}");
+        let normal = mean_latency(
+            LatencyModel::Normal {
+                mean: 150.0,
+                stddev: 20.0,
+                seed: 9,
+            },
+            20_000,
+        );
+        assert!((normal - 150.0).abs() < 1.0, "normal mean {normal}");
+        assert_eq!(mean_latency(LatencyModel::Fixed(70), 10), 70.0);
+    }
+
+    #[test]
+    fn memory_regions_first_match_wins() {
+        let mut mem = Memory::new(MemoryConfig {
+            regions: vec![
+                MemoryRegion {
+                    address_range: 0x1000..0x2000,
+                    latency_cycles: 4,
+                },
+                MemoryRegion {
+                    address_range: 0x0..0x10000,
+                    latency_cycles: 40,
+                },
+            ],
+            ..MemoryConfig::default()
+        });
+        assert_eq!(mem.access_latency_cycles(0x1800, false), 4);
+        assert_eq!(mem.access_latency_cycles(0x2000, false), 40);
+        assert_eq!(mem.access_latency_cycles(0x20000, false), 100);
+    }
+
+    #[test]
+    fn memory_numa_latency_is_jittered_around_the_node() {
+        let mut mem = Memory::new(MemoryConfig {
+            latency_model: Some(LatencyModel::Uniform {
+                min: 80,
+                max: 120,
+                seed: 3,
+            }),
+            nodes: vec![NodeConfig {
+                latency_cycles: 200,
+                address_range: 0..0x1000,
+            }],
+            ..MemoryConfig::default()
+        });
+        let samples: Vec<u32> = (0..20_000).map(|_| mem.latency_from(0, 0x10, false)).collect();
+        let mean = samples.iter().map(|&l| f64::from(l)).sum::<f64>() / samples.len() as f64;
+        assert!((mean - 200.0).abs() < 1.0, "mean {mean}");
+        assert_eq!(samples.iter().min(), Some(&180));
+        assert_eq!(samples.iter().max(), Some(&220));
+    }
+
+    #[test]
+    fn memory_numa_remote_pays_hop() {
+        let mut mem = Memory::new(MemoryConfig {
+            nodes: vec![
+                NodeConfig {
+                    latency_cycles: 80,
+                    address_range: 0..0x1000,
+                },
+                NodeConfig {
+                    latency_cycles: 90,
+                    address_range: 0x1000..0x2000,
+                },
+            ],
+            remote_hop_cycles: 40,
+            ..MemoryConfig::default()
+        });
+        assert_eq!(mem.node_of(0x1800), 1);
+        assert_eq!(mem.node_of(0x9000), 0);
+        assert_eq!(mem.latency_from(0, 0x10, false), 80);
+        assert_eq!(mem.latency_from(0, 0x1010, false), 130);
+        assert_eq!(mem.latency_from(1, 0x10, false), 120);
+    }
+
+    #[test]
+    fn memory_dram_without_banks_uses_one() {
+        let mut mem = Memory::new(MemoryConfig {
+            dram: Some(DramConfig {
+                num_banks: 0,
+                row_size_bytes: 0,
+                ..DramConfig::default()
+            }),
+            ..MemoryConfig::default()
+        });
+        assert_eq!(mem.latency_from(0, 0, false), 60);
+        assert_eq!(mem.latency_from(0, 0, false), 30);
+        assert_eq!(mem.stats().per_bank.len(), 1);
+    }
+
+    #[test]
+    fn memory_open_page_row_hits_and_conflicts() {
+        let dram = DramConfig {
+            num_banks: 2,
+            row_size_bytes: 1024,
+            t_cas: 10,
+            t_rcd: 20,
+            t_rp: 30,
+            page_policy: PagePolicy::Open,
+        };
+        let mut open = Memory::new(MemoryConfig {
+            dram: Some(dram.clone()),
+            ..MemoryConfig::default()
+        });
+        assert_eq!(open.latency_from(0, 0, false), 30); // bank 0 idle: activate + CAS
+        assert_eq!(open.latency_from(0, 64, false), 10); // same row: hit
+        assert_eq!(open.latency_from(0, 1024, false), 30); // bank 1 idle
+        assert_eq!(open.latency_from(0, 2048, false), 60); // bank 0, new row: conflict
+        let mut closed = Memory::new(MemoryConfig {
+            dram: Some(DramConfig {
+                page_policy: PagePolicy::Closed,
+                ..dram
+            }),
+            ..MemoryConfig::default()
+        });
+        assert_eq!(closed.latency_from(0, 0, false), 30);
+        assert_eq!(closed.latency_from(0, 64, false), 30);
+        assert_eq!(closed.latency_from(0, 2048, false), 30);
+    }
+
+    #[test]
+    fn memory_bus_serializes_transfers() {
+        let mut mem = Memory::new(MemoryConfig {
+            bytes_per_cycle: 16,
+            ..MemoryConfig::default()
+        });
+        let first = mem.reserve_bus(0, 64, 10);
+        let second = mem.reserve_bus(0, 64, 10);
+        assert_eq!(first, BusGrant { wait_cycles: 0, transfer_cycles: 4 });
+        assert_eq!(second, BusGrant { wait_cycles: 4, transfer_cycles: 4 });
+    }
+
+    #[test]
+    fn memory_refresh_delays_new_requests() {
+        let mut mem = Memory::new(MemoryConfig {
+            refresh_interval_cycles: 100,
+            refresh_duration_cycles: 10,
+            ..MemoryConfig::with_access_latency(50)
+        });
+        assert_eq!(mem.refresh_delay(50), 0);
+        assert_eq!(mem.refresh_delay(100), 10);
+        assert_eq!(mem.refresh_delay(207), 3);
+        let request = MemRequest {
+            address: 0,
+            bytes: 64,
+            home_node: 0,
+            is_write: false,
+        };
+        let issued = mem.issue(request, 104);
+        assert_eq!(issued.refresh_delay, 6);
+        assert_eq!(mem.completion_cycle(issued.id), Some(160));
+        mem.retire(issued.id);
+        assert_eq!(mem.completion_cycle(issued.id), None);
+    }
+
+    #[test]
+    fn memory_bandwidth_cap_delays_fills() {
+        let mut mem = Memory::new(MemoryConfig {
+            max_bytes_per_cycle: 2,
+            bandwidth_window_cycles: 64,
+            ..MemoryConfig::with_access_latency(10)
+        });
+        // Budget is 128 bytes per 64-cycle window: two 64-byte fills, then wait.
+        let request = MemRequest {
+            address: 0,
+            bytes: 64,
+            home_node: 0,
+            is_write: false,
+        };
+        assert_eq!(mem.issue(request, 0).throttle_delay, 0);
+        assert_eq!(mem.issue(request, 1).throttle_delay, 0);
+        let third = mem.issue(request, 2);
+        assert_eq!(third.throttle_delay, 62);
+        assert_eq!(third.completion_cycle, 74);
+    }
+
+    #[test]
+    fn memory_channels_interleave_and_limit_in_flight() {
+        let mut mem = Memory::new(MemoryConfig {
+            num_channels: 2,
+            channel_max_in_flight: 1,
+            ..MemoryConfig::with_access_latency(50)
+        });
+        let request = |address| MemRequest {
+            address,
+            bytes: 64,
+            home_node: 0,
+            is_write: false,
+        };
+        assert_eq!(mem.channel_of(0, 64), 0);
+        assert_eq!(mem.channel_of(64, 64), 1);
+        let a = mem.issue(request(0), 0);
+        let b = mem.issue(request(64), 0);
+        let c = mem.issue(request(128), 0);
+        assert_eq!((a.channel_wait, a.completion_cycle), (0, 50));
+        assert_eq!((b.channel_wait, b.completion_cycle), (0, 50));
+        assert_eq!((c.channel, c.channel_wait, c.completion_cycle), (0, 50, 100));
+        assert_eq!(c.channel_busy_cycles, 50);
+    }
+
+    #[test]
+    fn memory_contention_scales_with_outstanding() {
+        let mut mem = Memory::new(MemoryConfig {
+            contention_alpha: 10,
+            ..MemoryConfig::with_access_latency(100)
+        });
+        let request = MemRequest {
+            address: 0,
+            bytes: 64,
+            home_node: 0,
+            is_write: false,
+        };
+        assert_eq!(mem.issue(request, 0).completion_cycle, 100);
+        mem.begin_access();
+        mem.begin_access();
+        assert_eq!(mem.issue(request, 0).completion_cycle, 120);
+        mem.end_access();
+        mem.end_access();
+        assert_eq!(mem.outstanding(), 0);
+    }
+
+    #[test]
+    fn memory_presets_pinned() {
+        let ddr4 = MemoryConfig::ddr4();
+        assert_eq!(ddr4.read_latency_cycles, 240);
+        assert_eq!(ddr4.num_channels, 2);
+        assert_eq!(ddr4.bytes_per_cycle, 9);
+        assert_eq!(ddr4.refresh_interval_cycles, 23_400);
+        assert_eq!(ddr4.refresh_duration_cycles, 1050);
+
+        let lpddr5 = MemoryConfig::lpddr5();
+        assert_eq!(lpddr5.read_latency_cycles, 330);
+        assert_eq!(lpddr5.num_channels, 4);
+        assert_eq!(lpddr5.bytes_per_cycle, 4);
+        assert_eq!(lpddr5.refresh_interval_cycles, 11_700);
+        assert_eq!(lpddr5.refresh_duration_cycles, 840);
+
+        let hbm2 = MemoryConfig::hbm2();
+        assert_eq!(hbm2.read_latency_cycles, 300);
+        assert_eq!(hbm2.num_channels, 8);
+        assert_eq!(hbm2.bytes_per_cycle, 11);
+        assert_eq!(hbm2.refresh_interval_cycles, 11_700);
+        assert_eq!(hbm2.refresh_duration_cycles, 780);
+    }
+
+    #[test]
+    fn memory_config_scaled_to_ghz() {
+        let slow = MemoryConfig::ddr4().scaled_to_ghz(1.5);
+        assert_eq!(slow.read_latency_cycles, 120);
+        assert_eq!(slow.bytes_per_cycle, 18);
+        assert_eq!(slow.refresh_duration_cycles, 525);
+        assert_eq!(slow.core_clock_ghz, 1.5);
+        let back = slow.scaled_to_ghz(PRESET_CORE_GHZ);
+        assert_eq!(back.read_latency_cycles, 240);
+    }
+
+    #[test]
+    fn memory_fr_fcfs_serves_row_hit_first() {
+        let run = |policy| {
+            let mut mem = Memory::new(MemoryConfig {
+                dram: Some(DramConfig {
+                    num_banks: 1,
+                    row_size_bytes: 1024,
+                    t_cas: 10,
+                    t_rcd: 10,
+                    t_rp: 10,
+                    page_policy: PagePolicy::Open,
+                }),
+                scheduling_policy: Some(policy),
+                ..MemoryConfig::default()
+            });
+            let request = |address| MemRequest {
+                address,
+                bytes: 64,
+                home_node: 0,
+                is_write: false,
+            };
+            let first = mem.enqueue(request(0), 0);
+            assert_eq!(mem.completion_cycle(first), None);
+            mem.tick(0); // opens row 0, bank busy until 20
+            let other_row = mem.enqueue(request(4096), 1);
+            let same_row = mem.enqueue(request(64), 2);
+            for now in 1..=20 {
+                mem.tick(now);
+            }
+            (mem.completion_cycle(other_row), mem.completion_cycle(same_row))
+        };
+        // FCFS: the older row miss goes first and the row hit then has to reopen row 0.
+        assert_eq!(run(SchedulingPolicy::Fcfs), (Some(50), None));
+        // FR-FCFS: the row hit jumps ahead.
+        assert_eq!(run(SchedulingPolicy::FrFcfs), (None, Some(30)));
+    }
+
+    #[test]
+    fn memory_functional_read_write() {
+        let mut mem = Memory::new(MemoryConfig {
+            functional: true,
+            ..MemoryConfig::default()
+        });
+        assert_eq!(mem.read(0x100), 0);
+        mem.write(0x100, 42);
+        assert_eq!(mem.read(0x104), 42); // same word
+        assert_eq!(mem.read(0x108), 0);
+    }
+
+    #[test]
+    fn memory_dram_trace_format() {
+        let entries = [
+            MemoryTraceEntry {
+                cycle: 3,
+                core_id: CoreId(0),
+                address: 0x1f40,
+                is_write: false,
+                latency_paid: 100,
+            },
+            MemoryTraceEntry {
+                cycle: 9,
+                core_id: CoreId(1),
+                address: 0x40,
+                is_write: true,
+                latency_paid: 100,
+            },
+        ];
+        let mut out = Vec::new();
+        write_dram_trace(&entries, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "0x1f40 READ 3\n0x40 WRITE 9\n");
+    }
+
+    #[test]
+    fn memory_arbitration_round_robin() {
+        let mut mem = Memory::new(MemoryConfig::default());
+        let mut cores = vec![0, 1, 2];
+        mem.arbitrate(&mut cores, 3);
+        assert_eq!(cores, vec![0, 1, 2]);
+        let mut cores = vec![0, 1, 2];
+        mem.arbitrate(&mut cores, 3);
+        assert_eq!(cores, vec![1, 2, 0]);
     }
 }
@@ -1,10 +1,14 @@
 //! Metrics collection: cycles, cache hit/miss, memory stalls, slowdown.
 
-use crate::core::CoreId;
-use std::collections::HashMap;
+use crate::core::{CoreId, PipelineStage, ThreadId};
+use crate::memory::{HotspotStats, IssuedRequest};
+use crate::trace::StallReason;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{self, Write};
 
 /// Per-core and aggregate metrics.
-#[derive(Clone, Default, Debug)]
+#[derive(Clone, Default, Debug, PartialEq)]
 pub struct Metrics {
     /// Total simulation cycles.
     pub total_cycles: u64,
@@ -14,18 +18,304 @@ pub struct Metrics {
     pub cache_hits: u64,
     /// Cache misses.
     pub cache_misses: u64,
+    /// Instruction-cycles lost, by why the instruction could not advance (see
+    /// [`Metrics::stall_breakdown`]).
+    pub stall_cycles_by_reason: HashMap<StallReason, u64>,
     /// Cycles spent stalled on memory (cache miss penalty).
     pub memory_stall_cycles: u64,
-    /// Per-core breakdown (optional).
-    pub per_core: HashMap<CoreId, PerCoreMetrics>,
+    /// Bytes moved between the caches and memory (fills and write-buffer drains).
+    pub bytes_transferred: u64,
+    /// Misses that waited on memory for their data (excludes write-buffered stores).
+    pub memory_requests: u64,
+    /// Sum over misses of cycles from issue to data return.
+    pub miss_latency_cycles: u64,
+    /// Portion of `miss_latency_cycles` spent held back by the bandwidth cap.
+    pub throttle_stall_cycles: u64,
+    /// Cycles misses were held back by DRAM refresh.
+    pub refresh_stall_cycles: u64,
+    /// Cycles the memory channel buses spent transferring lines (summed over channels).
+    pub bus_busy_cycles: u64,
+    /// Cycles misses spent waiting for a memory channel bus.
+    pub bus_wait_cycles: u64,
+    /// Cycles stores waited in Execute because the write buffer was full.
+    pub write_buffer_full_stalls: u64,
+    /// Largest number of entries observed in any core's write buffer.
+    pub write_buffer_max_occupancy: u64,
+    /// Sum over core-cycles of write buffer entries in use (cores with a write buffer only).
+    pub write_buffer_occupancy_cycles: u64,
+    /// Core-cycles the write buffer was sampled.
+    pub write_buffer_cycles: u64,
+    /// Cycles memory operations waited in Execute for an older store (SC) or fence of their
+    /// thread to complete.
+    pub consistency_stall_cycles: u64,
+    /// Cycles fences waited in Execute for older instructions or the write buffer.
+    pub fence_stall_cycles: u64,
+    /// Fences that left Execute.
+    pub fences: u64,
+    /// DRAM accesses that hit the open row.
+    pub row_hits: u64,
+    /// DRAM accesses that had to activate a row.
+    pub row_misses: u64,
+    /// Cycles misses waited in the memory controller queue for their bank.
+    pub queue_wait_cycles: u64,
+    /// Energy spent in the caches, in picojoules.
+    pub cache_energy_pj: f64,
+    /// Energy spent in memory (line transfers plus background), in picojoules.
+    pub memory_energy_pj: f64,
+    /// Sum over cycles and cores of misses outstanding (for achieved MLP).
+    pub outstanding_miss_cycles: u64,
+    /// Core-cycles with at least one miss outstanding.
+    pub miss_active_cycles: u64,
+    /// Misses held in Execute because their core already had `max_outstanding_misses` in flight.
+    pub mlp_limit_stalls: u64,
+    /// Instruction-cycles a memory instruction waited in Execute for a free cache port.
+    pub structural_stall_cycles: u64,
+    /// Misses served by the requesting core's home memory node.
+    pub local_accesses: u64,
+    /// Misses served by a remote memory node.
+    pub remote_accesses: u64,
+    /// Per-memory-channel breakdown of traffic and occupancy.
+    pub per_channel: HashMap<usize, PerChannelMetrics>,
+    /// Per-memory-node breakdown of miss traffic and stalls.
+    pub per_node: HashMap<usize, PerNodeMetrics>,
+    /// Per-DRAM-bank hotspot breakdown (mirrors `Memory::stats`).
+    pub per_bank: HashMap<usize, HotspotStats>,
+    /// Per-configured-region hotspot breakdown (mirrors `Memory::stats`).
+    pub per_region: HashMap<usize, HotspotStats>,
+    /// Per-core breakdown (optional), in core order.
+    pub per_core: BTreeMap<CoreId, PerCoreMetrics>,
+    /// Times a core switched from one thread to another under time slicing.
+    pub context_switches: u64,
+    /// Times an idle core stole work from another core.
+    pub steals: u64,
+    /// Threads moved between cores (manually or by the load balancer).
+    pub migrations: u64,
+    /// Cycles Join instructions waited for their child thread to finish.
+    pub join_stall_cycles: u64,
+    /// Gang quanta skipped because the gang's members could not all run at once.
+    pub gang_blocked_quanta: u64,
+    /// Sum over cycles and cores of reorder buffer entries in use (ROB only).
+    pub rob_occupancy_cycles: u64,
+    /// Core-cycles the ROB was sampled (ROB only).
+    pub rob_cycles: u64,
+    /// Sum over sampled core-cycles of instructions in the fetch buffer (fetch buffer only).
+    pub fetch_buffer_occupancy_cycles: u64,
+    /// Core-cycles the fetch buffer was sampled (fetch buffer only).
+    pub fetch_buffer_cycles: u64,
+    /// Core-cycles issue found the fetch buffer empty while the back end had room and
+    /// instructions were still to be fetched.
+    pub fetch_starved_cycles: u64,
+    /// Core-cycles a finished instruction could not commit behind an older unfinished one (see
+    /// `Simulator::set_in_order_commit`).
+    pub commit_blocked_cycles: u64,
+    /// Core-cycles ready instructions waited because the core's commit width was used up.
+    pub commit_bound_cycles: u64,
+    /// Cycles instructions waited in Execute for a source register (RAW hazards).
+    pub raw_stall_cycles: u64,
+    /// Instructions moved from Fetch to Execute.
+    pub instructions_issued: u64,
+    /// Instructions that committed (left the pipeline), over all cores.
+    pub instructions_committed: u64,
+    /// Committed instructions by kind (see `InstructionKind::name`).
+    pub committed_by_kind: HashMap<&'static str, u64>,
+    /// Loads that took their data from an older store in the load/store queue.
+    pub store_forwards: u64,
+    /// Cycles a core stopped fetching because its load/store queue was full.
+    pub lsq_full_stalls: u64,
+    /// Per barrier id: cycles between the first and the last thread reaching it.
+    pub barrier_imbalance_cycles: HashMap<u64, u64>,
+    /// Lines invalidated in one core's L1 by another core's write.
+    pub coherence_invalidations: u64,
+    /// Misses on lines that were only gone because another core's write invalidated them.
+    pub coherence_misses: u64,
+    /// Dirty lines evicted from an L1 by a miss (each written back to memory).
+    pub writebacks: u64,
+    /// Locks taken.
+    pub lock_acquisitions: u64,
+    /// Locks taken after spinning on another thread's hold.
+    pub contended_lock_acquisitions: u64,
+    /// Atomic read-modify-writes executed.
+    pub atomics: u64,
+    /// Cycles memory operations waited in Execute because an atomic had to run alone.
+    pub atomic_serialization_stalls: u64,
+    /// Branches resolved.
+    pub branches: u64,
+    /// Branches whose direction was mispredicted.
+    pub mispredictions: u64,
+    /// Core-cycles fetch was stopped refilling after a misprediction.
+    pub misprediction_flush_cycles: u64,
+    /// Instructions fetched, wrong-path ones and refetches included.
+    pub instructions_fetched: u64,
+    /// Wrong-path instructions removed from the pipeline when their branch resolved.
+    pub instructions_squashed: u64,
+    /// L1 accesses made by wrong-path instructions.
+    pub wrong_path_cache_accesses: u64,
+    /// Those of them that missed.
+    pub wrong_path_cache_misses: u64,
+    /// Bytes the DMA engine copied.
+    pub dma_bytes: u64,
+    /// Cycles with at least one DMA transfer in flight.
+    pub dma_active_cycles: u64,
+    /// Those of them in which some core issued an instruction to Execute.
+    pub dma_overlap_cycles: u64,
+    /// Core-cycles instructions spent in Execute waiting for a DMA transfer.
+    pub dma_wait_cycles: u64,
+    /// Matrix ops that ran on a matrix unit.
+    pub matrix_ops: u64,
+    /// Cycles matrix ops spent in Execute waiting for their core's busy matrix unit.
+    pub matrix_queue_wait_cycles: u64,
+    /// Windowed snapshots, one per sampling interval (empty unless sampling is enabled).
+    pub samples: Vec<MetricsSample>,
+    /// Core-cycles cores were halted changing frequency.
+    pub dvfs_transition_cycles: u64,
+    /// Per stage: sum over core-cycles of instructions in it (instruction-cycles spent there).
+    pub stage_occupancy: HashMap<PipelineStage, u64>,
+    /// Per stage: core-cycles the core's oldest in-flight instruction was in it.
+    pub oldest_stage_cycles: HashMap<PipelineStage, u64>,
+    /// Core-cycles stage occupancy was sampled.
+    pub stage_sampled_cycles: u64,
+    /// Per-thread breakdown.
+    pub per_thread: HashMap<ThreadId, PerThreadMetrics>,
+    /// Cycle each finished thread's last instruction committed (the cycle it was loaded or
+    /// spawned, for a thread with none). The one record of when a thread completed.
+    pub thread_completion_cycles: HashMap<ThreadId, u64>,
 }
 
-#[derive(Clone, Default, Debug)]
+/// Counters over one sampling interval (see `Simulator::enable_sampling`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetricsSample {
+    /// Cycle the interval ended at.
+    pub cycle: u64,
+    /// Instructions committed per cycle over the interval, all cores together.
+    pub ipc: f64,
+    /// L1 hit rate of the interval's accesses (1.0 if there were none).
+    pub hit_rate: f64,
+    /// Fraction of the interval's core-cycles dominated by memory stalls.
+    pub stall_fraction: f64,
+    /// Instructions each core committed in the interval, by core index.
+    pub per_core_committed: Vec<u64>,
+}
+
+/// The stage that limits throughput (see [`Metrics::bottleneck_report`]).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BottleneckReport {
+    pub stage: PipelineStage,
+    /// Share of all instruction-cycles spent in `stage`.
+    pub share: f64,
+    /// Fraction of core-cycles the oldest instruction was in `stage`.
+    pub oldest_fraction: f64,
+}
+
+impl fmt::Display for BottleneckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({:.1}% of instruction-cycles, oldest instruction there {:.1}% of the time)",
+            self.stage,
+            self.share * 100.0,
+            self.oldest_fraction * 100.0
+        )
+    }
+}
+
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct PerThreadMetrics {
+    /// Cycles the thread held a core (fetching or waiting on its in-flight work).
+    pub run_cycles: u64,
+    /// Instructions of the thread committed so far (progress).
+    pub instructions_committed: u64,
+    pub memory_accesses: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    /// Cycles the thread's instructions spent waiting on memory (summed over instructions).
+    pub memory_stall_cycles: u64,
+    /// Cycles the thread's barriers waited for the other participants.
+    pub barrier_wait_cycles: u64,
+    /// Cycles the thread spun on a lock another thread held.
+    pub lock_wait_cycles: u64,
+    /// Cycles the thread's core spent on interrupts that arrived while it was running.
+    pub interrupted_cycles: u64,
+    /// Times the thread gave up its core with a Yield (under time slicing) or a Sleep.
+    pub voluntary_switches: u64,
+    /// Cycles the thread spent parked by Sleep instructions.
+    pub sleep_cycles: u64,
+    /// (cycle, instructions committed by then), sampled if progress sampling is enabled.
+    pub progress: Vec<(u64, u64)>,
+}
+
+impl PerThreadMetrics {
+    /// Hit rate of the thread's own accesses (1.0 if it made none).
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.cache_hits + self.cache_misses;
+        if total == 0 {
+            return 1.0;
+        }
+        self.cache_hits as f64 / total as f64
+    }
+
+    /// Instructions committed per cycle the thread held a core (or hardware context).
+    pub fn ipc(&self) -> f64 {
+        if self.run_cycles == 0 {
+            return 0.0;
+        }
+        self.instructions_committed as f64 / self.run_cycles as f64
+    }
+}
+
+#[derive(Clone, Default, Debug, PartialEq)]
 pub struct PerCoreMetrics {
     pub memory_accesses: u64,
     pub cache_hits: u64,
     pub cache_misses: u64,
     pub memory_stall_cycles: u64,
+    pub bus_wait_cycles: u64,
+    /// Cycles the core had at least one instruction in flight.
+    pub busy_cycles: u64,
+    /// Cycles the core had nothing in flight and nothing to fetch.
+    pub idle_cycles: u64,
+    /// Busy cycles in which at least half the in-flight instructions waited on memory.
+    pub stall_dominated_cycles: u64,
+    /// Thread switches on this core under time slicing.
+    pub context_switches: u64,
+    /// Threads migrated onto this core.
+    pub migrations: u64,
+    pub instructions_committed: u64,
+    /// Cycles of the core's own clock (fewer than the run's when slowed by DVFS).
+    pub clock_cycles: u64,
+    /// Instructions the core moved from Fetch to Execute.
+    pub instructions_issued: u64,
+    /// Cycles the core's misses spent crossing the interconnect to the L2.
+    pub interconnect_cycles: u64,
+    /// Cycles the core ran throttled for temperature.
+    pub throttled_cycles: u64,
+    /// Times the core's temperature crossed the throttle threshold.
+    pub throttle_events: u64,
+    /// Sum of the core's temperature at the end of each power window, in degrees C.
+    pub temperature_sum_c: f64,
+    /// Power windows the core's temperature was sampled.
+    pub temperature_windows: u64,
+    /// Cycles the core's matrix unit was working on an op.
+    pub matrix_busy_cycles: u64,
+    /// Interrupts the core finished servicing.
+    pub interrupts_serviced: u64,
+    /// Cycles the core spent on interrupts instead of its threads: draining, entry, handler
+    /// and exit.
+    pub interrupt_overhead_cycles: u64,
+    /// The core's share of `Metrics::stall_cycles_by_reason`.
+    pub stall_cycles_by_reason: HashMap<StallReason, u64>,
+}
+
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct PerNodeMetrics {
+    pub accesses: u64,
+    pub stall_cycles: u64,
+}
+
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct PerChannelMetrics {
+    pub accesses: u64,
+    /// Cycles with at least one request in flight on the channel.
+    pub busy_cycles: u64,
 }
 
 impl Metrics {
@@ -33,14 +323,15 @@ impl Metrics {
         Self::default()
     }
 
-    pub fn record_access(&mut self, core_id: CoreId, hit: bool, stall_cycles: u64) {
+    /// Counts one L1 access. Stall cycles are counted separately, cycle by cycle, while the
+    /// access waits in the Memory stage.
+    pub fn record_access(&mut self, core_id: CoreId, thread_id: ThreadId, hit: bool) {
         self.total_memory_accesses += 1;
         if hit {
             self.cache_hits += 1;
         } else {
             self.cache_misses += 1;
         }
-        self.memory_stall_cycles += stall_cycles;
         let per = self.per_core.entry(core_id).or_default();
         per.memory_accesses += 1;
         if hit {
@@ -48,7 +339,31 @@ impl Metrics {
         } else {
             per.cache_misses += 1;
         }
-        per.memory_stall_cycles += stall_cycles;
+        let per = self.per_thread.entry(thread_id).or_default();
+        per.memory_accesses += 1;
+        if hit {
+            per.cache_hits += 1;
+        } else {
+            per.cache_misses += 1;
+        }
+    }
+
+    pub fn record_bus_transfer(&mut self, core_id: CoreId, wait_cycles: u64, transfer_cycles: u64) {
+        self.bus_busy_cycles += transfer_cycles;
+        self.bus_wait_cycles += wait_cycles;
+        self.per_core.entry(core_id).or_default().bus_wait_cycles += wait_cycles;
+    }
+
+    /// Records a miss served by memory node `node`, attributing its stall to that node.
+    pub fn record_node_access(&mut self, node: usize, remote: bool, stall_cycles: u64) {
+        if remote {
+            self.remote_accesses += 1;
+        } else {
+            self.local_accesses += 1;
+        }
+        let per = self.per_node.entry(node).or_default();
+        per.accesses += 1;
+        per.stall_cycles += stall_cycles;
     }
 
     pub fn hit_rate(&self) -> f64 {
@@ -67,6 +382,312 @@ impl Metrics {
         self.cache_misses as f64 / total as f64
     }
 
+    /// Records a request served by memory channel `channel`.
+    pub fn record_channel_access(&mut self, channel: usize, busy_cycles: u64) {
+        let per = self.per_channel.entry(channel).or_default();
+        per.accesses += 1;
+        per.busy_cycles += busy_cycles;
+    }
+
+    /// Fraction of simulated cycles `channel` had a request in flight (0..1).
+    pub fn channel_utilization(&self, channel: usize) -> f64 {
+        if self.total_cycles == 0 {
+            return 0.0;
+        }
+        let busy = self.per_channel.get(&channel).map_or(0, |c| c.busy_cycles);
+        (busy as f64 / self.total_cycles as f64).min(1.0)
+    }
+
+    /// Records one miss sent to memory and how long it took to return data.
+    pub fn record_miss_latency(&mut self, latency_cycles: u64, throttle_cycles: u64) {
+        self.memory_requests += 1;
+        self.miss_latency_cycles += latency_cycles;
+        self.throttle_stall_cycles += throttle_cycles;
+    }
+
+    /// Cache plus memory energy, in picojoules.
+    pub fn total_energy_pj(&self) -> f64 {
+        self.cache_energy_pj + self.memory_energy_pj
+    }
+
+    /// Records whether a DRAM access hit the open row.
+    pub fn record_row_access(&mut self, hit: bool) {
+        if hit {
+            self.row_hits += 1;
+        } else {
+            self.row_misses += 1;
+        }
+    }
+
+    /// Records which bank / region served a request, for the hotspot breakdown.
+    pub fn record_hotspot(&mut self, issued: &IssuedRequest) {
+        let queue_wait = issued.queue_wait as u64;
+        if let Some(bank) = issued.bank {
+            self.per_bank
+                .entry(bank)
+                .or_default()
+                .record(issued.row_hit, queue_wait);
+        }
+        if let Some(region) = issued.region {
+            self.per_region
+                .entry(region)
+                .or_default()
+                .record(None, queue_wait);
+        }
+    }
+
+    /// Mean completion cycle of the finished threads (turnaround, as every thread is loaded
+    /// at cycle 0).
+    pub fn average_turnaround(&self) -> f64 {
+        let done = &self.thread_completion_cycles;
+        if done.is_empty() {
+            return 0.0;
+        }
+        done.values().sum::<u64>() as f64 / done.len() as f64
+    }
+
+    /// Fraction of DRAM accesses that hit the open row.
+    pub fn row_hit_rate(&self) -> f64 {
+        let total = self.row_hits + self.row_misses;
+        if total == 0 {
+            return 0.0;
+        }
+        self.row_hits as f64 / total as f64
+    }
+
+    /// Records that a core had `outstanding` misses in flight this cycle.
+    pub fn record_outstanding_misses(&mut self, outstanding: usize) {
+        if outstanding > 0 {
+            self.outstanding_miss_cycles += outstanding as u64;
+            self.miss_active_cycles += 1;
+        }
+    }
+
+    /// Achieved memory-level parallelism: average misses in flight per core while it had any.
+    pub fn mlp(&self) -> f64 {
+        if self.miss_active_cycles == 0 {
+            return 0.0;
+        }
+        self.outstanding_miss_cycles as f64 / self.miss_active_cycles as f64
+    }
+
+    /// Instructions issued (Fetch to Execute) per cycle, over all cores.
+    pub fn issue_rate(&self) -> f64 {
+        if self.total_cycles == 0 {
+            return 0.0;
+        }
+        self.instructions_issued as f64 / self.total_cycles as f64
+    }
+
+    /// Instructions committed per cycle, over all cores.
+    pub fn ipc(&self) -> f64 {
+        if self.total_cycles == 0 {
+            return 0.0;
+        }
+        self.instructions_committed as f64 / self.total_cycles as f64
+    }
+
+    /// Instructions `core_id` committed per cycle of the run (0.0 if unknown).
+    pub fn ipc_for_core(&self, core_id: CoreId) -> f64 {
+        match self.per_core.get(&core_id) {
+            Some(per) if self.total_cycles > 0 => {
+                per.instructions_committed as f64 / self.total_cycles as f64
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Instructions `core_id` committed per cycle of its own clock, which discounts running
+    /// at a lower frequency (0.0 if unknown).
+    pub fn ipc_per_core_clock(&self, core_id: CoreId) -> f64 {
+        match self.per_core.get(&core_id) {
+            Some(per) if per.clock_cycles > 0 => {
+                per.instructions_committed as f64 / per.clock_cycles as f64
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Average interconnect cycles per miss of `core_id` (0.0 if it never missed).
+    pub fn average_interconnect_latency(&self, core_id: CoreId) -> f64 {
+        match self.per_core.get(&core_id) {
+            Some(per) if per.cache_misses > 0 => {
+                per.interconnect_cycles as f64 / per.cache_misses as f64
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Average temperature of `core_id` over the power windows, in degrees C (0.0 without a
+    /// power model).
+    pub fn average_temperature(&self, core_id: CoreId) -> f64 {
+        match self.per_core.get(&core_id) {
+            Some(per) if per.temperature_windows > 0 => {
+                per.temperature_sum_c / per.temperature_windows as f64
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Fraction of the run `core_id`'s matrix unit was busy.
+    pub fn matrix_unit_utilization(&self, core_id: CoreId) -> f64 {
+        match self.per_core.get(&core_id) {
+            Some(per) if self.total_cycles > 0 => {
+                per.matrix_busy_cycles as f64 / self.total_cycles as f64
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Attributes `cycles` instruction-cycles on `core_id` to `reason`.
+    pub fn record_stall(&mut self, core_id: CoreId, reason: StallReason, cycles: u64) {
+        *self.stall_cycles_by_reason.entry(reason).or_default() += cycles;
+        let per = self.per_core.entry(core_id).or_default();
+        *per.stall_cycles_by_reason.entry(reason).or_default() += cycles;
+    }
+
+    /// Stall cycles per reason, largest first (ties in reason order).
+    pub fn stall_breakdown(&self) -> Vec<(StallReason, u64)> {
+        let mut breakdown: Vec<(StallReason, u64)> =
+            self.stall_cycles_by_reason.iter().map(|(&r, &c)| (r, c)).collect();
+        breakdown.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        breakdown
+    }
+
+    /// Records that a core's reorder buffer held `entries` instructions this cycle.
+    pub fn record_rob_occupancy(&mut self, entries: usize) {
+        self.rob_occupancy_cycles += entries as u64;
+        self.rob_cycles += 1;
+    }
+
+    /// Records that a core's fetch buffer held `entries` instructions this cycle.
+    pub fn record_fetch_buffer_occupancy(&mut self, entries: usize) {
+        self.fetch_buffer_occupancy_cycles += entries as u64;
+        self.fetch_buffer_cycles += 1;
+    }
+
+    /// Samples one core's pipeline for a cycle, oldest instruction first.
+    pub fn record_stage_occupancy(&mut self, stages: impl IntoIterator<Item = PipelineStage>) {
+        let mut stages = stages.into_iter();
+        if let Some(oldest) = stages.next() {
+            *self.oldest_stage_cycles.entry(oldest).or_default() += 1;
+            *self.stage_occupancy.entry(oldest).or_default() += 1;
+        }
+        for stage in stages {
+            *self.stage_occupancy.entry(stage).or_default() += 1;
+        }
+        self.stage_sampled_cycles += 1;
+    }
+
+    /// Average instructions in `stage` per core-cycle.
+    pub fn average_stage_occupancy(&self, stage: PipelineStage) -> f64 {
+        if self.stage_sampled_cycles == 0 {
+            return 0.0;
+        }
+        let occupancy = self.stage_occupancy.get(&stage).copied().unwrap_or(0);
+        occupancy as f64 / self.stage_sampled_cycles as f64
+    }
+
+    /// Fraction of core-cycles the oldest in-flight instruction was in `stage`.
+    pub fn oldest_stage_fraction(&self, stage: PipelineStage) -> f64 {
+        if self.stage_sampled_cycles == 0 {
+            return 0.0;
+        }
+        let cycles = self.oldest_stage_cycles.get(&stage).copied().unwrap_or(0);
+        cycles as f64 / self.stage_sampled_cycles as f64
+    }
+
+    /// The stage instructions spent the most cycles in (None before anything ran).
+    pub fn bottleneck_report(&self) -> Option<BottleneckReport> {
+        let total: u64 = self.stage_occupancy.values().sum();
+        let (stage, cycles) = PipelineStage::ALL
+            .iter()
+            .map(|&s| (s, self.stage_occupancy.get(&s).copied().unwrap_or(0)))
+            .max_by_key(|&(_, cycles)| cycles)?;
+        if total == 0 {
+            return None;
+        }
+        Some(BottleneckReport {
+            stage,
+            share: cycles as f64 / total as f64,
+            oldest_fraction: self.oldest_stage_fraction(stage),
+        })
+    }
+
+    /// Average write buffer entries in use per core-cycle (0.0 without a write buffer).
+    pub fn average_write_buffer_occupancy(&self) -> f64 {
+        if self.write_buffer_cycles == 0 {
+            return 0.0;
+        }
+        self.write_buffer_occupancy_cycles as f64 / self.write_buffer_cycles as f64
+    }
+
+    /// Fraction of the cycles with DMA in flight that the cores kept computing through.
+    pub fn dma_overlap_efficiency(&self) -> f64 {
+        if self.dma_active_cycles == 0 {
+            return 0.0;
+        }
+        self.dma_overlap_cycles as f64 / self.dma_active_cycles as f64
+    }
+
+    /// Average reorder buffer entries in use per core-cycle (0.0 without a ROB).
+    pub fn average_rob_occupancy(&self) -> f64 {
+        if self.rob_cycles == 0 {
+            return 0.0;
+        }
+        self.rob_occupancy_cycles as f64 / self.rob_cycles as f64
+    }
+
+    /// Average fetch buffer entries in use per core-cycle (0.0 without a fetch buffer).
+    pub fn average_fetch_buffer_occupancy(&self) -> f64 {
+        if self.fetch_buffer_cycles == 0 {
+            return 0.0;
+        }
+        self.fetch_buffer_occupancy_cycles as f64 / self.fetch_buffer_cycles as f64
+    }
+
+    /// Fraction of the run `core_id` had at least one instruction in flight (0.0 if unknown).
+    pub fn core_utilization(&self, core_id: CoreId) -> f64 {
+        match self.per_core.get(&core_id) {
+            Some(per) if self.total_cycles > 0 => {
+                per.busy_cycles as f64 / self.total_cycles as f64
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Average effective miss latency in cycles (issue to data return).
+    pub fn average_miss_latency(&self) -> f64 {
+        if self.memory_requests == 0 {
+            return 0.0;
+        }
+        self.miss_latency_cycles as f64 / self.memory_requests as f64
+    }
+
+    /// Achieved memory bandwidth in bytes per cycle over the whole run.
+    pub fn achieved_bandwidth(&self) -> f64 {
+        if self.total_cycles == 0 {
+            return 0.0;
+        }
+        self.bytes_transferred as f64 / self.total_cycles as f64
+    }
+
+    /// Fraction of total miss latency caused by bandwidth throttling (0..1).
+    pub fn throttle_fraction(&self) -> f64 {
+        if self.miss_latency_cycles == 0 {
+            return 0.0;
+        }
+        self.throttle_stall_cycles as f64 / self.miss_latency_cycles as f64
+    }
+
+    /// Fraction of simulated cycles the shared memory channel was busy (0..1).
+    pub fn bandwidth_utilization(&self) -> f64 {
+        if self.total_cycles == 0 {
+            return 0.0;
+        }
+        (self.bus_busy_cycles as f64 / self.total_cycles as f64).min(1.0)
+    }
+
     /// Slowdown = (actual_cycles - ideal_cycles) / ideal_cycles, or 0 if ideal is 0.
     pub fn slowdown_vs_ideal(&self, ideal_cycles: u64) -> f64 {
         if ideal_cycles == 0 {
@@ -83,6 +704,32 @@ impl Metrics {
     pub fn slowdown_percent(&self, ideal_cycles: u64) -> f64 {
         self.slowdown_vs_ideal(ideal_cycles) * 100.0
     }
+
+    /// Writes the per-core breakdown as CSV with a header row, cores in ascending id order.
+    pub fn write_per_core_csv<W: Write>(&self, mut w: W) -> io::Result<()> {
+        writeln!(
+            w,
+            "core,memory_accesses,cache_hits,cache_misses,memory_stall_cycles,busy_cycles,\
+             idle_cycles,instructions_committed,context_switches,utilization"
+        )?;
+        for (core_id, per) in &self.per_core {
+            writeln!(
+                w,
+                "{},{},{},{},{},{},{},{},{},{:.4}",
+                core_id.0,
+                per.memory_accesses,
+                per.cache_hits,
+                per.cache_misses,
+                per.memory_stall_cycles,
+                per.busy_cycles,
+                per.idle_cycles,
+                per.instructions_committed,
+                per.context_switches,
+                self.core_utilization(*core_id)
+            )?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -99,13 +746,38 @@ mod tests {
     #[test]
     fn metrics_hit_miss_rates() {
         let mut m = Metrics::new();
-        m.record_access(CoreId(0), true, 0);
-        m.record_access(CoreId(0), true, 0);
-        m.record_access(CoreId(0), false, 100);
+        m.record_access(CoreId(0), ThreadId(0), true);
+        m.record_access(CoreId(0), ThreadId(0), true);
+        m.record_access(CoreId(0), ThreadId(0), false);
         assert_eq!(m.total_memory_accesses, 3);
         assert!((m.hit_rate() - 2.0 / 3.0).abs() < 1e-9);
         assert!((m.miss_rate() - 1.0 / 3.0).abs() < 1e-9);
-        assert_eq!(m.memory_stall_cycles, 100);
+        assert_eq!(m.memory_stall_cycles, 0);
+    }
+
+    #[test]
+    fn metrics_core_utilization() {
+        let mut m = Metrics::new();
+        assert_eq!(m.core_utilization(CoreId(0)), 0.0);
+        m.total_cycles = 200;
+        m.per_core.entry(CoreId(0)).or_default().busy_cycles = 150;
+        assert!((m.core_utilization(CoreId(0)) - 0.75).abs() < 1e-9);
+        assert_eq!(m.core_utilization(CoreId(1)), 0.0);
+    }
+
+    #[test]
+    fn metrics_bottleneck_is_the_busiest_stage() {
+        let mut m = Metrics::new();
+        assert_eq!(m.bottleneck_report(), None);
+        use PipelineStage::*;
+        m.record_stage_occupancy([Memory, Execute, Fetch]);
+        m.record_stage_occupancy([Memory, Memory]);
+        m.record_stage_occupancy([]);
+        assert!((m.average_stage_occupancy(Memory) - 1.0).abs() < 1e-9);
+        assert!((m.oldest_stage_fraction(Memory) - 2.0 / 3.0).abs() < 1e-9);
+        let report = m.bottleneck_report().unwrap();
+        assert_eq!(report.stage, Memory);
+        assert!((report.share - 0.6).abs() < 1e-9);
     }
 
     #[test]
@@ -115,4 +787,27 @@ mod tests {
         let ideal = 100;
         assert!((m.slowdown_percent(ideal) - 17.0).abs() < 0.01);
     }
+
+    #[test]
+    fn metrics_achieved_bandwidth_and_throttle_fraction() {
+        let mut m = Metrics::new();
+        m.total_cycles = 200;
+        m.bytes_transferred = 640;
+        m.record_miss_latency(100, 25);
+        m.record_miss_latency(100, 0);
+        assert!((m.achieved_bandwidth() - 3.2).abs() < 1e-9);
+        assert!((m.throttle_fraction() - 0.125).abs() < 1e-9);
+        assert!((m.average_miss_latency() - 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn metrics_bandwidth_utilization() {
+        let mut m = Metrics::new();
+        assert_eq!(m.bandwidth_utilization(), 0.0);
+        m.total_cycles = 100;
+        m.record_bus_transfer(CoreId(1), 3, 4);
+        m.record_bus_transfer(CoreId(1), 0, 4);
+        assert!((m.bandwidth_utilization() - 0.08).abs() < 1e-9);
+        assert_eq!(m.per_core[&CoreId(1)].bus_wait_cycles, 3);
+    }
 }
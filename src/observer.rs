@@ -0,0 +1,45 @@
+//! Observer hooks: callbacks on pipeline events, for visualizers and custom analyses.
+
+use crate::core::{CoreId, Cycle, Instruction, PipelineStage};
+
+/// Receives pipeline events from `Simulator::step` (register with `Simulator::add_observer`).
+/// Every method defaults to doing nothing; implement the ones you need. Each gets the cycle,
+/// the core, and the instruction as it is at the event.
+pub trait SimObserver {
+    /// The instruction entered the pipeline (Fetch stage).
+    fn on_fetch(&mut self, _cycle: Cycle, _core_id: CoreId, _instr: &Instruction) {}
+
+    /// The instruction moved from stage `old` to stage `new`.
+    fn on_stage_change(
+        &mut self,
+        _cycle: Cycle,
+        _core_id: CoreId,
+        _instr: &Instruction,
+        _old: PipelineStage,
+        _new: PipelineStage,
+    ) {
+    }
+
+    /// The instruction accessed its core's L1.
+    fn on_cache_access(
+        &mut self,
+        _cycle: Cycle,
+        _core_id: CoreId,
+        _instr: &Instruction,
+        _hit: bool,
+    ) {
+    }
+
+    /// The instruction missed and started waiting on memory.
+    fn on_stall_begin(&mut self, _cycle: Cycle, _core_id: CoreId, _instr: &Instruction) {}
+
+    /// The instruction's data returned from memory.
+    fn on_stall_end(&mut self, _cycle: Cycle, _core_id: CoreId, _instr: &Instruction) {}
+
+    /// The instruction committed and left the pipeline.
+    fn on_commit(&mut self, _cycle: Cycle, _core_id: CoreId, _instr: &Instruction) {}
+
+    /// The instruction left the pipeline without committing: squashed down a wrong path, or
+    /// flushed to be fetched again (under a new id).
+    fn on_squash(&mut self, _cycle: Cycle, _core_id: CoreId, _instr: &Instruction) {}
+}
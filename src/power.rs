@@ -0,0 +1,105 @@
+//! Activity-based core power and a thermal model that throttles hot cores.
+
+/// Power and thermal parameters (see `Simulator::set_power_model`).
+#[derive(Clone, Debug)]
+pub struct PowerConfig {
+    /// Cycles per power window; temperature is updated once per window.
+    pub window_cycles: u64,
+    /// Energy per instruction issued to Execute.
+    pub instruction_energy_pj: f64,
+    /// Energy per L1 access.
+    pub cache_access_energy_pj: f64,
+    /// Temperature of an idle core, in degrees C.
+    pub ambient_c: f64,
+    /// Degrees C a window's energy heats the core by, per pJ.
+    pub heating_c_per_pj: f64,
+    /// Fraction of the excess over ambient lost to cooling each window.
+    pub leak: f64,
+    /// Temperature above which the core is throttled.
+    pub throttle_threshold_c: f64,
+    /// Degrees below the threshold the core must cool to before it runs at full speed again.
+    pub hysteresis_c: f64,
+    /// Clock ratio applied on top of the core's own while throttled.
+    pub throttle_ratio: f64,
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self {
+            window_cycles: 100,
+            instruction_energy_pj: 10.0,
+            cache_access_energy_pj: 20.0,
+            ambient_c: 45.0,
+            heating_c_per_pj: 0.002,
+            leak: 0.1,
+            throttle_threshold_c: 85.0,
+            hysteresis_c: 5.0,
+            throttle_ratio: 0.5,
+        }
+    }
+}
+
+/// A core's temperature and the activity of its current window.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThermalState {
+    pub temperature_c: f64,
+    pub throttled: bool,
+    /// Energy spent so far in the current window.
+    window_energy_pj: f64,
+}
+
+impl ThermalState {
+    pub fn new(config: &PowerConfig) -> Self {
+        Self {
+            temperature_c: config.ambient_c,
+            throttled: false,
+            window_energy_pj: 0.0,
+        }
+    }
+
+    /// Adds one cycle's activity to the current window.
+    pub fn record_activity(&mut self, config: &PowerConfig, issued: u64, cache_accesses: u64) {
+        self.window_energy_pj += issued as f64 * config.instruction_energy_pj
+            + cache_accesses as f64 * config.cache_access_energy_pj;
+    }
+
+    /// Closes the window: heats by its energy, cools toward ambient, then throttles above the
+    /// threshold or releases below it minus the hysteresis. Returns whether throttling began.
+    pub fn end_window(&mut self, config: &PowerConfig) -> bool {
+        let excess = self.temperature_c - config.ambient_c;
+        self.temperature_c +=
+            self.window_energy_pj * config.heating_c_per_pj - config.leak * excess;
+        self.window_energy_pj = 0.0;
+        let was_throttled = self.throttled;
+        if self.temperature_c > config.throttle_threshold_c {
+            self.throttled = true;
+        } else if self.temperature_c < config.throttle_threshold_c - config.hysteresis_c {
+            self.throttled = false;
+        }
+        self.throttled && !was_throttled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thermal_heats_throttles_and_cools() {
+        let config = PowerConfig::default();
+        let mut state = ThermalState::new(&config);
+        let mut began = 0;
+        for _ in 0..100 {
+            state.record_activity(&config, 400, 0);
+            began += u32::from(state.end_window(&config));
+        }
+        assert!(state.throttled);
+        assert_eq!(began, 1);
+        // Without activity it cools back below the hysteresis band.
+        for _ in 0..100 {
+            state.end_window(&config);
+        }
+        assert!(!state.throttled);
+        assert!((state.temperature_c - config.ambient_c).abs() < 1.0);
+    }
+}
@@ -0,0 +1,48 @@
+//! Record/replay of the simulator's nondeterministic decisions, for reproducing a run exactly
+//! without relying on its PRNG seeds.
+
+use std::collections::VecDeque;
+
+/// One nondeterministic decision the simulator made.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decision {
+    /// A memory latency sampled from a randomized [`crate::memory::LatencyModel`].
+    MemoryLatency(u32),
+}
+
+/// Whether decisions are drawn fresh, logged as they are made, or taken from a log.
+#[derive(Clone, Debug, Default)]
+pub enum DecisionLog {
+    #[default]
+    Off,
+    Record(Vec<Decision>),
+    Replay(VecDeque<Decision>),
+}
+
+impl DecisionLog {
+    /// A memory latency: sampled via `sample`, unless replaying, in which case the next
+    /// logged decision is used. Panics if the log is exhausted, meaning the replayed run has
+    /// diverged from the recorded one.
+    pub fn memory_latency(&mut self, sample: impl FnOnce() -> u32) -> u32 {
+        match self {
+            DecisionLog::Off => sample(),
+            DecisionLog::Record(log) => {
+                let cycles = sample();
+                log.push(Decision::MemoryLatency(cycles));
+                cycles
+            }
+            DecisionLog::Replay(log) => match log.pop_front() {
+                Some(Decision::MemoryLatency(cycles)) => cycles,
+                None => panic!("replay log exhausted"),
+            },
+        }
+    }
+
+    /// Decisions recorded so far (empty unless recording).
+    pub fn recorded(&self) -> &[Decision] {
+        match self {
+            DecisionLog::Record(log) => log,
+            _ => &[],
+        }
+    }
+}
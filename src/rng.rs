@@ -0,0 +1,121 @@
+//! Small seeded PRNG (SplitMix64) so randomized models stay reproducible without external crates.
+
+/// Deterministic pseudo-random number generator: same seed, same sequence.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform float in [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform integer in [lo, hi] (inclusive).
+    pub fn range_inclusive(&mut self, lo: u64, hi: u64) -> u64 {
+        if hi <= lo {
+            return lo;
+        }
+        let span = hi - lo + 1;
+        lo + self.next_u64() % span
+    }
+
+    /// Standard normal sample (mean 0, stddev 1) via Box-Muller.
+    pub fn next_normal(&mut self) -> f64 {
+        let u1 = 1.0 - self.next_f64(); // (0, 1], keeps ln finite
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+}
+
+/// Zipf distribution over ranks `0..n`: rank k has probability proportional to
+/// `1 / (k + 1)^exponent`. Sampled by inverting a precomputed CDF.
+#[derive(Clone, Debug)]
+pub struct Zipf {
+    cdf: Vec<f64>,
+}
+
+impl Zipf {
+    pub fn new(n: usize, exponent: f64) -> Self {
+        let mut total = 0.0;
+        let mut cdf: Vec<f64> = (1..=n.max(1))
+            .map(|rank| {
+                total += (rank as f64).powf(-exponent);
+                total
+            })
+            .collect();
+        for c in &mut cdf {
+            *c /= total;
+        }
+        Self { cdf }
+    }
+
+    /// Probability of drawing `rank` (0 for a rank past the last).
+    pub fn probability(&self, rank: usize) -> f64 {
+        match rank {
+            _ if rank >= self.cdf.len() => 0.0,
+            0 => self.cdf[0],
+            _ => self.cdf[rank] - self.cdf[rank - 1],
+        }
+    }
+
+    pub fn sample(&self, rng: &mut Rng) -> usize {
+        let u = rng.next_f64();
+        self.cdf.partition_point(|&c| c <= u).min(self.cdf.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rng_same_seed_same_sequence() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+        assert_ne!(Rng::new(1).next_u64(), Rng::new(2).next_u64());
+    }
+
+    #[test]
+    fn rng_zipf_frequencies_match_the_distribution() {
+        let zipf = Zipf::new(100, 1.0);
+        let mut rng = Rng::new(3);
+        let mut counts = [0u32; 100];
+        let samples = 200_000;
+        for _ in 0..samples {
+            counts[zipf.sample(&mut rng)] += 1;
+        }
+        for rank in [0, 1, 2, 9, 49] {
+            let expected = zipf.probability(rank);
+            let actual = f64::from(counts[rank]) / f64::from(samples);
+            assert!((actual / expected - 1.0).abs() < 0.1, "rank {rank}: {actual} vs {expected}");
+        }
+        assert!((zipf.probability(0) / zipf.probability(1) - 2.0).abs() < 1e-9);
+        assert_eq!(zipf.probability(100), 0.0);
+    }
+
+    #[test]
+    fn rng_range_inclusive_bounds() {
+        let mut rng = Rng::new(7);
+        for _ in 0..1000 {
+            let v = rng.range_inclusive(10, 12);
+            assert!((10..=12).contains(&v));
+        }
+    }
+}
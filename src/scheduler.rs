@@ -1,12 +1,167 @@
 //! Thread scheduling model: round-robin assignment of threads to cores.
 
-use crate::core::{CoreId, ThreadId};
+use crate::core::{CoreId, Cycle, ThreadId};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Invalid scheduler configuration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SchedulerError {
+    /// A thread was pinned or migrated to a core that does not exist.
+    NoSuchCore { thread_id: ThreadId, core_id: CoreId },
+    /// The thread has no pending instructions on any core.
+    NoSuchThread(ThreadId),
+}
+
+impl fmt::Display for SchedulerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchedulerError::NoSuchCore { thread_id, core_id } => write!(
+                f,
+                "thread {} pinned to nonexistent core {}",
+                thread_id.0, core_id.0
+            ),
+            SchedulerError::NoSuchThread(thread_id) => {
+                write!(f, "thread {} has no pending work", thread_id.0)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SchedulerError {}
+
+/// Time slicing for cores that run more than one thread.
+#[derive(Clone, Debug)]
+pub struct TimeSliceConfig {
+    /// Cycles a thread holds its core before the core switches to its next thread, unless the
+    /// thread has its own quantum (see [`Scheduler::set_quantum`]). 0 is treated as 1.
+    pub quantum_cycles: u64,
+    /// Cycles each switch costs; the core fetches nothing meanwhile.
+    pub context_switch_cycles: u32,
+    /// Invalidate the core's L1 on every switch, so the incoming thread starts cold.
+    pub flush_cache_on_switch: bool,
+    /// Preempt a thread whose quantum expires while it waits on a miss right away. By default
+    /// the switch waits until its misses return, so the data it waited for is still used.
+    pub preempt_on_miss: bool,
+}
+
+impl Default for TimeSliceConfig {
+    fn default() -> Self {
+        Self {
+            quantum_cycles: 1000,
+            context_switch_cycles: 50,
+            flush_cache_on_switch: false,
+            preempt_on_miss: false,
+        }
+    }
+}
+
+/// Idle cores steal a whole queued thread from the most-loaded core. A thread's own
+/// instructions are never split across cores.
+#[derive(Clone, Debug)]
+pub struct WorkStealingConfig {
+    /// Cycles the thief spends before it can fetch stolen work.
+    pub steal_penalty_cycles: u32,
+}
+
+impl Default for WorkStealingConfig {
+    fn default() -> Self {
+        Self {
+            steal_penalty_cycles: 20,
+        }
+    }
+}
+
+/// Dynamic runtime: threads are not placed on cores; a core that runs out of work claims the
+/// next batch of instructions from a shared ready queue.
+#[derive(Clone, Debug)]
+pub struct GlobalQueueConfig {
+    /// Instructions a core claims at a time, from the least recently served thread.
+    pub batch_size: usize,
+}
+
+impl Default for GlobalQueueConfig {
+    fn default() -> Self {
+        Self { batch_size: 8 }
+    }
+}
+
+/// How a core picks among its runnable threads of equal priority.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ThreadPolicy {
+    /// Take turns in arrival order.
+    #[default]
+    RoundRobin,
+    /// Run the thread with the fewest remaining instructions (minimizes average completion
+    /// time for batch workloads; long threads can starve).
+    ShortestRemainingWorkFirst,
+}
+
+/// Thread migration cost and the optional automatic load balancer.
+#[derive(Clone, Debug)]
+pub struct MigrationConfig {
+    /// Cycles the destination core spends before it can fetch a migrated thread.
+    pub migration_penalty_cycles: u32,
+    /// Migrate automatically when the most- and least-loaded cores' pending instruction
+    /// counts differ by more than this. 0 = manual migration only.
+    pub imbalance_threshold: usize,
+    /// Cycles between load balancer checks.
+    pub check_interval_cycles: u64,
+}
+
+impl Default for MigrationConfig {
+    fn default() -> Self {
+        Self {
+            migration_penalty_cycles: 100,
+            imbalance_threshold: 0,
+            check_interval_cycles: 1000,
+        }
+    }
+}
+
+/// Performance class of a core, for placement policies on heterogeneous machines.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CoreType {
+    /// Wide / fast core.
+    #[default]
+    Big,
+    /// Narrow / slow, energy-efficient core.
+    Little,
+}
+
+/// Thread placement computed by [`Scheduler::assign_lpt`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LptAssignment {
+    /// Core each thread was pinned to.
+    pub mapping: HashMap<ThreadId, CoreId>,
+    /// Instructions placed on the most-loaded core, a proxy for how long the run takes.
+    pub predicted_makespan: usize,
+}
 
 /// Maps threads to cores and decides which thread runs on which core each cycle.
-/// Simplified: round-robin assignment (thread T runs on core T % N).
+/// Threads go where they are pinned; unpinned thread T runs on core T % N.
+#[derive(Clone)]
 pub struct Scheduler {
     num_cores: usize,
     num_threads: usize,
+    /// None = each core runs its threads back to back, switching only when one finishes.
+    time_slice: Option<TimeSliceConfig>,
+    /// Per-thread quanta; unlisted threads use the time slice's `quantum_cycles`.
+    quanta: HashMap<ThreadId, Cycle>,
+    /// Thread priorities (higher runs first); unlisted threads have priority 0.
+    priorities: HashMap<ThreadId, u8>,
+    policy: ThreadPolicy,
+    /// None = cores only run the threads assigned to them.
+    work_stealing: Option<WorkStealingConfig>,
+    /// Some = threads wait in a global ready queue instead of being placed on cores.
+    global_queue: Option<GlobalQueueConfig>,
+    /// Thread groups that run all together or not at all (time slicing only).
+    gangs: Vec<Vec<ThreadId>>,
+    /// Explicit thread placements; threads not listed fall back to round-robin.
+    affinity: HashMap<ThreadId, CoreId>,
+    migration: MigrationConfig,
+    /// Class of each core; cores not listed are big.
+    core_types: Vec<CoreType>,
 }
 
 impl Scheduler {
@@ -14,22 +169,259 @@ impl Scheduler {
         Self {
             num_cores,
             num_threads,
+            time_slice: None,
+            quanta: HashMap::new(),
+            priorities: HashMap::new(),
+            policy: ThreadPolicy::default(),
+            work_stealing: None,
+            global_queue: None,
+            gangs: Vec::new(),
+            affinity: HashMap::new(),
+            migration: MigrationConfig::default(),
+            core_types: Vec::new(),
+        }
+    }
+
+    /// Pins every thread in `map` to its core (see [`Scheduler::pin`]).
+    pub fn with_affinity(
+        mut self,
+        map: HashMap<ThreadId, CoreId>,
+    ) -> Result<Self, SchedulerError> {
+        for (thread_id, core_id) in map {
+            self.pin(thread_id, core_id)?;
+        }
+        Ok(self)
+    }
+
+    /// Places `thread_id` on `core_id` for subsequent `load_workload` calls.
+    pub fn pin(&mut self, thread_id: ThreadId, core_id: CoreId) -> Result<(), SchedulerError> {
+        if core_id.0 >= self.num_cores {
+            return Err(SchedulerError::NoSuchCore { thread_id, core_id });
+        }
+        self.affinity.insert(thread_id, core_id);
+        Ok(())
+    }
+
+    /// Longest-processing-time placement: takes threads largest first (`workload_sizes[t]` is
+    /// thread t's instruction count) and pins each to the least-loaded core so far, so
+    /// `load_workload` follows it.
+    pub fn assign_lpt(&mut self, workload_sizes: &[usize]) -> LptAssignment {
+        let mut order: Vec<usize> = (0..workload_sizes.len()).collect();
+        order.sort_by_key(|&t| std::cmp::Reverse(workload_sizes[t]));
+        let mut load = vec![0usize; self.num_cores.max(1)];
+        let mut mapping = HashMap::new();
+        for t in order {
+            let core = (0..load.len()).min_by_key(|&c| load[c]).unwrap_or(0);
+            load[core] += workload_sizes[t];
+            mapping.insert(ThreadId(t), CoreId(core));
+        }
+        self.affinity.extend(mapping.iter().map(|(&t, &c)| (t, c)));
+        LptAssignment {
+            mapping,
+            predicted_makespan: load.into_iter().max().unwrap_or(0),
+        }
+    }
+
+    /// Class of each core, by core id (`Simulator::new_heterogeneous` sets this).
+    pub fn set_core_types(&mut self, core_types: Vec<CoreType>) {
+        self.core_types = core_types;
+    }
+
+    pub fn core_type(&self, core_id: CoreId) -> CoreType {
+        self.core_types.get(core_id.0).copied().unwrap_or_default()
+    }
+
+    /// Size-aware placement for big/little machines: the largest threads (`workload_sizes[t]`
+    /// is thread t's instruction count) are pinned to big cores, one per big core, and the
+    /// rest are spread round-robin over the little cores (over all cores if there are none).
+    pub fn assign_big_first(&mut self, workload_sizes: &[usize]) -> HashMap<ThreadId, CoreId> {
+        let (big, little): (Vec<usize>, Vec<usize>) = (0..self.num_cores)
+            .partition(|&c| self.core_type(CoreId(c)) == CoreType::Big);
+        let rest = if little.is_empty() { big.clone() } else { little };
+        let mut order: Vec<usize> = (0..workload_sizes.len()).collect();
+        order.sort_by_key(|&t| std::cmp::Reverse(workload_sizes[t]));
+        let mapping: HashMap<ThreadId, CoreId> = order
+            .into_iter()
+            .enumerate()
+            .filter_map(|(rank, t)| {
+                let core = match big.get(rank) {
+                    Some(&core) => core,
+                    None => *rest.get((rank - big.len()) % rest.len().max(1))?,
+                };
+                Some((ThreadId(t), CoreId(core)))
+            })
+            .collect();
+        self.affinity.extend(mapping.iter().map(|(&t, &c)| (t, c)));
+        mapping
+    }
+
+    /// Locality-aware placement from a [`shared_footprint`](crate::workload::shared_footprint)
+    /// matrix: the most-sharing pairs are grouped first (groups hold at most
+    /// max(2, threads / cores) threads), then groups go to the core with the fewest threads.
+    /// Every thread is pinned, so `load_workload` follows it.
+    pub fn assign_by_affinity(&mut self, matrix: &[Vec<usize>]) -> HashMap<ThreadId, CoreId> {
+        let n = matrix.len();
+        let cap = n.div_ceil(self.num_cores.max(1)).max(2);
+        let mut pairs: Vec<(usize, usize, usize)> = (0..n)
+            .flat_map(|a| (a + 1..n).map(move |b| (a, b)))
+            .map(|(a, b)| (matrix[a][b], a, b))
+            .filter(|&(shared, _, _)| shared > 0)
+            .collect();
+        pairs.sort_by_key(|&(shared, a, b)| (std::cmp::Reverse(shared), a, b));
+        // group[t] = index into `groups` of the group holding thread t.
+        let mut groups: Vec<Vec<usize>> = (0..n).map(|t| vec![t]).collect();
+        let mut group: Vec<usize> = (0..n).collect();
+        for (_, a, b) in pairs {
+            let (ga, gb) = (group[a], group[b]);
+            if ga == gb || groups[ga].len() + groups[gb].len() > cap {
+                continue;
+            }
+            let moved = std::mem::take(&mut groups[gb]);
+            for &t in &moved {
+                group[t] = ga;
+            }
+            groups[ga].extend(moved);
+        }
+        let mut groups: Vec<Vec<usize>> = groups.into_iter().filter(|g| !g.is_empty()).collect();
+        groups.sort_by_key(|g| std::cmp::Reverse(g.len()));
+        let mut load = vec![0usize; self.num_cores.max(1)];
+        let mut mapping = HashMap::new();
+        for g in groups {
+            let core = (0..load.len()).min_by_key(|&c| load[c]).unwrap_or(0);
+            load[core] += g.len();
+            for t in g {
+                mapping.insert(ThreadId(t), CoreId(core));
+            }
+        }
+        self.affinity.extend(mapping.iter().map(|(&t, &c)| (t, c)));
+        mapping
+    }
+
+    /// At each scheduling decision a core runs its highest-priority thread; equal priorities
+    /// take turns. Lower-priority threads may starve.
+    pub fn set_priority(&mut self, thread_id: ThreadId, priority: u8) {
+        self.priorities.insert(thread_id, priority);
+    }
+
+    pub fn priority(&self, thread_id: ThreadId) -> u8 {
+        self.priorities.get(&thread_id).copied().unwrap_or(0)
+    }
+
+    pub fn set_policy(&mut self, policy: ThreadPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn policy(&self) -> ThreadPolicy {
+        self.policy
+    }
+
+    /// Scheduling decision for one core: given its runnable threads as (thread, remaining
+    /// instructions) in turn order, returns the index of the one to run next. Highest priority
+    /// wins; ties go by the policy, then by turn order.
+    pub fn pick_thread(&self, runnable: &[(ThreadId, usize)]) -> Option<usize> {
+        let key = |&(thread_id, remaining): &(ThreadId, usize)| {
+            let work = match self.policy {
+                ThreadPolicy::RoundRobin => 0,
+                ThreadPolicy::ShortestRemainingWorkFirst => remaining,
+            };
+            (std::cmp::Reverse(self.priority(thread_id)), work)
+        };
+        let best = runnable.iter().map(key).min()?;
+        runnable.iter().position(|t| key(t) == best)
+    }
+
+    /// Preempts each core's running thread every quantum (see [`TimeSliceConfig`]).
+    pub fn set_time_slice(&mut self, config: TimeSliceConfig) {
+        self.time_slice = Some(config);
+    }
+
+    pub fn time_slice(&self) -> Option<&TimeSliceConfig> {
+        self.time_slice.as_ref()
+    }
+
+    /// Gives `thread_id` its own quantum (e.g. long for a latency-sensitive thread, short for
+    /// background ones). Only takes effect under time slicing.
+    pub fn set_quantum(&mut self, thread_id: ThreadId, quantum_cycles: Cycle) {
+        self.quanta.insert(thread_id, quantum_cycles);
+    }
+
+    /// Quantum `thread_id` gets each time it is scheduled (None without time slicing), at
+    /// least one cycle.
+    pub fn quantum(&self, thread_id: ThreadId) -> Option<Cycle> {
+        let slice = self.time_slice.as_ref()?;
+        let quantum = self.quanta.get(&thread_id).copied().unwrap_or(slice.quantum_cycles);
+        Some(quantum.max(1))
+    }
+
+    /// Lets cores that run out of work steal from others (see [`WorkStealingConfig`]).
+    pub fn set_work_stealing(&mut self, config: WorkStealingConfig) {
+        self.work_stealing = Some(config);
+    }
+
+    pub fn work_stealing(&self) -> Option<&WorkStealingConfig> {
+        self.work_stealing.as_ref()
+    }
+
+    /// Declares a gang: under time slicing, quanta rotate between gangs and the threads in no
+    /// gang, and a gang's members all hold their cores in the same quantum. A gang whose
+    /// members share a core cannot be co-scheduled and waits its turn out. Members are
+    /// removed from any earlier gang.
+    pub fn set_gang(&mut self, members: &[ThreadId]) {
+        for gang in &mut self.gangs {
+            gang.retain(|t| !members.contains(t));
         }
+        self.gangs.retain(|g| !g.is_empty());
+        self.gangs.push(members.to_vec());
+    }
+
+    pub fn gangs(&self) -> &[Vec<ThreadId>] {
+        &self.gangs
+    }
+
+    /// Index (into [`Scheduler::gangs`]) of the gang `thread_id` belongs to.
+    pub fn gang_of(&self, thread_id: ThreadId) -> Option<usize> {
+        self.gangs.iter().position(|g| g.contains(&thread_id))
+    }
+
+    /// Replaces static placement with a global ready queue (see [`GlobalQueueConfig`]).
+    pub fn set_global_queue(&mut self, config: GlobalQueueConfig) {
+        self.global_queue = Some(config);
+    }
+
+    pub fn global_queue(&self) -> Option<&GlobalQueueConfig> {
+        self.global_queue.as_ref()
+    }
+
+    /// Sets the migration penalty and load balancer (see [`MigrationConfig`]).
+    pub fn set_migration(&mut self, config: MigrationConfig) {
+        self.migration = config;
+    }
+
+    pub fn migration(&self) -> &MigrationConfig {
+        &self.migration
     }
 
-    /// Returns the core that should run the given thread (round-robin).
+    /// Returns the core that should run the given thread: its pin, else round-robin.
     pub fn thread_to_core(&self, thread_id: ThreadId) -> CoreId {
-        CoreId(thread_id.0 % self.num_cores)
+        self.affinity
+            .get(&thread_id)
+            .copied()
+            .unwrap_or(CoreId(thread_id.0 % self.num_cores))
     }
 
-    /// Returns the thread assigned to run on the given core for the current scheduling quantum.
-    /// We use a simple model: core K runs thread K, K+N, K+2N, ... (round-robin by core).
+    /// Threads placed on the given core, in the order it first runs them.
+    pub fn threads_on_core(&self, core_id: CoreId) -> Vec<ThreadId> {
+        (0..self.num_threads)
+            .map(ThreadId)
+            .filter(|&t| self.thread_to_core(t) == core_id)
+            .collect()
+    }
+
+    /// Returns the first thread the given core runs (None if no thread is placed on it). A core
+    /// with several threads runs them in turn; see [`Scheduler::threads_on_core`] and
+    /// `Simulator::running_thread` for the thread on the core at a given cycle.
     pub fn core_to_thread(&self, core_id: CoreId) -> Option<ThreadId> {
-        if core_id.0 < self.num_cores {
-            Some(ThreadId(core_id.0 % self.num_threads.max(1)))
-        } else {
-            None
-        }
+        self.threads_on_core(core_id).first().copied()
     }
 
     pub fn num_cores(&self) -> usize {
@@ -68,6 +460,109 @@ mod tests {
         assert_eq!(s.core_to_thread(CoreId(1)), Some(ThreadId(1)));
     }
 
+    #[test]
+    fn scheduler_threads_on_core_covers_every_thread() {
+        let s = Scheduler::new(2, 4);
+        assert_eq!(s.threads_on_core(CoreId(0)), vec![ThreadId(0), ThreadId(2)]);
+        assert_eq!(s.threads_on_core(CoreId(1)), vec![ThreadId(1), ThreadId(3)]);
+        let idle = Scheduler::new(4, 2);
+        assert_eq!(idle.core_to_thread(CoreId(3)), None);
+    }
+
+    #[test]
+    fn scheduler_default_priority_is_zero() {
+        let mut s = Scheduler::new(1, 2);
+        s.set_priority(ThreadId(1), 7);
+        assert_eq!(s.priority(ThreadId(0)), 0);
+        assert_eq!(s.priority(ThreadId(1)), 7);
+    }
+
+    #[test]
+    fn scheduler_quantum_falls_back_to_time_slice() {
+        let mut s = Scheduler::new(1, 2);
+        s.set_quantum(ThreadId(1), 5000);
+        assert_eq!(s.quantum(ThreadId(0)), None);
+        s.set_time_slice(TimeSliceConfig::default());
+        assert_eq!(s.quantum(ThreadId(0)), Some(1000));
+        assert_eq!(s.quantum(ThreadId(1)), Some(5000));
+        s.set_quantum(ThreadId(1), 0);
+        assert_eq!(s.quantum(ThreadId(1)), Some(1));
+    }
+
+    #[test]
+    fn scheduler_pins_override_round_robin() {
+        let mut s = Scheduler::new(2, 3)
+            .with_affinity(HashMap::from([(ThreadId(1), CoreId(0))]))
+            .unwrap();
+        assert_eq!(s.thread_to_core(ThreadId(1)), CoreId(0));
+        assert_eq!(s.thread_to_core(ThreadId(2)), CoreId(0));
+        assert_eq!(
+            s.pin(ThreadId(0), CoreId(2)),
+            Err(SchedulerError::NoSuchCore {
+                thread_id: ThreadId(0),
+                core_id: CoreId(2)
+            })
+        );
+    }
+
+    #[test]
+    fn scheduler_lpt_separates_large_threads() {
+        let mut s = Scheduler::new(2, 4);
+        let lpt = s.assign_lpt(&[9000, 8000, 100, 50]);
+        assert_ne!(lpt.mapping[&ThreadId(0)], lpt.mapping[&ThreadId(1)]);
+        assert_eq!(lpt.predicted_makespan, 9000);
+        assert_eq!(s.thread_to_core(ThreadId(2)), lpt.mapping[&ThreadId(1)]);
+    }
+
+    #[test]
+    fn scheduler_big_first_puts_large_threads_on_big_cores() {
+        let mut s = Scheduler::new(3, 4);
+        s.set_core_types(vec![CoreType::Little, CoreType::Big, CoreType::Little]);
+        let mapping = s.assign_big_first(&[100, 9000, 50, 80]);
+        assert_eq!(mapping[&ThreadId(1)], CoreId(1));
+        assert_eq!(s.core_type(mapping[&ThreadId(0)]), CoreType::Little);
+        assert_ne!(mapping[&ThreadId(0)], mapping[&ThreadId(3)]);
+        assert_eq!(s.thread_to_core(ThreadId(2)), mapping[&ThreadId(2)]);
+    }
+
+    #[test]
+    fn scheduler_affinity_groups_sharing_pairs() {
+        let mut s = Scheduler::new(2, 4);
+        // Threads 0 and 3 share heavily, 1 and 2 a little.
+        let matrix = vec![
+            vec![32, 0, 0, 30],
+            vec![0, 32, 2, 0],
+            vec![0, 2, 32, 0],
+            vec![30, 0, 0, 32],
+        ];
+        let mapping = s.assign_by_affinity(&matrix);
+        assert_eq!(mapping[&ThreadId(0)], mapping[&ThreadId(3)]);
+        assert_eq!(mapping[&ThreadId(1)], mapping[&ThreadId(2)]);
+        assert_ne!(mapping[&ThreadId(0)], mapping[&ThreadId(1)]);
+    }
+
+    #[test]
+    fn scheduler_gang_membership_is_exclusive() {
+        let mut s = Scheduler::new(2, 4);
+        s.set_gang(&[ThreadId(0), ThreadId(1)]);
+        s.set_gang(&[ThreadId(1), ThreadId(2)]);
+        assert_eq!(s.gang_of(ThreadId(0)), Some(0));
+        assert_eq!(s.gang_of(ThreadId(1)), Some(1));
+        assert_eq!(s.gang_of(ThreadId(3)), None);
+    }
+
+    #[test]
+    fn scheduler_pick_thread_by_priority_then_policy() {
+        let mut s = Scheduler::new(1, 3);
+        let runnable = [(ThreadId(0), 500), (ThreadId(1), 50), (ThreadId(2), 5000)];
+        assert_eq!(s.pick_thread(&runnable), Some(0));
+        s.set_policy(ThreadPolicy::ShortestRemainingWorkFirst);
+        assert_eq!(s.pick_thread(&runnable), Some(1));
+        s.set_priority(ThreadId(2), 1);
+        assert_eq!(s.pick_thread(&runnable), Some(2));
+        assert_eq!(s.pick_thread(&[]), None);
+    }
+
     #[test]
     fn scheduler_single_core() {
         let s = Scheduler::new(1, 4);
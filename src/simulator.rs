@@ -1,285 +1,7078 @@
 //! Event-driven multicore simulator: cycle stepping, pipeline, cache/memory, metrics.
 
+use crate::branch::{BranchPredictor, BranchPredictorConfig, SpeculationConfig};
+use crate::builder::{
+    check_pipeline_widths, validate_core, validate_memory, ConfigError, SimulatorBuilder,
+};
 use crate::cache::{Cache, CacheAccessResult, CacheConfig};
-use crate::core::{CoreId, Cycle, Instruction, InstructionKind, PipelineStage, ThreadId};
-use crate::memory::{Memory, MemoryConfig};
-use crate::metrics::Metrics;
-use crate::scheduler::Scheduler;
-use std::collections::VecDeque;
+use crate::core::{
+    CoreId, Cycle, Instruction, InstructionKind, PipelineStage, RegId, ThreadId,
+};
+use crate::dma::{DmaConfig, DmaEngine};
+use crate::interrupt::{
+    ActiveInterrupt, InterruptConfig, InterruptMode, InterruptPhase, PendingInterrupt,
+    HANDLER_THREAD,
+};
+use crate::matrix::MatrixUnitConfig;
+use crate::memory::{
+    self, IssuedRequest, MemRequest, Memory, MemoryConfig, MemoryTraceEntry, NodeConfig,
+    RequestId,
+};
+use crate::metrics::{Metrics, MetricsSample};
+use crate::observer::SimObserver;
+use crate::power::{PowerConfig, ThermalState};
+use crate::scheduler::{
+    CoreType, GlobalQueueConfig, MigrationConfig, Scheduler, SchedulerError, TimeSliceConfig,
+    WorkStealingConfig,
+};
+use crate::replay::Decision;
+use crate::topology::Topology;
+use crate::trace::{InstructionRecord, InstructionTrace, StallReason};
+use crate::workload::{WorkloadError, WorkloadGraph};
+use crate::write_buffer::{ConsistencyModel, WriteBuffer, WriteBufferConfig};
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
 
-/// Per-core state: L1 cache, pipeline (in-flight instructions), and workload queue.
+/// What [`Metrics`] held at the last sample, to window the next one against.
+#[derive(Clone, Debug, Default)]
+struct SampleBase {
+    cycle: Cycle,
+    committed: u64,
+    hits: u64,
+    misses: u64,
+    stall_dominated_cycles: u64,
+    per_core_committed: Vec<u64>,
+}
+
+/// What [`Simulator::reset`] keeps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResetMode {
+    /// Nothing but configuration: caches start empty.
+    Cold,
+    /// Cache contents too, for warm-start runs.
+    KeepCaches,
+}
+
+/// Per-core state: L1 cache, pipeline (in-flight instructions), and assigned threads.
+#[derive(Clone)]
 struct CoreState {
     cache: Cache,
+    /// Cycles per pipeline stage (fetch=1, execute=1, memory=1 or hit/miss, commit=1).
+    stage_cycles: StageCycles,
     /// Instructions in pipeline (fetch -> execute -> memory -> commit).
     pipeline: VecDeque<Instruction>,
-    /// Pending workload (instructions not yet fetched).
-    workload: VecDeque<Instruction>,
+    /// Threads assigned to this core; the front one is running.
+    threads: VecDeque<ThreadContext>,
+    /// Thread that last held the core (None before the first fetch).
+    running: Option<ThreadId>,
+    /// Cycles the running thread has left in its quantum (time slicing only).
+    quantum_left: u64,
+    /// Cycles left in the current context switch.
+    switch_cycles_left: u32,
+    /// Hardware thread contexts (SMT): the first this many threads fetch side by side.
+    smt_contexts: usize,
+    /// Context that fetches first next (round-robin between SMT contexts).
+    smt_next: usize,
     /// Max pipeline width (instructions in flight per core).
     pipeline_width: usize,
+    /// Max instructions fetched per cycle.
+    fetch_width: usize,
+    /// Max instructions moved from Fetch to Execute per cycle.
+    issue_width: usize,
+    /// Max instructions retired per cycle (None = unlimited).
+    commit_width: Option<usize>,
+    /// Buffer that absorbs store misses (None = stores stall like loads).
+    write_buffer: Option<WriteBuffer>,
+    /// Cycle the last store drained from the write buffer completes in memory.
+    write_drain_until: Cycle,
+    /// Misses this core may have waiting on memory at once (0 = unlimited).
+    max_outstanding_misses: usize,
+    /// Reorder buffer entries (None = no ROB: up to `pipeline_width` in flight).
+    rob_size: Option<usize>,
+    /// Fetch buffer entries (None = no buffer: fetched instructions count toward the window).
+    fetch_buffer_depth: Option<usize>,
+    /// Whether each thread's instructions commit in program order (with a ROB, the whole
+    /// core's do regardless).
+    in_order_commit: bool,
+    /// Load/store queue entries (None = no LSQ: loads never see older stores).
+    lsq_depth: Option<usize>,
+    branch_predictor: BranchPredictor,
+    /// Cycles left before fetch resumes after a branch misprediction.
+    fetch_stall_cycles: u32,
+    /// Threads fetching down a wrong path, by the id of the mispredicted branch (speculation
+    /// only).
+    speculating: HashMap<ThreadId, u64>,
+    /// Threads parked by a Sleep, by the first cycle they may fetch again.
+    sleeping: HashMap<ThreadId, Cycle>,
+    /// Lines another core's write invalidated here (their next miss is a coherence miss).
+    coherence_lost: HashSet<u64>,
+    /// Cycle each non-pipelined execution unit (by kind) frees up.
+    unit_busy_until: HashMap<InstructionKind, Cycle>,
+    /// Cache accesses the L1 accepts per cycle.
+    cache_ports: usize,
+    matrix_unit: MatrixUnitConfig,
+    /// Id of the matrix op holding the matrix unit, if any.
+    matrix_holder: Option<u64>,
+    /// Interrupt being serviced, if any.
+    interrupt: Option<ActiveInterrupt>,
+    /// Core clock over the global clock (1.0 = full speed, 0.5 = every other cycle).
+    clock_ratio: f64,
+    /// Fraction of a core cycle accumulated toward the next one.
+    clock_credit: f64,
+    /// Cycles left in a frequency change (the core is halted meanwhile).
+    frequency_switch_left: u32,
+    /// Whether the core's clock has an edge in the current cycle.
+    clocked: bool,
+    /// Temperature and window activity (used with a power model).
+    thermal: ThermalState,
+    /// This cycle's hazard flags, in buffers reused from cycle to cycle.
+    hazards: Hazards,
+    /// What this cycle's stage work changed outside the core, not yet applied.
+    log: CoreLog,
 }
 
-/// Event-driven multicore simulator.
-pub struct Simulator {
-    num_cores: usize,
-    num_threads: usize,
-    cores: Vec<CoreState>,
-    memory: Memory,
-    scheduler: Scheduler,
-    pub metrics: Metrics,
-    current_cycle: Cycle,
-    /// Cycles per pipeline stage (fetch=1, execute=1, memory=1 or hit/miss, commit=1).
-    stage_cycles: StageCycles,
+/// Per pipeline entry hazard flags (see [`Hazards::update`]).
+#[derive(Clone, Default)]
+struct Hazards {
+    /// Whether one of the entry's source registers is still being produced by an older
+    /// instruction of its thread (one that has not reached Commit).
+    waiting_on_operands: Vec<bool>,
+    /// Whether an older instruction of the entry's thread has not reached Commit.
+    waiting_on_older: Vec<bool>,
+    /// Scratch: for each (thread, register) written so far, whether its latest writer is
+    /// unfinished.
+    producers: HashMap<(ThreadId, RegId), bool>,
+    /// Scratch: threads with an unfinished instruction so far.
+    unfinished: HashSet<ThreadId>,
+}
+
+impl Hazards {
+    /// Recomputes the flags for `pipeline`, oldest entry first.
+    fn update(&mut self, pipeline: &VecDeque<Instruction>) {
+        self.waiting_on_operands.clear();
+        self.waiting_on_older.clear();
+        self.producers.clear();
+        self.unfinished.clear();
+        for instr in pipeline {
+            let producers = &self.producers;
+            let pending = |reg: &RegId| producers.get(&(instr.thread, *reg)) == Some(&true);
+            self.waiting_on_operands.push(instr.srcs.iter().any(pending));
+            self.waiting_on_older.push(self.unfinished.contains(&instr.thread));
+            let unfinished = instr.stage != PipelineStage::Commit;
+            if let Some(dest) = instr.dest {
+                self.producers.insert((instr.thread, dest), unfinished);
+            }
+            if unfinished {
+                self.unfinished.insert(instr.thread);
+            }
+        }
+    }
+}
+
+/// What a core's stage work changed outside the core, in order (see
+/// [`Simulator::apply_core_logs`]). Buffers are reused from cycle to cycle.
+#[derive(Clone, Default)]
+struct CoreLog {
+    effects: Vec<Effect>,
+    /// Whether anyone observes the run, i.e. whether to log [`Effect::Observe`] at all.
+    observing: bool,
+}
+
+impl CoreLog {
+    fn count(&mut self, update: fn(&mut Metrics)) {
+        self.effects.push(Effect::Count(update));
+    }
+
+    fn count_for(
+        &mut self,
+        update: fn(&mut Metrics, CoreId, ThreadId, u64),
+        thread: ThreadId,
+        n: u64,
+    ) {
+        self.effects.push(Effect::CountFor(update, thread, n));
+    }
+
+    fn stall(&mut self, reason: StallReason, cycles: u64) {
+        self.effects.push(Effect::Stall(reason, cycles));
+    }
+
+    fn observe(&mut self, event: Event, instr: &Instruction) {
+        if self.observing {
+            self.effects.push(Effect::Observe(event, Box::new(instr.clone())));
+        }
+    }
 }
 
+/// One change a core's stage work makes outside the core.
 #[derive(Clone)]
-pub struct StageCycles {
-    pub fetch_cycles: u32,
-    pub execute_cycles: u32,
-    pub commit_cycles: u32,
+enum Effect {
+    /// Updates the metrics.
+    Count(fn(&mut Metrics)),
+    /// Updates the metrics given the core, a thread and a value.
+    CountFor(fn(&mut Metrics, CoreId, ThreadId, u64), ThreadId, u64),
+    /// Stall cycles (or held-back instructions) for a reason.
+    Stall(StallReason, u64),
+    CacheEnergy(f64),
+    /// Tells the observers about the instruction, as it was at the time.
+    Observe(Event, Box<Instruction>),
+    /// The instruction committed and has left the pipeline.
+    Commit(Box<Instruction>),
+    /// The pipeline entry at this index missed and waits on memory.
+    Miss(usize),
+    /// The branch at this pipeline index was mispredicted.
+    Mispredicted(usize),
+    /// A write to this address invalidates the other cores' copies.
+    Invalidate(u64),
+    /// The memory request has completed and memory may forget it.
+    Retire(RequestId),
 }
 
-impl Default for StageCycles {
-    fn default() -> Self {
-        Self {
-            fetch_cycles: 1,
-            execute_cycles: 1,
-            commit_cycles: 1,
+/// An observer callback logged by [`CoreLog::observe`].
+#[derive(Clone, Copy)]
+enum Event {
+    StageChange(PipelineStage, PipelineStage),
+    /// A cache access (hit or not) moving the instruction from Execute to Memory.
+    CacheAccess(bool),
+    StallBegin,
+    StallEnd,
+}
+
+impl Event {
+    fn send(self, observer: &mut dyn SimObserver, now: Cycle, core: CoreId, instr: &Instruction) {
+        match self {
+            Event::StageChange(old, new) => observer.on_stage_change(now, core, instr, old, new),
+            Event::CacheAccess(hit) => {
+                observer.on_cache_access(now, core, instr, hit);
+                let (old, new) = (PipelineStage::Execute, PipelineStage::Memory);
+                observer.on_stage_change(now, core, instr, old, new);
+            }
+            Event::StallBegin => observer.on_stall_begin(now, core, instr),
+            Event::StallEnd => observer.on_stall_end(now, core, instr),
         }
     }
 }
 
-impl Simulator {
-    pub fn new(
-        num_cores: usize,
-        num_threads: usize,
-        cache_config: CacheConfig,
-        memory_config: MemoryConfig,
-        pipeline_width: usize,
-    ) -> Self {
-        let cores = (0..num_cores)
-            .map(|_| CoreState {
-                cache: Cache::new(cache_config.clone()),
-                pipeline: VecDeque::new(),
-                workload: VecDeque::new(),
-                pipeline_width,
-            })
-            .collect();
-        let scheduler = Scheduler::new(num_cores, num_threads);
-        let mut sim = Self {
-            num_cores,
-            num_threads,
-            cores,
-            memory: Memory::new(memory_config),
-            scheduler,
-            metrics: Metrics::new(),
-            current_cycle: 0,
-            stage_cycles: StageCycles::default(),
+/// What a core's execute stage reads from outside the core.
+struct ExecuteContext<'a> {
+    now: Cycle,
+    speculative: bool,
+    /// Sequential consistency: a store holds back its thread's younger memory ops.
+    sc: bool,
+    keep_wrong_path: bool,
+    coherence: bool,
+    /// Barriers every participant had reached by last cycle.
+    released: &'a [u64],
+    /// Cycle each finished thread finished in, for joins.
+    finished: &'a HashMap<ThreadId, Cycle>,
+}
+
+/// State the cores share that an execute stage may change: memory contents (functional
+/// memory only), DMA, barriers and locks. Cores needing it run one after another.
+struct SharedState<'a> {
+    memory: &'a mut Memory,
+    dma: &'a mut DmaEngine,
+    barriers: &'a mut HashMap<u64, BarrierState>,
+    locks: &'a mut HashMap<u64, ThreadId>,
+    lock_waiters: &'a mut HashSet<(ThreadId, u64)>,
+}
+
+/// What the cores' logs add up to within one [`Simulator::step`].
+#[derive(Default)]
+struct StageOutputs {
+    committed: u32,
+    /// Threads that committed something (they may have finished).
+    committed_threads: Vec<ThreadId>,
+    /// Children whose spawn committed.
+    spawned: Vec<ThreadId>,
+    /// (core, pipeline index) of each miss, in core order.
+    misses: Vec<(usize, usize)>,
+    /// (core, pipeline index) of each mispredicted branch, in core order.
+    mispredicted: Vec<(usize, usize)>,
+    /// (writing core, address) of each invalidating write.
+    invalidations: Vec<(usize, u64)>,
+}
+
+impl CoreState {
+    fn new(config: &CoreConfig) -> Self {
+        Self {
+            cache: Cache::new(config.cache.clone()),
+            stage_cycles: config.stage_cycles.clone(),
+            pipeline: VecDeque::new(),
+            threads: VecDeque::new(),
+            running: None,
+            quantum_left: 0,
+            switch_cycles_left: 0,
+            smt_contexts: 1,
+            smt_next: 0,
+            pipeline_width: config.pipeline_width,
+            fetch_width: config.pipeline_width,
+            issue_width: config.pipeline_width,
+            commit_width: config.commit_width,
+            write_buffer: None,
+            write_drain_until: 0,
+            max_outstanding_misses: 0,
+            rob_size: None,
+            fetch_buffer_depth: None,
+            in_order_commit: true,
+            lsq_depth: None,
+            branch_predictor: BranchPredictor::new(BranchPredictorConfig::default()),
+            fetch_stall_cycles: 0,
+            speculating: HashMap::new(),
+            sleeping: HashMap::new(),
+            coherence_lost: HashSet::new(),
+            unit_busy_until: HashMap::new(),
+            cache_ports: 1,
+            matrix_unit: MatrixUnitConfig::default(),
+            matrix_holder: None,
+            interrupt: None,
+            clock_ratio: 1.0,
+            clock_credit: 0.0,
+            frequency_switch_left: 0,
+            clocked: true,
+            thermal: ThermalState::new(&PowerConfig::default()),
+            hazards: Hazards::default(),
+            log: CoreLog::default(),
+        }
+    }
+
+    /// Advances the core's clock by one global cycle, setting whether the core runs in it.
+    /// `throttle` scales the clock ratio (1.0 = not throttled).
+    fn tick_clock(&mut self, throttle: f64) {
+        self.clocked = false;
+        if self.frequency_switch_left > 0 {
+            self.frequency_switch_left -= 1;
+            return;
+        }
+        self.clock_credit += self.clock_ratio * throttle;
+        if self.clock_credit >= 1.0 {
+            self.clock_credit -= 1.0;
+            self.clocked = true;
+        }
+    }
+
+    /// Drops all run state (pipeline, threads, buffered stores, predictor history, clock and
+    /// temperature), keeping the core's configuration and, if `keep_cache`, its cache contents.
+    fn reset(&mut self, keep_cache: bool, power: Option<&PowerConfig>) {
+        if !keep_cache {
+            self.cache.flush();
+        }
+        self.pipeline.clear();
+        self.threads.clear();
+        self.running = None;
+        self.quantum_left = 0;
+        self.switch_cycles_left = 0;
+        self.smt_next = 0;
+        if let Some(wb) = self.write_buffer.as_mut() {
+            *wb = WriteBuffer::new(wb.config().clone());
+        }
+        self.write_drain_until = 0;
+        self.branch_predictor = BranchPredictor::new(self.branch_predictor.config().clone());
+        self.fetch_stall_cycles = 0;
+        self.speculating.clear();
+        self.sleeping.clear();
+        self.coherence_lost.clear();
+        self.unit_busy_until.clear();
+        self.matrix_holder = None;
+        self.interrupt = None;
+        self.clock_credit = 0.0;
+        self.frequency_switch_left = 0;
+        self.clocked = true;
+        self.thermal = ThermalState::new(power.unwrap_or(&PowerConfig::default()));
+    }
+
+    /// Whether any assigned thread still has instructions to fetch.
+    fn has_pending_work(&self) -> bool {
+        self.threads.iter().any(|t| !t.workload.is_empty())
+    }
+
+    /// Instructions not yet fetched, across all assigned threads.
+    fn pending_instructions(&self) -> usize {
+        self.threads.iter().map(|t| t.workload.len()).sum()
+    }
+
+    /// Instructions in Fetch or Decode (the fetch buffer's contents, if the core has one).
+    fn front_end_len(&self) -> usize {
+        self.pipeline
+            .iter()
+            .filter(|i| matches!(i.stage, PipelineStage::Fetch | PipelineStage::Decode))
+            .count()
+    }
+
+    /// Instructions fetch may bring in this cycle: the fetch buffer's free entries or, without
+    /// one, the in-flight window's, capped at the fetch width.
+    fn fetch_slots(&self) -> usize {
+        let free = match self.fetch_buffer_depth {
+            Some(depth) => depth.saturating_sub(self.front_end_len()),
+            None => {
+                let window = self.rob_size.unwrap_or(self.pipeline_width);
+                window.saturating_sub(self.pipeline.len())
+            }
         };
-        sim.metrics.total_cycles = 0;
-        sim
+        free.min(self.fetch_width)
     }
 
-    /// Load workload per thread: thread_workloads[thread_id] = list of instructions.
-    pub fn load_workload(&mut self, thread_workloads: Vec<Vec<Instruction>>) {
-        for (thread_id, instrs) in thread_workloads.into_iter().enumerate() {
-            let core_id = self.scheduler.thread_to_core(ThreadId(thread_id));
-            for i in instrs {
-                self.cores[core_id.0].workload.push_back(i);
+    /// Whether `thread_id` has a Join, Barrier, LockAcquire or DmaWait in flight (it fetches
+    /// nothing until that commits).
+    fn synchronizing(&self, thread_id: ThreadId) -> bool {
+        self.pipeline.iter().any(|i| {
+            i.thread == thread_id
+                && matches!(
+                    i.kind,
+                    InstructionKind::Join { .. }
+                        | InstructionKind::Barrier { .. }
+                        | InstructionKind::LockAcquire { .. }
+                        | InstructionKind::DmaWait { .. }
+                )
+        })
+    }
+}
+
+/// Per-core halves of [`Simulator::step`]'s commit, memory, execute and issue stages. Each
+/// reads only its own core and what it is handed, and logs every change it makes elsewhere to
+/// `self.log`, so cores can run side by side (see [`Simulator::apply_core_logs`]).
+impl CoreState {
+    /// Commit stage: retires finished instructions.
+    fn commit(&mut self) {
+        if !self.clocked {
+            return;
+        }
+        // With a ROB, only instructions older than every unfinished one may leave; in
+        // order without one, only those older than every unfinished one of their thread.
+        let rob = self.rob_size.is_some();
+        let in_order = self.in_order_commit;
+        let mut older_unfinished = false;
+        let mut unfinished_threads = Vec::new();
+        // Finished instructions held back this cycle, by ordering and by commit width.
+        let mut blocked = 0;
+        let mut retired = 0;
+        let mut commit_bound = 0;
+        let mut i = 0;
+        while i < self.pipeline.len() {
+            let instr = &mut self.pipeline[i];
+            let done = instr.stage == PipelineStage::Commit
+                && instr.stage_cycles_left == 0
+                && !instr.wrong_path;
+            if instr.stage == PipelineStage::Commit && instr.stage_cycles_left > 0 {
+                instr.stage_cycles_left -= 1;
+            }
+            if !done {
+                older_unfinished |= rob;
+                if in_order && !unfinished_threads.contains(&instr.thread) {
+                    unfinished_threads.push(instr.thread);
+                }
+                i += 1;
+                continue;
+            }
+            if older_unfinished || unfinished_threads.contains(&instr.thread) {
+                blocked += 1;
+                i += 1;
+                continue;
             }
+            if self.commit_width == Some(retired) {
+                commit_bound += 1;
+                i += 1;
+                continue;
+            }
+            retired += 1;
+            // Remove from pipeline.
+            if let Some(done) = self.pipeline.remove(i) {
+                self.log.effects.push(Effect::Commit(Box::new(done)));
+            }
+        }
+        if blocked > 0 {
+            self.log.count(|m| m.commit_blocked_cycles += 1);
+            self.log.stall(StallReason::CommitBlocked, blocked);
+        }
+        if commit_bound > 0 {
+            self.log.count(|m| m.commit_bound_cycles += 1);
+            self.log.stall(StallReason::Structural, commit_bound);
         }
     }
 
-    /// Run one cycle of the event-driven simulation.
-    pub fn step(&mut self) {
-        self.current_cycle += 1;
-
-        // 1) Commit stage: drain completed instructions.
-        for core_id in 0..self.num_cores {
-            let core = &mut self.cores[core_id];
-            let mut i = 0;
-            while i < core.pipeline.len() {
-                let instr = &mut core.pipeline[i];
-                if instr.stage != PipelineStage::Commit {
-                    i += 1;
-                    continue;
+    /// Memory stage: instructions waiting on memory stall until their request completes and
+    /// its data has crossed `interconnect` cycles; the others advance to Commit.
+    fn advance_memory(
+        &mut self,
+        now: Cycle,
+        interconnect: u32,
+        memory: &Memory,
+        queued_misses: &HashMap<RequestId, QueuedMiss>,
+    ) {
+        if !self.clocked {
+            return;
+        }
+        for instr in self.pipeline.iter_mut() {
+            if instr.stage != PipelineStage::Memory {
+                continue;
+            }
+            if instr.stalled {
+                // Ask memory when the outstanding request completes; the data then still
+                // has to cross the interconnect.
+                let done_at = instr
+                    .mem_request
+                    .and_then(|id| memory.completion_cycle(id))
+                    .map(|done| {
+                        done + Cycle::from(interconnect) + Cycle::from(instr.writeback_cycles)
+                    });
+                if done_at.is_none_or(|done| now <= done) {
+                    let stall = |m: &mut Metrics, core, thread, _| {
+                        m.memory_stall_cycles += 1;
+                        m.per_core.entry(core).or_default().memory_stall_cycles += 1;
+                        m.per_thread.entry(thread).or_default().memory_stall_cycles += 1;
+                    };
+                    self.log.count_for(stall, instr.thread, 0);
+                    let queued =
+                        instr.mem_request.is_some_and(|id| queued_misses.contains_key(&id));
+                    let reason = match queued {
+                        true => StallReason::MemoryQueue,
+                        false => StallReason::CacheMiss,
+                    };
+                    self.log.stall(reason, 1);
                 }
-                if instr.stage_cycles_left > 0 {
-                    instr.stage_cycles_left -= 1;
-                    i += 1;
-                    continue;
+                if done_at.is_some_and(|done| now >= done) {
+                    if let Some(id) = instr.mem_request.take() {
+                        self.log.effects.push(Effect::Retire(id));
+                    }
+                    instr.stalled = false;
+                    instr.writeback_cycles = 0;
+                    instr.stage_cycles_left = self.cache.hit_latency_cycles();
+                    self.log.observe(Event::StallEnd, instr);
                 }
-                // Remove from pipeline.
-                core.pipeline.remove(i);
                 continue;
             }
+            if instr.stage_cycles_left > 0 {
+                instr.stage_cycles_left -= 1;
+                continue;
+            }
+            // Memory stage done -> go to commit.
+            instr.stage = PipelineStage::Commit;
+            instr.stage_cycles_left = self.stage_cycles.commit_cycles;
+            let event = Event::StageChange(PipelineStage::Memory, PipelineStage::Commit);
+            self.log.observe(event, instr);
         }
+    }
 
-        // 2) Memory stage: advance or stall.
-        for core_id in 0..self.num_cores {
-            let core = &mut self.cores[core_id];
-            for instr in core.pipeline.iter_mut() {
-                if instr.stage != PipelineStage::Memory {
-                    continue;
+    /// Whether an instruction in Execute may start or wait on DMA, or reach a barrier or lock:
+    /// state other cores share. Such a core's execute stage needs that state (see
+    /// [`SharedState`]).
+    fn touches_shared_state(&self) -> bool {
+        self.pipeline.iter().any(|i| {
+            i.stage == PipelineStage::Execute
+                && matches!(
+                    i.kind,
+                    InstructionKind::DmaStart { .. }
+                        | InstructionKind::DmaWait { .. }
+                        | InstructionKind::Barrier { .. }
+                        | InstructionKind::LockAcquire { .. }
+                        | InstructionKind::LockRelease { .. }
+                )
+        })
+    }
+
+    /// Execute stage: advances; memory ops go to the Memory stage and access the cache. `shared`
+    /// must be given if memory is functional or [`CoreState::touches_shared_state`].
+    fn execute(
+        &mut self,
+        core_id: usize,
+        ctx: &ExecuteContext,
+        mut shared: Option<&mut SharedState>,
+    ) {
+        if !self.clocked {
+            return;
+        }
+        const SERIAL: &str = "cores touching shared state get it";
+        type Update = fn(&mut Metrics, CoreId, ThreadId, u64);
+        const HIT: Update = |m, core, thread, _| m.record_access(core, thread, true);
+        const MISS: Update = |m, core, thread, _| m.record_access(core, thread, false);
+        let now = ctx.now;
+        self.hazards.update(&self.pipeline);
+        let Hazards {
+            waiting_on_operands,
+            waiting_on_older,
+            ..
+        } = &self.hazards;
+        // Threads with an older store (SC only) or fence not yet complete.
+        let mut storing_threads = Vec::new();
+        let mut fenced_threads = Vec::new();
+        let mut outstanding = self.pipeline.iter().filter(|i| i.stalled).count();
+        let lsq = self.lsq_depth.is_some();
+        // Addresses of older stores that have / have not yet executed (LSQ only).
+        let mut executed_stores = HashSet::new();
+        let mut pending_stores = Vec::new();
+        // An atomic runs alone: no other memory operation of the core overlaps it.
+        let in_memory = |i: &Instruction| i.is_memory_op() && i.stage == PipelineStage::Memory;
+        let mut memory_busy = self.pipeline.iter().any(in_memory);
+        let mut atomic_busy = self
+            .pipeline
+            .iter()
+            .any(|i| in_memory(i) && i.kind == InstructionKind::AtomicRmw);
+        let mut ports_used = 0;
+        for (idx, instr) in self.pipeline.iter_mut().enumerate() {
+            let ordered_after = storing_threads.contains(&instr.thread)
+                || fenced_threads.contains(&instr.thread);
+            if instr.stage != PipelineStage::Commit {
+                match instr.kind {
+                    InstructionKind::Store if ctx.sc => storing_threads.push(instr.thread),
+                    InstructionKind::Fence => fenced_threads.push(instr.thread),
+                    _ => {}
                 }
-                if instr.stalled {
-                    if instr.stall_cycles_left > 0 {
-                        instr.stall_cycles_left -= 1;
-                        self.metrics.memory_stall_cycles += 1;
-                        let per = self.metrics.per_core.entry(CoreId(core_id)).or_default();
-                        per.memory_stall_cycles += 1;
+            }
+            if lsq && instr.kind == InstructionKind::Store {
+                match instr.stage {
+                    PipelineStage::Fetch | PipelineStage::Decode | PipelineStage::Execute => {
+                        pending_stores.push(instr.address)
                     }
-                    if instr.stall_cycles_left == 0 {
-                        instr.stalled = false;
-                        instr.stage_cycles_left = core.cache.hit_latency_cycles();
+                    _ => {
+                        executed_stores.insert(instr.address);
                     }
+                }
+            }
+            if instr.stage != PipelineStage::Execute {
+                continue;
+            }
+            if instr.stage_cycles_left > 0 {
+                instr.stage_cycles_left -= 1;
+                continue;
+            }
+            if waiting_on_operands[idx] {
+                self.log.count(|m| m.raw_stall_cycles += 1);
+                self.log.stall(StallReason::Dependency, 1);
+                continue;
+            }
+            if instr.is_memory_op() {
+                let is_store = instr.kind == InstructionKind::Store;
+                let is_atomic = instr.kind == InstructionKind::AtomicRmw;
+                if instr.wrong_path && !ctx.keep_wrong_path {
+                    // Executes without touching the cache.
+                    instr.stage = PipelineStage::Memory;
+                    instr.stage_cycles_left = self.cache.hit_latency_cycles();
                     continue;
                 }
-                if instr.stage_cycles_left > 0 {
-                    instr.stage_cycles_left -= 1;
+                if ordered_after {
+                    self.log.count(|m| m.consistency_stall_cycles += 1);
+                    self.log.stall(StallReason::Fence, 1);
                     continue;
                 }
-                // Memory stage done -> go to commit.
-                instr.stage = PipelineStage::Commit;
-                instr.stage_cycles_left = self.stage_cycles.commit_cycles;
-            }
-        }
-
-        // 3) Execute stage: advance; memory ops go to Memory stage and trigger cache access.
-        for core_id in 0..self.num_cores {
-            let core = &mut self.cores[core_id];
-            for instr in core.pipeline.iter_mut() {
-                if instr.stage != PipelineStage::Execute {
+                if atomic_busy || (is_atomic && memory_busy) {
+                    self.log.count(|m| m.atomic_serialization_stalls += 1);
+                    self.log.stall(StallReason::Structural, 1);
                     continue;
                 }
-                if instr.stage_cycles_left > 0 {
-                    instr.stage_cycles_left -= 1;
+                if lsq && !is_store && pending_stores.contains(&instr.address) {
+                    self.log.stall(StallReason::Dependency, 1);
                     continue;
                 }
-                if instr.is_memory_op() {
-                    let result = core.cache.access(instr.address);
-                    let hit = result == CacheAccessResult::Hit;
-                    let stall = if hit {
-                        0u64
-                    } else {
-                        self.memory.access_latency_cycles() as u64
-                    };
-                    self.metrics.record_access(CoreId(core_id), hit, stall);
+                if lsq && !is_store && executed_stores.contains(&instr.address) {
+                    // Forwarded from an older store in the LSQ: no cache access.
+                    if let Some(shared) = shared.as_deref_mut() {
+                        transfer_data(shared.memory, instr);
+                    }
+                    self.log.count(|m| m.store_forwards += 1);
+                    memory_busy = true;
                     instr.stage = PipelineStage::Memory;
-                    if hit {
-                        instr.stage_cycles_left = core.cache.hit_latency_cycles();
-                    } else {
-                        instr.stalled = true;
-                        instr.stall_cycles_left = self.memory.access_latency_cycles();
+                    instr.stage_cycles_left = 1;
+                    let event = Event::StageChange(PipelineStage::Execute, PipelineStage::Memory);
+                    self.log.observe(event, instr);
+                    continue;
+                }
+                let line_addr = self.cache.line_address(instr.address);
+                if let Some(wb) = self.write_buffer.as_ref() {
+                    if instr.kind == InstructionKind::Load && wb.contains(line_addr) {
+                        // Load forwarded from a buffered store: counts as a hit.
+                        if let Some(shared) = shared.as_deref_mut() {
+                            transfer_data(shared.memory, instr);
+                        }
+                        memory_busy = true;
+                        self.log.count_for(HIT, instr.thread, 0);
+                        instr.stage = PipelineStage::Memory;
+                        instr.stage_cycles_left = self.cache.hit_latency_cycles();
+                        self.log.observe(Event::CacheAccess(true), instr);
+                        continue;
+                    }
+                    if is_store && wb.is_full() && !self.cache.probe(instr.address) {
+                        // Store miss with nowhere to go: retry next cycle.
+                        self.log.count(|m| m.write_buffer_full_stalls += 1);
+                        self.log.stall(StallReason::Structural, 1);
+                        continue;
                     }
-                } else {
-                    instr.stage = PipelineStage::Commit;
-                    instr.stage_cycles_left = self.stage_cycles.commit_cycles;
                 }
-            }
-        }
-
-        // 4) Fetch stage: advance to Execute.
-        for core_id in 0..self.num_cores {
-            let core = &mut self.cores[core_id];
-            for instr in core.pipeline.iter_mut() {
-                if instr.stage != PipelineStage::Fetch {
+                let limit = self.max_outstanding_misses;
+                let buffered = is_store && self.write_buffer.is_some();
+                if limit > 0
+                    && outstanding >= limit
+                    && !buffered
+                    && !self.cache.probe(instr.address)
+                {
+                    // Would be one miss too many: retry next cycle.
+                    self.log.count(|m| m.mlp_limit_stalls += 1);
+                    self.log.stall(StallReason::Structural, 1);
                     continue;
                 }
-                if instr.stage_cycles_left > 0 {
-                    instr.stage_cycles_left -= 1;
+                if ports_used == self.cache_ports {
+                    // Every cache port is taken this cycle.
+                    self.log.count(|m| m.structural_stall_cycles += 1);
+                    self.log.stall(StallReason::Structural, 1);
                     continue;
                 }
-                instr.stage = PipelineStage::Execute;
-                instr.stage_cycles_left = self.stage_cycles.execute_cycles;
-            }
-        }
-
-        // 5) Fetch new instructions from workload into pipeline (up to pipeline_width).
-        for core_id in 0..self.num_cores {
-            let core = &mut self.cores[core_id];
-            while core.pipeline.len() < core.pipeline_width {
-                let Some(mut instr) = core.workload.pop_front() else {
-                    break;
-                };
-                instr.stage = PipelineStage::Fetch;
-                instr.stage_cycles_left = self.stage_cycles.fetch_cycles;
-                core.pipeline.push_back(instr);
+                ports_used += 1;
+                if let Some(shared) = shared.as_deref_mut() {
+                    transfer_data(shared.memory, instr);
+                }
+                let (result, evicted) =
+                    self.cache.access_with_eviction(instr.address, is_store || is_atomic);
+                if evicted.is_some_and(|e| e.dirty) {
+                    self.log.count(|m| m.writebacks += 1);
+                    instr.writeback_cycles = self.cache.config().writeback_latency_cycles;
+                }
+                let energy = self.cache.config().access_energy_pj;
+                self.log.effects.push(Effect::CacheEnergy(energy));
+                if instr.wrong_path {
+                    self.log.count(|m| m.wrong_path_cache_accesses += 1);
+                    if result == CacheAccessResult::Miss {
+                        self.log.count(|m| m.wrong_path_cache_misses += 1);
+                    }
+                }
+                instr.stage = PipelineStage::Memory;
+                memory_busy = true;
+                if is_atomic {
+                    atomic_busy = true;
+                    self.log.count(|m| m.atomics += 1);
+                }
+                if is_atomic || (is_store && ctx.coherence) {
+                    self.log.effects.push(Effect::Invalidate(instr.address));
+                }
+                if result == CacheAccessResult::Miss && self.coherence_lost.remove(&line_addr) {
+                    self.log.count(|m| m.coherence_misses += 1);
+                }
+                if lsq && is_store {
+                    if let Some(pos) = pending_stores.iter().position(|&a| a == instr.address) {
+                        pending_stores.remove(pos);
+                    }
+                    executed_stores.insert(instr.address);
+                }
+                let hit = result == CacheAccessResult::Hit;
+                self.log.observe(Event::CacheAccess(hit), instr);
+                if result == CacheAccessResult::Hit {
+                    self.log.count_for(HIT, instr.thread, 0);
+                    instr.stage_cycles_left = self.cache.hit_latency_cycles();
+                } else if let (true, Some(wb)) = (is_store, self.write_buffer.as_mut()) {
+                    // Store miss retires into the write buffer in one cycle.
+                    wb.push(line_addr);
+                    let occupancy = |m: &mut Metrics, _, _, len: u64| {
+                        m.write_buffer_max_occupancy = m.write_buffer_max_occupancy.max(len)
+                    };
+                    self.log.count_for(occupancy, instr.thread, wb.len() as u64);
+                    self.log.count_for(MISS, instr.thread, 0);
+                    instr.stage_cycles_left = 1;
+                } else {
+                    // Stall length depends on channel arbitration, resolved below.
+                    instr.stalled = true;
+                    outstanding += 1;
+                    self.log.effects.push(Effect::Miss(idx));
+                    self.log.observe(Event::StallBegin, instr);
+                }
+            } else {
+                if instr.kind == InstructionKind::Fence {
+                    // Buffered stores must also have reached memory.
+                    let buffered = self.write_buffer.as_ref().is_some_and(|wb| !wb.is_empty())
+                        || self.write_drain_until > now;
+                    if waiting_on_older[idx] || buffered {
+                        self.log.count(|m| m.fence_stall_cycles += 1);
+                        self.log.stall(StallReason::Fence, 1);
+                        continue;
+                    }
+                    self.log.count(|m| m.fences += 1);
+                }
+                if let InstructionKind::MatrixOp { m, n, k } = instr.kind {
+                    if self.matrix_holder.is_none() {
+                        // Holds the unit from this cycle; Commit once its latency is up.
+                        let latency = self.matrix_unit.latency(m, n, k);
+                        let left = u32::try_from(latency - 1).unwrap_or(u32::MAX);
+                        self.matrix_holder = Some(instr.id);
+                        instr.stage_cycles_left = left;
+                        let busy = |m: &mut Metrics, core, _, latency| {
+                            m.matrix_ops += 1;
+                            m.per_core.entry(core).or_default().matrix_busy_cycles += latency;
+                        };
+                        self.log.count_for(busy, instr.thread, latency);
+                        continue;
+                    }
+                    if self.matrix_holder != Some(instr.id) {
+                        self.log.count(|m| m.matrix_queue_wait_cycles += 1);
+                        self.log.stall(StallReason::Structural, 1);
+                        continue;
+                    }
+                    self.matrix_holder = None;
+                }
+                if let InstructionKind::DmaWait { id } = instr.kind {
+                    let shared = shared.as_deref_mut().expect(SERIAL);
+                    if !shared.dma.is_done(instr.thread, id) {
+                        self.log.count(|m| m.dma_wait_cycles += 1);
+                        self.log.stall(StallReason::Synchronization, 1);
+                        continue;
+                    }
+                }
+                if let InstructionKind::DmaStart { src, dst, bytes } = instr.kind {
+                    let shared = shared.as_deref_mut().expect(SERIAL);
+                    shared.dma.start(CoreId(core_id), instr.thread, src, dst, bytes);
+                }
+                if let InstructionKind::Join { child } = instr.kind {
+                    if !ctx.finished.contains_key(&child) {
+                        self.log.count(|m| m.join_stall_cycles += 1);
+                        self.log.stall(StallReason::Synchronization, 1);
+                        continue;
+                    }
+                }
+                if let InstructionKind::Barrier { id, participants } = instr.kind {
+                    if !ctx.released.contains(&id) {
+                        let shared = shared.as_deref_mut().expect(SERIAL);
+                        let barrier = shared.barriers.entry(id).or_default();
+                        if !barrier.arrived.contains(&instr.thread) {
+                            if barrier.arrived.is_empty() {
+                                barrier.first_arrival = now;
+                            }
+                            barrier.participants = participants;
+                            barrier.arrived.push(instr.thread);
+                            barrier.last_arrival = now;
+                        }
+                        let wait = |m: &mut Metrics, _, thread, _| {
+                            m.per_thread.entry(thread).or_default().barrier_wait_cycles += 1
+                        };
+                        self.log.count_for(wait, instr.thread, 0);
+                        self.log.stall(StallReason::Synchronization, 1);
+                        continue;
+                    }
+                }
+                if let InstructionKind::LockAcquire { lock_id } = instr.kind {
+                    // Not before the thread's previous release, or it would re-enter
+                    // a section that release is about to end.
+                    if waiting_on_older[idx] {
+                        self.log.stall(StallReason::Synchronization, 1);
+                        continue;
+                    }
+                    let shared = shared.as_deref_mut().expect(SERIAL);
+                    let holder = *shared.locks.entry(lock_id).or_insert(instr.thread);
+                    if holder != instr.thread {
+                        shared.lock_waiters.insert((instr.thread, lock_id));
+                        let wait = |m: &mut Metrics, _, thread, _| {
+                            m.per_thread.entry(thread).or_default().lock_wait_cycles += 1
+                        };
+                        self.log.count_for(wait, instr.thread, 0);
+                        self.log.stall(StallReason::Synchronization, 1);
+                        continue;
+                    }
+                    self.log.count(|m| m.lock_acquisitions += 1);
+                    if shared.lock_waiters.remove(&(instr.thread, lock_id)) {
+                        self.log.count(|m| m.contended_lock_acquisitions += 1);
+                    }
+                }
+                if let InstructionKind::LockRelease { lock_id } = instr.kind {
+                    // The critical section must be done before others may enter.
+                    if waiting_on_older[idx] {
+                        self.log.stall(StallReason::Synchronization, 1);
+                        continue;
+                    }
+                    let shared = shared.as_deref_mut().expect(SERIAL);
+                    if shared.locks.get(&lock_id) == Some(&instr.thread) {
+                        shared.locks.remove(&lock_id);
+                    }
+                }
+                if let InstructionKind::Branch { taken } = instr.kind {
+                    self.log.count(|m| m.branches += 1);
+                    // Speculating, it was predicted at fetch.
+                    let predictor = &mut self.branch_predictor;
+                    let wrong = match ctx.speculative {
+                        true => self.speculating.get(&instr.thread) == Some(&instr.id),
+                        false => !predictor.predict_and_update(instr.address, taken),
+                    };
+                    if wrong {
+                        self.log.count(|m| m.mispredictions += 1);
+                        self.log.effects.push(Effect::Mispredicted(idx));
+                    }
+                }
+                instr.stage = PipelineStage::Commit;
+                instr.stage_cycles_left = self.stage_cycles.commit_cycles;
+                let event = Event::StageChange(PipelineStage::Execute, PipelineStage::Commit);
+                self.log.observe(event, instr);
             }
         }
-
-        self.metrics.total_cycles = self.current_cycle;
     }
 
-    /// Run until all cores have empty workload and empty pipeline.
-    pub fn run_to_completion(&mut self) {
-        loop {
-            let busy = self.cores.iter().any(|c| !c.workload.is_empty() || !c.pipeline.is_empty());
-            if !busy {
-                break;
+    /// Front end: Fetch advances to Decode, Decode to Execute.
+    fn issue(&mut self, now: Cycle) {
+        if !self.clocked {
+            return;
+        }
+        let mut issued = 0;
+        // With a fetch buffer, only issued instructions take up the window.
+        let buffered = self.fetch_buffer_depth.map(|_| self.front_end_len());
+        let window = self.rob_size.unwrap_or(self.pipeline_width);
+        let mut back_end = self.pipeline.len() - buffered.unwrap_or(0);
+        if buffered == Some(0) && back_end < window && self.has_pending_work() {
+            self.log.count(|m| m.fetch_starved_cycles += 1);
+        }
+        for instr in self.pipeline.iter_mut() {
+            if !matches!(instr.stage, PipelineStage::Fetch | PipelineStage::Decode) {
+                continue;
             }
-            self.step();
+            if instr.stage_cycles_left > 0 {
+                instr.stage_cycles_left -= 1;
+                continue;
+            }
+            let decode_cycles = self.stage_cycles.decode_cycles;
+            if instr.stage == PipelineStage::Fetch && decode_cycles > 0 {
+                // The cycle it moves in counts, so Decode adds exactly `decode_cycles`.
+                instr.stage = PipelineStage::Decode;
+                instr.stage_cycles_left = decode_cycles - 1;
+                let event = Event::StageChange(PipelineStage::Fetch, PipelineStage::Decode);
+                self.log.observe(event, instr);
+                continue;
+            }
+            if issued == self.issue_width || (buffered.is_some() && back_end >= window) {
+                continue;
+            }
+            let latency = self.stage_cycles.execute_cycles_for(instr.kind);
+            if self.stage_cycles.unpipelined.contains(&instr.kind) {
+                let free_at = self.unit_busy_until.entry(instr.kind).or_insert(0);
+                if *free_at > now {
+                    self.log.stall(StallReason::Structural, 1);
+                    continue;
+                }
+                *free_at = now + Cycle::from(latency) + 1;
+            }
+            issued += 1;
+            back_end += 1;
+            let count = |m: &mut Metrics, core, _, _| {
+                m.instructions_issued += 1;
+                m.per_core.entry(core).or_default().instructions_issued += 1;
+            };
+            self.log.count_for(count, instr.thread, 0);
+            let old = instr.stage;
+            instr.stage = PipelineStage::Execute;
+            instr.stage_cycles_left = latency;
+            self.log.observe(Event::StageChange(old, PipelineStage::Execute), instr);
         }
     }
+}
 
-    pub fn current_cycle(&self) -> Cycle {
-        self.current_cycle
-    }
+/// A software thread's instructions that have not been fetched yet.
+#[derive(Clone)]
+struct ThreadContext {
+    id: ThreadId,
+    workload: VecDeque<Instruction>,
+}
 
-    pub fn metrics(&self) -> &Metrics {
-        &self.metrics
-    }
+/// A thread waiting in the global ready queue.
+#[derive(Clone)]
+struct ReadyThread {
+    context: ThreadContext,
+    /// Cycle a core last claimed work from this thread (0 = never).
+    last_served: Cycle,
+}
 
-    pub fn num_cores(&self) -> usize {
-        self.num_cores
-    }
+/// Event-driven multicore simulator.
+pub struct Simulator {
+    num_cores: usize,
+    num_threads: usize,
+    cores: Vec<CoreState>,
+    memory: Memory,
+    scheduler: Scheduler,
+    topology: Topology,
+    pub metrics: Metrics,
+    current_cycle: Cycle,
+    /// Accesses that reached memory, if tracing is enabled.
+    memory_trace: Option<Vec<MemoryTraceEntry>>,
+    /// (address, value) of each committed load, in commit order (functional memory only).
+    load_results: Vec<(u64, u64)>,
+    /// Misses waiting in the memory controller queue, by request.
+    queued_misses: HashMap<RequestId, QueuedMiss>,
+    /// Threads no core has claimed yet (global ready queue only).
+    ready_queue: Vec<ReadyThread>,
+    /// Workloads of threads waiting for a Spawn.
+    dormant: HashMap<ThreadId, Vec<Instruction>>,
+    /// Gang whose quantum it is (None = threads in no gang), under gang scheduling.
+    active_gang: Option<usize>,
+    /// Cycle the current gang quantum started.
+    gang_quantum_start: Cycle,
+    /// Barriers some thread is waiting at, by id.
+    barriers: HashMap<u64, BarrierState>,
+    /// Whether stores invalidate other cores' copies of their line (atomics always do).
+    coherence: bool,
+    /// Holder of each taken lock.
+    locks: HashMap<u64, ThreadId>,
+    /// (thread, lock) pairs where the thread found the lock taken and is spinning.
+    lock_waiters: HashSet<(ThreadId, u64)>,
+    /// Cycles between per-thread progress samples (0 = no sampling).
+    progress_interval: Cycle,
+    /// Cycles between [`MetricsSample`]s (0 = no sampling).
+    sample_interval: Cycle,
+    sample_base: SampleBase,
+    /// Unchanged cycles after which a run is declared hung (0 = never). None = derived from
+    /// the memory latencies (see [`Simulator::set_hang_window`]).
+    hang_window: Option<Cycle>,
+    /// Called with (thread, cycle) when a thread's last instruction commits.
+    thread_complete_callbacks: Vec<Box<dyn FnMut(ThreadId, Cycle)>>,
+    /// Receive pipeline events (see [`SimObserver`]).
+    observers: Observers,
+    /// Id the next fetched instruction gets.
+    next_instruction_id: u64,
+    /// `seq` the next instruction loaded for each thread gets.
+    next_seq: HashMap<ThreadId, u64>,
+    /// `global_seq` the next loaded instruction gets.
+    next_global_seq: u64,
+    /// Threads whose instructions are still being pulled from a stream.
+    streams: HashMap<ThreadId, InstructionStream>,
+    /// Cycles at the start of the run whose metrics are kept apart (0 = none).
+    warmup_cycles: Cycle,
+    /// Metrics of the warm-up, once it is over.
+    warmup_metrics: Option<Metrics>,
+    /// Whether the run loops skip cycles in which nothing can change (see
+    /// [`Simulator::set_fast_forward`]).
+    fast_forward: bool,
+    /// Cycles a core halts while changing frequency (see [`Simulator::set_core_frequency`]).
+    dvfs_transition_cycles: u32,
+    /// Power and thermal model (None = cores never throttle).
+    power: Option<PowerConfig>,
+    /// Memory ordering every core enforces.
+    consistency: ConsistencyModel,
+    /// Fetch past mispredicted branches (None = fetch stays on the correct path).
+    speculation: Option<SpeculationConfig>,
+    /// Background copies started by `DmaStart`.
+    dma: DmaEngine,
+    /// Injected interrupts whose cycle has not come yet.
+    pending_interrupts: Vec<PendingInterrupt>,
+    interrupt_config: InterruptConfig,
+    /// Whether per-core work may run on several host threads (see [`PARALLEL_MIN_CORES`]).
+    #[cfg(feature = "parallel")]
+    parallel: bool,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::workload::{build_workload, AccessPattern, WorkloadConfig};
+/// Registered observers, plus the instruction trace if enabled.
+#[derive(Default)]
+struct Observers {
+    custom: Vec<Box<dyn SimObserver>>,
+    trace: Option<InstructionTrace>,
+}
+
+/// Threads that have reached a barrier that has not released yet.
+#[derive(Clone, Default)]
+struct BarrierState {
+    participants: usize,
+    arrived: Vec<ThreadId>,
+    first_arrival: Cycle,
+    last_arrival: Cycle,
+}
+
+/// A miss sent to a queued memory controller that has not started yet.
+#[derive(Clone, Copy)]
+struct QueuedMiss {
+    core_id: usize,
+    thread_id: ThreadId,
+    address: u64,
+    is_write: bool,
+    issued_at: Cycle,
+    /// Extra cycles for the dirty line the miss evicted.
+    writeback_cycles: u32,
+}
+
+/// Cycle cap of [`Simulator::run_to_completion`].
+pub const DEFAULT_MAX_CYCLES: Cycle = 1 << 40;
+
+/// Shortest hang window (see [`Simulator::set_hang_window`]) used unless one is set.
+pub const DEFAULT_HANG_WINDOW_CYCLES: Cycle = 100_000;
+
+/// Unless a hang window is set, it spans at least this many of the slowest memory access
+/// (see [`MemoryConfig::slowest_access_cycles`]), so long latencies are not taken for a hang.
+pub const HANG_WINDOW_ACCESSES: Cycle = 100;
+
+/// Unfetched instructions kept pulled from each stream of [`Simulator::load_streams`].
+pub const STREAM_BUFFER_INSTRUCTIONS: usize = 256;
+
+/// A thread's instructions, pulled as the simulator needs them.
+type InstructionStream = Box<dyn Iterator<Item = Instruction> + Send>;
+
+/// Cores from which per-core work is spread across host threads (`parallel` feature only);
+/// below this the thread handoff costs more than it saves.
+#[cfg(feature = "parallel")]
+pub const PARALLEL_MIN_CORES: usize = 16;
+
+/// Why [`Simulator::run_to_completion_with_limit`] gave up.
+#[derive(Clone, Debug)]
+pub enum SimTimeout {
+    /// Work remained after `max_cycles` cycles.
+    CycleLimit { max_cycles: Cycle },
+    /// Nothing changed for `stalled_for` cycles; `core_id` is the first core with work left and
+    /// `oldest` the instruction at the head of its pipeline.
+    NoProgress {
+        cycle: Cycle,
+        stalled_for: Cycle,
+        core_id: CoreId,
+        oldest: Option<Box<Instruction>>,
+    },
+}
+
+impl fmt::Display for SimTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimTimeout::CycleLimit { max_cycles } => {
+                write!(f, "work remains after {max_cycles} cycles")
+            }
+            SimTimeout::NoProgress {
+                cycle,
+                stalled_for,
+                core_id,
+                oldest,
+            } => {
+                write!(
+                    f,
+                    "no progress for {stalled_for} cycles at cycle {cycle}; core {} is stuck",
+                    core_id.0
+                )?;
+                match oldest {
+                    Some(i) => write!(
+                        f,
+                        " on {:?} #{} (thread {}, address {:#x}) in {}",
+                        i.kind, i.seq, i.thread.0, i.address, i.stage
+                    ),
+                    None => write!(f, " with unfetched work"),
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for SimTimeout {}
+
+/// Snapshot of a simulator's complete state (see [`Simulator::save_checkpoint`]). Thread
+/// completion callbacks and observers are not part of it, and a simulator reading threads
+/// from streams cannot be checkpointed.
+#[derive(Clone)]
+pub struct Checkpoint {
+    num_threads: usize,
+    cores: Vec<CoreState>,
+    memory: Memory,
+    scheduler: Scheduler,
+    topology: Topology,
+    metrics: Metrics,
+    current_cycle: Cycle,
+    memory_trace: Option<Vec<MemoryTraceEntry>>,
+    load_results: Vec<(u64, u64)>,
+    queued_misses: HashMap<RequestId, QueuedMiss>,
+    ready_queue: Vec<ReadyThread>,
+    dormant: HashMap<ThreadId, Vec<Instruction>>,
+    active_gang: Option<usize>,
+    gang_quantum_start: Cycle,
+    barriers: HashMap<u64, BarrierState>,
+    coherence: bool,
+    locks: HashMap<u64, ThreadId>,
+    lock_waiters: HashSet<(ThreadId, u64)>,
+    dma: DmaEngine,
+    pending_interrupts: Vec<PendingInterrupt>,
+    next_instruction_id: u64,
+    next_seq: HashMap<ThreadId, u64>,
+    next_global_seq: u64,
+    warmup_cycles: Cycle,
+    warmup_metrics: Option<Metrics>,
+    progress_interval: Cycle,
+    sample_interval: Cycle,
+    sample_base: SampleBase,
+    hang_window: Option<Cycle>,
+    fast_forward: bool,
+    dvfs_transition_cycles: u32,
+    power: Option<PowerConfig>,
+    consistency: ConsistencyModel,
+    speculation: Option<SpeculationConfig>,
+    interrupt_config: InterruptConfig,
+    #[cfg(feature = "parallel")]
+    parallel: bool,
+}
+
+impl Checkpoint {
+    /// Cycle the checkpoint was taken at.
+    pub fn cycle(&self) -> Cycle {
+        self.current_cycle
+    }
+}
+
+/// What happened in one cycle (returned by [`Simulator::step`]).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CycleSummary {
+    /// Instructions that committed.
+    pub committed: u32,
+    /// Instructions that entered the pipeline.
+    pub fetched: u32,
+    pub cache_hits: u32,
+    pub cache_misses: u32,
+    /// Instructions waiting on memory at the end of the cycle.
+    pub stalled_instructions: u32,
+}
+
+/// Steps the simulator one cycle per item until the work drains (see
+/// [`Simulator::iter_cycles`]). The simulator can be resumed once the iterator is dropped.
+pub struct CycleIter<'a> {
+    sim: &'a mut Simulator,
+}
+
+impl Iterator for CycleIter<'_> {
+    type Item = CycleSummary;
+
+    fn next(&mut self) -> Option<CycleSummary> {
+        if self.sim.is_finished() {
+            return None;
+        }
+        Some(self.sim.step())
+    }
+}
+
+/// One in-flight instruction as seen between steps (see [`Simulator::pipeline_view`]).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InFlightView {
+    pub id: u64,
+    pub thread: ThreadId,
+    pub kind: InstructionKind,
+    pub address: u64,
+    pub stage: PipelineStage,
+    /// Cycles left in the current stage (not counting a stall).
+    pub stage_cycles_left: u32,
+    /// Waiting on a miss.
+    pub stalled: bool,
+    /// Cycles until the miss's data arrives (None if not stalled or memory has not scheduled
+    /// it yet).
+    pub stall_cycles_left: Option<Cycle>,
+}
+
+/// Whether a bounded run drained all the work.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RunStatus {
+    Completed,
+    Running,
+}
+
+/// Whether a budgeted run finished its work (see [`Simulator::run_to_completion_with_budget`]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutcomeStatus {
+    Completed,
+    /// The cycle budget ran out first.
+    Truncated,
+}
+
+/// Result of [`Simulator::run_to_completion_with_budget`]; meaningful whether or not the run
+/// finished.
+#[derive(Clone, Debug)]
+pub struct RunOutcome {
+    pub status: OutcomeStatus,
+    /// Metrics up to the cycle the run stopped at.
+    pub metrics: Metrics,
+    /// Instructions each thread committed. Both maps have an entry for every thread.
+    pub committed_per_thread: HashMap<ThreadId, u64>,
+    /// Instructions each thread has not committed yet (unfetched or in flight).
+    pub remaining_per_thread: HashMap<ThreadId, usize>,
+}
+
+/// Why [`Simulator::run_until`] returned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    PredicateMet,
+    Completed,
+    MaxCyclesReached,
+}
+
+/// Out-of-order window (see [`Simulator::set_reorder_buffer`]).
+#[derive(Clone, Debug)]
+pub struct ReorderBufferConfig {
+    /// Instructions in flight per core, oldest first.
+    pub size: usize,
+}
+
+impl Default for ReorderBufferConfig {
+    fn default() -> Self {
+        Self { size: 64 }
+    }
+}
+
+/// Fetch buffer between fetch and issue (see [`Simulator::set_fetch_buffer`]).
+#[derive(Clone, Debug)]
+pub struct FetchBufferConfig {
+    /// Instructions fetched but not yet issued, per core.
+    pub depth: usize,
+}
+
+impl Default for FetchBufferConfig {
+    fn default() -> Self {
+        Self { depth: 8 }
+    }
+}
+
+/// Load/store queue (see [`Simulator::set_load_store_queue`]).
+#[derive(Clone, Debug)]
+pub struct LoadStoreQueueConfig {
+    /// Loads and stores in flight per core.
+    pub depth: usize,
+}
+
+impl Default for LoadStoreQueueConfig {
+    fn default() -> Self {
+        Self { depth: 16 }
+    }
+}
+
+/// Cycles each pipeline stage takes (see [`Simulator::set_stage_cycles`]).
+#[derive(Clone, Debug)]
+pub struct StageCycles {
+    pub fetch_cycles: u32,
+    /// Cycles between Fetch and Execute, one per front-end stage of a pipelined front end
+    /// (0 = straight from Fetch to Execute).
+    pub decode_cycles: u32,
+    /// Execute latency of kinds not in `execute_latencies`.
+    pub execute_cycles: u32,
+    pub commit_cycles: u32,
+    /// Execute latency by instruction kind.
+    pub execute_latencies: HashMap<InstructionKind, u32>,
+    /// Kinds whose execution unit takes one instruction at a time: a second one waits in
+    /// Fetch until the first has finished executing.
+    pub unpipelined: HashSet<InstructionKind>,
+}
+
+impl StageCycles {
+    /// Execute latency of an instruction of `kind`.
+    pub fn execute_cycles_for(&self, kind: InstructionKind) -> u32 {
+        self.execute_latencies
+            .get(&kind)
+            .copied()
+            .unwrap_or(self.execute_cycles)
+    }
+}
+
+impl Default for StageCycles {
+    fn default() -> Self {
+        Self {
+            fetch_cycles: 1,
+            decode_cycles: 0,
+            execute_cycles: 1,
+            commit_cycles: 1,
+            execute_latencies: HashMap::from([
+                (InstructionKind::Mul, 3),
+                (InstructionKind::Div, 20),
+                (InstructionKind::Fma, 4),
+            ]),
+            unpipelined: HashSet::from([InstructionKind::Div]),
+        }
+    }
+}
+
+/// One core's microarchitecture (see [`Simulator::new_heterogeneous`]).
+#[derive(Clone, Debug)]
+pub struct CoreConfig {
+    pub pipeline_width: usize,
+    /// Max instructions retired per cycle (None = every ready one).
+    pub commit_width: Option<usize>,
+    pub cache: CacheConfig,
+    pub stage_cycles: StageCycles,
+    /// Class reported to placement policies (see [`Scheduler::core_type`]).
+    pub core_type: CoreType,
+}
+
+impl Default for CoreConfig {
+    fn default() -> Self {
+        Self {
+            pipeline_width: 4,
+            commit_width: None,
+            cache: CacheConfig::default(),
+            stage_cycles: StageCycles::default(),
+            core_type: CoreType::Big,
+        }
+    }
+}
+
+/// One socket: its cores and the memory node attached to it (see
+/// [`Simulator::new_multisocket`]).
+#[derive(Clone, Debug)]
+pub struct SocketConfig {
+    pub cores: Vec<CoreConfig>,
+    pub memory: NodeConfig,
+}
+
+impl Simulator {
+    pub fn new(
+        num_cores: usize,
+        num_threads: usize,
+        cache_config: CacheConfig,
+        memory_config: MemoryConfig,
+        pipeline_width: usize,
+    ) -> Self {
+        Self::with_topology(
+            num_cores,
+            num_threads,
+            cache_config,
+            memory_config,
+            pipeline_width,
+            Topology::uniform(),
+        )
+    }
+
+    /// A simulator whose cores differ, core `i` built from `core_configs[i]` (e.g. big and
+    /// little cores). The configuration is validated as [`SimulatorBuilder::build`] does.
+    pub fn new_heterogeneous(
+        core_configs: Vec<CoreConfig>,
+        num_threads: usize,
+        memory_config: MemoryConfig,
+    ) -> Result<Self, ConfigError> {
+        let Some(first) = core_configs.first().cloned() else {
+            return Err(ConfigError::NoCores);
+        };
+        if num_threads == 0 {
+            return Err(ConfigError::NoThreads);
+        }
+        core_configs.iter().try_for_each(validate_core)?;
+        validate_memory(&memory_config)?;
+        let num_cores = core_configs.len();
+        let mut sim = Self::new(num_cores, num_threads, first.cache, memory_config, 4);
+        sim.cores = core_configs.iter().map(CoreState::new).collect();
+        sim.scheduler.set_core_types(core_configs.iter().map(|c| c.core_type).collect());
+        Ok(sim)
+    }
+
+    /// A multi-socket machine: cores are numbered socket by socket, and socket `i`'s memory
+    /// is node `i`, local to its cores. Accesses to data homed on another socket pay
+    /// `memory_config.remote_hop_cycles` on top (the inter-socket penalty); the sockets'
+    /// nodes replace `memory_config.nodes`. Validated as [`Simulator::new_heterogeneous`].
+    pub fn new_multisocket(
+        sockets: Vec<SocketConfig>,
+        num_threads: usize,
+        mut memory_config: MemoryConfig,
+    ) -> Result<Self, ConfigError> {
+        memory_config.nodes = sockets.iter().map(|s| s.memory.clone()).collect();
+        let core_nodes = sockets
+            .iter()
+            .enumerate()
+            .flat_map(|(socket, s)| std::iter::repeat_n(socket, s.cores.len()))
+            .collect();
+        let cores = sockets.into_iter().flat_map(|s| s.cores).collect();
+        let mut sim = Self::new_heterogeneous(cores, num_threads, memory_config)?;
+        sim.topology = Topology::new(core_nodes);
+        Ok(sim)
+    }
+
+    /// A builder that validates the configuration (see [`SimulatorBuilder`]).
+    pub fn builder() -> SimulatorBuilder {
+        SimulatorBuilder::default()
+    }
+
+    /// Like [`Simulator::new`], placing cores on memory nodes according to `topology`, which
+    /// the caller has checked against the nodes (see [`SimulatorBuilder::topology`]).
+    pub(crate) fn with_topology(
+        num_cores: usize,
+        num_threads: usize,
+        cache_config: CacheConfig,
+        memory_config: MemoryConfig,
+        pipeline_width: usize,
+        topology: Topology,
+    ) -> Self {
+        let core_config = CoreConfig {
+            pipeline_width,
+            cache: cache_config,
+            ..CoreConfig::default()
+        };
+        let cores = (0..num_cores).map(|_| CoreState::new(&core_config)).collect();
+        let scheduler = Scheduler::new(num_cores, num_threads);
+        let mut sim = Self {
+            num_cores,
+            num_threads,
+            cores,
+            memory: Memory::new(memory_config),
+            scheduler,
+            topology,
+            metrics: Metrics::new(),
+            current_cycle: 0,
+            memory_trace: None,
+            load_results: Vec::new(),
+            queued_misses: HashMap::new(),
+            ready_queue: Vec::new(),
+            dormant: HashMap::new(),
+            active_gang: None,
+            gang_quantum_start: 0,
+            barriers: HashMap::new(),
+            coherence: false,
+            locks: HashMap::new(),
+            lock_waiters: HashSet::new(),
+            progress_interval: 0,
+            sample_interval: 0,
+            sample_base: SampleBase::default(),
+            hang_window: None,
+            thread_complete_callbacks: Vec::new(),
+            observers: Observers::default(),
+            next_instruction_id: 1,
+            next_seq: HashMap::new(),
+            next_global_seq: 0,
+            streams: HashMap::new(),
+            warmup_cycles: 0,
+            warmup_metrics: None,
+            fast_forward: true,
+            dvfs_transition_cycles: 0,
+            power: None,
+            consistency: ConsistencyModel::Relaxed,
+            speculation: None,
+            dma: DmaEngine::default(),
+            pending_interrupts: Vec::new(),
+            interrupt_config: InterruptConfig::default(),
+            #[cfg(feature = "parallel")]
+            parallel: true,
+        };
+        sim.metrics.total_cycles = 0;
+        sim
+    }
+
+    /// Load workload per thread: thread_workloads[thread_id] = list of instructions, one
+    /// entry per thread. Replaces any workload not fetched yet, numbering the new one from 0;
+    /// fails if the thread count is wrong or an earlier workload still has instructions in
+    /// flight. With a global ready queue the threads go there instead of onto cores.
+    pub fn load_workload(
+        &mut self,
+        thread_workloads: Vec<Vec<Instruction>>,
+    ) -> Result<(), WorkloadError> {
+        self.check_thread_count(thread_workloads.len())?;
+        if self.cores.iter().any(|c| !c.pipeline.is_empty()) {
+            return Err(WorkloadError::InFlight);
+        }
+        self.replace_workload();
+        self.append_workload(thread_workloads)
+    }
+
+    /// Like [`Simulator::load_workload`], but appends each thread's instructions to what it
+    /// has left instead of replacing it, and may be called mid-run.
+    pub fn append_workload(
+        &mut self,
+        thread_workloads: Vec<Vec<Instruction>>,
+    ) -> Result<(), WorkloadError> {
+        self.check_thread_count(thread_workloads.len())?;
+        for (thread_id, instrs) in thread_workloads.into_iter().enumerate() {
+            self.place_thread(ThreadId(thread_id), instrs);
+        }
+        Ok(())
+    }
+
+    /// Like [`Simulator::load_workload`], but each thread's instructions are pulled from its
+    /// stream as fetch needs them (up to [`STREAM_BUFFER_INSTRUCTIONS`] ahead), so a trace
+    /// larger than memory can be replayed. Checkpoints do not capture a stream's position. A
+    /// stream that fails just ends its thread; for a binary trace, keep a
+    /// [`ThreadStream::status`](crate::workload::ThreadStream::status) handle to check it.
+    pub fn load_streams<I>(&mut self, streams: Vec<I>) -> Result<(), WorkloadError>
+    where
+        I: Iterator<Item = Instruction> + Send + 'static,
+    {
+        if streams.len() != self.num_threads {
+            return Err(WorkloadError::ThreadCount {
+                expected: self.num_threads,
+                got: streams.len(),
+            });
+        }
+        if self.cores.iter().any(|c| !c.pipeline.is_empty()) {
+            return Err(WorkloadError::InFlight);
+        }
+        self.replace_workload();
+        for (thread_id, stream) in streams.into_iter().enumerate() {
+            let stream: InstructionStream = Box::new(stream);
+            self.streams.insert(ThreadId(thread_id), stream);
+        }
+        self.top_up_streams();
+        Ok(())
+    }
+
+    /// Pulls from each stream until its thread has [`STREAM_BUFFER_INSTRUCTIONS`] unfetched
+    /// instructions (more than a cycle's fetch, so a thread never looks finished while its
+    /// stream has more), and drops the streams that have run dry.
+    fn top_up_streams(&mut self) {
+        if self.streams.is_empty() {
+            return;
+        }
+        let widest_fetch = self.cores.iter().map(|c| c.fetch_width).max().unwrap_or(1);
+        let target = STREAM_BUFFER_INSTRUCTIONS.max(widest_fetch + 1);
+        let mut thread_ids: Vec<ThreadId> = self.streams.keys().copied().collect();
+        thread_ids.sort_by_key(|t| t.0);
+        for thread_id in thread_ids {
+            let buffered: usize = self
+                .cores
+                .iter()
+                .flat_map(|c| &c.threads)
+                .chain(self.ready_queue.iter().map(|r| &r.context))
+                .filter(|t| t.id == thread_id)
+                .map(|t| t.workload.len())
+                .sum();
+            if buffered >= target {
+                continue;
+            }
+            let stream = self.streams.get_mut(&thread_id).expect("listed above");
+            let batch: Vec<Instruction> = stream.take(target - buffered).collect();
+            if batch.len() < target - buffered {
+                self.streams.remove(&thread_id);
+            }
+            if !batch.is_empty() {
+                self.place_thread(thread_id, batch);
+            }
+        }
+    }
+
+    /// Clears the workload for one that replaces it, with nothing in flight: the new one's
+    /// `seq` and `global_seq` start from 0 again.
+    fn replace_workload(&mut self) {
+        self.clear_workload();
+        self.next_seq.clear();
+        self.next_global_seq = 0;
+    }
+
+    /// Drops every thread's unfetched instructions: those queued on cores, in the ready
+    /// queue, waiting for a Spawn, or still in a stream. Instructions already in a pipeline
+    /// still complete. Instructions loaded afterwards continue the sequence numbers, so the
+    /// dropped ones leave a gap in them (a replacing `load_workload` starts from 0 instead).
+    pub fn clear_workload(&mut self) {
+        for core in &mut self.cores {
+            core.threads.clear();
+            core.running = None;
+            core.quantum_left = 0;
+        }
+        self.ready_queue.clear();
+        self.dormant.clear();
+        self.streams.clear();
+    }
+
+    fn check_thread_count(&self, got: usize) -> Result<(), WorkloadError> {
+        if got != self.num_threads {
+            return Err(WorkloadError::ThreadCount {
+                expected: self.num_threads,
+                got,
+            });
+        }
+        Ok(())
+    }
+
+    /// Loads a fork/join workload: threads added with `add_child` wait until a Spawn for them
+    /// commits, then run like any loaded thread. A thread with no instructions is finished as
+    /// soon as it is loaded (or spawned). Fails if a Join names a thread that never runs (see
+    /// [`WorkloadGraph::check_joins`]) or, like `load_workload`, if the graph's thread count
+    /// is not the configured one.
+    pub fn load_graph(&mut self, graph: WorkloadGraph) -> Result<(), WorkloadError> {
+        graph.check_joins()?;
+        let threads = graph.into_threads();
+        self.check_thread_count(threads.len())?;
+        for (thread_id, (instrs, spawned)) in threads.into_iter().enumerate() {
+            let thread_id = ThreadId(thread_id);
+            if spawned {
+                self.metrics.per_thread.entry(thread_id).or_default();
+                self.dormant.insert(thread_id, instrs);
+            } else if instrs.is_empty() {
+                self.metrics.per_thread.entry(thread_id).or_default();
+                self.finish_thread(thread_id);
+            } else {
+                self.place_thread(thread_id, instrs);
+            }
+        }
+        Ok(())
+    }
+
+    /// Tags `instrs` with `thread_id` and their sequence numbers and appends them to the
+    /// thread's unfetched instructions wherever they are (on a core after a migration or
+    /// steal, or in the ready queue), so one thread never has two contexts fetching at once.
+    /// A thread with none goes to its core (or the ready queue).
+    fn place_thread(&mut self, thread_id: ThreadId, instrs: Vec<Instruction>) {
+        self.metrics.per_thread.entry(thread_id).or_default();
+        let next_seq = self.next_seq.entry(thread_id).or_default();
+        let next_global_seq = &mut self.next_global_seq;
+        let instrs = instrs.into_iter().map(|mut i| {
+            i.thread = thread_id;
+            i.seq = *next_seq;
+            i.global_seq = *next_global_seq;
+            *next_seq += 1;
+            *next_global_seq += 1;
+            i
+        });
+        let existing = self
+            .ready_queue
+            .iter_mut()
+            .map(|r| &mut r.context)
+            .chain(self.cores.iter_mut().flat_map(|c| c.threads.iter_mut()))
+            .find(|t| t.id == thread_id);
+        if let Some(thread) = existing {
+            thread.workload.extend(instrs);
+            return;
+        }
+        if self.scheduler.global_queue().is_some() {
+            let context = ThreadContext {
+                id: thread_id,
+                workload: instrs.collect(),
+            };
+            self.push_ready(context);
+            return;
+        }
+        self.cores[self.scheduler.thread_to_core(thread_id).0]
+            .threads
+            .push_back(ThreadContext {
+                id: thread_id,
+                workload: instrs.collect(),
+            });
+    }
+
+    /// Thread that holds `core_id` right now (None before its first fetch or once it is idle).
+    pub fn running_thread(&self, core_id: CoreId) -> Option<ThreadId> {
+        let core = self.cores.get(core_id.0)?;
+        core.running.filter(|t| core.threads.iter().any(|c| c.id == *t))
+    }
+
+    /// Threads with unfetched work on `core_id`, running one first. Unlike
+    /// [`Scheduler::threads_on_core`] this reflects steals and migrations.
+    pub fn threads_on_core(&self, core_id: CoreId) -> Vec<ThreadId> {
+        self.cores
+            .get(core_id.0)
+            .map(|core| core.threads.iter().map(|t| t.id).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn scheduler(&self) -> &Scheduler {
+        &self.scheduler
+    }
+
+    /// Scheduling policy knobs (priorities, ...); change them between steps.
+    pub fn scheduler_mut(&mut self) -> &mut Scheduler {
+        &mut self.scheduler
+    }
+
+    /// Lets idle cores steal work from the most-loaded core (see [`WorkStealingConfig`]).
+    pub fn set_work_stealing(&mut self, config: WorkStealingConfig) {
+        self.scheduler.set_work_stealing(config);
+    }
+
+    /// Gives every core `contexts` hardware threads (SMT) sharing its pipeline width and L1:
+    /// each cycle fetch alternates between the first `contexts` threads on the core, so one
+    /// thread's misses don't keep the other from issuing. 1 = no SMT.
+    pub fn set_smt_contexts(&mut self, contexts: usize) {
+        for core in &mut self.cores {
+            core.smt_contexts = contexts.max(1);
+        }
+    }
+
+    /// Switches to a global ready queue (see [`GlobalQueueConfig`]): unfetched work already
+    /// placed on cores moves to the queue, and cores claim batches from it as they run dry.
+    pub fn set_global_queue(&mut self, config: GlobalQueueConfig) {
+        self.scheduler.set_global_queue(config);
+        for core_id in 0..self.num_cores {
+            let threads = std::mem::take(&mut self.cores[core_id].threads);
+            for context in threads {
+                self.push_ready(context);
+            }
+        }
+    }
+
+    fn push_ready(&mut self, context: ThreadContext) {
+        match self.ready_queue.iter_mut().find(|t| t.context.id == context.id) {
+            Some(ready) => ready.context.workload.extend(context.workload),
+            None => self.ready_queue.push(ReadyThread {
+                context,
+                last_served: 0,
+            }),
+        }
+    }
+
+    /// Gives `core_id` the next batch from the least recently served thread in the global ready
+    /// queue, if the core has run out of work.
+    fn claim_ready_work(&mut self, core_id: usize) {
+        let Some(config) = self.scheduler.global_queue() else {
+            return;
+        };
+        let batch_size = config.batch_size.max(1);
+        if self.cores[core_id].has_pending_work() {
+            return;
+        }
+        let Some(pos) = (0..self.ready_queue.len()).min_by_key(|&i| self.ready_queue[i].last_served)
+        else {
+            return;
+        };
+        let ready = &mut self.ready_queue[pos];
+        ready.last_served = self.current_cycle;
+        let take = batch_size.min(ready.context.workload.len());
+        let batch = ThreadContext {
+            id: ready.context.id,
+            workload: ready.context.workload.drain(..take).collect(),
+        };
+        if ready.context.workload.is_empty() {
+            self.ready_queue.remove(pos);
+        }
+        self.cores[core_id].threads.push_back(batch);
+    }
+
+    /// Sets the migration penalty and enables the load balancer if it has a threshold.
+    pub fn set_migration(&mut self, config: MigrationConfig) {
+        self.scheduler.set_migration(config);
+    }
+
+    /// Moves `thread_id`'s unfetched instructions to `core_id`; instructions already in flight
+    /// finish on the old core. The destination pays the migration penalty and starts with
+    /// whatever its own L1 holds.
+    pub fn migrate_thread(
+        &mut self,
+        thread_id: ThreadId,
+        core_id: CoreId,
+    ) -> Result<(), SchedulerError> {
+        if core_id.0 >= self.num_cores {
+            return Err(SchedulerError::NoSuchCore { thread_id, core_id });
+        }
+        let mut workload = VecDeque::new();
+        for core in &mut self.cores {
+            if let Some(pos) = core.threads.iter().position(|t| t.id == thread_id) {
+                let thread = core.threads.remove(pos).expect("position is in range");
+                workload.extend(thread.workload);
+            }
+        }
+        if workload.is_empty() {
+            return Err(SchedulerError::NoSuchThread(thread_id));
+        }
+        let core = &mut self.cores[core_id.0];
+        match core.threads.iter_mut().find(|t| t.id == thread_id) {
+            Some(thread) => thread.workload.extend(workload),
+            None => core.threads.push_back(ThreadContext {
+                id: thread_id,
+                workload,
+            }),
+        }
+        core.running = None;
+        core.switch_cycles_left = self.scheduler.migration().migration_penalty_cycles;
+        self.metrics.migrations += 1;
+        self.metrics.per_core.entry(core_id).or_default().migrations += 1;
+        Ok(())
+    }
+
+    /// Load balancer: moves a queued thread from the most- to the least-loaded core when their
+    /// pending work differs by more than the threshold.
+    fn balance_load(&mut self) {
+        let config = self.scheduler.migration();
+        if config.imbalance_threshold == 0
+            || !self
+                .current_cycle
+                .is_multiple_of(config.check_interval_cycles.max(1))
+        {
+            return;
+        }
+        let threshold = config.imbalance_threshold;
+        let load = |c: &usize| self.cores[*c].pending_instructions();
+        let (Some(busiest), Some(lightest)) =
+            ((0..self.num_cores).max_by_key(load), (0..self.num_cores).min_by_key(load))
+        else {
+            return;
+        };
+        if load(&busiest) - load(&lightest) <= threshold {
+            return;
+        }
+        // Moving a core's only thread would just move the imbalance.
+        let source = &self.cores[busiest];
+        if source.threads.len() < 2 {
+            return;
+        }
+        if let Some(thread) = source.threads.back().map(|t| t.id) {
+            let _ = self.migrate_thread(thread, CoreId(lightest));
+        }
+    }
+
+    /// Records every thread's committed instruction count every `interval` cycles
+    /// (see `PerThreadMetrics::progress`). 0 = off.
+    pub fn set_progress_interval(&mut self, interval: Cycle) {
+        self.progress_interval = interval;
+    }
+
+    /// Records a [`MetricsSample`] of the last `interval_cycles` every `interval_cycles`
+    /// cycles, plus one for the partial interval the run ends in (see
+    /// [`Simulator::samples`]). 0 = off.
+    pub fn enable_sampling(&mut self, interval_cycles: Cycle) {
+        self.sample_interval = interval_cycles;
+    }
+
+    /// `core_id`'s in-flight instructions, oldest first.
+    pub fn pipeline_view(&self, core_id: CoreId) -> Vec<InFlightView> {
+        let interconnect = self.topology.interconnect_cycles(core_id, self.num_cores);
+        self.cores[core_id.0]
+            .pipeline
+            .iter()
+            .map(|instr| InFlightView {
+                id: instr.id,
+                thread: instr.thread,
+                kind: instr.kind,
+                address: instr.address,
+                stage: instr.stage,
+                stage_cycles_left: instr.stage_cycles_left,
+                stalled: instr.stalled,
+                stall_cycles_left: instr
+                    .mem_request
+                    .filter(|_| instr.stalled)
+                    .and_then(|id| self.memory.completion_cycle(id))
+                    .map(|done| {
+                        let done = done + Cycle::from(interconnect + instr.writeback_cycles);
+                        done.saturating_sub(self.current_cycle)
+                    }),
+            })
+            .collect()
+    }
+
+    /// Instructions the threads assigned to `core_id` have not fetched yet.
+    pub fn workload_remaining(&self, core_id: CoreId) -> usize {
+        self.cores[core_id.0].pending_instructions()
+    }
+
+    /// Samples recorded so far, oldest first.
+    pub fn samples(&self) -> &[MetricsSample] {
+        &self.metrics.samples
+    }
+
+    /// Registers an observer of pipeline events. Observers are not part of checkpoints.
+    pub fn add_observer(&mut self, observer: Box<dyn SimObserver>) {
+        self.observers.custom.push(observer);
+    }
+
+    /// Starts recording an [`InstructionRecord`] for every instruction that commits from now
+    /// on (see [`crate::trace`]).
+    pub fn enable_instruction_trace(&mut self) {
+        self.observers.trace = Some(InstructionTrace::new());
+    }
+
+    /// Like [`Simulator::enable_instruction_trace`], keeping only the last `capacity` records.
+    pub fn enable_instruction_trace_ring(&mut self, capacity: usize) {
+        self.observers.trace = Some(InstructionTrace::ring(capacity));
+    }
+
+    /// Records of committed instructions, in commit order (empty unless tracing is enabled).
+    pub fn instruction_trace(&self) -> Cow<'_, [InstructionRecord]> {
+        self.observers.trace.as_ref().map_or(Cow::Borrowed(&[]), |t| t.records())
+    }
+
+    /// Records every nondeterministic decision (see [`Decision`]) from now on, appending to
+    /// `log`; read it back with [`Simulator::recorded_decisions`].
+    pub fn record_to(&mut self, log: Vec<Decision>) {
+        self.memory.record_decisions(log);
+    }
+
+    /// Replays a recorded run: decisions come from `log` instead of the PRNGs, so the run
+    /// reproduces the recorded metrics and traces. Panics if the run outlasts the log.
+    pub fn replay_from(&mut self, log: &[Decision]) {
+        self.memory.replay_decisions(log);
+    }
+
+    /// Decisions logged since [`Simulator::record_to`].
+    pub fn recorded_decisions(&self) -> &[Decision] {
+        self.memory.recorded_decisions()
+    }
+
+    /// Registers `callback` to run with (thread, cycle) whenever a thread finishes, i.e. its
+    /// last instruction commits (see also `Metrics::thread_completion_cycles`).
+    pub fn on_thread_complete(&mut self, callback: Box<dyn FnMut(ThreadId, Cycle)>) {
+        self.thread_complete_callbacks.push(callback);
+    }
+
+    /// Records that `thread_id` finished this cycle and tells the callbacks.
+    fn finish_thread(&mut self, thread_id: ThreadId) {
+        let now = self.current_cycle;
+        self.metrics.thread_completion_cycles.insert(thread_id, now);
+        for callback in &mut self.thread_complete_callbacks {
+            callback(thread_id, now);
+        }
+    }
+
+    /// Whether `thread_id` has nothing left to fetch or commit.
+    fn thread_finished(&self, thread_id: ThreadId) -> bool {
+        let queued = self.ready_queue.iter().any(|t| t.context.id == thread_id)
+            || self.dormant.contains_key(&thread_id);
+        !queued
+            && self.cores.iter().all(|core| {
+                core.threads
+                    .iter()
+                    .all(|t| t.id != thread_id || t.workload.is_empty())
+                    && core.pipeline.iter().all(|i| i.thread != thread_id)
+            })
+    }
+
+    /// Core holding `thread_id`'s unfetched instructions, if it has any.
+    fn core_with_work(&self, thread_id: ThreadId) -> Option<usize> {
+        self.cores.iter().position(|core| {
+            core.threads
+                .iter()
+                .any(|t| t.id == thread_id && !t.workload.is_empty())
+        })
+    }
+
+    /// Gang scheduling: at each quantum boundary (or once the current gang runs out of work)
+    /// hands the cores to the next gang that can run all its remaining members at once, or to
+    /// the threads in no gang.
+    fn rotate_gangs(&mut self) {
+        let Some(quantum) = self.scheduler.time_slice().map(|s| s.quantum_cycles) else {
+            return;
+        };
+        let gangs = self.scheduler.gangs().to_vec();
+        if gangs.is_empty() {
+            return;
+        }
+        let members_left = |sim: &Self, gang: usize| -> Vec<usize> {
+            gangs[gang].iter().filter_map(|&t| sim.core_with_work(t)).collect()
+        };
+        let loners_left = |sim: &Self| {
+            sim.cores.iter().any(|core| {
+                core.threads
+                    .iter()
+                    .any(|t| !t.workload.is_empty() && sim.scheduler.gang_of(t.id).is_none())
+            })
+        };
+        let live = match self.active_gang {
+            Some(g) => !members_left(self, g).is_empty(),
+            None => loners_left(self),
+        };
+        if live && self.current_cycle - self.gang_quantum_start < quantum {
+            return;
+        }
+        // Slots in turn: gang 0, gang 1, ..., then the threads in no gang.
+        let slots: Vec<Option<usize>> = (0..gangs.len()).map(Some).chain([None]).collect();
+        let current = slots.iter().position(|&s| s == self.active_gang).unwrap_or(0);
+        let mut fallback = None;
+        for step in 1..=slots.len() {
+            let slot = slots[(current + step) % slots.len()];
+            let runnable = match slot {
+                Some(g) => {
+                    let cores = members_left(self, g);
+                    let mut distinct = cores.clone();
+                    distinct.sort_unstable();
+                    distinct.dedup();
+                    if !cores.is_empty() && distinct.len() < cores.len() {
+                        self.metrics.gang_blocked_quanta += 1;
+                        fallback = fallback.or(Some(slot));
+                        false
+                    } else {
+                        !cores.is_empty()
+                    }
+                }
+                None => loners_left(self),
+            };
+            if runnable {
+                self.active_gang = slot;
+                self.gang_quantum_start = self.current_cycle;
+                return;
+            }
+        }
+        // Only gangs that cannot be co-scheduled are left: run one rather than stop.
+        if let (false, Some(slot)) = (live, fallback) {
+            self.active_gang = slot;
+            self.gang_quantum_start = self.current_cycle;
+        }
+    }
+
+    /// Time-slices cores that have more than one thread (see [`TimeSliceConfig`]).
+    pub fn set_time_slice(&mut self, config: TimeSliceConfig) {
+        self.scheduler.set_time_slice(config);
+    }
+
+    /// Gives every core a write buffer so store misses retire without waiting for memory.
+    /// A depth of 0 is treated as 1, since an empty buffer could never accept a store.
+    pub fn set_write_buffer(&mut self, config: WriteBufferConfig) {
+        let config = WriteBufferConfig {
+            depth: config.depth.max(1),
+            ..config
+        };
+        for core in &mut self.cores {
+            core.write_buffer = Some(WriteBuffer::new(config.clone()));
+        }
+    }
+
+    /// Sets the memory ordering of every core. SC drops the write buffers; TSO uses them as
+    /// store buffers, giving cores without one the default (see [`ConsistencyModel`]).
+    pub fn set_consistency(&mut self, model: ConsistencyModel) {
+        for core in &mut self.cores {
+            match model {
+                ConsistencyModel::Relaxed => {}
+                ConsistencyModel::Sc => core.write_buffer = None,
+                ConsistencyModel::Tso => {
+                    if core.write_buffer.is_none() {
+                        core.write_buffer = Some(WriteBuffer::new(WriteBufferConfig::default()));
+                    }
+                }
+            }
+        }
+        self.consistency = model;
+    }
+
+    /// Limits how many misses each core may overlap (memory-level parallelism); further misses
+    /// wait in Execute until one returns. 1 = blocking cache, 0 = unlimited. Instructions are
+    /// independent, so any misses in flight together may overlap.
+    pub fn set_max_outstanding_misses(&mut self, max: usize) {
+        for core in &mut self.cores {
+            core.max_outstanding_misses = max;
+        }
+    }
+
+    /// Gives every core a reorder buffer: up to `size` instructions in flight, fetched at most
+    /// `fetch_width` per cycle. Independent instructions execute under an older miss, but
+    /// they commit strictly in program order, so the oldest unfinished entry blocks commit.
+    pub fn set_reorder_buffer(&mut self, config: ReorderBufferConfig) {
+        for core in &mut self.cores {
+            core.rob_size = Some(config.size.max(1));
+        }
+    }
+
+    /// Gives every core a fetch buffer: fetch fills it at `fetch_width` per cycle and issue
+    /// drains it at `issue_width`, so fetched instructions no longer count toward the
+    /// in-flight window (only issued ones do). A short fetch stall then drains the buffer
+    /// instead of starving issue.
+    pub fn set_fetch_buffer(&mut self, config: FetchBufferConfig) {
+        for core in &mut self.cores {
+            core.fetch_buffer_depth = Some(config.depth.max(1));
+        }
+    }
+
+    /// Whether a finished instruction must wait for every older one of its thread to commit
+    /// first (default on). Off, it leaves as soon as it is done, passing older ones still
+    /// waiting on memory and freeing their pipeline slots early. A ROB always commits in order.
+    ///
+    /// Order is kept per thread rather than per core: threads sharing a core have no program
+    /// order between them, and holding one thread's commits behind another's would deadlock a
+    /// `Join` (or lock, or barrier) waiting in Execute on a thread queued behind it.
+    pub fn set_in_order_commit(&mut self, enabled: bool) {
+        for core in &mut self.cores {
+            core.in_order_commit = enabled;
+        }
+    }
+
+    /// Gives every core a load/store queue holding its in-flight loads and stores. A load
+    /// waits for an older store to the same address to execute, then takes the store's data in
+    /// one cycle without accessing the cache. Fetch stops while the queue is full.
+    pub fn set_load_store_queue(&mut self, config: LoadStoreQueueConfig) {
+        for core in &mut self.cores {
+            core.lsq_depth = Some(config.depth.max(1));
+        }
+    }
+
+    /// Keeps the L1s coherent (write-invalidate): a store invalidates every other core's copy
+    /// of its line, and that core's next access to the line is a coherence miss.
+    pub fn enable_coherence(&mut self) {
+        self.coherence = true;
+    }
+
+    /// Max instructions each core fetches per cycle (default: the pipeline width, which still
+    /// caps instructions in flight).
+    pub fn set_fetch_width(&mut self, width: usize) {
+        for core in &mut self.cores {
+            core.fetch_width = width.max(1);
+        }
+    }
+
+    /// Max instructions each core moves from Fetch to Execute per cycle (default: the pipeline
+    /// width); the rest wait in Fetch.
+    pub fn set_issue_width(&mut self, width: usize) {
+        for core in &mut self.cores {
+            core.issue_width = width.max(1);
+        }
+    }
+
+    /// Max instructions each core retires per cycle (default: unlimited; at least 1); the
+    /// rest wait in Commit.
+    pub fn set_commit_width(&mut self, width: usize) {
+        for core in &mut self.cores {
+            core.commit_width = Some(width.max(1));
+        }
+    }
+
+    /// Treats the first `cycles` cycles of the run as warm-up: they run normally, but their
+    /// metrics go to [`Simulator::warmup_metrics`], and [`Simulator::metrics`] (including
+    /// `total_cycles`) covers only the cycles after. Cycle stamps such as completion cycles
+    /// still count from the start of the run. Call before stepping.
+    pub fn set_warmup_cycles(&mut self, cycles: Cycle) {
+        self.warmup_cycles = cycles;
+    }
+
+    /// Metrics of the warm-up, once it has ended (see [`Simulator::set_warmup_cycles`]).
+    pub fn warmup_metrics(&self) -> Option<&Metrics> {
+        self.warmup_metrics.as_ref()
+    }
+
+    /// Gives core i a pipeline width of `widths[i]`, as if passed to [`Simulator::new`] for
+    /// that core alone (fetch and issue widths follow it). Needs one width (at least 1) per
+    /// core.
+    pub fn set_pipeline_widths(&mut self, widths: &[usize]) -> Result<(), ConfigError> {
+        check_pipeline_widths(self.num_cores, widths)?;
+        for (core, &width) in self.cores.iter_mut().zip(widths) {
+            core.pipeline_width = width;
+            core.fetch_width = width;
+            core.issue_width = width;
+        }
+        Ok(())
+    }
+
+    /// Sets how many memory instructions per core may access the L1 in one cycle (default 1;
+    /// at least 1). The rest wait in Execute.
+    pub fn set_cache_ports(&mut self, ports: usize) {
+        for core in &mut self.cores {
+            core.cache_ports = ports.max(1);
+        }
+    }
+
+    /// Replaces every core's matrix unit (see [`MatrixUnitConfig`]).
+    pub fn set_matrix_unit(&mut self, config: MatrixUnitConfig) {
+        for core in &mut self.cores {
+            core.matrix_unit = config.clone();
+        }
+    }
+
+    /// Turns host-thread parallelism off (or back on), e.g. to compare against the sequential
+    /// path; results are identical either way.
+    #[cfg(feature = "parallel")]
+    pub fn set_parallel(&mut self, enabled: bool) {
+        self.parallel = enabled;
+    }
+
+    /// Lets [`Simulator::run_to_completion`] and [`Simulator::run_for`] jump over stretches
+    /// where every in-flight instruction waits on memory and nothing can be fetched, instead
+    /// of stepping through them (default on). The results are the same either way.
+    pub fn set_fast_forward(&mut self, enabled: bool) {
+        self.fast_forward = enabled;
+    }
+
+    /// Runs `core_id` at `ratio` times the global clock from now on (DVFS): its pipeline
+    /// advances only on that share of cycles, while memory latencies stay in global cycles.
+    /// A change of frequency halts the core for the transition penalty first.
+    ///
+    /// # Panics
+    /// If `ratio` is not in (0, 1].
+    pub fn set_core_frequency(&mut self, core_id: CoreId, ratio: f64) {
+        assert!(ratio > 0.0 && ratio <= 1.0, "clock ratio {ratio} is not in (0, 1]");
+        let core = &mut self.cores[core_id.0];
+        if core.clock_ratio != ratio {
+            core.clock_ratio = ratio;
+            core.frequency_switch_left = self.dvfs_transition_cycles;
+        }
+    }
+
+    /// Sets the cycles a core halts on each frequency change (default 0).
+    pub fn set_dvfs_transition_penalty(&mut self, cycles: u32) {
+        self.dvfs_transition_cycles = cycles;
+    }
+
+    /// Models each core's power from its activity and its temperature from that power; a
+    /// core above the threshold runs at a reduced clock until it cools down.
+    pub fn set_power_model(&mut self, config: PowerConfig) {
+        for core in &mut self.cores {
+            core.thermal = ThermalState::new(&config);
+        }
+        self.power = Some(config);
+    }
+
+    /// Sets how many cycles each stage takes, including per-kind execute latencies.
+    pub fn set_stage_cycles(&mut self, stage_cycles: StageCycles) {
+        for core in &mut self.cores {
+            core.stage_cycles = stage_cycles.clone();
+        }
+    }
+
+    /// Replaces every core's branch predictor (see [`BranchPredictorConfig`]).
+    pub fn set_branch_predictor(&mut self, config: BranchPredictorConfig) {
+        for core in &mut self.cores {
+            core.branch_predictor = BranchPredictor::new(config.clone());
+        }
+    }
+
+    /// Predicts branches at fetch and keeps fetching down the predicted path. A misprediction
+    /// fills the pipeline with wrong-path instructions, squashed when the branch resolves in
+    /// Execute; without this, fetch continues on the correct path and only the instructions
+    /// behind the branch are flushed.
+    pub fn set_speculation(&mut self, config: SpeculationConfig) {
+        self.speculation = Some(config);
+    }
+
+    /// Replaces the DMA engine that runs `DmaStart` transfers (see [`DmaConfig`]).
+    pub fn set_dma(&mut self, config: DmaConfig) {
+        self.dma = DmaEngine::new(config);
+    }
+
+    /// Starts recording every access that misses the cache and reaches memory.
+    pub fn enable_memory_trace(&mut self) {
+        self.memory_trace.get_or_insert_with(Vec::new);
+    }
+
+    /// Accesses that reached memory so far (empty unless tracing is enabled).
+    pub fn memory_trace(&self) -> &[MemoryTraceEntry] {
+        self.memory_trace.as_deref().unwrap_or(&[])
+    }
+
+    /// Writes the memory trace in the plain-text DRAM simulator format
+    /// (see [`memory::write_dram_trace`]).
+    pub fn write_memory_trace<W: Write>(&self, w: W) -> io::Result<()> {
+        memory::write_dram_trace(self.memory_trace(), w)
+    }
+
+    fn trace_memory_access(&mut self, entry: MemoryTraceEntry) {
+        if let Some(trace) = self.memory_trace.as_mut() {
+            trace.push(entry);
+        }
+    }
+
+    /// Run one cycle of the event-driven simulation, returning what happened in it.
+    pub fn step(&mut self) -> CycleSummary {
+        self.top_up_streams();
+        self.current_cycle += 1;
+        let mut summary = CycleSummary::default();
+        let (hits_before, misses_before) = (self.metrics.cache_hits, self.metrics.cache_misses);
+        // Cores on a slower clock sit out the cycles their clock has no edge in.
+        // Activity counters at the start of the cycle, for the power model only.
+        let mut activity_before = Vec::new();
+        for (core_id, core) in self.cores.iter_mut().enumerate() {
+            if core.frequency_switch_left > 0 {
+                self.metrics.dvfs_transition_cycles += 1;
+            }
+            let throttle = match &self.power {
+                Some(power) => {
+                    let per = self.metrics.per_core.entry(CoreId(core_id)).or_default();
+                    activity_before.push((per.instructions_issued, per.memory_accesses));
+                    if core.thermal.throttled {
+                        per.throttled_cycles += 1;
+                        power.throttle_ratio
+                    } else {
+                        1.0
+                    }
+                }
+                None => 1.0,
+            };
+            core.tick_clock(throttle);
+            if core.clocked {
+                self.metrics.per_core.entry(CoreId(core_id)).or_default().clock_cycles += 1;
+            }
+        }
+        let dma_active = self.dma.is_active();
+        let issued_before = self.metrics.instructions_issued;
+        self.tick_dma();
+
+        // 0) Drain write buffers: each drained store occupies the memory channel.
+        for core_id in 0..self.num_cores {
+            if !self.cores[core_id].clocked {
+                continue;
+            }
+            let line_size = self.cores[core_id].cache.line_size();
+            let Some(wb) = self.cores[core_id].write_buffer.as_mut() else {
+                continue;
+            };
+            self.metrics.write_buffer_occupancy_cycles += wb.len() as u64;
+            self.metrics.write_buffer_cycles += 1;
+            if let Some(line_addr) = wb.tick() {
+                let request = MemRequest {
+                    address: line_addr * line_size as u64,
+                    bytes: line_size,
+                    home_node: self.topology.home_node(CoreId(core_id)),
+                    is_write: true,
+                };
+                let issued = self.memory.post(request, self.current_cycle);
+                let core = &mut self.cores[core_id];
+                core.write_drain_until = core.write_drain_until.max(issued.completion_cycle);
+                self.trace_memory_access(MemoryTraceEntry {
+                    cycle: self.current_cycle,
+                    core_id: CoreId(core_id),
+                    address: request.address,
+                    is_write: true,
+                    latency_paid: issued.completion_cycle - self.current_cycle,
+                });
+                self.metrics.bytes_transferred += line_size as u64;
+                self.metrics.memory_energy_pj += self.memory.access_energy_pj(true);
+                self.metrics
+                    .record_channel_access(issued.channel, issued.channel_busy_cycles);
+                self.metrics
+                    .record_bus_transfer(CoreId(core_id), 0, issued.bus.transfer_cycles as u64);
+                self.metrics.record_hotspot(&issued);
+            }
+        }
+
+        // Stages 1-4 run core by core, on several host threads with the `parallel` feature;
+        // what they change outside a core is logged and applied in core order afterwards.
+        #[cfg(feature = "parallel")]
+        let parallel = self.parallel && self.num_cores >= PARALLEL_MIN_CORES;
+        #[cfg(not(feature = "parallel"))]
+        let parallel = false;
+        let observing = !self.observers.custom.is_empty() || self.observers.trace.is_some();
+        for core in &mut self.cores {
+            core.log.observing = observing;
+        }
+        let mut out = StageOutputs::default();
+
+        // 1) Commit stage: drain completed instructions.
+        for_each_core(&mut self.cores, parallel, |_, core| core.commit());
+        self.apply_core_logs(&mut out);
+        summary.committed = out.committed;
+        for child in out.spawned.drain(..) {
+            match self.dormant.remove(&child) {
+                Some(instrs) if instrs.is_empty() => self.finish_thread(child),
+                Some(instrs) => self.place_thread(child, instrs),
+                None => {}
+            }
+        }
+        for thread_id in out.committed_threads.drain(..) {
+            if self.thread_finished(thread_id) {
+                self.finish_thread(thread_id);
+            }
+        }
+
+        // 2) Memory stage: advance or stall.
+        let now = self.current_cycle;
+        let (memory, queued_misses) = (&self.memory, &self.queued_misses);
+        let (topology, num_cores) = (&self.topology, self.num_cores);
+        for_each_core(&mut self.cores, parallel, |core_id, core| {
+            let interconnect = topology.interconnect_cycles(CoreId(core_id), num_cores);
+            core.advance_memory(now, interconnect, memory, queued_misses);
+        });
+        self.apply_core_logs(&mut out);
+
+        // 3) Execute stage: advance; memory ops go to Memory stage and trigger cache access.
+        let speculative = self.speculation.is_some();
+        // Barriers every participant had reached by last cycle release together now.
+        let released: Vec<u64> = self
+            .barriers
+            .iter()
+            .filter(|(_, b)| b.arrived.len() >= b.participants)
+            .map(|(&id, _)| id)
+            .collect();
+        let ctx = ExecuteContext {
+            now,
+            speculative,
+            sc: self.consistency == ConsistencyModel::Sc,
+            keep_wrong_path: self.speculation.as_ref().is_some_and(|s| s.keep_cache_effects),
+            coherence: self.coherence,
+            released: &released,
+            finished: &self.metrics.thread_completion_cycles,
+        };
+        // Cores that may change shared state go one after another, after the others.
+        let functional = self.memory.is_functional();
+        let serial: Vec<bool> =
+            self.cores.iter().map(|c| functional || c.touches_shared_state()).collect();
+        for_each_core(&mut self.cores, parallel, |core_id, core| {
+            if !serial[core_id] {
+                core.execute(core_id, &ctx, None);
+            }
+        });
+        let mut shared = SharedState {
+            memory: &mut self.memory,
+            dma: &mut self.dma,
+            barriers: &mut self.barriers,
+            locks: &mut self.locks,
+            lock_waiters: &mut self.lock_waiters,
+        };
+        for (core_id, core) in self.cores.iter_mut().enumerate() {
+            if serial[core_id] {
+                core.execute(core_id, &ctx, Some(&mut shared));
+            }
+        }
+        self.apply_core_logs(&mut out);
+        for id in released {
+            if let Some(barrier) = self.barriers.remove(&id) {
+                let imbalance = barrier.last_arrival - barrier.first_arrival;
+                self.metrics.barrier_imbalance_cycles.insert(id, imbalance);
+            }
+        }
+        for &(owner, address) in &out.invalidations {
+            for (core_id, core) in self.cores.iter_mut().enumerate() {
+                if core_id != owner && core.cache.invalidate(address) {
+                    self.metrics.coherence_invalidations += 1;
+                    core.coherence_lost.insert(core.cache.line_address(address));
+                }
+            }
+        }
+        self.issue_misses(&out.misses);
+        for issued in self.memory.tick(self.current_cycle) {
+            match self.queued_misses.remove(&issued.id) {
+                Some(miss) => self.record_miss(miss, &issued),
+                // Its instruction was squashed while the request waited.
+                None => self.memory.retire(issued.id),
+            }
+        }
+        for core in &self.cores {
+            let outstanding = core.pipeline.iter().filter(|i| i.stalled).count();
+            self.metrics.record_outstanding_misses(outstanding);
+        }
+        // Younger branches come later in the pipeline, so flush from the back.
+        for &(core_id, idx) in out.mispredicted.iter().rev() {
+            match speculative {
+                true => self.squash_after_branch(core_id, idx),
+                false => self.flush_after_branch(core_id, idx),
+            }
+        }
+
+        // 4) Front end: Fetch advances to Decode, Decode to Execute.
+        for_each_core(&mut self.cores, parallel, |_, core| core.issue(now));
+        self.apply_core_logs(&mut out);
+
+        // 5) Fetch new instructions from the running thread into pipeline (up to pipeline_width).
+        // Serial: cores claim from shared queues, steal from one another, take interrupts and
+        // number instructions globally, so the order cores fetch in is part of the result.
+        self.balance_load();
+        self.rotate_gangs();
+        for core_id in 0..self.num_cores {
+            if !self.cores[core_id].clocked {
+                continue;
+            }
+            self.claim_ready_work(core_id);
+            let now = self.current_cycle;
+            let core = &mut self.cores[core_id];
+            core.sleeping.retain(|_, wake| *wake > now);
+            for &thread_id in core.sleeping.keys() {
+                self.metrics.per_thread.entry(thread_id).or_default().sleep_cycles += 1;
+            }
+            let per = self.metrics.per_core.entry(CoreId(core_id)).or_default();
+            if !core.pipeline.is_empty() {
+                per.busy_cycles += 1;
+                let stalled = core.pipeline.iter().filter(|i| i.stalled).count();
+                if stalled * 2 >= core.pipeline.len() {
+                    per.stall_dominated_cycles += 1;
+                }
+            } else if !core.has_pending_work() {
+                per.idle_cycles += 1;
+                self.steal_work(core_id);
+            }
+            if self.service_interrupt(core_id) || !self.schedule_thread(core_id) {
+                continue;
+            }
+            let core = &mut self.cores[core_id];
+            if core.fetch_stall_cycles > 0 {
+                core.fetch_stall_cycles -= 1;
+                self.metrics.misprediction_flush_cycles += 1;
+                continue;
+            }
+            let contexts = core.smt_contexts.min(core.threads.len());
+            for thread in core.threads.iter().take(contexts) {
+                self.metrics.per_thread.entry(thread.id).or_default().run_cycles += 1;
+            }
+            // Round-robin one instruction at a time across the active contexts.
+            let mut lsq_free = core.lsq_depth.map(|depth| {
+                depth.saturating_sub(core.pipeline.iter().filter(|i| i.is_memory_op()).count())
+            });
+            let time_sliced = self.scheduler.time_slice().is_some();
+            let slots = core.fetch_slots();
+            let mut fetched = 0;
+            let mut drained = 0;
+            while fetched < slots && drained < contexts {
+                let ctx = core.smt_next % contexts;
+                core.smt_next = core.smt_next.wrapping_add(1);
+                let thread_id = core.threads[ctx].id;
+                if core.synchronizing(thread_id) || core.sleeping.contains_key(&thread_id) {
+                    drained += 1;
+                    continue;
+                }
+                let workload = &mut core.threads[ctx].workload;
+                // Down a wrong path the workload stays put; what it would fetch is made up.
+                let speculating = core.speculating.contains_key(&thread_id);
+                let wrong_path = match (&self.speculation, speculating) {
+                    (Some(spec), true) => workload
+                        .front()
+                        .map(|next| next.wrong_path_copy(spec.wrong_path_offset)),
+                    _ => None,
+                };
+                let next = wrong_path.as_ref().or(workload.front());
+                let next_is_memory = next.is_some_and(|i| i.is_memory_op());
+                if next_is_memory && lsq_free == Some(0) {
+                    // Fetch stops here; every context whose next instruction needs an LSQ
+                    // entry waits for one.
+                    let waiting = core
+                        .threads
+                        .iter()
+                        .take(contexts)
+                        .filter(|t| {
+                            !core.synchronizing(t.id)
+                                && !core.sleeping.contains_key(&t.id)
+                                && t.workload.front().is_some_and(|i| i.is_memory_op())
+                        })
+                        .count();
+                    self.metrics.lsq_full_stalls += 1;
+                    self.metrics.record_stall(
+                        CoreId(core_id),
+                        StallReason::Structural,
+                        waiting.max(1) as u64,
+                    );
+                    break;
+                }
+                let Some(mut instr) = wrong_path.or_else(|| workload.pop_front()) else {
+                    drained += 1;
+                    continue;
+                };
+                if let (true, Some(free)) = (next_is_memory, lsq_free.as_mut()) {
+                    *free -= 1;
+                }
+                drained = 0;
+                instr.stage = PipelineStage::Fetch;
+                instr.stage_cycles_left = core.stage_cycles.fetch_cycles;
+                instr.issue_cycle = self.current_cycle;
+                instr.id = self.next_instruction_id;
+                self.next_instruction_id += 1;
+                self.metrics.instructions_fetched += 1;
+                if let (true, InstructionKind::Branch { taken }) = (speculative, instr.kind) {
+                    if !instr.wrong_path
+                        && !core.branch_predictor.predict_and_update(instr.address, taken)
+                    {
+                        core.speculating.insert(thread_id, instr.id);
+                    }
+                }
+                let yielded = instr.kind == InstructionKind::Yield && time_sliced;
+                if yielded {
+                    core.quantum_left = 0;
+                }
+                if let InstructionKind::Sleep { cycles } = instr.kind {
+                    core.sleeping.insert(thread_id, now.saturating_add(cycles).saturating_add(1));
+                }
+                notify(&mut self.observers, |o| o.on_fetch(now, CoreId(core_id), &instr));
+                core.pipeline.push_back(instr);
+                fetched += 1;
+                if yielded {
+                    break;
+                }
+            }
+            if core.rob_size.is_some() {
+                self.metrics.record_rob_occupancy(core.pipeline.len());
+            }
+            if core.fetch_buffer_depth.is_some() {
+                self.metrics.record_fetch_buffer_occupancy(core.front_end_len());
+            }
+            summary.fetched += fetched as u32;
+            let stalled = core.pipeline.iter().filter(|i| i.stalled).count();
+            summary.stalled_instructions += stalled as u32;
+        }
+        for core in &self.cores {
+            self.metrics.record_stage_occupancy(core.pipeline.iter().map(|i| i.stage));
+        }
+        summary.cache_hits = (self.metrics.cache_hits - hits_before) as u32;
+        summary.cache_misses = (self.metrics.cache_misses - misses_before) as u32;
+
+        if self.progress_interval > 0 && self.current_cycle.is_multiple_of(self.progress_interval) {
+            for per in self.metrics.per_thread.values_mut() {
+                per.progress.push((self.current_cycle, per.instructions_committed));
+            }
+        }
+        if self.sample_interval > 0
+            && (self.current_cycle.is_multiple_of(self.sample_interval) || self.is_finished())
+        {
+            self.take_sample();
+        }
+        self.update_thermal(&activity_before);
+        if dma_active {
+            self.metrics.dma_active_cycles += 1;
+            if self.metrics.instructions_issued > issued_before {
+                self.metrics.dma_overlap_cycles += 1;
+            }
+        }
+        self.metrics.memory_energy_pj += self.memory.config().background_power_pw_per_cycle;
+        match self.warmup_metrics {
+            Some(_) => self.metrics.total_cycles = self.current_cycle - self.warmup_cycles,
+            None => {
+                self.metrics.total_cycles = self.current_cycle;
+                if self.warmup_cycles > 0 && self.current_cycle >= self.warmup_cycles {
+                    self.end_warmup();
+                }
+            }
+        }
+        summary
+    }
+
+    /// Adds each core's activity this cycle (since `before`: issued instructions and L1
+    /// accesses) to its power window, and at the end of a window updates its temperature.
+    fn update_thermal(&mut self, before: &[(u64, u64)]) {
+        let Some(power) = &self.power else {
+            return;
+        };
+        let window_done = self.current_cycle.is_multiple_of(power.window_cycles.max(1));
+        for (core_id, core) in self.cores.iter_mut().enumerate() {
+            let per = self.metrics.per_core.entry(CoreId(core_id)).or_default();
+            let (issued, accesses) = before[core_id];
+            core.thermal.record_activity(
+                power,
+                per.instructions_issued - issued,
+                per.memory_accesses - accesses,
+            );
+            if window_done {
+                per.throttle_events += u64::from(core.thermal.end_window(power));
+                per.temperature_sum_c += core.thermal.temperature_c;
+                per.temperature_windows += 1;
+            }
+        }
+    }
+
+    /// Applies what every core's stage work logged, core by core in id order and each in the
+    /// order it happened, so the outcome does not depend on which core ran first.
+    fn apply_core_logs(&mut self, out: &mut StageOutputs) {
+        let now = self.current_cycle;
+        for core_id in 0..self.num_cores {
+            let mut effects = std::mem::take(&mut self.cores[core_id].log.effects);
+            let core = CoreId(core_id);
+            for effect in effects.drain(..) {
+                match effect {
+                    Effect::Count(update) => update(&mut self.metrics),
+                    Effect::CountFor(update, thread, n) => {
+                        update(&mut self.metrics, core, thread, n)
+                    }
+                    Effect::Stall(reason, cycles) => {
+                        self.metrics.record_stall(core, reason, cycles)
+                    }
+                    Effect::CacheEnergy(pj) => self.metrics.cache_energy_pj += pj,
+                    Effect::Observe(event, instr) => {
+                        notify(&mut self.observers, |o| event.send(o, now, core, &instr))
+                    }
+                    Effect::Commit(done) => self.record_commit(core_id, *done, out),
+                    Effect::Miss(idx) => out.misses.push((core_id, idx)),
+                    Effect::Mispredicted(idx) => out.mispredicted.push((core_id, idx)),
+                    Effect::Invalidate(address) => out.invalidations.push((core_id, address)),
+                    Effect::Retire(id) => {
+                        self.memory.retire(id);
+                        self.memory.end_access();
+                    }
+                }
+            }
+            self.cores[core_id].log.effects = effects;
+        }
+    }
+
+    /// Accounts for an instruction `core_id` committed.
+    fn record_commit(&mut self, core_id: usize, done: Instruction, out: &mut StageOutputs) {
+        let now = self.current_cycle;
+        notify(&mut self.observers, |o| o.on_commit(now, CoreId(core_id), &done));
+        out.committed += 1;
+        if done.thread != HANDLER_THREAD {
+            let time_sliced = self.scheduler.time_slice().is_some();
+            let per = self.metrics.per_thread.entry(done.thread).or_default();
+            per.instructions_committed += 1;
+            // Counted here rather than at fetch, where a flush may fetch it twice.
+            let switched = match done.kind {
+                InstructionKind::Yield => time_sliced,
+                InstructionKind::Sleep { .. } => true,
+                _ => false,
+            };
+            if switched {
+                per.voluntary_switches += 1;
+            }
+            if !out.committed_threads.contains(&done.thread) {
+                out.committed_threads.push(done.thread);
+            }
+        }
+        self.metrics.instructions_committed += 1;
+        *self.metrics.committed_by_kind.entry(done.kind.name()).or_default() += 1;
+        let per = self.metrics.per_core.entry(CoreId(core_id)).or_default();
+        per.instructions_committed += 1;
+        if let InstructionKind::Spawn { child } = done.kind {
+            out.spawned.push(child);
+        }
+        if let (InstructionKind::Load, Some(value)) = (done.kind, done.value) {
+            self.load_results.push((done.address, value));
+        }
+    }
+
+    /// If no cycle before the next memory completion can change anything but counters, jumps
+    /// over those cycles (at most `limit`), accounting for them exactly as [`Simulator::step`]
+    /// would, and returns how many were skipped. Only the plainest setups qualify: no time
+    /// slicing, migration, work stealing, barriers, buffered stores, queued misses, progress
+    /// sampling, pending or in-service interrupts, or pending fetch stalls. A core whose threads
+    /// all sleep counts as quiet until the first of them wakes.
+    fn skip_quiet_cycles(&mut self, limit: Cycle) -> Cycle {
+        let scheduler = &self.scheduler;
+        if !self.fast_forward
+            || scheduler.time_slice().is_some()
+            || scheduler.work_stealing().is_some()
+            || scheduler.migration().imbalance_threshold > 0
+            || !self.ready_queue.is_empty()
+            || !self.queued_misses.is_empty()
+            || !self.barriers.is_empty()
+            || self.progress_interval > 0
+            || self.sample_interval > 0
+            || self.power.is_some()
+            || self.dma.is_active()
+            || !self.pending_interrupts.is_empty()
+            || self.cores.iter().any(|c| c.interrupt.is_some())
+            || self.cores.iter().any(|c| c.clock_ratio != 1.0 || c.frequency_switch_left > 0)
+        {
+            return 0;
+        }
+        let mut next_completion = Cycle::MAX;
+        for (core_id, core) in self.cores.iter().enumerate() {
+            // With every thread asleep the core is not scheduled: nothing changes until one
+            // wakes, whichever thread ran last. Otherwise the thread to run must be awake.
+            let asleep = core.threads.iter().all(|t| core.sleeping.contains_key(&t.id));
+            let parked = asleep && !core.threads.is_empty();
+            let front = core.threads.front().map(|t| t.id);
+            let settled = core.switch_cycles_left == 0
+                && core.fetch_stall_cycles == 0
+                && core.write_buffer.as_ref().is_none_or(|wb| wb.is_empty())
+                && (core.threads.is_empty()
+                    || parked
+                    || (core.running == front
+                        && front.is_some_and(|t| !core.sleeping.contains_key(&t))))
+                && (core.fetch_slots() == 0 || !core.has_pending_work() || asleep);
+            if !settled {
+                return 0;
+            }
+            // A thread fetches again in the cycle it wakes.
+            if let Some(&wake) = core.sleeping.values().min() {
+                next_completion = next_completion.min(wake);
+            }
+            let interconnect = self.topology.interconnect_cycles(CoreId(core_id), self.num_cores);
+            for instr in &core.pipeline {
+                let done = instr.mem_request.and_then(|id| self.memory.completion_cycle(id));
+                match done {
+                    Some(done) if instr.stalled && instr.stage == PipelineStage::Memory => {
+                        let done = done + Cycle::from(interconnect + instr.writeback_cycles);
+                        next_completion = next_completion.min(done);
+                    }
+                    _ => return 0,
+                }
+            }
+        }
+        // Cycles current+1 .. next_completion-1 only count stalls.
+        let mut skip = next_completion
+            .saturating_sub(self.current_cycle + 1)
+            .min(limit);
+        if self.warmup_metrics.is_none() && self.warmup_cycles > self.current_cycle {
+            skip = skip.min(self.warmup_cycles - self.current_cycle - 1);
+        }
+        if skip == 0 {
+            return 0;
+        }
+        for (core_id, core) in self.cores.iter().enumerate() {
+            let in_flight = core.pipeline.len() as u64;
+            self.metrics.memory_stall_cycles += skip * in_flight;
+            if in_flight > 0 {
+                let stalled = skip * in_flight;
+                self.metrics.record_stall(CoreId(core_id), StallReason::CacheMiss, stalled);
+                self.metrics.outstanding_miss_cycles += skip * in_flight;
+                self.metrics.miss_active_cycles += skip;
+            }
+            let per = self.metrics.per_core.entry(CoreId(core_id)).or_default();
+            per.clock_cycles += skip;
+            per.memory_stall_cycles += skip * in_flight;
+            if in_flight > 0 {
+                per.busy_cycles += skip;
+                per.stall_dominated_cycles += skip;
+            } else if !core.has_pending_work() {
+                per.idle_cycles += skip;
+            }
+            for instr in &core.pipeline {
+                let per = self.metrics.per_thread.entry(instr.thread).or_default();
+                per.memory_stall_cycles += skip;
+            }
+            for &thread_id in core.sleeping.keys() {
+                self.metrics.per_thread.entry(thread_id).or_default().sleep_cycles += skip;
+            }
+            // A parked core runs no thread and does not sample its occupancy.
+            let parked = !core.threads.is_empty()
+                && core.threads.iter().all(|t| core.sleeping.contains_key(&t.id));
+            if !parked {
+                let contexts = core.smt_contexts.min(core.threads.len());
+                for thread in core.threads.iter().take(contexts) {
+                    self.metrics.per_thread.entry(thread.id).or_default().run_cycles += skip;
+                }
+                if core.rob_size.is_some() {
+                    self.metrics.rob_occupancy_cycles += skip * in_flight;
+                    self.metrics.rob_cycles += skip;
+                }
+                if core.fetch_buffer_depth.is_some() {
+                    self.metrics.fetch_buffer_cycles += skip;
+                }
+            }
+            if core.write_buffer.is_some() {
+                self.metrics.write_buffer_cycles += skip;
+            }
+            if in_flight > 0 {
+                let memory = PipelineStage::Memory;
+                *self.metrics.stage_occupancy.entry(memory).or_default() += skip * in_flight;
+                *self.metrics.oldest_stage_cycles.entry(memory).or_default() += skip;
+            }
+            self.metrics.stage_sampled_cycles += skip;
+        }
+        for _ in 0..skip {
+            // One addition per cycle, so the float sum matches stepping bit for bit.
+            self.metrics.memory_energy_pj += self.memory.config().background_power_pw_per_cycle;
+        }
+        self.current_cycle += skip;
+        self.metrics.total_cycles += skip;
+        skip
+    }
+
+    /// Sets aside the warm-up's metrics and starts counting afresh. Completion cycles carry
+    /// over (a Join may wait on a thread that finished during warm-up).
+    fn end_warmup(&mut self) {
+        let warmup = std::mem::take(&mut self.metrics);
+        self.metrics.thread_completion_cycles = warmup.thread_completion_cycles.clone();
+        for &thread_id in warmup.per_thread.keys() {
+            self.metrics.per_thread.entry(thread_id).or_default();
+        }
+        self.warmup_metrics = Some(warmup);
+        self.sample_base = SampleBase {
+            cycle: self.current_cycle,
+            ..SampleBase::default()
+        };
+    }
+
+    /// Appends a [`MetricsSample`] covering the cycles since the last one.
+    fn take_sample(&mut self) {
+        let cycles = self.current_cycle - self.sample_base.cycle;
+        if cycles == 0 {
+            return;
+        }
+        let m = &self.metrics;
+        let per_core_total: Vec<u64> = (0..self.num_cores)
+            .map(|c| m.per_core.get(&CoreId(c)).map_or(0, |p| p.instructions_committed))
+            .collect();
+        let stall_dominated: u64 = m.per_core.values().map(|p| p.stall_dominated_cycles).sum();
+        let base = &self.sample_base;
+        let hits = m.cache_hits - base.hits;
+        let misses = m.cache_misses - base.misses;
+        let per_core_committed = per_core_total
+            .iter()
+            .enumerate()
+            .map(|(c, &total)| total - base.per_core_committed.get(c).copied().unwrap_or(0))
+            .collect();
+        let sample = MetricsSample {
+            cycle: self.current_cycle,
+            ipc: (m.instructions_committed - base.committed) as f64 / cycles as f64,
+            hit_rate: match hits + misses {
+                0 => 1.0,
+                accesses => hits as f64 / accesses as f64,
+            },
+            stall_fraction: (stall_dominated - base.stall_dominated_cycles) as f64
+                / (cycles * self.num_cores as u64) as f64,
+            per_core_committed,
+        };
+        self.sample_base = SampleBase {
+            cycle: self.current_cycle,
+            committed: m.instructions_committed,
+            hits: m.cache_hits,
+            misses: m.cache_misses,
+            stall_dominated_cycles: stall_dominated,
+            per_core_committed: per_core_total,
+        };
+        self.metrics.samples.push(sample);
+    }
+
+    /// Picks the thread that runs on `core_id` this cycle: drops finished threads and, when the
+    /// running thread finishes or (under time slicing) its quantum expires, switches to the
+    /// thread the scheduler picks (see [`Scheduler::pick_thread`]). An expired thread waiting
+    /// on a miss is preempted once the miss returns, unless `preempt_on_miss` is set. Returns
+    /// false while the core is context switching (nothing is fetched).
+    fn schedule_thread(&mut self, core_id: usize) -> bool {
+        let time_slice = self.scheduler.time_slice().cloned();
+        let scheduler = &self.scheduler;
+        let core = &mut self.cores[core_id];
+        core.threads.retain(|t| !t.workload.is_empty());
+        if core.switch_cycles_left > 0 {
+            core.switch_cycles_left -= 1;
+            return false;
+        }
+        // Under gang scheduling only the threads whose quantum it is may run.
+        let gangs_on = time_slice.is_some() && !scheduler.gangs().is_empty();
+        let active_gang = self.active_gang;
+        let may_run = |t: ThreadId| !gangs_on || scheduler.gang_of(t) == active_gang;
+        // A thread waiting to join or at a barrier yields the core to the others.
+        let running_alive = core.running.is_some_and(|t| {
+            may_run(t) && !core.synchronizing(t) && !core.sleeping.contains_key(&t)
+        }) && core.threads.front().map(|t| t.id) == core.running;
+        // A thread waiting on a miss keeps the core until the data returns.
+        let waiting_on_miss = core
+            .pipeline
+            .iter()
+            .any(|i| i.stalled && Some(i.thread) == core.running);
+        let expired = time_slice.as_ref().is_some_and(|slice| {
+            core.quantum_left == 0 && (slice.preempt_on_miss || !waiting_on_miss)
+        });
+        if !running_alive || expired {
+            if running_alive {
+                // Preempted: go to the back so equal-priority threads take turns.
+                core.threads.rotate_left(1);
+            }
+            let candidates: Vec<usize> = (0..core.threads.len())
+                .filter(|&i| {
+                    let id = core.threads[i].id;
+                    may_run(id) && !core.synchronizing(id) && !core.sleeping.contains_key(&id)
+                })
+                .collect();
+            let runnable: Vec<(ThreadId, usize)> = candidates
+                .iter()
+                .map(|&i| (core.threads[i].id, core.threads[i].workload.len()))
+                .collect();
+            if let Some(pos) = scheduler.pick_thread(&runnable) {
+                let thread = core.threads.remove(candidates[pos]).expect("position is in range");
+                core.threads.push_front(thread);
+            }
+        }
+        let Some(next) = core.threads.front().map(|t| t.id) else {
+            return true;
+        };
+        if !may_run(next) || core.sleeping.contains_key(&next) {
+            return false;
+        }
+        if let Some(slice) = time_slice {
+            if core.running != Some(next) && core.running.is_some() {
+                self.metrics.context_switches += 1;
+                self.metrics.per_core.entry(CoreId(core_id)).or_default().context_switches += 1;
+                core.switch_cycles_left = slice.context_switch_cycles;
+                if slice.flush_cache_on_switch {
+                    core.cache.flush();
+                }
+            }
+            if core.running != Some(next) || expired {
+                core.quantum_left = scheduler.quantum(next).unwrap_or(1);
+            }
+            core.running = Some(next);
+            if core.switch_cycles_left > 0 {
+                core.switch_cycles_left -= 1;
+                return false;
+            }
+            core.quantum_left = core.quantum_left.saturating_sub(1);
+        }
+        core.running = Some(next);
+        true
+    }
+
+    /// Mispredicted branch at `pipeline[idx]`: the thread's younger instructions still in
+    /// Fetch or Execute go back to the front of its workload, and the core stops fetching for
+    /// the misprediction penalty. Younger instructions already past Execute are kept.
+    fn flush_after_branch(&mut self, core_id: usize, idx: usize) {
+        let thread_id = self.cores[core_id].pipeline[idx].thread;
+        self.flush_front_end(core_id, idx + 1, Some(thread_id));
+        let core = &mut self.cores[core_id];
+        core.fetch_stall_cycles = core.branch_predictor.config().mispredict_penalty_cycles;
+    }
+
+    /// Sends the instructions at `pipeline[start..]` still in Fetch, Decode or Execute (only
+    /// `thread`'s, if given) back to the front of their workloads; those past Execute stay.
+    /// Wrong-path instructions are dropped, and handler instructions go back to the handler.
+    fn flush_front_end(&mut self, core_id: usize, start: usize, thread: Option<ThreadId>) {
+        let core = &mut self.cores[core_id];
+        let mut i = core.pipeline.len();
+        while i > start {
+            i -= 1;
+            let instr = &core.pipeline[i];
+            let in_front_end = matches!(
+                instr.stage,
+                PipelineStage::Fetch | PipelineStage::Decode | PipelineStage::Execute
+            );
+            if !in_front_end || instr.stalled || thread.is_some_and(|t| t != instr.thread) {
+                continue;
+            }
+            let instr = core.pipeline.remove(i).expect("index is in range");
+            let now = self.current_cycle;
+            notify(&mut self.observers, |o| o.on_squash(now, CoreId(core_id), &instr));
+            if core.matrix_holder == Some(instr.id) {
+                core.matrix_holder = None;
+            }
+            if core.speculating.get(&instr.thread) == Some(&instr.id) {
+                core.speculating.remove(&instr.thread);
+            }
+            if instr.wrong_path {
+                self.metrics.instructions_squashed += 1;
+                continue;
+            }
+            if let InstructionKind::Sleep { .. } = instr.kind {
+                // It parks the thread again when it is fetched again.
+                core.sleeping.remove(&instr.thread);
+            }
+            if instr.thread == HANDLER_THREAD {
+                if let Some(interrupt) = core.interrupt.as_mut() {
+                    interrupt.handler.push_front(instr);
+                }
+                continue;
+            }
+            match core.threads.iter_mut().find(|t| t.id == instr.thread) {
+                Some(thread) => thread.workload.push_front(instr),
+                None => core.threads.push_front(ThreadContext {
+                    id: instr.thread,
+                    workload: VecDeque::from([instr]),
+                }),
+            }
+        }
+    }
+
+    /// Takes the core's next due interrupt if it has none in progress, then advances the one
+    /// in progress by a cycle. Returns whether the core spent the cycle on it (and so fetches
+    /// nothing from its threads).
+    fn service_interrupt(&mut self, core_id: usize) -> bool {
+        let now = self.current_cycle;
+        if self.cores[core_id].interrupt.is_none() {
+            let Some(pos) = self
+                .pending_interrupts
+                .iter()
+                .position(|p| p.core_id == CoreId(core_id) && p.at <= now)
+            else {
+                return false;
+            };
+            let pending = self.pending_interrupts.remove(pos);
+            let phase = match self.interrupt_config.mode {
+                InterruptMode::Squash => {
+                    self.flush_front_end(core_id, 0, None);
+                    InterruptPhase::Entry(self.interrupt_config.entry_cycles)
+                }
+                InterruptMode::Drain => InterruptPhase::Draining,
+            };
+            let core = &mut self.cores[core_id];
+            core.interrupt = Some(ActiveInterrupt {
+                phase,
+                handler: pending.handler.into(),
+                victim: core.running,
+            });
+        }
+        let (entry_cycles, exit_cycles) =
+            (self.interrupt_config.entry_cycles, self.interrupt_config.exit_cycles);
+        let core = &mut self.cores[core_id];
+        let slots = core.fetch_slots();
+        let interrupt = core.interrupt.as_mut().expect("an interrupt is in progress");
+        if interrupt.phase == InterruptPhase::Draining && core.pipeline.is_empty() {
+            interrupt.phase = InterruptPhase::Entry(entry_cycles);
+        }
+        match interrupt.phase {
+            InterruptPhase::Draining | InterruptPhase::Handler => {}
+            InterruptPhase::Entry(0) => interrupt.phase = InterruptPhase::Handler,
+            InterruptPhase::Entry(left) => interrupt.phase = InterruptPhase::Entry(left - 1),
+            InterruptPhase::Exit(0) => {
+                core.interrupt = None;
+                let per = self.metrics.per_core.entry(CoreId(core_id)).or_default();
+                per.interrupts_serviced += 1;
+                return false;
+            }
+            InterruptPhase::Exit(left) => interrupt.phase = InterruptPhase::Exit(left - 1),
+        }
+        if interrupt.phase == InterruptPhase::Handler {
+            let mut fetched = 0;
+            while fetched < slots {
+                let Some(mut instr) = interrupt.handler.pop_front() else {
+                    break;
+                };
+                instr.stage = PipelineStage::Fetch;
+                instr.stage_cycles_left = core.stage_cycles.fetch_cycles;
+                instr.issue_cycle = now;
+                instr.thread = HANDLER_THREAD;
+                instr.id = self.next_instruction_id;
+                self.next_instruction_id += 1;
+                self.metrics.instructions_fetched += 1;
+                notify(&mut self.observers, |o| o.on_fetch(now, CoreId(core_id), &instr));
+                core.pipeline.push_back(instr);
+                fetched += 1;
+            }
+            let running = core.pipeline.iter().any(|i| i.thread == HANDLER_THREAD);
+            if interrupt.handler.is_empty() && !running {
+                interrupt.phase = InterruptPhase::Exit(exit_cycles);
+            }
+        }
+        let victim = interrupt.victim;
+        self.metrics.per_core.entry(CoreId(core_id)).or_default().interrupt_overhead_cycles += 1;
+        if let Some(victim) = victim {
+            self.metrics.per_thread.entry(victim).or_default().interrupted_cycles += 1;
+        }
+        true
+    }
+
+    /// Runs `handler_instructions` on `core_id` from cycle `at_cycle`, interrupting whatever
+    /// thread it is running (see [`InterruptConfig`] for what happens to its in-flight work).
+    /// Handler instructions count toward no thread.
+    pub fn inject_interrupt(
+        &mut self,
+        core_id: CoreId,
+        at_cycle: Cycle,
+        handler_instructions: Vec<Instruction>,
+    ) {
+        self.pending_interrupts.push(PendingInterrupt {
+            core_id,
+            at: at_cycle,
+            handler: handler_instructions,
+        });
+    }
+
+    /// Sets how interrupts are taken (see [`InterruptConfig`]).
+    pub fn set_interrupt_config(&mut self, config: InterruptConfig) {
+        self.interrupt_config = config;
+    }
+
+    /// Sends this cycle's DMA chunks to memory: a read of the source and a write of the
+    /// destination each, with the destination lines filled into the issuing core's cache when
+    /// the engine prefetches.
+    fn tick_dma(&mut self) {
+        let now = self.current_cycle;
+        let prefetch = self.dma.config().prefetch_into_cache;
+        self.dma.tick(now, |chunk| {
+            let home_node = self.topology.home_node(chunk.core_id);
+            let mut done_at = now;
+            for (address, is_write) in [(chunk.src, false), (chunk.dst, true)] {
+                let request = MemRequest {
+                    address,
+                    bytes: chunk.bytes as usize,
+                    home_node,
+                    is_write,
+                };
+                let issued = self.memory.post(request, now);
+                done_at = done_at.max(issued.completion_cycle);
+                self.metrics.bytes_transferred += chunk.bytes;
+                self.metrics.memory_energy_pj += self.memory.access_energy_pj(is_write);
+                self.metrics
+                    .record_channel_access(issued.channel, issued.channel_busy_cycles);
+                self.metrics.record_hotspot(&issued);
+            }
+            if self.memory.is_functional() {
+                for offset in (0..chunk.bytes).step_by(8) {
+                    let word = self.memory.read(chunk.src + offset);
+                    self.memory.write(chunk.dst + offset, word);
+                }
+            }
+            if prefetch {
+                let cache = &mut self.cores[chunk.core_id.0].cache;
+                let line_size = cache.line_size() as u64;
+                let first = cache.line_address(chunk.dst);
+                let last = cache.line_address(chunk.dst + chunk.bytes.max(1) - 1);
+                for line in first..=last {
+                    cache.access(line * line_size);
+                }
+            }
+            self.metrics.dma_bytes += chunk.bytes;
+            done_at
+        });
+    }
+
+    /// Mispredicted branch at `pipeline[idx]` under speculation: the thread's wrong-path
+    /// instructions are squashed wherever they are, outstanding misses included, and fetch
+    /// resumes on the correct path after the misprediction penalty.
+    fn squash_after_branch(&mut self, core_id: usize, idx: usize) {
+        let core = &mut self.cores[core_id];
+        let thread_id = core.pipeline[idx].thread;
+        let mut i = idx + 1;
+        while i < core.pipeline.len() {
+            let instr = &core.pipeline[i];
+            if instr.thread != thread_id || !instr.wrong_path {
+                i += 1;
+                continue;
+            }
+            let instr = core.pipeline.remove(i).expect("index is in range");
+            let now = self.current_cycle;
+            notify(&mut self.observers, |o| o.on_squash(now, CoreId(core_id), &instr));
+            self.metrics.instructions_squashed += 1;
+            if let Some(id) = instr.mem_request {
+                // Still queued, memory issues it anyway but nobody waits for it.
+                if self.queued_misses.remove(&id).is_none() {
+                    self.memory.retire(id);
+                    self.memory.end_access();
+                }
+            }
+        }
+        core.speculating.remove(&thread_id);
+        core.fetch_stall_cycles = core.branch_predictor.config().mispredict_penalty_cycles;
+    }
+
+    /// Work stealing: idle `thief` takes a whole queued thread from the core with the most
+    /// pending work. A thread is only ever on one core, so the running thread, and any thread
+    /// with instructions still in the victim's pipeline or asleep there, stays put.
+    fn steal_work(&mut self, thief: usize) {
+        let Some(config) = self.scheduler.work_stealing().cloned() else {
+            return;
+        };
+        let Some(victim) = (0..self.num_cores)
+            .filter(|&c| c != thief)
+            .max_by_key(|&c| self.cores[c].pending_instructions())
+        else {
+            return;
+        };
+        let victim_core = &mut self.cores[victim];
+        let running = victim_core.running;
+        let Some(pos) = (0..victim_core.threads.len()).rev().find(|&i| {
+            let id = victim_core.threads[i].id;
+            Some(id) != running
+                && !victim_core.sleeping.contains_key(&id)
+                && !victim_core.pipeline.iter().any(|instr| instr.thread == id)
+        }) else {
+            return;
+        };
+        let stolen = victim_core.threads.remove(pos).expect("position is in range");
+        let core = &mut self.cores[thief];
+        core.threads.push_back(stolen);
+        core.running = None;
+        core.switch_cycles_left = config.steal_penalty_cycles;
+        self.metrics.steals += 1;
+    }
+
+    /// Sends this cycle's cache misses (core, pipeline index) to memory. Cores take turns on the
+    /// shared channel round-robin; each miss stalls until memory reports its request complete
+    /// (queue wait + refresh wait + channel wait + transfer + latency of the node holding the
+    /// address). With a controller queue, a miss's stats are recorded once its bank takes it.
+    fn issue_misses(&mut self, misses: &[(usize, usize)]) {
+        let mut cores: Vec<usize> = misses.iter().map(|&(core_id, _)| core_id).collect();
+        cores.dedup();
+        self.memory.arbitrate(&mut cores, self.num_cores);
+        for core_id in cores {
+            let line_size = self.cores[core_id].cache.line_size();
+            let home_node = self.topology.home_node(CoreId(core_id));
+            for &(_, idx) in misses.iter().filter(|&&(c, _)| c == core_id) {
+                let instr = &self.cores[core_id].pipeline[idx];
+                let address = instr.address;
+                let is_write = instr.kind == InstructionKind::Store;
+                let request = MemRequest {
+                    address,
+                    bytes: line_size,
+                    home_node,
+                    is_write,
+                };
+                let miss = QueuedMiss {
+                    core_id,
+                    thread_id: instr.thread,
+                    address,
+                    is_write,
+                    issued_at: self.current_cycle,
+                    writeback_cycles: instr.writeback_cycles,
+                };
+                let id = if self.memory.is_queued() {
+                    let id = self.memory.enqueue(request, self.current_cycle);
+                    self.queued_misses.insert(id, miss);
+                    id
+                } else {
+                    let issued = self.memory.issue(request, self.current_cycle);
+                    self.record_miss(miss, &issued);
+                    issued.id
+                };
+                self.cores[core_id].pipeline[idx].mem_request = Some(id);
+            }
+        }
+    }
+
+    /// Records the stats of a miss once memory has scheduled it.
+    fn record_miss(&mut self, miss: QueuedMiss, issued: &IssuedRequest) {
+        self.memory.begin_access();
+        let QueuedMiss {
+            core_id,
+            thread_id,
+            address,
+            is_write,
+            issued_at,
+            writeback_cycles,
+        } = miss;
+        let line_size = self.cores[core_id].cache.line_size();
+        let home_node = self.topology.home_node(CoreId(core_id));
+        let interconnect = self.topology.interconnect_cycles(CoreId(core_id), self.num_cores);
+        let stall = issued.completion_cycle - issued_at
+            + u64::from(interconnect)
+            + u64::from(writeback_cycles);
+        let per = self.metrics.per_core.entry(CoreId(core_id)).or_default();
+        per.interconnect_cycles += u64::from(interconnect);
+        self.trace_memory_access(MemoryTraceEntry {
+            cycle: issued_at,
+            core_id: CoreId(core_id),
+            address,
+            is_write,
+            latency_paid: stall,
+        });
+        self.metrics.record_access(CoreId(core_id), thread_id, false);
+        let node = self.memory.node_of(address);
+        self.metrics.record_node_access(node, node != home_node, stall);
+        self.metrics.record_bus_transfer(
+            CoreId(core_id),
+            issued.bus.wait_cycles as u64,
+            issued.bus.transfer_cycles as u64,
+        );
+        self.metrics.refresh_stall_cycles += issued.refresh_delay as u64;
+        self.metrics.queue_wait_cycles += issued.queue_wait as u64;
+        if let Some(hit) = issued.row_hit {
+            self.metrics.record_row_access(hit);
+        }
+        self.metrics.record_hotspot(issued);
+        self.metrics.bytes_transferred += line_size as u64;
+        self.metrics.memory_energy_pj += self.memory.access_energy_pj(is_write);
+        self.metrics
+            .record_miss_latency(stall, issued.throttle_delay as u64);
+        self.metrics
+            .record_channel_access(issued.channel, issued.channel_busy_cycles);
+    }
+
+    /// Captures everything needed to resume from this cycle: pipelines, unfetched work, cache
+    /// tags and LRU order, memory and scheduler state, configuration, and metrics. Fails if
+    /// threads are still read from streams (see [`Simulator::load_streams`]), whose unread
+    /// instructions cannot be copied.
+    pub fn save_checkpoint(&self) -> Result<Checkpoint, WorkloadError> {
+        if !self.streams.is_empty() {
+            return Err(WorkloadError::Streamed);
+        }
+        Ok(Checkpoint {
+            num_threads: self.num_threads,
+            cores: self.cores.clone(),
+            memory: self.memory.clone(),
+            scheduler: self.scheduler.clone(),
+            topology: self.topology.clone(),
+            metrics: self.metrics.clone(),
+            current_cycle: self.current_cycle,
+            memory_trace: self.memory_trace.clone(),
+            load_results: self.load_results.clone(),
+            queued_misses: self.queued_misses.clone(),
+            ready_queue: self.ready_queue.clone(),
+            dormant: self.dormant.clone(),
+            active_gang: self.active_gang,
+            gang_quantum_start: self.gang_quantum_start,
+            barriers: self.barriers.clone(),
+            coherence: self.coherence,
+            locks: self.locks.clone(),
+            lock_waiters: self.lock_waiters.clone(),
+            dma: self.dma.clone(),
+            pending_interrupts: self.pending_interrupts.clone(),
+            next_instruction_id: self.next_instruction_id,
+            next_seq: self.next_seq.clone(),
+            next_global_seq: self.next_global_seq,
+            warmup_cycles: self.warmup_cycles,
+            warmup_metrics: self.warmup_metrics.clone(),
+            progress_interval: self.progress_interval,
+            sample_interval: self.sample_interval,
+            sample_base: self.sample_base.clone(),
+            hang_window: self.hang_window,
+            fast_forward: self.fast_forward,
+            dvfs_transition_cycles: self.dvfs_transition_cycles,
+            power: self.power.clone(),
+            consistency: self.consistency,
+            speculation: self.speculation.clone(),
+            interrupt_config: self.interrupt_config.clone(),
+            #[cfg(feature = "parallel")]
+            parallel: self.parallel,
+        })
+    }
+
+    /// Replaces this simulator's state with `checkpoint`'s, so stepping on continues exactly
+    /// as the saved run would have. Registered callbacks are kept.
+    pub fn restore(&mut self, checkpoint: Checkpoint) {
+        self.num_cores = checkpoint.cores.len();
+        self.num_threads = checkpoint.num_threads;
+        self.cores = checkpoint.cores;
+        self.memory = checkpoint.memory;
+        self.scheduler = checkpoint.scheduler;
+        self.topology = checkpoint.topology;
+        self.metrics = checkpoint.metrics;
+        self.current_cycle = checkpoint.current_cycle;
+        self.memory_trace = checkpoint.memory_trace;
+        self.load_results = checkpoint.load_results;
+        self.queued_misses = checkpoint.queued_misses;
+        self.ready_queue = checkpoint.ready_queue;
+        self.dormant = checkpoint.dormant;
+        self.active_gang = checkpoint.active_gang;
+        self.gang_quantum_start = checkpoint.gang_quantum_start;
+        self.barriers = checkpoint.barriers;
+        self.coherence = checkpoint.coherence;
+        self.locks = checkpoint.locks;
+        self.lock_waiters = checkpoint.lock_waiters;
+        self.dma = checkpoint.dma;
+        self.pending_interrupts = checkpoint.pending_interrupts;
+        self.next_instruction_id = checkpoint.next_instruction_id;
+        self.next_seq = checkpoint.next_seq;
+        self.next_global_seq = checkpoint.next_global_seq;
+        self.warmup_cycles = checkpoint.warmup_cycles;
+        self.warmup_metrics = checkpoint.warmup_metrics;
+        self.progress_interval = checkpoint.progress_interval;
+        self.sample_interval = checkpoint.sample_interval;
+        self.sample_base = checkpoint.sample_base;
+        self.hang_window = checkpoint.hang_window;
+        self.fast_forward = checkpoint.fast_forward;
+        self.dvfs_transition_cycles = checkpoint.dvfs_transition_cycles;
+        self.power = checkpoint.power;
+        self.consistency = checkpoint.consistency;
+        self.speculation = checkpoint.speculation;
+        self.interrupt_config = checkpoint.interrupt_config;
+        #[cfg(feature = "parallel")]
+        {
+            self.parallel = checkpoint.parallel;
+        }
+        self.streams.clear();
+    }
+
+    /// Clears everything a run leaves behind (workloads, pipelines, memory state, metrics,
+    /// traces, the cycle counter), back to how a freshly built simulator with the same
+    /// configuration starts. Configuration, observers and callbacks stay; with
+    /// [`ResetMode::KeepCaches`], so do the caches' contents.
+    pub fn reset(&mut self, mode: ResetMode) {
+        let keep_caches = mode == ResetMode::KeepCaches;
+        for core in &mut self.cores {
+            core.reset(keep_caches, self.power.as_ref());
+        }
+        self.memory.reset();
+        self.metrics = Metrics::new();
+        self.current_cycle = 0;
+        if let Some(trace) = self.memory_trace.as_mut() {
+            trace.clear();
+        }
+        if let Some(trace) = self.observers.trace.as_mut() {
+            trace.clear();
+        }
+        self.load_results.clear();
+        self.queued_misses.clear();
+        self.ready_queue.clear();
+        self.dormant.clear();
+        self.active_gang = None;
+        self.gang_quantum_start = 0;
+        self.barriers.clear();
+        self.locks.clear();
+        self.lock_waiters.clear();
+        self.next_instruction_id = 1;
+        self.next_seq.clear();
+        self.next_global_seq = 0;
+        self.streams.clear();
+        self.warmup_metrics = None;
+        self.sample_base = SampleBase::default();
+        self.dma = DmaEngine::new(self.dma.config().clone());
+        self.pending_interrupts.clear();
+    }
+
+    /// Whether every core has drained its workload, pipeline, and write buffer, and every DMA
+    /// transfer has finished.
+    pub fn is_finished(&self) -> bool {
+        self.ready_queue.is_empty()
+            && self.cores.iter().all(|c| {
+                !c.has_pending_work()
+                    && c.pipeline.is_empty()
+                    && c.write_buffer.as_ref().is_none_or(|wb| wb.is_empty())
+                    && c.interrupt.is_none()
+            })
+            && !self.dma.is_active()
+    }
+
+    /// Iterator that runs one [`Simulator::step`] per item and ends once the work drains. No
+    /// cycles are skipped, and there is no cycle limit or hang detection.
+    pub fn iter_cycles(&mut self) -> CycleIter<'_> {
+        CycleIter { sim: self }
+    }
+
+    /// Run until all cores have empty workload and empty pipeline.
+    ///
+    /// # Panics
+    /// If the run hangs or exceeds [`DEFAULT_MAX_CYCLES`] (see
+    /// [`Simulator::run_to_completion_with_limit`]).
+    pub fn run_to_completion(&mut self) {
+        if let Err(e) = self.run_to_completion_with_limit(DEFAULT_MAX_CYCLES) {
+            panic!("simulation did not complete: {e}");
+        }
+    }
+
+    /// Like [`Simulator::run_to_completion`], but gives up once `max_cycles` more cycles have
+    /// run, or once nothing in any pipeline or workload has changed over a whole hang window
+    /// (see [`Simulator::set_hang_window`]) while work remains. The state is compared at window
+    /// boundaries, so a hang is reported within two windows of its start.
+    pub fn run_to_completion_with_limit(&mut self, max_cycles: Cycle) -> Result<(), SimTimeout> {
+        let start = self.current_cycle;
+        let window = self.hang_window();
+        let mut last_state = self.progress_fingerprint();
+        let mut next_check = start.saturating_add(window);
+        while !self.is_finished() {
+            let ran = self.current_cycle - start;
+            if ran >= max_cycles {
+                return Err(SimTimeout::CycleLimit { max_cycles });
+            }
+            // Stop on the next window boundary so the state is checked there.
+            let mut limit = max_cycles - ran;
+            if window > 0 {
+                limit = limit.min(next_check - self.current_cycle);
+            }
+            if self.skip_quiet_cycles(limit) == 0 {
+                self.step();
+            }
+            if window == 0 || self.current_cycle < next_check {
+                continue;
+            }
+            let state = self.progress_fingerprint();
+            if state == last_state && !self.is_finished() {
+                let core_id = self
+                    .cores
+                    .iter()
+                    .position(|c| !c.pipeline.is_empty() || c.has_pending_work())
+                    .unwrap_or(0);
+                return Err(SimTimeout::NoProgress {
+                    cycle: self.current_cycle,
+                    stalled_for: window,
+                    core_id: CoreId(core_id),
+                    oldest: self.cores[core_id].pipeline.front().cloned().map(Box::new),
+                });
+            }
+            last_state = state;
+            next_check = self.current_cycle.saturating_add(window);
+        }
+        Ok(())
+    }
+
+    /// Cycles with no change anywhere after which a run is declared hung (0 = never). Unless
+    /// set, the window is [`DEFAULT_HANG_WINDOW_CYCLES`] or [`HANG_WINDOW_ACCESSES`] of the
+    /// slowest memory access, whichever is longer.
+    pub fn set_hang_window(&mut self, cycles: Cycle) {
+        self.hang_window = Some(cycles);
+    }
+
+    /// The hang window in effect (see [`Simulator::set_hang_window`]).
+    fn hang_window(&self) -> Cycle {
+        self.hang_window.unwrap_or_else(|| {
+            let slowest = self.memory.config().slowest_access_cycles();
+            DEFAULT_HANG_WINDOW_CYCLES.max(slowest.saturating_mul(HANG_WINDOW_ACCESSES))
+        })
+    }
+
+    /// Hash of everything that moves when the simulation makes progress: instruction stages
+    /// and countdowns, outstanding requests, unfetched work, and buffered stores.
+    fn progress_fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for core in &self.cores {
+            core.pending_instructions().hash(&mut hasher);
+            core.switch_cycles_left.hash(&mut hasher);
+            core.write_buffer.as_ref().map(|wb| wb.len()).hash(&mut hasher);
+            // A sleeping thread is progress: its wake-up gets closer.
+            let sleep_left: Cycle = core
+                .sleeping
+                .values()
+                .map(|wake| wake.saturating_sub(self.current_cycle))
+                .sum();
+            sleep_left.hash(&mut hasher);
+            for instr in &core.pipeline {
+                (instr.stage as u8, instr.stage_cycles_left, instr.stalled).hash(&mut hasher);
+                instr.mem_request.hash(&mut hasher);
+            }
+        }
+        self.ready_queue.len().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Steps at most `cycles` times, stopping early once everything drains. Running in chunks
+    /// is exactly equivalent to one `run_to_completion`.
+    pub fn run_for(&mut self, cycles: u64) -> RunStatus {
+        let mut left = cycles;
+        while left > 0 && !self.is_finished() {
+            left -= match self.skip_quiet_cycles(left) {
+                0 => {
+                    self.step();
+                    1
+                }
+                skipped => skipped,
+            };
+        }
+        if self.is_finished() {
+            RunStatus::Completed
+        } else {
+            RunStatus::Running
+        }
+    }
+
+    /// Runs until the work drains or `max_cycles` more cycles have passed, then reports what
+    /// was done, including how far each thread got if the budget ran out.
+    pub fn run_to_completion_with_budget(&mut self, max_cycles: Cycle) -> RunOutcome {
+        let status = match self.run_for(max_cycles) {
+            RunStatus::Completed => OutcomeStatus::Completed,
+            RunStatus::Running => OutcomeStatus::Truncated,
+        };
+        let mut remaining_per_thread: HashMap<ThreadId, usize> =
+            self.metrics.per_thread.keys().map(|&t| (t, 0)).collect();
+        let mut count = |thread: ThreadId, n: usize| {
+            *remaining_per_thread.entry(thread).or_default() += n;
+        };
+        for core in &self.cores {
+            for thread in &core.threads {
+                count(thread.id, thread.workload.len());
+            }
+            for instr in core.pipeline.iter().filter(|i| !i.wrong_path) {
+                count(instr.thread, 1);
+            }
+        }
+        for ready in &self.ready_queue {
+            count(ready.context.id, ready.context.workload.len());
+        }
+        for (&thread, instrs) in &self.dormant {
+            count(thread, instrs.len());
+        }
+        remaining_per_thread.remove(&HANDLER_THREAD);
+        let committed_per_thread = remaining_per_thread
+            .keys()
+            .map(|t| {
+                let committed = self.metrics.per_thread.get(t);
+                (*t, committed.map_or(0, |per| per.instructions_committed))
+            })
+            .collect();
+        RunOutcome {
+            status,
+            metrics: self.metrics.clone(),
+            committed_per_thread,
+            remaining_per_thread,
+        }
+    }
+
+    /// Steps until `pred` (checked after every step) holds, the work drains, or `max_cycles`
+    /// steps have run, whichever comes first.
+    pub fn run_until(
+        &mut self,
+        mut pred: impl FnMut(&Simulator) -> bool,
+        max_cycles: u64,
+    ) -> StopReason {
+        for _ in 0..max_cycles {
+            if self.is_finished() {
+                return StopReason::Completed;
+            }
+            self.step();
+            if pred(self) {
+                return StopReason::PredicateMet;
+            }
+        }
+        if self.is_finished() {
+            StopReason::Completed
+        } else {
+            StopReason::MaxCyclesReached
+        }
+    }
+
+    /// (address, value) of every committed load in commit order. Only populated when the
+    /// memory is functional.
+    pub fn load_results(&self) -> &[(u64, u64)] {
+        &self.load_results
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    pub fn current_cycle(&self) -> Cycle {
+        self.current_cycle
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn num_cores(&self) -> usize {
+        self.num_cores
+    }
+
+    pub fn num_threads(&self) -> usize {
+        self.num_threads
+    }
+}
+
+/// Outcome of [`run_fairness_study`].
+#[derive(Clone, Debug)]
+pub struct FairnessReport {
+    /// Each thread's completion cycle when sharing the machine over its completion cycle alone.
+    pub slowdowns: HashMap<ThreadId, f64>,
+    /// Smallest slowdown over largest: 1.0 = every thread slowed equally.
+    pub fairness_index: f64,
+    /// Metrics of the shared run.
+    pub shared: Metrics,
+}
+
+/// Runs `thread_workloads` together on a machine of `num_cores` cores, then each thread alone
+/// on the same machine, and compares how much sharing slowed each thread down.
+pub fn run_fairness_study(
+    num_cores: usize,
+    cache_config: CacheConfig,
+    memory_config: MemoryConfig,
+    pipeline_width: usize,
+    thread_workloads: Vec<Vec<Instruction>>,
+) -> FairnessReport {
+    let num_threads = thread_workloads.len();
+    let mut sim = Simulator::new(
+        num_cores,
+        num_threads,
+        cache_config.clone(),
+        memory_config.clone(),
+        pipeline_width,
+    );
+    sim.load_workload(thread_workloads.clone()).expect("one workload per thread");
+    sim.run_to_completion();
+    let shared = sim.metrics().clone();
+
+    let mut slowdowns = HashMap::new();
+    for (t, instrs) in thread_workloads.into_iter().enumerate() {
+        let mut solo = Simulator::new(
+            num_cores,
+            1,
+            cache_config.clone(),
+            memory_config.clone(),
+            pipeline_width,
+        );
+        solo.load_workload(vec![instrs]).expect("one workload per thread");
+        solo.run_to_completion();
+        let alone = solo.metrics().thread_completion_cycles.get(&ThreadId(0)).copied();
+        let together = shared.thread_completion_cycles.get(&ThreadId(t)).copied();
+        if let (Some(alone), Some(together)) = (alone, together) {
+            slowdowns.insert(ThreadId(t), together as f64 / alone.max(1) as f64);
+        }
+    }
+    let min = slowdowns.values().copied().fold(f64::INFINITY, f64::min);
+    let max = slowdowns.values().copied().fold(0.0, f64::max);
+    let fairness_index = if max > 0.0 { min / max } else { 1.0 };
+    FairnessReport {
+        slowdowns,
+        fairness_index,
+        shared,
+    }
+}
+
+/// Runs `f` on every core with its id: across host threads if `parallel`, else in order.
+fn for_each_core<F>(cores: &mut [CoreState], parallel: bool, f: F)
+where
+    F: Fn(usize, &mut CoreState) + Send + Sync,
+{
+    #[cfg(feature = "parallel")]
+    if parallel {
+        use rayon::prelude::*;
+        cores.par_iter_mut().enumerate().for_each(|(id, core)| f(id, core));
+        return;
+    }
+    #[cfg(not(feature = "parallel"))]
+    let _ = parallel;
+    for (id, core) in cores.iter_mut().enumerate() {
+        f(id, core);
+    }
+}
+
+/// Hands an event to every observer.
+fn notify(observers: &mut Observers, mut event: impl FnMut(&mut dyn SimObserver)) {
+    for observer in &mut observers.custom {
+        event(observer.as_mut());
+    }
+    if let Some(trace) = observers.trace.as_mut() {
+        event(trace);
+    }
+}
+
+/// Functional memory: a store writes its value, a load reads the current value.
+/// Timing-only memories skip this entirely.
+fn transfer_data(memory: &mut Memory, instr: &mut Instruction) {
+    if !memory.is_functional() {
+        return;
+    }
+    match instr.kind {
+        InstructionKind::Store => memory.write(instr.address, instr.value.unwrap_or(0)),
+        InstructionKind::Load => instr.value = Some(memory.read(instr.address)),
+        InstructionKind::AtomicRmw => {
+            // Fetch-and-add: `value` is the addend going in and the old value coming out.
+            let old = memory.read(instr.address);
+            memory.write(instr.address, old.wrapping_add(instr.value.unwrap_or(1)));
+            instr.value = Some(old);
+        }
+        InstructionKind::Compute
+        | InstructionKind::Mul
+        | InstructionKind::Div
+        | InstructionKind::Fma
+        | InstructionKind::Spawn { .. }
+        | InstructionKind::Join { .. }
+        | InstructionKind::Barrier { .. }
+        | InstructionKind::LockAcquire { .. }
+        | InstructionKind::LockRelease { .. }
+        | InstructionKind::Branch { .. }
+        | InstructionKind::Fence
+        | InstructionKind::DmaStart { .. }
+        | InstructionKind::DmaWait { .. }
+        | InstructionKind::MatrixOp { .. }
+        | InstructionKind::Yield
+        | InstructionKind::Sleep { .. } => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::branch::PredictorKind;
+    use crate::memory::{
+        DramConfig, LatencyModel, MemoryRegion, NodeConfig, PagePolicy, SchedulingPolicy,
+    };
+    use crate::rng::Rng;
+    use crate::scheduler::ThreadPolicy;
+    use crate::topology::Interconnect;
+    use crate::write_buffer::ConsistencyModel;
+    use crate::workload::{
+        build_workload, shared_footprint, AccessPattern, BranchPattern, FalseSharing,
+        InstructionMix, WorkloadConfig,
+    };
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Runs `workload` to completion on `cores` cores with `memory`, the default cache and
+    /// pipeline width 4, after `setup`, and returns the metrics.
+    fn run_metrics(
+        cores: usize,
+        memory: MemoryConfig,
+        workload: Vec<Vec<Instruction>>,
+        setup: impl FnOnce(&mut Simulator),
+    ) -> Metrics {
+        let mut sim = Simulator::new(cores, workload.len(), CacheConfig::default(), memory, 4);
+        setup(&mut sim);
+        sim.load_workload(workload).unwrap();
+        sim.run_to_completion();
+        sim.metrics().clone()
+    }
+
+    /// Steps until `core_id` has drained its workload and pipeline; returns that cycle.
+    fn run_until_core_idle(sim: &mut Simulator, core_id: usize) -> Cycle {
+        while sim.cores[core_id].has_pending_work() || !sim.cores[core_id].pipeline.is_empty() {
+            sim.step();
+        }
+        sim.current_cycle()
+    }
+
+    #[test]
+    fn simulator_steps_and_drains_workload() {
+        let cache_config = CacheConfig::default();
+        let memory_config = MemoryConfig::default();
+        let mut sim = Simulator::new(2, 2, cache_config, memory_config, 4);
+        let workload = build_workload(
+            2,
+            WorkloadConfig {
+                instructions_per_thread: 20,
+                memory_fraction: 0.2,
+                access_pattern: AccessPattern::Sequential,
+                line_size: 64,
+                cache_num_sets: 64,
+                working_set_lines: 0,
+                ..WorkloadConfig::default()
+            },
+        );
+        sim.load_workload(workload).unwrap();
+        sim.run_to_completion();
+        assert!(sim.current_cycle() > 0);
+        assert!(sim.metrics().total_cycles > 0);
+    }
+
+    #[test]
+    fn simulator_run_for_chunks_match_single_run() {
+        let build = || {
+            let mut sim = Simulator::new(2, 3, CacheConfig::default(), MemoryConfig::default(), 4);
+            sim.set_time_slice(TimeSliceConfig {
+                quantum_cycles: 150,
+                ..TimeSliceConfig::default()
+            });
+            sim.load_workload(build_workload(3, WorkloadConfig::default())).unwrap();
+            sim
+        };
+        let mut whole = build();
+        whole.run_to_completion();
+        let mut chunked = build();
+        let mut chunks = 0;
+        while chunked.run_for(10) == RunStatus::Running {
+            chunks += 1;
+        }
+        assert!(chunks > 10);
+        assert_eq!(chunked.run_for(10), RunStatus::Completed);
+        assert_eq!(chunked.metrics(), whole.metrics());
+        assert_eq!(chunked.current_cycle(), whole.current_cycle());
+    }
+
+    #[test]
+    fn simulator_restored_checkpoint_matches_uninterrupted_run() {
+        let build = || {
+            let memory_config = MemoryConfig {
+                dram: Some(DramConfig::default()),
+                scheduling_policy: Some(SchedulingPolicy::FrFcfs),
+                latency_model: Some(LatencyModel::Normal {
+                    mean: 100.0,
+                    stddev: 25.0,
+                    seed: 3,
+                }),
+                ..MemoryConfig::default()
+            };
+            let mut sim = Simulator::new(2, 3, CacheConfig::default(), memory_config, 4);
+            sim.set_time_slice(TimeSliceConfig {
+                quantum_cycles: 150,
+                ..TimeSliceConfig::default()
+            });
+            sim.set_write_buffer(WriteBufferConfig::default());
+            sim.set_consistency(ConsistencyModel::Sc);
+            sim.set_speculation(SpeculationConfig::default());
+            sim.set_power_model(PowerConfig::default());
+            sim.set_dvfs_transition_penalty(7);
+            sim.set_fast_forward(false);
+            sim.set_interrupt_config(InterruptConfig {
+                mode: InterruptMode::Drain,
+                ..InterruptConfig::default()
+            });
+            sim.inject_interrupt(CoreId(1), 2000, vec![Instruction::new_compute(0); 20]);
+            sim
+        };
+        let workload = || {
+            build_workload(
+                3,
+                WorkloadConfig {
+                    working_set_lines: 80,
+                    branch_fraction: 0.1,
+                    fence_interval: 50,
+                    ..WorkloadConfig::default()
+                },
+            )
+        };
+        // Slowing a core after the checkpoint pays the saved DVFS penalty.
+        let finish = |sim: &mut Simulator, cycles: Cycle| {
+            sim.run_for(cycles);
+            sim.set_core_frequency(CoreId(0), 0.5);
+            sim.run_to_completion();
+        };
+        let mut uninterrupted = build();
+        uninterrupted.load_workload(workload()).unwrap();
+        finish(&mut uninterrupted, 1500);
+
+        let mut first = build();
+        first.load_workload(workload()).unwrap();
+        first.run_for(1234);
+        let checkpoint = first.save_checkpoint().unwrap();
+        assert_eq!(checkpoint.cycle(), 1234);
+        // Diverge the original, then rewind it.
+        first.run_for(500);
+        first.restore(checkpoint.clone());
+        finish(&mut first, 266);
+        assert_eq!(first.metrics(), uninterrupted.metrics());
+
+        // A default simulator takes the saved one's configuration along with its state.
+        let mut resumed = Simulator::new(1, 1, CacheConfig::default(), MemoryConfig::default(), 1);
+        resumed.restore(checkpoint);
+        finish(&mut resumed, 266);
+        assert_eq!(resumed.metrics(), uninterrupted.metrics());
+        assert!(resumed.metrics().mispredictions > 0);
+
+        let mut streamed = build();
+        let stream = (0..1000).map(|_| Instruction::new_compute(0));
+        streamed.load_streams(vec![stream.clone(), stream.clone(), stream]).unwrap();
+        assert_eq!(streamed.save_checkpoint().err(), Some(WorkloadError::Streamed));
+    }
+
+    #[test]
+    fn simulator_reset_reruns_like_a_fresh_simulator() {
+        let build = || {
+            let memory_config = MemoryConfig {
+                dram: Some(DramConfig::default()),
+                latency_model: Some(LatencyModel::Normal {
+                    mean: 100.0,
+                    stddev: 25.0,
+                    seed: 3,
+                }),
+                ..MemoryConfig::default()
+            };
+            let mut sim = Simulator::new(2, 3, CacheConfig::default(), memory_config, 4);
+            sim.set_write_buffer(WriteBufferConfig::default());
+            sim.enable_instruction_trace();
+            sim
+        };
+        let workload = || {
+            build_workload(
+                3,
+                WorkloadConfig {
+                    working_set_lines: 40,
+                    ..WorkloadConfig::default()
+                },
+            )
+        };
+        let mut fresh = build();
+        fresh.load_workload(workload()).unwrap();
+        fresh.run_to_completion();
+
+        let mut reused = build();
+        reused.load_workload(workload()).unwrap();
+        reused.run_for(777);
+        reused.reset(ResetMode::Cold);
+        reused.load_workload(workload()).unwrap();
+        reused.run_to_completion();
+        assert_eq!(reused.metrics(), fresh.metrics());
+        assert_eq!(reused.instruction_trace(), fresh.instruction_trace());
+
+        // Warm: the second run finds the working set already cached.
+        reused.reset(ResetMode::KeepCaches);
+        reused.load_workload(workload()).unwrap();
+        reused.run_to_completion();
+        let (cold, warm) = (fresh.metrics(), reused.metrics());
+        assert!(warm.hit_rate() > cold.hit_rate(), "{} vs {}", warm.hit_rate(), cold.hit_rate());
+        assert!(warm.total_cycles < cold.total_cycles);
+    }
+
+    #[test]
+    fn simulator_samples_window_each_interval() {
+        let mut sim = Simulator::new(2, 2, CacheConfig::default(), MemoryConfig::default(), 4);
+        sim.enable_sampling(500);
+        // Each thread loops over 8 lines, then streams through lines it never revisits.
+        let thread = |base: u64| -> Vec<Instruction> {
+            let hot = (0..2000).map(move |i| base + (i % 8) * 64);
+            let cold = (0..200).map(move |i| base + 0x10_0000 + i * 64);
+            hot.chain(cold)
+                .map(|address| Instruction::new_memory(InstructionKind::Load, address, 0))
+                .collect()
+        };
+        sim.load_workload(vec![thread(0), thread(0x100_0000)]).unwrap();
+        sim.run_to_completion();
+        let samples = sim.samples();
+        let m = sim.metrics();
+        assert!(samples.len() > 4);
+        assert_eq!(samples.last().unwrap().cycle, m.total_cycles);
+        let committed: u64 = samples.iter().flat_map(|s| &s.per_core_committed).sum();
+        assert_eq!(committed, m.instructions_committed);
+        // Hits while looping, then the streaming phase misses and stalls.
+        let first = &samples[0];
+        assert!(first.hit_rate > 0.9, "first {}", first.hit_rate);
+        let streaming = samples.iter().find(|s| s.hit_rate < 0.1).expect("a missing phase");
+        assert!(streaming.stall_fraction > first.stall_fraction);
+        assert!(streaming.ipc < first.ipc);
+    }
+
+    /// Rebuilds each instruction's latency from its events; keyed by address (unique here).
+    #[derive(Default)]
+    struct LatencyObserver {
+        fetched: HashMap<u64, Cycle>,
+        last_change: HashMap<u64, Cycle>,
+        stage_time: HashMap<u64, Cycle>,
+        latencies: Vec<(Cycle, Cycle, Cycle)>,
+        stalls: u64,
+    }
+
+    impl SimObserver for Rc<RefCell<LatencyObserver>> {
+        fn on_fetch(&mut self, cycle: Cycle, _: CoreId, instr: &Instruction) {
+            let mut s = self.borrow_mut();
+            s.fetched.insert(instr.address, cycle);
+            s.last_change.insert(instr.address, cycle);
+        }
+
+        fn on_stage_change(
+            &mut self,
+            cycle: Cycle,
+            _: CoreId,
+            instr: &Instruction,
+            _: PipelineStage,
+            _: PipelineStage,
+        ) {
+            let mut s = self.borrow_mut();
+            let since = s.last_change.insert(instr.address, cycle).unwrap();
+            *s.stage_time.entry(instr.address).or_default() += cycle - since;
+        }
+
+        fn on_stall_begin(&mut self, _: Cycle, _: CoreId, _: &Instruction) {
+            self.borrow_mut().stalls += 1;
+        }
+
+        fn on_commit(&mut self, cycle: Cycle, _: CoreId, instr: &Instruction) {
+            let mut s = self.borrow_mut();
+            let fetched = s.fetched[&instr.address];
+            let in_stages = s.stage_time[&instr.address] + cycle - s.last_change[&instr.address];
+            s.latencies.push((cycle - fetched, in_stages, cycle - instr.issue_cycle));
+        }
+    }
+
+    #[test]
+    fn simulator_observer_reconstructs_latency() {
+        let mut sim = Simulator::new(1, 1, CacheConfig::default(), MemoryConfig::default(), 4);
+        let observer = Rc::new(RefCell::new(LatencyObserver::default()));
+        sim.add_observer(Box::new(Rc::clone(&observer)));
+        // Loads over 8 lines: distinct addresses, a few misses then hits.
+        let loads = (0..64u64)
+            .map(|i| Instruction::new_memory(InstructionKind::Load, (i % 8) * 64 + i, 0))
+            .collect();
+        sim.load_workload(vec![loads]).unwrap();
+        sim.run_to_completion();
+        let observed = observer.borrow();
+        assert_eq!(observed.latencies.len(), 64);
+        assert_eq!(observed.stalls, sim.metrics().cache_misses);
+        for &(from_events, from_stages, from_issue) in &observed.latencies {
+            assert_eq!(from_events, from_issue);
+            assert_eq!(from_stages, from_issue);
+        }
+        assert!(observed.latencies.iter().any(|l| l.0 > 100));
+    }
+
+    #[test]
+    fn simulator_instruction_trace_records_lifetimes() {
+        let workload = || {
+            let mut instrs: Vec<_> = (0..4).map(|_| Instruction::new_compute(0)).collect();
+            instrs.push(Instruction::new_memory(InstructionKind::Load, 0x1000, 0));
+            vec![instrs]
+        };
+        let mut sim = Simulator::new(1, 1, CacheConfig::default(), MemoryConfig::default(), 4);
+        assert!(sim.instruction_trace().is_empty());
+        sim.enable_instruction_trace();
+        sim.load_workload(workload()).unwrap();
+        sim.run_to_completion();
+        let records = sim.instruction_trace();
+        assert_eq!(records.len(), 5);
+        for r in records.iter() {
+            let total: u64 = r.stage_cycles().iter().map(|&(_, c)| c).sum();
+            assert_eq!(total, r.commit_cycle - r.issue_cycle);
+        }
+        let load = records.iter().find(|r| r.kind == InstructionKind::Load).unwrap();
+        assert!(load.stall_cycles > 0);
+        assert_eq!(load.stall_reason, Some(crate::trace::StallReason::CacheMiss));
+
+        let mut csv = Vec::new();
+        crate::trace::write_csv(&records, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 6);
+        assert!(csv.starts_with("id,core,thread,kind,address,issue,decode,execute,memory,"));
+
+        let mut ring = Simulator::new(1, 1, CacheConfig::default(), MemoryConfig::default(), 4);
+        ring.enable_instruction_trace_ring(2);
+        ring.load_workload(workload()).unwrap();
+        ring.run_to_completion();
+        assert_eq!(ring.instruction_trace(), &records[3..]);
+    }
+
+    #[test]
+    fn simulator_pipeline_diagram_matches_golden() {
+        let memory = MemoryConfig {
+            read_latency_cycles: 8,
+            ..MemoryConfig::default()
+        };
+        let mut sim = Simulator::new(1, 1, CacheConfig::default(), memory, 4);
+        sim.enable_instruction_trace();
+        sim.load_workload(vec![vec![
+            Instruction::new_compute(0),
+            Instruction::new_memory(InstructionKind::Load, 0x1000, 0),
+            Instruction::new_compute(0),
+        ]]).unwrap();
+        sim.run_to_completion();
+        let diagram =
+            crate::trace::render_pipeline_diagram(&sim.instruction_trace(), CoreId(0), 0..18);
+        let golden = "\
+cycle           012345678901234567
+   1 compute     F=E=C=
+   2 load        F=E=MMMMMMMMMMC=
+   3 compute     F=E=C=----------
+";
+        assert_eq!(diagram, golden);
+
+        // A long label widens the column instead of pushing its row out of line.
+        let mut records = sim.instruction_trace().into_owned();
+        records[0].id = 12345;
+        records[0].kind = InstructionKind::LockAcquire { lock_id: 0 };
+        let diagram = crate::trace::render_pipeline_diagram(&records, CoreId(0), 0..18);
+        let golden = "\
+cycle              012345678901234567
+   2 load           F=E=MMMMMMMMMMC=
+   3 compute        F=E=C=----------
+12345 lock_acquire  F=E=C=
+";
+        assert_eq!(diagram, golden);
+    }
+
+    #[test]
+    fn simulator_load_workload_validates_and_replaces() {
+        let computes = |n| vec![Instruction::new_compute(0); n];
+        let mut sim = Simulator::new(1, 2, CacheConfig::default(), MemoryConfig::default(), 4);
+        assert_eq!(
+            sim.load_workload(vec![computes(4)]),
+            Err(WorkloadError::ThreadCount { expected: 2, got: 1 })
+        );
+        assert_eq!(
+            sim.append_workload(vec![computes(4); 3]),
+            Err(WorkloadError::ThreadCount { expected: 2, got: 3 })
+        );
+
+        sim.load_workload(vec![computes(4), computes(4)]).unwrap();
+        sim.load_workload(vec![computes(2), computes(3)]).unwrap();
+        assert_eq!(sim.workload_remaining(CoreId(0)), 5);
+        sim.append_workload(vec![computes(1), computes(1)]).unwrap();
+        assert_eq!(sim.workload_remaining(CoreId(0)), 7);
+
+        sim.step();
+        assert_eq!(sim.load_workload(vec![computes(1), computes(1)]), Err(WorkloadError::InFlight));
+        sim.append_workload(vec![computes(1), computes(1)]).unwrap();
+        sim.run_to_completion();
+        assert_eq!(sim.metrics().instructions_committed, 9);
+
+        sim.load_workload(vec![computes(8), computes(8)]).unwrap();
+        sim.clear_workload();
+        assert_eq!(sim.workload_remaining(CoreId(0)), 0);
+        assert!(sim.is_finished());
+    }
+
+    #[test]
+    fn simulator_per_thread_metrics_sum_to_aggregates() {
+        let mut sim = Simulator::new(2, 4, CacheConfig::default(), MemoryConfig::default(), 4);
+        let config = WorkloadConfig {
+            instructions_per_thread: 500,
+            ..WorkloadConfig::default()
+        };
+        sim.load_workload(build_workload(4, config)).unwrap();
+        sim.run_to_completion();
+        let m = sim.metrics();
+        assert_eq!(m.per_thread.len(), 4);
+        let sum = |f: fn(&crate::metrics::PerThreadMetrics) -> u64| {
+            m.per_thread.values().map(f).sum::<u64>()
+        };
+        assert_eq!(sum(|t| t.instructions_committed), m.instructions_committed);
+        assert_eq!(sum(|t| t.memory_accesses), m.total_memory_accesses);
+        assert_eq!(sum(|t| t.cache_hits), m.cache_hits);
+        assert_eq!(sum(|t| t.cache_misses), m.cache_misses);
+        assert_eq!(sum(|t| t.memory_stall_cycles), m.memory_stall_cycles);
+        assert!(m.per_thread.values().all(|t| t.memory_accesses > 0 && t.memory_stall_cycles > 0));
+    }
+
+    #[test]
+    fn simulator_sequence_numbers_are_dense_in_commit_order() {
+        let mut sim = Simulator::new(2, 4, CacheConfig::default(), MemoryConfig::default(), 4);
+        sim.enable_instruction_trace();
+        let config = WorkloadConfig {
+            instructions_per_thread: 300,
+            ..WorkloadConfig::default()
+        };
+        sim.load_workload(build_workload(4, config.clone())).unwrap();
+        sim.append_workload(build_workload(4, config)).unwrap();
+        sim.run_to_completion();
+        for t in 0..4 {
+            let seqs: Vec<u64> = sim
+                .instruction_trace()
+                .iter()
+                .filter(|r| r.thread == ThreadId(t))
+                .map(|r| r.seq)
+                .collect();
+            assert_eq!(seqs, (0..600).collect::<Vec<u64>>());
+        }
+
+        let mut sim = Simulator::new(1, 2, CacheConfig::default(), MemoryConfig::default(), 4);
+        sim.load_workload(vec![vec![Instruction::new_compute(0); 3]; 2]).unwrap();
+        let loaded: Vec<(u64, u64)> = sim.cores[0]
+            .threads
+            .iter()
+            .flat_map(|t| t.workload.iter().map(|i| (i.seq, i.global_seq)))
+            .collect();
+        assert_eq!(loaded, [(0, 0), (1, 1), (2, 2), (0, 3), (1, 4), (2, 5)]);
+        // A replacing load numbers its workload from 0 again.
+        sim.load_workload(vec![vec![Instruction::new_compute(0); 2]; 2]).unwrap();
+        let reloaded: Vec<(u64, u64)> = sim.cores[0]
+            .threads
+            .iter()
+            .flat_map(|t| t.workload.iter().map(|i| (i.seq, i.global_seq)))
+            .collect();
+        assert_eq!(reloaded, [(0, 0), (1, 1), (0, 2), (1, 3)]);
+    }
+
+    #[test]
+    fn simulator_yielding_thread_cedes_the_core_to_a_greedy_one() {
+        let mut sim = Simulator::new(1, 2, CacheConfig::default(), MemoryConfig::default(), 4);
+        sim.set_time_slice(TimeSliceConfig {
+            quantum_cycles: 200,
+            context_switch_cycles: 5,
+            ..TimeSliceConfig::default()
+        });
+        let config = |yield_fraction| WorkloadConfig {
+            instructions_per_thread: 5000,
+            memory_fraction: 0.0,
+            yield_fraction,
+            ..WorkloadConfig::default()
+        };
+        let yielding = build_workload(1, config(0.05)).remove(0);
+        let greedy = build_workload(1, config(0.0)).remove(0);
+        sim.load_workload(vec![yielding, greedy]).unwrap();
+        sim.run_for(2000);
+        let m = sim.metrics();
+        let (yielder, hog) = (&m.per_thread[&ThreadId(0)], &m.per_thread[&ThreadId(1)]);
+        assert!(yielder.voluntary_switches > 5);
+        assert_eq!(hog.voluntary_switches, 0);
+        assert!(hog.run_cycles > 4 * yielder.run_cycles);
+        assert!(hog.instructions_committed > 4 * yielder.instructions_committed);
+    }
+
+    #[test]
+    fn simulator_sleep_parks_the_thread_and_frees_the_core() {
+        let mut sim = Simulator::new(1, 2, CacheConfig::default(), MemoryConfig::default(), 4);
+        let mut sleeper = vec![Instruction::new_control(InstructionKind::Sleep { cycles: 500 })];
+        sleeper.push(Instruction::new_compute(0));
+        let worker = vec![Instruction::new_compute(0); 200];
+        sim.load_workload(vec![sleeper, worker]).unwrap();
+        sim.run_to_completion();
+        let m = sim.metrics();
+        assert_eq!(m.per_thread[&ThreadId(0)].sleep_cycles, 500);
+        assert_eq!(m.per_thread[&ThreadId(0)].voluntary_switches, 1);
+        assert!(m.thread_completion_cycles[&ThreadId(1)] < 500);
+        assert!(m.thread_completion_cycles[&ThreadId(0)] > 500);
+    }
+
+    #[test]
+    fn simulator_sleep_counts_once_and_never_overflows() {
+        // The mispredicted branch flushes the Sleep fetched behind it, which is fetched again.
+        let mut sim = Simulator::new(1, 1, CacheConfig::default(), MemoryConfig::default(), 4);
+        let sleeper = vec![
+            Instruction::new_branch(0, true),
+            Instruction::new_control(InstructionKind::Sleep { cycles: 50 }),
+            Instruction::new_compute(0),
+        ];
+        sim.load_workload(vec![sleeper]).unwrap();
+        sim.run_to_completion();
+        assert_eq!(sim.metrics().mispredictions, 1);
+        let per = &sim.metrics().per_thread[&ThreadId(0)];
+        assert_eq!((per.voluntary_switches, per.sleep_cycles), (1, 50));
+
+        let mut sim = Simulator::new(1, 1, CacheConfig::default(), MemoryConfig::default(), 4);
+        let forever = Instruction::new_control(InstructionKind::Sleep { cycles: u64::MAX });
+        sim.load_workload(vec![vec![forever]]).unwrap();
+        for _ in 0..10 {
+            sim.step();
+        }
+        assert_eq!(sim.cores[0].sleeping[&ThreadId(0)], u64::MAX);
+    }
+
+    #[test]
+    fn simulator_fast_forward_skips_to_the_first_wake() {
+        let run = |fast_forward: bool| {
+            let mut sim = Simulator::new(2, 3, CacheConfig::default(), MemoryConfig::default(), 2);
+            let sleeper = |cycles| {
+                let mut thread = vec![Instruction::new_control(InstructionKind::Sleep { cycles })];
+                thread.extend(vec![Instruction::new_compute(0); 10]);
+                thread
+            };
+            sim.load_workload(vec![sleeper(3000), sleeper(1000), sleeper(2000)]).unwrap();
+            let mut steps = 0;
+            while !sim.is_finished() {
+                if !fast_forward || sim.skip_quiet_cycles(Cycle::MAX) == 0 {
+                    sim.step();
+                }
+                steps += 1;
+            }
+            (sim.metrics().clone(), steps)
+        };
+        let (stepped, every_cycle) = run(false);
+        let (skipped, steps) = run(true);
+        assert_eq!(skipped, stepped);
+        assert_eq!(stepped.per_thread[&ThreadId(1)].sleep_cycles, 1000);
+        assert!(steps * 10 < every_cycle, "{steps} vs {every_cycle}");
+    }
+
+    #[test]
+    fn simulator_budgeted_run_reports_partial_progress() {
+        let build = || {
+            let mut sim = Simulator::new(2, 2, CacheConfig::default(), MemoryConfig::default(), 4);
+            sim.load_workload(build_workload(2, WorkloadConfig::default())).unwrap();
+            sim
+        };
+        let mut full = build();
+        full.run_to_completion();
+        let total = full.metrics().instructions_committed;
+        let end = full.current_cycle();
+
+        let half = build().run_to_completion_with_budget(end / 2);
+        assert_eq!(half.status, OutcomeStatus::Truncated);
+        assert_eq!(half.metrics.total_cycles, end / 2);
+        let committed: u64 = half.committed_per_thread.values().sum();
+        assert_eq!(committed, half.metrics.instructions_committed);
+        assert!(committed > total * 2 / 5 && committed < total * 3 / 5);
+        let remaining: usize = half.remaining_per_thread.values().sum();
+        assert_eq!(committed + remaining as u64, total);
+
+        let generous = build().run_to_completion_with_budget(end * 2);
+        assert_eq!(generous.status, OutcomeStatus::Completed);
+        assert_eq!(&generous.metrics, full.metrics());
+        assert!(generous.remaining_per_thread.values().all(|&n| n == 0));
+
+        // The budget counts from the call, and threads yet to commit are listed too.
+        let mut sim = build();
+        let start = sim.run_to_completion_with_budget(1);
+        assert_eq!(start.committed_per_thread.len(), 2);
+        assert!(start.committed_per_thread.values().all(|&n| n == 0));
+        let rest = sim.run_to_completion_with_budget(end);
+        assert_eq!(rest.status, OutcomeStatus::Completed);
+        assert_eq!(&rest.metrics, full.metrics());
+    }
+
+    #[test]
+    fn simulator_per_core_export_is_byte_identical_across_runs() {
+        let export = || {
+            let mut sim = Simulator::new(4, 8, CacheConfig::default(), MemoryConfig::default(), 4);
+            sim.load_workload(build_workload(8, WorkloadConfig::default())).unwrap();
+            sim.run_to_completion();
+            let mut csv = Vec::new();
+            sim.metrics().write_per_core_csv(&mut csv).unwrap();
+            String::from_utf8(csv).unwrap()
+        };
+        let first = export();
+        assert_eq!(first, export());
+        let cores: Vec<&str> = first.lines().skip(1).map(|l| &l[..2]).collect();
+        assert_eq!(cores, ["0,", "1,", "2,", "3,"]);
+    }
+
+    #[test]
+    fn simulator_exported_traces_replay_identically() {
+        let zipf = WorkloadConfig {
+            instructions_per_thread: 2000,
+            memory_fraction: 0.5,
+            access_pattern: AccessPattern::Zipf {
+                num_lines: 4096,
+                exponent: 0.9,
+                seed: 2,
+            },
+            ..WorkloadConfig::default()
+        };
+        // Each load of the chase waits for the one before it; dropping that would overlap them.
+        let chase = WorkloadConfig {
+            access_pattern: AccessPattern::PointerChase {
+                footprint_lines: 4096,
+                seed: 2,
+            },
+            ..zipf.clone()
+        };
+        let run = |workload: Vec<Vec<Instruction>>| {
+            run_metrics(2, MemoryConfig::default(), workload, |_| {})
+        };
+        for config in [zipf, chase] {
+            let workload = build_workload(2, config);
+            let expected = run(workload.clone());
+
+            let mut csv = Vec::new();
+            crate::workload::to_csv(&workload, &mut csv).unwrap();
+            let from_csv = crate::workload::from_csv_reader(csv.as_slice()).unwrap();
+            assert_eq!(run(from_csv), expected);
+
+            let mut binary = Vec::new();
+            crate::workload::write_binary(&mut binary, &workload).unwrap();
+            let from_binary = crate::workload::read_binary(binary.as_slice()).unwrap();
+            assert_eq!(run(from_binary), expected);
+            assert!(binary.len() < csv.len() / 2);
+        }
+    }
+
+    #[test]
+    fn simulator_streamed_trace_matches_the_preloaded_workload() {
+        let config = WorkloadConfig {
+            instructions_per_thread: 3000,
+            memory_fraction: 0.5,
+            ..WorkloadConfig::default()
+        };
+        let mut bytes = Vec::new();
+        crate::workload::write_binary(&mut bytes, &build_workload(2, config)).unwrap();
+        let sim = || Simulator::new(2, 2, CacheConfig::default(), MemoryConfig::default(), 4);
+
+        let mut preloaded = sim();
+        let workload = crate::workload::read_binary(bytes.as_slice()).unwrap();
+        preloaded.load_workload(workload).unwrap();
+        preloaded.run_to_completion();
+
+        let mut streamed = sim();
+        let reader = crate::workload::BinaryTraceReader::new(std::io::Cursor::new(bytes));
+        streamed.load_streams(reader.unwrap().into_thread_streams()).unwrap();
+        assert!(streamed.workload_remaining(CoreId(0)) <= STREAM_BUFFER_INSTRUCTIONS);
+        streamed.run_to_completion();
+        assert_eq!(streamed.metrics().instructions_committed, 6000);
+        assert_eq!(streamed.metrics(), preloaded.metrics());
+    }
+
+    #[test]
+    fn simulator_stream_refills_follow_a_migrated_thread() {
+        let contexts = |sim: &Simulator| {
+            let on_cores = sim.cores.iter().flat_map(|c| &c.threads);
+            let queued = sim.ready_queue.iter().map(|r| &r.context);
+            on_cores.chain(queued).filter(|t| t.id == ThreadId(0)).count()
+        };
+        let mut sim = Simulator::new(2, 1, CacheConfig::default(), MemoryConfig::default(), 4);
+        let stream = (0..3000u64).map(|i| Instruction::new_memory(InstructionKind::Load, i, 0));
+        sim.load_streams(vec![stream]).unwrap();
+        for cycle in 0..4000 {
+            if cycle == 100 {
+                sim.migrate_thread(ThreadId(0), CoreId(1)).unwrap();
+            }
+            sim.step();
+            assert!(contexts(&sim) <= 1, "cycle {cycle}");
+        }
+        sim.run_to_completion();
+        assert_eq!(sim.metrics().instructions_committed, 3000);
+        assert!(sim.metrics().per_core[&CoreId(1)].instructions_committed > 2500);
+    }
+
+    #[test]
+    fn simulator_streamed_trace_reports_a_malformed_record() {
+        let computes = vec![Instruction::new_compute(0); 3000];
+        let mut bytes = Vec::new();
+        crate::workload::write_binary(&mut bytes, &[computes.clone(), computes]).unwrap();
+        // Header of 6 bytes, then 2-byte records (thread, kind): corrupt a kind byte halfway.
+        bytes[6 + 2 * 3000 + 1] = 31;
+
+        let mut sim = Simulator::new(2, 2, CacheConfig::default(), MemoryConfig::default(), 4);
+        let reader = crate::workload::BinaryTraceReader::new(std::io::Cursor::new(bytes));
+        let streams = reader.unwrap().into_thread_streams();
+        let status = streams[0].status();
+        sim.load_streams(streams).unwrap();
+        assert_eq!(status.error(), None);
+        sim.run_to_completion();
+        assert!(sim.metrics().instructions_committed < 6000);
+        assert_eq!(status.error().as_deref(), Some("bad binary trace: unknown kind byte 31"));
+    }
+
+    #[test]
+    fn simulator_iter_cycles_matches_run_to_completion() {
+        let build = || {
+            let mut sim = Simulator::new(2, 2, CacheConfig::default(), MemoryConfig::default(), 4);
+            sim.load_workload(build_workload(2, WorkloadConfig::default())).unwrap();
+            sim
+        };
+        let mut reference = build();
+        reference.run_to_completion();
+
+        let mut sim = build();
+        assert_eq!(sim.iter_cycles().count() as Cycle, reference.current_cycle());
+        assert!(sim.is_finished());
+        assert_eq!(sim.iter_cycles().next(), None);
+
+        let mut resumed = build();
+        let committed: u32 = resumed.iter_cycles().take(50).map(|c| c.committed).sum();
+        assert_eq!(resumed.current_cycle(), 50);
+        assert_eq!(u64::from(committed), resumed.metrics().instructions_committed);
+        resumed.run_to_completion();
+        assert_eq!(resumed.current_cycle(), reference.current_cycle());
+        assert_eq!(
+            resumed.metrics().instructions_committed,
+            reference.metrics().instructions_committed
+        );
+    }
+
+    #[test]
+    fn simulator_run_until_stops_at_threshold() {
+        let build = || {
+            let mut sim = Simulator::new(2, 2, CacheConfig::default(), MemoryConfig::default(), 4);
+            sim.load_workload(build_workload(2, WorkloadConfig::default())).unwrap();
+            sim
+        };
+        let committed = |sim: &Simulator| -> u64 {
+            sim.metrics().per_thread.values().map(|t| t.instructions_committed).sum()
+        };
+        let mut sim = build();
+        let reason = sim.run_until(|s| committed(s) >= 1000, 1_000_000);
+        assert_eq!(reason, StopReason::PredicateMet);
+        assert!(committed(&sim) >= 1000);
+        let mut one_cycle_earlier = build();
+        one_cycle_earlier.run_for(sim.current_cycle() - 1);
+        assert!(committed(&one_cycle_earlier) < 1000);
+
+        assert_eq!(sim.run_until(|_| false, 10), StopReason::MaxCyclesReached);
+        assert_eq!(sim.run_until(|_| false, u64::MAX), StopReason::Completed);
+    }
+
+    #[test]
+    fn simulator_tracks_memory_accesses() {
+        let cache_config = CacheConfig::default();
+        let memory_config = MemoryConfig::default();
+        let mut sim = Simulator::new(1, 1, cache_config, memory_config, 4);
+        let workload = build_workload(
+            1,
+            WorkloadConfig {
+                instructions_per_thread: 100,
+                memory_fraction: 0.5,
+                access_pattern: AccessPattern::Sequential,
+                line_size: 64,
+                cache_num_sets: 64,
+                working_set_lines: 0,
+                ..WorkloadConfig::default()
+            },
+        );
+        sim.load_workload(workload).unwrap();
+        sim.run_to_completion();
+        assert!(sim.metrics().total_memory_accesses > 0);
+        assert!(sim.metrics().cache_hits + sim.metrics().cache_misses == sim.metrics().total_memory_accesses);
+    }
+
+    #[test]
+    fn simulator_bus_contention_slows_co_runner() {
+        let memory_config = MemoryConfig {
+            bytes_per_cycle: 8,
+            ..MemoryConfig::with_access_latency(20)
+        };
+        let fixed = WorkloadConfig {
+            instructions_per_thread: 200,
+            memory_fraction: 0.5,
+            access_pattern: AccessPattern::Sequential,
+            line_size: 64,
+            cache_num_sets: 64,
+            working_set_lines: 0,
+            ..WorkloadConfig::default()
+        };
+        let streaming = WorkloadConfig {
+            instructions_per_thread: 2000,
+            memory_fraction: 1.0,
+            ..fixed.clone()
+        };
+
+        let mut alone = Simulator::new(2, 1, CacheConfig::default(), memory_config.clone(), 4);
+        alone.load_workload(build_workload(1, fixed.clone())).unwrap();
+        let alone_cycles = run_until_core_idle(&mut alone, 0);
+
+        let mut shared = Simulator::new(2, 2, CacheConfig::default(), memory_config, 4);
+        let mut workload = build_workload(1, fixed);
+        workload.extend(build_workload(1, streaming));
+        shared.load_workload(workload).unwrap();
+        let shared_cycles = run_until_core_idle(&mut shared, 0);
+
+        assert!(shared_cycles > alone_cycles);
+        assert!(shared.metrics().per_core[&CoreId(0)].bus_wait_cycles > 0);
+        assert!(shared.metrics().bandwidth_utilization() > 0.0);
+    }
+
+    #[test]
+    fn simulator_remote_numa_placement_is_slower() {
+        let memory_config = MemoryConfig {
+            nodes: vec![
+                NodeConfig {
+                    latency_cycles: 60,
+                    address_range: 0..1 << 20,
+                },
+                NodeConfig {
+                    latency_cycles: 60,
+                    address_range: 1 << 20..1 << 21,
+                },
+            ],
+            remote_hop_cycles: 80,
+            ..MemoryConfig::default()
+        };
+        let workload_config = WorkloadConfig {
+            instructions_per_thread: 400,
+            memory_fraction: 0.5,
+            access_pattern: AccessPattern::Sequential,
+            line_size: 64,
+            cache_num_sets: 64,
+            working_set_lines: 0,
+            ..WorkloadConfig::default()
+        };
+        let run = |home_node: usize| {
+            let mut sim = Simulator::builder()
+                .memory(memory_config.clone())
+                .topology(Topology::new(vec![home_node]))
+                .build()
+                .unwrap();
+            sim.load_workload(build_workload(1, workload_config.clone())).unwrap();
+            sim.run_to_completion();
+            sim.metrics().clone()
+        };
+        let local = run(0);
+        let remote = run(1);
+        assert_eq!(local.remote_accesses, 0);
+        assert_eq!(remote.local_accesses, 0);
+        assert_eq!(remote.remote_accesses, remote.cache_misses);
+        assert!(remote.total_cycles > local.total_cycles);
+        assert!(remote.per_node[&0].stall_cycles > local.per_node[&0].stall_cycles);
+    }
+
+    #[test]
+    fn simulator_jittered_latency_is_reproducible() {
+        let run = |seed: u64| {
+            let memory_config = MemoryConfig {
+                latency_model: Some(LatencyModel::Normal {
+                    mean: 100.0,
+                    stddev: 25.0,
+                    seed,
+                }),
+                ..MemoryConfig::default()
+            };
+            let workload = build_workload(
+                2,
+                WorkloadConfig {
+                    instructions_per_thread: 300,
+                    memory_fraction: 0.5,
+                    ..WorkloadConfig::default()
+                },
+            );
+            run_metrics(2, memory_config, workload, |_| {})
+        };
+        assert_eq!(run(11), run(11));
+        assert_ne!(run(11).total_cycles, run(12).total_cycles);
+    }
+
+    #[test]
+    fn simulator_replay_reproduces_recorded_run() {
+        let build = |seed: u64| {
+            let memory_config = MemoryConfig {
+                latency_model: Some(LatencyModel::Uniform {
+                    min: 50,
+                    max: 250,
+                    seed,
+                }),
+                ..MemoryConfig::default()
+            };
+            let mut sim = Simulator::new(2, 2, CacheConfig::default(), memory_config, 4);
+            sim.enable_instruction_trace();
+            sim.load_workload(build_workload(
+                2,
+                WorkloadConfig {
+                    instructions_per_thread: 300,
+                    memory_fraction: 0.5,
+                    ..WorkloadConfig::default()
+                },
+            )).unwrap();
+            sim
+        };
+        let mut recorded = build(3);
+        recorded.record_to(Vec::new());
+        recorded.run_to_completion();
+        let log = recorded.recorded_decisions().to_vec();
+        assert!(!log.is_empty());
+
+        // A different seed: only the log can make the runs match.
+        let mut replayed = build(4);
+        replayed.replay_from(&log);
+        replayed.run_to_completion();
+        assert_eq!(replayed.metrics(), recorded.metrics());
+        assert_eq!(replayed.instruction_trace(), recorded.instruction_trace());
+
+        let mut fresh = build(4);
+        fresh.run_to_completion();
+        assert_ne!(fresh.metrics(), recorded.metrics());
+    }
+
+    #[test]
+    fn simulator_scratchpad_region_is_faster() {
+        const SCRATCHPAD_BASE: u64 = 0x1000_0000;
+        let memory_config = MemoryConfig {
+            regions: vec![MemoryRegion {
+                address_range: SCRATCHPAD_BASE..SCRATCHPAD_BASE + (1 << 20),
+                latency_cycles: 4,
+            }],
+            ..MemoryConfig::default()
+        };
+        let run = |base_address: u64| {
+            let mut sim = Simulator::new(1, 1, CacheConfig::default(), memory_config.clone(), 4);
+            sim.load_workload(build_workload(
+                1,
+                WorkloadConfig {
+                    instructions_per_thread: 400,
+                    memory_fraction: 0.5,
+                    base_address,
+                    ..WorkloadConfig::default()
+                },
+            )).unwrap();
+            sim.run_to_completion();
+            sim.metrics().total_cycles
+        };
+        assert!(run(SCRATCHPAD_BASE) < run(0));
+    }
+
+    #[test]
+    fn simulator_write_buffer_hides_store_misses() {
+        let conflict_stores = || {
+            vec![(0..200u64)
+                .map(|i| Instruction::new_memory(InstructionKind::Store, i * 64 * 64, i))
+                .collect::<Vec<_>>()]
+        };
+        let mut blocking = Simulator::new(1, 1, CacheConfig::default(), MemoryConfig::default(), 4);
+        blocking.load_workload(conflict_stores()).unwrap();
+        blocking.run_to_completion();
+
+        let mut buffered = Simulator::new(1, 1, CacheConfig::default(), MemoryConfig::default(), 4);
+        buffered.set_write_buffer(WriteBufferConfig {
+            depth: 4,
+            drain_interval: 4,
+        });
+        buffered.load_workload(conflict_stores()).unwrap();
+        buffered.run_to_completion();
+
+        let m = buffered.metrics();
+        assert_eq!(m.cache_misses, 200);
+        assert!(m.total_cycles * 5 < blocking.metrics().total_cycles);
+        assert!(m.write_buffer_full_stalls > 0);
+        assert_eq!(m.write_buffer_max_occupancy, 4);
+    }
+
+    #[test]
+    fn simulator_zero_depth_write_buffer_holds_one_store() {
+        let mut sim = Simulator::new(1, 1, CacheConfig::default(), MemoryConfig::default(), 4);
+        sim.set_write_buffer(WriteBufferConfig {
+            depth: 0,
+            drain_interval: 1,
+        });
+        sim.load_workload(vec![(0..20u64)
+            .map(|i| Instruction::new_memory(InstructionKind::Store, i * 64 * 64, i))
+            .collect()])
+            .unwrap();
+        sim.run_to_completion();
+        assert!(sim.is_finished());
+        assert_eq!(sim.metrics().write_buffer_max_occupancy, 1);
+    }
+
+    #[test]
+    fn simulator_refresh_slows_memory_bound_run() {
+        let run = |refresh_interval_cycles: u64| {
+            let memory_config = MemoryConfig {
+                refresh_interval_cycles,
+                refresh_duration_cycles: 40,
+                ..MemoryConfig::default()
+            };
+            let workload = build_workload(
+                1,
+                WorkloadConfig {
+                    instructions_per_thread: 2000,
+                    memory_fraction: 1.0,
+                    ..WorkloadConfig::default()
+                },
+            );
+            run_metrics(1, memory_config, workload, |_| {})
+        };
+        let no_refresh = run(0);
+        let refresh = run(100);
+        assert_eq!(no_refresh.refresh_stall_cycles, 0);
+        assert!(refresh.refresh_stall_cycles > 0);
+        assert!(refresh.total_cycles > no_refresh.total_cycles);
+    }
+
+    #[test]
+    fn simulator_page_policy_depends_on_pattern() {
+        let run = |page_policy: PagePolicy, access_pattern: AccessPattern| {
+            let memory_config = MemoryConfig {
+                dram: Some(DramConfig {
+                    page_policy,
+                    ..DramConfig::default()
+                }),
+                ..MemoryConfig::default()
+            };
+            let cache_config = CacheConfig::default();
+            let workload_config = WorkloadConfig {
+                instructions_per_thread: 1000,
+                memory_fraction: 1.0,
+                access_pattern,
+                cache_num_sets: cache_config.num_sets(),
+                ..WorkloadConfig::default()
+            };
+            let mut sim = Simulator::new(1, 1, cache_config, memory_config, 4);
+            sim.load_workload(build_workload(1, workload_config)).unwrap();
+            sim.run_to_completion();
+            sim.metrics().total_cycles
+        };
+        let seq = AccessPattern::Sequential;
+        let conflict = AccessPattern::ConflictHeavy;
+        assert!(run(PagePolicy::Open, seq) < run(PagePolicy::Closed, seq));
+        assert!(run(PagePolicy::Closed, conflict) < run(PagePolicy::Open, conflict));
+    }
+
+    #[test]
+    fn simulator_tighter_bandwidth_cap_is_slower() {
+        let run = |max_bytes_per_cycle: usize| {
+            let memory_config = MemoryConfig {
+                max_bytes_per_cycle,
+                ..MemoryConfig::with_access_latency(40)
+            };
+            let workload = build_workload(
+                2,
+                WorkloadConfig {
+                    instructions_per_thread: 800,
+                    memory_fraction: 1.0,
+                    ..WorkloadConfig::default()
+                },
+            );
+            run_metrics(2, memory_config, workload, |_| {})
+        };
+        let sweep: Vec<Metrics> = [64, 16, 4, 1].into_iter().map(run).collect();
+        for pair in sweep.windows(2) {
+            assert!(pair[1].total_cycles >= pair[0].total_cycles);
+        }
+        let (generous, tight) = (&sweep[0], &sweep[3]);
+        assert!(tight.total_cycles > generous.total_cycles);
+        assert!(tight.throttle_fraction() > generous.throttle_fraction());
+        // The last window's fill counts in full, so allow a sliver above the cap.
+        assert!(tight.achieved_bandwidth() < 1.01);
+    }
+
+    #[test]
+    fn simulator_memory_trace_matches_misses() {
+        for write_buffer in [false, true] {
+            let mut sim = Simulator::new(2, 2, CacheConfig::default(), MemoryConfig::default(), 4);
+            if write_buffer {
+                sim.set_write_buffer(WriteBufferConfig::default());
+            }
+            sim.enable_memory_trace();
+            sim.load_workload(build_workload(
+                2,
+                WorkloadConfig {
+                    instructions_per_thread: 300,
+                    memory_fraction: 0.5,
+                    working_set_lines: 100,
+                    ..WorkloadConfig::default()
+                },
+            )).unwrap();
+            sim.run_to_completion();
+            let trace = sim.memory_trace();
+            assert_eq!(trace.len() as u64, sim.metrics().cache_misses);
+            assert!(trace.iter().any(|e| e.is_write) && trace.iter().any(|e| !e.is_write));
+
+            let mut out = Vec::new();
+            sim.write_memory_trace(&mut out).unwrap();
+            assert_eq!(String::from_utf8(out).unwrap().lines().count(), trace.len());
+        }
+    }
+
+    #[test]
+    fn simulator_channel_interleaving_spreads_sequential_misses() {
+        let run = |access_pattern: AccessPattern| {
+            let memory_config = MemoryConfig {
+                num_channels: 4,
+                channel_max_in_flight: 1,
+                ..MemoryConfig::default()
+            };
+            let workload = build_workload(
+                1,
+                WorkloadConfig {
+                    instructions_per_thread: 400,
+                    memory_fraction: 1.0,
+                    access_pattern,
+                    ..WorkloadConfig::default()
+                },
+            );
+            run_metrics(1, memory_config, workload, |_| {})
+        };
+        let seq = run(AccessPattern::Sequential);
+        for ch in 0..4 {
+            assert_eq!(seq.per_channel[&ch].accesses, 100);
+        }
+        let conflict = run(AccessPattern::ConflictHeavy);
+        assert_eq!(conflict.per_channel.len(), 1);
+        assert_eq!(conflict.per_channel[&0].accesses, conflict.cache_misses);
+        assert!(conflict.channel_utilization(0) > 0.9);
+        assert!(conflict.total_cycles > seq.total_cycles);
+    }
+
+    #[test]
+    fn simulator_contention_raises_average_latency() {
+        let run = |num_cores: usize, pipeline_width: usize| {
+            let memory_config = MemoryConfig {
+                contention_alpha: 5,
+                ..MemoryConfig::with_access_latency(100)
+            };
+            let mut sim = Simulator::new(
+                num_cores,
+                num_cores,
+                CacheConfig::default(),
+                memory_config,
+                pipeline_width,
+            );
+            sim.load_workload(build_workload(
+                num_cores,
+                WorkloadConfig {
+                    instructions_per_thread: 100,
+                    memory_fraction: 1.0,
+                    ..WorkloadConfig::default()
+                },
+            )).unwrap();
+            sim.run_to_completion();
+            sim.metrics().average_miss_latency()
+        };
+        assert_eq!(run(1, 1), 100.0);
+        assert!(run(4, 1) > 100.0);
+    }
+
+    #[test]
+    fn simulator_functional_memory_round_trips_values() {
+        let run = |functional: bool| {
+            let memory_config = MemoryConfig {
+                functional,
+                ..MemoryConfig::default()
+            };
+            let mut sim = Simulator::new(1, 1, CacheConfig::default(), memory_config, 4);
+            let stores = (0..32u64)
+                .map(|i| Instruction::new_memory(InstructionKind::Store, i * 8, i).with_value(i * i))
+                .collect();
+            sim.load_workload(vec![stores]).unwrap();
+            sim.run_to_completion();
+            let loads = (0..32u64)
+                .map(|i| Instruction::new_memory(InstructionKind::Load, i * 8, i))
+                .collect();
+            sim.load_workload(vec![loads]).unwrap();
+            sim.run_to_completion();
+            (sim.metrics().clone(), sim.load_results().to_vec())
+        };
+        let (functional_metrics, loaded) = run(true);
+        let expected: Vec<(u64, u64)> = (0..32u64).map(|i| (i * 8, i * i)).collect();
+        assert_eq!(loaded, expected);
+        let (timing_metrics, timing_loaded) = run(false);
+        assert!(timing_loaded.is_empty());
+        assert_eq!(functional_metrics, timing_metrics);
+    }
+
+    #[test]
+    fn simulator_write_latency_only_affects_stores() {
+        let run = |kind: InstructionKind, write_latency_cycles: u32| {
+            let memory_config = MemoryConfig {
+                read_latency_cycles: 100,
+                write_latency_cycles,
+                ..MemoryConfig::default()
+            };
+            let mut sim = Simulator::new(1, 1, CacheConfig::default(), memory_config, 4);
+            // Distinct lines: every access misses.
+            let instrs = (0..50u64)
+                .map(|i| Instruction::new_memory(kind, i * 4096, i))
+                .collect();
+            sim.load_workload(vec![instrs]).unwrap();
+            sim.run_to_completion();
+            sim.metrics().total_cycles
+        };
+        assert!(run(InstructionKind::Store, 20) < run(InstructionKind::Store, 200));
+        assert_eq!(run(InstructionKind::Load, 20), run(InstructionKind::Load, 200));
+    }
+
+    #[test]
+    fn simulator_overlapping_misses_cut_runtime() {
+        let run = |max_outstanding_misses: usize| {
+            // Independent loads to distinct lines: every access misses.
+            let instrs = (0..64u64)
+                .map(|i| Instruction::new_memory(InstructionKind::Load, i * 4096, i))
+                .collect();
+            run_metrics(1, MemoryConfig::default(), vec![instrs], |sim| {
+                sim.set_pipeline_widths(&[8]).unwrap();
+                sim.set_max_outstanding_misses(max_outstanding_misses);
+            })
+        };
+        let blocking = run(1);
+        let overlapped = run(4);
+        assert!((blocking.mlp() - 1.0).abs() < 1e-9);
+        assert!(overlapped.mlp() > 3.5, "mlp {}", overlapped.mlp());
+        let ratio = overlapped.total_cycles as f64 / blocking.total_cycles as f64;
+        assert!((0.2..0.3).contains(&ratio), "ratio {ratio}");
+    }
+
+    #[test]
+    fn simulator_pointer_chase_pays_the_full_latency_per_access() {
+        let run = |access_pattern: AccessPattern| {
+            let config = WorkloadConfig {
+                instructions_per_thread: 400,
+                memory_fraction: 1.0,
+                access_pattern,
+                ..WorkloadConfig::default()
+            };
+            run_metrics(1, MemoryConfig::default(), build_workload(1, config), |sim| {
+                sim.set_pipeline_widths(&[8]).unwrap();
+            })
+        };
+        let footprint_lines = 1 << 16;
+        let chase = run(AccessPattern::PointerChase {
+            footprint_lines,
+            seed: 1,
+        });
+        let per_access = chase.total_cycles as f64 / chase.total_memory_accesses as f64;
+        let amat = chase.miss_latency_cycles as f64 / chase.cache_misses as f64;
+        assert!((per_access / amat - 1.0).abs() < 0.05, "{per_access} vs {amat}");
+        assert!((chase.mlp() - 1.0).abs() < 1e-9);
+
+        // The same lines in an independent order overlap.
+        let random = run(AccessPattern::Random {
+            footprint_lines,
+            seed: 1,
+        });
+        assert!(random.total_cycles * 2 < chase.total_cycles);
+    }
+
+    #[test]
+    fn simulator_fence_after_each_miss_serializes_them() {
+        let run = |fence_interval: usize| {
+            let config = WorkloadConfig {
+                instructions_per_thread: 64,
+                memory_fraction: 1.0,
+                fence_interval,
+                ..WorkloadConfig::default()
+            };
+            run_metrics(1, MemoryConfig::default(), build_workload(1, config), |sim| {
+                sim.set_pipeline_widths(&[8]).unwrap();
+                sim.set_max_outstanding_misses(4);
+            })
+        };
+        let overlapped = run(0);
+        let fenced = run(1);
+        assert_eq!(fenced.cache_misses, 64);
+        assert_eq!(fenced.fences, 64);
+        assert!(fenced.fence_stall_cycles > 0);
+        assert!(overlapped.mlp() > 3.0, "mlp {}", overlapped.mlp());
+        assert!((fenced.mlp() - 1.0).abs() < 1e-9, "mlp {}", fenced.mlp());
+        // Nothing overlaps: the run lasts at least as long as all the misses back to back.
+        assert!(fenced.total_cycles >= fenced.miss_latency_cycles);
+        assert!(overlapped.total_cycles < overlapped.miss_latency_cycles / 2);
+    }
+
+    #[test]
+    fn simulator_fetch_buffer_hides_short_front_end_stalls() {
+        let run = |buffer: Option<usize>, stall_every: Cycle| {
+            let mut sim = Simulator::new(1, 1, CacheConfig::default(), MemoryConfig::default(), 4);
+            sim.set_fetch_width(8);
+            if let Some(depth) = buffer {
+                sim.set_fetch_buffer(FetchBufferConfig { depth });
+            }
+            let instrs = (0..2000).map(Instruction::new_compute).collect();
+            sim.load_workload(vec![instrs]).unwrap();
+            while !sim.is_finished() {
+                if stall_every > 0 && (sim.current_cycle() + 1).is_multiple_of(stall_every) {
+                    // A 3-cycle front-end bubble, as an I-cache miss would cause.
+                    sim.cores[0].fetch_stall_cycles = 3;
+                }
+                sim.step();
+            }
+            sim.metrics().clone()
+        };
+        let unbuffered = run(None, 0);
+        let unbuffered_stalled = run(None, 10);
+        assert!(unbuffered_stalled.ipc() < unbuffered.ipc() * 0.95);
+
+        let buffered = run(Some(16), 0);
+        let buffered_stalled = run(Some(16), 10);
+        assert_eq!(buffered_stalled.instructions_committed, 2000);
+        assert!(
+            buffered_stalled.ipc() >= buffered.ipc() * 0.99,
+            "ipc {} vs {}",
+            buffered_stalled.ipc(),
+            buffered.ipc()
+        );
+        assert!(buffered_stalled.average_fetch_buffer_occupancy() > 4.0);
+        // Only the start-up cycles, before the first fetch reaches issue, starve.
+        assert_eq!(buffered_stalled.fetch_starved_cycles, buffered.fetch_starved_cycles);
+        assert!(run(Some(2), 10).fetch_starved_cycles > buffered.fetch_starved_cycles);
+    }
+
+    #[test]
+    fn simulator_rob_runs_independent_work_under_a_miss() {
+        let run = |rob_size: usize| {
+            let mut instrs = vec![Instruction::new_memory(InstructionKind::Load, 0x1000, 0)];
+            instrs.extend((0..400).map(Instruction::new_compute));
+            run_metrics(1, MemoryConfig::default(), vec![instrs], |sim| {
+                sim.set_reorder_buffer(ReorderBufferConfig { size: rob_size });
+            })
+        };
+        // A window as narrow as the pipeline stalls behind the miss; a deep one does not.
+        let in_order = run(4);
+        let out_of_order = run(128);
+        assert_eq!(out_of_order.per_thread[&ThreadId(0)].instructions_committed, 401);
+        assert!(
+            (out_of_order.total_cycles as f64) < 0.6 * in_order.total_cycles as f64,
+            "rob 128: {} cycles, rob 4: {}",
+            out_of_order.total_cycles,
+            in_order.total_cycles
+        );
+        assert!(out_of_order.commit_blocked_cycles > 0);
+        // The breakdown counts every instruction held back, not just the cycle.
+        let blocked = out_of_order.stall_cycles_by_reason[&StallReason::CommitBlocked];
+        assert!(blocked > out_of_order.commit_blocked_cycles * 10);
+        assert!(out_of_order.average_rob_occupancy() > in_order.average_rob_occupancy());
+    }
+
+    #[test]
+    fn simulator_divides_are_slow_and_unpipelined() {
+        let run = |instruction_mix: InstructionMix| {
+            let workload = build_workload(
+                1,
+                WorkloadConfig {
+                    instructions_per_thread: 200,
+                    memory_fraction: 0.0,
+                    instruction_mix,
+                    ..WorkloadConfig::default()
+                },
+            );
+            run_metrics(1, MemoryConfig::default(), workload, |_| {})
+        };
+        let adds = run(InstructionMix::default());
+        let divs = run(InstructionMix {
+            div: 1.0,
+            ..InstructionMix::default()
+        });
+        assert_eq!(adds.committed_by_kind["compute"], 200);
+        assert_eq!(divs.committed_by_kind["div"], 200);
+        // One divide at a time: about 20 cycles each, nothing overlaps.
+        assert!(divs.total_cycles > 200 * 20, "div {}", divs.total_cycles);
+        assert!(divs.total_cycles > 10 * adds.total_cycles, "add {}", adds.total_cycles);
+    }
+
+    #[test]
+    fn simulator_dependent_chain_serializes_computes() {
+        let run = |dependency_chain_length: usize| {
+            let config = WorkloadConfig {
+                instructions_per_thread: 100,
+                memory_fraction: 0.0,
+                dependency_chain_length,
+                ..WorkloadConfig::default()
+            };
+            run_metrics(1, MemoryConfig::default(), build_workload(1, config), |sim| {
+                // A window deep enough that independent computes flow at the full 4 per cycle.
+                sim.set_reorder_buffer(ReorderBufferConfig { size: 32 });
+            })
+        };
+        let independent = run(0);
+        let chained = run(100);
+        assert_eq!(independent.raw_stall_cycles, 0);
+        assert!(chained.raw_stall_cycles > 0);
+        // Independent: ~100 / 4 cycles plus pipeline fill. Chained: one compute per cycle.
+        assert!(independent.total_cycles < 40, "independent {}", independent.total_cycles);
+        let extra = chained.total_cycles - independent.total_cycles;
+        assert!((70..=100).contains(&extra), "extra cycles {extra}");
+    }
+
+    #[test]
+    fn simulator_bimodal_predictor_recovers_branch_penalty() {
+        let run = |kind: PredictorKind| {
+            let config = WorkloadConfig {
+                instructions_per_thread: 2000,
+                memory_fraction: 0.0,
+                branch_fraction: 0.2,
+                branch_pattern: BranchPattern::Loop { trip_count: 16 },
+                ..WorkloadConfig::default()
+            };
+            let metrics = run_metrics(1, MemoryConfig::default(), build_workload(1, config), |sim| {
+                sim.set_branch_predictor(BranchPredictorConfig {
+                    kind,
+                    mispredict_penalty_cycles: 10,
+                });
+            });
+            assert_eq!(metrics.per_thread[&ThreadId(0)].instructions_committed, 2000);
+            metrics
+        };
+        let static_nt = run(PredictorKind::StaticNotTaken);
+        let bimodal = run(PredictorKind::Bimodal { table_entries: 64 });
+        let perfect = run(PredictorKind::Perfect);
+        assert_eq!(static_nt.branches, 400);
+        assert_eq!(perfect.mispredictions, 0);
+        assert_eq!(static_nt.mispredictions, 375);
+        assert!(bimodal.mispredictions <= 26, "bimodal {}", bimodal.mispredictions);
+        assert!(static_nt.misprediction_flush_cycles > 0);
+        let penalty = (static_nt.total_cycles - perfect.total_cycles) as f64;
+        let recovered = (static_nt.total_cycles - bimodal.total_cycles) as f64;
+        assert!(recovered > 0.85 * penalty, "recovered {recovered} of {penalty}");
+    }
+
+    #[test]
+    fn simulator_speculation_squashes_the_wrong_path() {
+        let run = |keep_cache_effects: bool| {
+            let mut sim = Simulator::new(1, 1, CacheConfig::default(), MemoryConfig::default(), 4);
+            sim.set_branch_predictor(BranchPredictorConfig {
+                kind: PredictorKind::StaticNotTaken,
+                mispredict_penalty_cycles: 2,
+            });
+            sim.set_speculation(SpeculationConfig {
+                keep_cache_effects,
+                ..SpeculationConfig::default()
+            });
+            // Every branch waits on a divide and is mispredicted; the 64 lines exactly fill the
+            // cache, and the wrong-path copies of each load map to the same sets.
+            let workload = (0..10)
+                .flat_map(|_| 0..64u64)
+                .flat_map(|line| {
+                    [
+                        Instruction::new_op(InstructionKind::Div, 0).with_dest(RegId(1)),
+                        Instruction::new_branch(0x400, true).with_srcs(vec![RegId(1)]),
+                        Instruction::new_memory(InstructionKind::Load, line * 64, 0),
+                    ]
+                })
+                .collect();
+            sim.load_workload(vec![workload]).unwrap();
+            sim.run_to_completion();
+            let m = sim.metrics().clone();
+            assert_eq!(m.instructions_committed, 1920);
+            assert_eq!(m.mispredictions, 640);
+            assert!(m.instructions_fetched > m.instructions_committed);
+            assert_eq!(m.instructions_fetched - m.instructions_committed, m.instructions_squashed);
+            m
+        };
+        let clean = run(false);
+        let polluting = run(true);
+        assert_eq!(clean.wrong_path_cache_accesses, 0);
+        assert_eq!(clean.cache_misses, 64);
+        assert!(polluting.wrong_path_cache_accesses > 0);
+        let correct_path_misses = polluting.cache_misses - polluting.wrong_path_cache_misses;
+        assert!(correct_path_misses > 2 * 64, "correct-path misses {correct_path_misses}");
+    }
+
+    /// Counts fetches, commits and squashes.
+    #[derive(Default)]
+    struct Lifetimes {
+        fetched: u64,
+        committed: u64,
+        squashed: u64,
+    }
+
+    impl SimObserver for Rc<RefCell<Lifetimes>> {
+        fn on_fetch(&mut self, _: Cycle, _: CoreId, _: &Instruction) {
+            self.borrow_mut().fetched += 1;
+        }
+
+        fn on_commit(&mut self, _: Cycle, _: CoreId, _: &Instruction) {
+            self.borrow_mut().committed += 1;
+        }
+
+        fn on_squash(&mut self, _: Cycle, _: CoreId, _: &Instruction) {
+            self.borrow_mut().squashed += 1;
+        }
+    }
+
+    #[test]
+    fn simulator_every_fetched_instruction_commits_or_is_squashed() {
+        // Wrong-path instructions are squashed; an interrupt in squash mode flushes
+        // correct-path ones, which are fetched again.
+        for speculative in [true, false] {
+            let mut sim = Simulator::new(1, 1, CacheConfig::default(), MemoryConfig::default(), 4);
+            let lifetimes = Rc::new(RefCell::new(Lifetimes::default()));
+            sim.add_observer(Box::new(Rc::clone(&lifetimes)));
+            if speculative {
+                sim.set_branch_predictor(BranchPredictorConfig {
+                    kind: PredictorKind::StaticNotTaken,
+                    mispredict_penalty_cycles: 2,
+                });
+                sim.set_speculation(SpeculationConfig::default());
+                let workload = (0..50u64)
+                    .flat_map(|line| {
+                        [
+                            Instruction::new_branch(0x400, true),
+                            Instruction::new_memory(InstructionKind::Load, line * 64, 0),
+                            Instruction::new_compute(0),
+                        ]
+                    })
+                    .collect();
+                sim.load_workload(vec![workload]).unwrap();
+            } else {
+                sim.load_workload(vec![(0..400).map(Instruction::new_compute).collect()]).unwrap();
+                for at in (10..200).step_by(20) {
+                    sim.inject_interrupt(CoreId(0), at, vec![Instruction::new_compute(0)]);
+                }
+            }
+            sim.run_to_completion();
+            let seen = lifetimes.borrow();
+            assert_eq!(seen.committed, sim.metrics().instructions_committed);
+            assert!(seen.squashed > 0, "speculative {speculative}");
+            assert_eq!(seen.fetched, seen.committed + seen.squashed);
+        }
+    }
+
+    #[test]
+    fn simulator_double_buffered_dma_beats_blocking_loads() {
+        const TILES: u64 = 8;
+        const TILE_BYTES: u64 = 512;
+        let compute = || (0..64).map(Instruction::new_compute);
+        let loads = |base: u64| {
+            let load = move |line| Instruction::new_memory(InstructionKind::Load, base + line, 0);
+            (0..TILE_BYTES).step_by(64).map(load)
+        };
+        let run = |workload: Vec<Instruction>| {
+            run_metrics(1, MemoryConfig::default(), vec![workload], |sim| {
+                sim.set_dma(DmaConfig {
+                    prefetch_into_cache: true,
+                    ..DmaConfig::default()
+                });
+            })
+        };
+        let blocking: Vec<_> =
+            (0..TILES).flat_map(|t| loads(0x100000 + t * TILE_BYTES).chain(compute())).collect();
+        // Copy tile t + 1 into one buffer while computing on tile t in the other.
+        let buffer = |t: u64| 0x8000 + (t % 2) * TILE_BYTES;
+        let dma_start = |t: u64| {
+            let (src, dst, bytes) = (0x100000 + t * TILE_BYTES, buffer(t), TILE_BYTES);
+            Instruction::new_control(InstructionKind::DmaStart { src, dst, bytes })
+        };
+        let mut double_buffered = vec![dma_start(0)];
+        for t in 0..TILES {
+            if t + 1 < TILES {
+                double_buffered.push(dma_start(t + 1));
+            }
+            double_buffered.push(Instruction::new_control(InstructionKind::DmaWait { id: t }));
+            double_buffered.extend(loads(buffer(t)).chain(compute()));
+        }
+        let blocking = run(blocking);
+        let dma = run(double_buffered);
+        assert_eq!(dma.dma_bytes, TILES * TILE_BYTES);
+        assert_eq!(dma.cache_misses, 0);
+        assert!(dma.dma_overlap_efficiency() > 0.3, "{}", dma.dma_overlap_efficiency());
+        assert!(
+            dma.total_cycles < blocking.total_cycles,
+            "dma {} vs blocking {}",
+            dma.total_cycles,
+            blocking.total_cycles
+        );
+    }
+
+    #[test]
+    fn simulator_matrix_ops_queue_on_the_unit() {
+        let run = |m: u32, loads_per_op: u64| {
+            let workload = (0..20u64)
+                .flat_map(|op| {
+                    let kind = InstructionKind::MatrixOp { m, n: 8, k: 8 };
+                    std::iter::once(Instruction::new_control(kind)).chain((0..loads_per_op).map(
+                        move |i| {
+                            let address = (op * loads_per_op + i) * 4096;
+                            Instruction::new_memory(InstructionKind::Load, address, 0)
+                        },
+                    ))
+                })
+                .collect();
+            run_metrics(1, MemoryConfig::default(), vec![workload], |sim| {
+                sim.set_pipeline_widths(&[8]).unwrap();
+                sim.set_matrix_unit(MatrixUnitConfig { macs_per_cycle: 64 });
+            })
+        };
+        // 32×8×8 MACs at 64 per cycle: 32 cycles each, back to back.
+        let compute_bound = run(32, 1);
+        let per = &compute_bound.per_core[&CoreId(0)];
+        assert_eq!(compute_bound.matrix_ops, 20);
+        assert_eq!(per.matrix_busy_cycles, 20 * 32);
+        assert!(compute_bound.matrix_queue_wait_cycles > 0);
+        assert!(compute_bound.total_cycles >= 20 * 32);
+        assert!(compute_bound.matrix_unit_utilization(CoreId(0)) > 0.8);
+        let report = compute_bound.bottleneck_report().unwrap();
+        assert_eq!(report.stage, PipelineStage::Execute, "{report}");
+        // One-cycle ops among streaming misses: memory dominates.
+        let memory_bound = run(1, 8);
+        assert!(memory_bound.matrix_unit_utilization(CoreId(0)) < 0.1);
+        let report = memory_bound.bottleneck_report().unwrap();
+        assert_eq!(report.stage, PipelineStage::Memory, "{report}");
+    }
+
+    #[test]
+    fn simulator_periodic_interrupts_cost_handler_plus_overhead() {
+        let run = |mode: Option<InterruptMode>| {
+            let mut sim = Simulator::new(1, 1, CacheConfig::default(), MemoryConfig::default(), 1);
+            sim.load_workload(vec![(0..4000).map(Instruction::new_compute).collect()]).unwrap();
+            if let Some(mode) = mode {
+                sim.set_interrupt_config(InterruptConfig {
+                    mode,
+                    ..InterruptConfig::default()
+                });
+                // A timer tick every 2000 cycles with a 50-instruction handler.
+                for at in (1000..16_000).step_by(2000) {
+                    let handler = (0..50).map(Instruction::new_compute).collect();
+                    sim.inject_interrupt(CoreId(0), at, handler);
+                }
+            }
+            sim.run_to_completion();
+            sim.metrics().clone()
+        };
+        let quiet = run(None);
+        let cycles_per_instruction = quiet.total_cycles as f64 / 4000.0;
+        for mode in [InterruptMode::Squash, InterruptMode::Drain] {
+            let ticked = run(Some(mode));
+            let per = &ticked.per_core[&CoreId(0)];
+            assert_eq!(per.interrupts_serviced, 8);
+            assert_eq!(ticked.per_thread[&ThreadId(0)].instructions_committed, 4000);
+            assert_eq!(ticked.instructions_committed, 4000 + 8 * 50);
+            // Each costs about its 50 handler instructions plus 10 + 10 cycles of overhead.
+            let expected = 8.0 * (50.0 * cycles_per_instruction + 20.0);
+            let extra = (ticked.total_cycles - quiet.total_cycles) as f64;
+            assert!((extra / expected - 1.0).abs() < 0.1, "{mode:?}: {extra} vs {expected}");
+            let victim = ticked.per_thread[&ThreadId(0)].interrupted_cycles;
+            assert_eq!(victim, per.interrupt_overhead_cycles);
+        }
+    }
+
+    #[test]
+    fn simulator_commit_width_spreads_retirement() {
+        let retire_pattern = |commit_width: Option<usize>| {
+            let mut sim = Simulator::new(1, 1, CacheConfig::default(), MemoryConfig::default(), 8);
+            if let Some(width) = commit_width {
+                sim.set_commit_width(width);
+            }
+            // Eight independent computes fetched together finish Execute in the same cycle.
+            sim.load_workload(vec![(0..8).map(Instruction::new_compute).collect()]).unwrap();
+            let mut retired = Vec::new();
+            while !sim.is_finished() {
+                let committed = sim.step().committed;
+                if committed > 0 {
+                    retired.push(committed);
+                }
+            }
+            (retired, sim.metrics().commit_bound_cycles)
+        };
+        assert_eq!(retire_pattern(None), (vec![8], 0));
+        assert_eq!(retire_pattern(Some(4)), (vec![4, 4], 1));
+        assert_eq!(retire_pattern(Some(1)), (vec![1; 8], 7));
+    }
+
+    #[test]
+    fn simulator_pipeline_view_shows_in_flight_state() {
+        let mut sim = Simulator::new(1, 1, CacheConfig::default(), MemoryConfig::default(), 4);
+        let loads = (0..20).map(|i| Instruction::new_memory(InstructionKind::Load, i * 64, 0));
+        sim.load_workload(vec![loads.collect()]).unwrap();
+        sim.step();
+        let view = sim.pipeline_view(CoreId(0));
+        assert_eq!(view.len(), 4);
+        assert!(view.iter().all(|v| v.stage == PipelineStage::Fetch && !v.stalled));
+        assert_eq!(view[1].address, 64);
+        assert_eq!(sim.workload_remaining(CoreId(0)), 16);
+        // Run until the oldest load waits on its miss; the countdown then ticks down.
+        while !sim.pipeline_view(CoreId(0))[0].stalled {
+            sim.step();
+        }
+        let left = sim.pipeline_view(CoreId(0))[0].stall_cycles_left.unwrap();
+        assert!(left > 0);
+        sim.step();
+        let oldest = &sim.pipeline_view(CoreId(0))[0];
+        assert_eq!(oldest.stage, PipelineStage::Memory);
+        assert_eq!(oldest.stall_cycles_left, Some(left - 1));
+    }
+
+    #[test]
+    fn simulator_issue_width_bounds_throughput() {
+        let run = |issue_width: usize| {
+            let workload = vec![(0..400).map(Instruction::new_compute).collect()];
+            run_metrics(1, MemoryConfig::default(), workload, |sim| {
+                // Plenty of slots in flight, so only the issue width limits throughput.
+                sim.set_pipeline_widths(&[32]).unwrap();
+                sim.set_fetch_width(4);
+                sim.set_issue_width(issue_width);
+            })
+        };
+        let narrow = run(1);
+        let wide = run(4);
+        assert!((400..420).contains(&narrow.total_cycles), "1-wide {}", narrow.total_cycles);
+        assert!((100..120).contains(&wide.total_cycles), "4-wide {}", wide.total_cycles);
+        assert_eq!(wide.instructions_issued, 400);
+        assert!(wide.issue_rate() > 3.4, "issue rate {}", wide.issue_rate());
+    }
+
+    #[test]
+    fn simulator_ipc_approaches_width() {
+        let run = |instrs: Vec<Instruction>| {
+            run_metrics(1, MemoryConfig::default(), vec![instrs], |sim| {
+                // Enough slots in flight that the 1-wide front end is the only limit.
+                sim.set_pipeline_widths(&[32]).unwrap();
+                sim.set_fetch_width(1);
+                sim.set_issue_width(1);
+            })
+        };
+        let compute = run((0..1000).map(Instruction::new_compute).collect());
+        assert_eq!(compute.instructions_committed, 1000);
+        assert!(compute.ipc() > 0.95 && compute.ipc() <= 1.0, "ipc {}", compute.ipc());
+        assert_eq!(compute.ipc_for_core(CoreId(0)), compute.ipc());
+        // Every tenth instruction a load to a new line: each one misses.
+        let with_misses = run(
+            (0..1000u64)
+                .map(|i| match i % 10 {
+                    0 => Instruction::new_memory(InstructionKind::Load, i * 64, 0),
+                    _ => Instruction::new_compute(0),
+                })
+                .collect(),
+        );
+        assert!(with_misses.ipc() < compute.ipc() - 0.05, "ipc {}", with_misses.ipc());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn simulator_parallel_matches_sequential() {
+        // Out-of-order commit and coherence; SMT, speculation, store buffering and an LSQ;
+        // barriers, locks and fences, whose cores run serially among parallel ones; and
+        // functional memory, where every core does.
+        let run = |parallel: bool, setup: usize| {
+            let memory_config = MemoryConfig {
+                functional: setup == 3,
+                ..MemoryConfig::default()
+            };
+            let mut sim = Simulator::new(16, 32, CacheConfig::default(), memory_config, 4);
+            sim.set_parallel(parallel);
+            sim.enable_instruction_trace();
+            let mut workload = WorkloadConfig {
+                instructions_per_thread: 100,
+                ..WorkloadConfig::default()
+            };
+            match setup {
+                0 => {
+                    sim.set_reorder_buffer(ReorderBufferConfig::default());
+                    sim.enable_coherence();
+                    workload.dependency_chain_length = 4;
+                }
+                1 => {
+                    sim.set_smt_contexts(2);
+                    sim.set_speculation(SpeculationConfig {
+                        keep_cache_effects: true,
+                        ..SpeculationConfig::default()
+                    });
+                    sim.set_write_buffer(WriteBufferConfig::default());
+                    sim.set_load_store_queue(LoadStoreQueueConfig::default());
+                    workload.branch_fraction = 0.1;
+                    workload.atomic_fraction = 0.05;
+                }
+                2 => {
+                    workload.barrier_interval = 50;
+                    workload.lock_fraction = 0.1;
+                    workload.fence_interval = 20;
+                }
+                _ => workload.memory_fraction = 0.6,
+            }
+            sim.load_workload(build_workload(32, workload)).unwrap();
+            sim.run_to_completion();
+            let trace = sim.instruction_trace().into_owned();
+            (sim.metrics().clone(), trace, sim.load_results().to_vec())
+        };
+        for setup in 0..4 {
+            let (metrics, trace, loads) = run(true, setup);
+            assert!(!trace.is_empty());
+            assert!(setup != 2 || metrics.lock_acquisitions > 0);
+            assert!(setup != 3 || !loads.is_empty());
+            assert_eq!((metrics, trace, loads), run(false, setup), "setup {setup}");
+        }
+    }
+
+    #[test]
+    fn simulator_fast_forward_keeps_interrupt_time() {
+        let run = |fast_forward: bool, mode: InterruptMode| {
+            let loads = (0..20u64)
+                .map(|i| Instruction::new_memory(InstructionKind::Load, i * 4096, i))
+                .collect();
+            run_metrics(1, MemoryConfig::with_access_latency(500), vec![loads], |sim| {
+                sim.set_fast_forward(fast_forward);
+                sim.set_interrupt_config(InterruptConfig {
+                    mode,
+                    ..InterruptConfig::default()
+                });
+                // Taken while loads wait on memory: its entry and exit are the only activity.
+                sim.inject_interrupt(CoreId(0), 100, vec![Instruction::new_compute(0); 20]);
+            })
+        };
+        for mode in [InterruptMode::Squash, InterruptMode::Drain] {
+            let stepped = run(false, mode);
+            let per = &stepped.per_core[&CoreId(0)];
+            assert_eq!(per.interrupts_serviced, 1);
+            assert!(per.interrupt_overhead_cycles >= 20, "{mode:?}");
+            assert_eq!(run(true, mode), stepped, "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn simulator_fast_forward_matches_stepping() {
+        let run = |fast_forward: bool, rob: bool| {
+            let memory_config = MemoryConfig {
+                read_latency_cycles: 2000,
+                write_latency_cycles: 2000,
+                background_power_pw_per_cycle: 0.1,
+                ..MemoryConfig::default()
+            };
+            let mut sim = Simulator::new(2, 3, CacheConfig::default(), memory_config, 2);
+            sim.set_fast_forward(fast_forward);
+            sim.set_warmup_cycles(5000);
+            sim.enable_instruction_trace();
+            if rob {
+                sim.set_reorder_buffer(ReorderBufferConfig { size: 2 });
+            }
+            sim.load_workload(build_workload(
+                3,
+                WorkloadConfig {
+                    instructions_per_thread: 100,
+                    memory_fraction: 0.5,
+                    ..WorkloadConfig::default()
+                },
+            )).unwrap();
+            sim.run_to_completion();
+            (sim.metrics().clone(), sim.instruction_trace().to_vec())
+        };
+        // How much faster it is, `cargo bench` measures.
+        for rob in [false, true] {
+            let (stepped, stepped_trace) = run(false, rob);
+            let (skipped, skipped_trace) = run(true, rob);
+            assert_eq!(skipped, stepped);
+            assert_eq!(skipped_trace, stepped_trace);
+        }
+        // Chunked runs stay exact too.
+        let mut chunked = Simulator::new(1, 1, CacheConfig::default(), MemoryConfig::default(), 1);
+        let loads = (0..20u64)
+            .map(|i| Instruction::new_memory(InstructionKind::Load, i * 64, 0))
+            .collect();
+        chunked.load_workload(vec![loads]).unwrap();
+        while chunked.run_for(37) == RunStatus::Running {}
+        assert_eq!(chunked.metrics().memory_stall_cycles, 20 * 100);
+    }
+
+    #[test]
+    fn simulator_big_core_outruns_little_core() {
+        let big = CoreConfig {
+            pipeline_width: 8,
+            ..CoreConfig::default()
+        };
+        // A quarter of the width, with twice the execute latency.
+        let little = CoreConfig {
+            pipeline_width: 2,
+            stage_cycles: StageCycles {
+                execute_cycles: 2,
+                ..StageCycles::default()
+            },
+            core_type: CoreType::Little,
+            ..CoreConfig::default()
+        };
+        let cores = vec![big, little];
+        let mut sim = Simulator::new_heterogeneous(cores, 2, MemoryConfig::default()).unwrap();
+        assert_eq!(sim.scheduler().core_type(CoreId(1)), CoreType::Little);
+        let thread = || (0..2000).map(Instruction::new_compute).collect::<Vec<_>>();
+        sim.load_workload(vec![thread(), thread()]).unwrap();
+        sim.run_to_completion();
+        let m = sim.metrics();
+        let done = |t| m.thread_completion_cycles[&ThreadId(t)] as f64;
+        let ratio = done(1) / done(0);
+        assert!((4.0..6.0).contains(&ratio), "little / big = {ratio}");
+    }
+
+    #[test]
+    fn simulator_heterogeneous_rejects_unusable_cores() {
+        let memory = MemoryConfig::default;
+        let build = |cores: Vec<CoreConfig>| Simulator::new_heterogeneous(cores, 1, memory()).err();
+        assert_eq!(build(vec![]), Some(ConfigError::NoCores));
+        let narrow = CoreConfig {
+            pipeline_width: 0,
+            ..CoreConfig::default()
+        };
+        assert_eq!(build(vec![CoreConfig::default(), narrow]), Some(ConfigError::NoPipelineWidth));
+        let stuck = CoreConfig {
+            commit_width: Some(0),
+            ..CoreConfig::default()
+        };
+        assert_eq!(build(vec![stuck]), Some(ConfigError::NoCommitWidth));
+        let idle = Simulator::new_heterogeneous(vec![CoreConfig::default()], 0, memory());
+        assert_eq!(idle.err(), Some(ConfigError::NoThreads));
+    }
+
+    #[test]
+    fn simulator_per_core_pipeline_widths() {
+        let mut sim = Simulator::new(2, 2, CacheConfig::default(), MemoryConfig::default(), 4);
+        assert!(sim.set_pipeline_widths(&[1]).is_err());
+        assert!(sim.set_pipeline_widths(&[0, 4]).is_err());
+        sim.set_pipeline_widths(&[1, 4]).unwrap();
+        let thread = || (0..100_000).map(Instruction::new_compute).collect::<Vec<_>>();
+        sim.load_workload(vec![thread(), thread()]).unwrap();
+        sim.run_for(2000);
+        let m = sim.metrics();
+        let committed = |c| m.per_core[&CoreId(c)].instructions_committed as f64;
+        let ratio = committed(0) / committed(1);
+        assert!((0.2..0.3).contains(&ratio), "narrow / wide = {ratio}");
+    }
+
+    #[test]
+    fn simulator_in_order_commit_waits_for_an_older_miss() {
+        let run = |in_order: bool| {
+            let mut thread = vec![Instruction::new_memory(InstructionKind::Load, 0, 0)];
+            thread.extend((0..200).map(Instruction::new_compute));
+            run_metrics(1, MemoryConfig::default(), vec![thread], |sim| {
+                sim.set_in_order_commit(in_order);
+            })
+        };
+        let (strict, relaxed) = (run(true), run(false));
+        assert!(strict.total_cycles > relaxed.total_cycles);
+        assert!(strict.commit_blocked_cycles > 0);
+        assert_eq!(relaxed.commit_blocked_cycles, 0);
+    }
+
+    #[test]
+    fn simulator_in_order_commit_orders_each_thread_separately() {
+        let mut sim = Simulator::new(1, 2, CacheConfig::default(), MemoryConfig::default(), 64);
+        sim.set_smt_contexts(2);
+        let mut missing = vec![Instruction::new_memory(InstructionKind::Load, 0, 0)];
+        missing.extend((0..20).map(Instruction::new_compute));
+        let computing = (0..20).map(Instruction::new_compute).collect();
+        sim.load_workload(vec![missing, computing]).unwrap();
+        sim.run_to_completion();
+        // The other thread's computes do not wait behind the miss.
+        let done = &sim.metrics().thread_completion_cycles;
+        assert!(done[&ThreadId(1)] * 2 < done[&ThreadId(0)]);
+    }
+
+    #[test]
+    fn simulator_decode_stages_add_their_latency() {
+        // Wide enough that the whole workload is in flight at once.
+        let run = |decode_cycles: u32| {
+            let mut sim = Simulator::new(1, 1, CacheConfig::default(), MemoryConfig::default(), 32);
+            sim.set_stage_cycles(StageCycles {
+                decode_cycles,
+                ..StageCycles::default()
+            });
+            sim.enable_instruction_trace();
+            let mut thread: Vec<_> = (0..20).map(Instruction::new_compute).collect();
+            thread.push(Instruction::new_memory(InstructionKind::Load, 0, 0));
+            sim.load_workload(vec![thread]).unwrap();
+            sim.run_to_completion();
+            let decode = sim.instruction_trace()[0].stage_cycles()[1];
+            (sim.metrics().total_cycles, decode)
+        };
+        let (shallow, _) = run(0);
+        for depth in [1, 8] {
+            let (cycles, decode) = run(depth);
+            assert_eq!(cycles, shallow + u64::from(depth));
+            assert_eq!(decode, (PipelineStage::Decode, u64::from(depth)));
+        }
+    }
+
+    #[test]
+    fn simulator_conflict_misses_make_memory_the_bottleneck() {
+        // The adverse scenario of the example binary.
+        let cache = CacheConfig {
+            size_bytes: 32 * 64 * 2,
+            ..CacheConfig::default()
+        };
+        let mut sim = Simulator::new(2, 2, cache, MemoryConfig::ddr4(), 4);
+        let config = WorkloadConfig {
+            instructions_per_thread: 2000,
+            memory_fraction: 0.5,
+            access_pattern: AccessPattern::ConflictHeavy,
+            cache_num_sets: 32,
+            ..WorkloadConfig::default()
+        };
+        sim.load_workload(build_workload(2, config)).unwrap();
+        sim.run_to_completion();
+        let m = sim.metrics();
+        let report = m.bottleneck_report().unwrap();
+        assert_eq!(report.stage, PipelineStage::Memory);
+        assert!(report.share > 0.8, "{report}");
+        assert!(m.average_stage_occupancy(PipelineStage::Memory) > 1.0);
+    }
+
+    #[test]
+    fn simulator_half_clock_slows_compute_more_than_memory() {
+        let run = |memory_bound: bool, ratio: f64| {
+            let thread: Vec<_> = if memory_bound {
+                (0..200u64)
+                    .map(|i| Instruction::new_memory(InstructionKind::Load, i * 64, 0))
+                    .collect()
+            } else {
+                (0..2000).map(Instruction::new_compute).collect()
+            };
+            run_metrics(1, MemoryConfig::default(), vec![thread], |sim| {
+                sim.set_core_frequency(CoreId(0), ratio);
+            })
+        };
+        let slowdown = |memory_bound| {
+            let (fast, slow) = (run(memory_bound, 1.0), run(memory_bound, 0.5));
+            assert!((slow.ipc_per_core_clock(CoreId(0)) - slow.ipc() * 2.0).abs() < 0.01);
+            slow.total_cycles as f64 / fast.total_cycles as f64
+        };
+        let compute = slowdown(false);
+        let memory = slowdown(true);
+        assert!((1.9..2.1).contains(&compute), "compute slowdown {compute}");
+        assert!(memory < 1.3, "memory slowdown {memory}");
+    }
+
+    #[test]
+    fn simulator_frequency_change_halts_the_core() {
+        let run = |penalty: u32| {
+            let mut sim = Simulator::new(1, 1, CacheConfig::default(), MemoryConfig::default(), 4);
+            sim.set_dvfs_transition_penalty(penalty);
+            sim.load_workload(vec![(0..2000).map(Instruction::new_compute).collect()]).unwrap();
+            sim.run_for(100);
+            sim.set_core_frequency(CoreId(0), 0.5);
+            sim.run_to_completion();
+            sim.metrics().clone()
+        };
+        let (instant, slow_switch) = (run(0), run(50));
+        assert_eq!(slow_switch.dvfs_transition_cycles, 50);
+        assert_eq!(slow_switch.total_cycles, instant.total_cycles + 50);
+    }
+
+    #[test]
+    fn simulator_hot_core_throttles_periodically() {
+        let run = |power: Option<PowerConfig>| {
+            let workload = vec![(0..20_000).map(Instruction::new_compute).collect()];
+            run_metrics(1, MemoryConfig::default(), workload, |sim| {
+                if let Some(power) = power {
+                    sim.set_power_model(power);
+                }
+            })
+        };
+        // Hot enough to cross the threshold at full speed, cool enough to drop back throttled.
+        let throttling = PowerConfig {
+            throttle_threshold_c: 55.0,
+            throttle_ratio: 0.25,
+            ..PowerConfig::default()
+        };
+        let (cool, hot) = (run(None), run(Some(throttling)));
+        let per = &hot.per_core[&CoreId(0)];
+        assert!(per.throttle_events >= 2, "{} throttle events", per.throttle_events);
+        assert!(per.throttled_cycles > 0 && per.throttled_cycles < hot.total_cycles);
+        assert!(hot.total_cycles as f64 > cool.total_cycles as f64 * 1.1);
+        let temperature = hot.average_temperature(CoreId(0));
+        assert!((45.0..60.0).contains(&temperature), "{temperature} C");
+        assert_eq!(cool.average_temperature(CoreId(0)), 0.0);
+    }
 
     #[test]
-    fn simulator_steps_and_drains_workload() {
-        let cache_config = CacheConfig::default();
-        let memory_config = MemoryConfig::default();
-        let mut sim = Simulator::new(2, 2, cache_config, memory_config, 4);
-        let workload = build_workload(
-            2,
-            WorkloadConfig {
-                instructions_per_thread: 20,
-                memory_fraction: 0.2,
-                access_pattern: AccessPattern::Sequential,
-                line_size: 64,
-                cache_num_sets: 64,
-                working_set_lines: 0,
-            },
+    fn simulator_far_ring_stop_runs_slower() {
+        let ring = Topology {
+            interconnect: Interconnect::Ring { hop_cycles: 10 },
+            ..Topology::uniform()
+        };
+        let mut sim = Simulator::builder().cores(8).threads(8).topology(ring).build().unwrap();
+        let workload = (0..8u64)
+            .map(|t| {
+                (0..100u64)
+                    .map(|i| Instruction::new_memory(InstructionKind::Load, (t << 20) + i * 64, 0))
+                    .collect()
+            })
+            .collect();
+        sim.load_workload(workload).unwrap();
+        sim.run_to_completion();
+        let m = sim.metrics();
+        assert_eq!(m.average_interconnect_latency(CoreId(0)), 0.0);
+        assert_eq!(m.average_interconnect_latency(CoreId(4)), 40.0);
+        let done = |t| m.thread_completion_cycles[&ThreadId(t)];
+        assert!(done(4) > done(0) + 100, "far {} near {}", done(4), done(0));
+    }
+
+    #[test]
+    fn simulator_remote_socket_data_pays_the_penalty() {
+        let run = |base: u64| {
+            let socket = |node: u64| SocketConfig {
+                // One miss in flight at a time, so every penalty is paid in full.
+                cores: vec![CoreConfig {
+                    pipeline_width: 1,
+                    ..CoreConfig::default()
+                }],
+                memory: NodeConfig {
+                    latency_cycles: 100,
+                    address_range: node << 20..(node + 1) << 20,
+                },
+            };
+            let memory = MemoryConfig {
+                remote_hop_cycles: 50,
+                ..MemoryConfig::default()
+            };
+            let mut sim =
+                Simulator::new_multisocket(vec![socket(0), socket(1)], 1, memory).unwrap();
+            let loads = (0..100u64)
+                .map(|i| Instruction::new_memory(InstructionKind::Load, base + i * 64, 0))
+                .collect();
+            sim.load_workload(vec![loads]).unwrap();
+            sim.run_to_completion();
+            sim.metrics().clone()
+        };
+        let (local, remote) = (run(0), run(1 << 20));
+        assert_eq!(remote.cache_misses, 100);
+        let extra = (remote.total_cycles - local.total_cycles) as f64;
+        assert!((extra / (50.0 * 100.0) - 1.0).abs() < 0.1, "{extra} extra cycles");
+    }
+
+    #[test]
+    fn simulator_tso_buffers_stores_and_fences_drain_them() {
+        let run = |model: ConsistencyModel, fences: bool| {
+            let mut thread = Vec::new();
+            for i in 0..100u64 {
+                thread.push(Instruction::new_memory(InstructionKind::Store, i * 64, 0));
+                if fences {
+                    thread.push(Instruction::new_control(InstructionKind::Fence));
+                }
+                thread.push(Instruction::new_memory(InstructionKind::Load, 0x10_0000 + i * 64, 0));
+            }
+            run_metrics(1, MemoryConfig::default(), vec![thread], |sim| {
+                sim.set_consistency(model);
+            })
+        };
+        let sc = run(ConsistencyModel::Sc, false);
+        let tso = run(ConsistencyModel::Tso, false);
+        let fenced = run(ConsistencyModel::Tso, true);
+        assert!(sc.consistency_stall_cycles > 0);
+        assert!(tso.average_write_buffer_occupancy() > 0.0);
+        assert!(fenced.fence_stall_cycles > 0);
+        assert!(
+            (tso.total_cycles as f64) < sc.total_cycles as f64 * 0.6,
+            "tso {} sc {}",
+            tso.total_cycles,
+            sc.total_cycles
         );
-        sim.load_workload(workload);
+        let near_sc = fenced.total_cycles as f64 / sc.total_cycles as f64;
+        assert!((0.8..1.2).contains(&near_sc), "fenced tso / sc = {near_sc}");
+    }
+
+    #[test]
+    fn simulator_cycle_summaries_add_up_to_metrics() {
+        let mut sim = Simulator::new(2, 2, CacheConfig::default(), MemoryConfig::default(), 4);
+        sim.load_workload(build_workload(2, WorkloadConfig::default())).unwrap();
+        let mut total = CycleSummary::default();
+        let mut stalled = 0;
+        while !sim.is_finished() {
+            let cycle = sim.step();
+            total.committed += cycle.committed;
+            total.fetched += cycle.fetched;
+            total.cache_hits += cycle.cache_hits;
+            total.cache_misses += cycle.cache_misses;
+            stalled += u64::from(cycle.stalled_instructions);
+        }
+        let m = sim.metrics();
+        assert_eq!(u64::from(total.committed), m.instructions_committed);
+        assert_eq!(total.fetched, total.committed);
+        assert_eq!(u64::from(total.cache_hits), m.cache_hits);
+        assert_eq!(u64::from(total.cache_misses), m.cache_misses);
+        assert!(stalled > 0);
+    }
+
+    #[test]
+    fn simulator_counts_each_stall_cycle_once() {
+        // One instruction in flight, so the misses never overlap.
+        let mut sim = Simulator::new(1, 1, CacheConfig::default(), MemoryConfig::default(), 1);
+        let loads = (0..10u64)
+            .map(|i| Instruction::new_memory(InstructionKind::Load, i * 64, 0))
+            .collect();
+        sim.load_workload(vec![loads]).unwrap();
         sim.run_to_completion();
-        assert!(sim.current_cycle() > 0);
-        assert!(sim.metrics().total_cycles > 0);
+        let m = sim.metrics();
+        assert_eq!(m.cache_misses, 10);
+        assert_eq!(m.memory_stall_cycles, m.miss_latency_cycles);
+        assert_eq!(m.memory_stall_cycles, 10 * 100);
+        assert_eq!(m.per_core[&CoreId(0)].memory_stall_cycles, 10 * 100);
     }
 
     #[test]
-    fn simulator_tracks_memory_accesses() {
-        let cache_config = CacheConfig::default();
-        let memory_config = MemoryConfig::default();
+    fn simulator_warmup_excludes_cold_misses() {
+        let run = |warmup: Cycle| {
+            let mut sim = Simulator::new(1, 1, CacheConfig::default(), MemoryConfig::default(), 4);
+            sim.set_warmup_cycles(warmup);
+            // Ten passes over 16 lines; only the first pass misses.
+            sim.load_workload(build_workload(
+                1,
+                WorkloadConfig {
+                    instructions_per_thread: 2000,
+                    memory_fraction: 1.0,
+                    working_set_lines: 16,
+                    ..WorkloadConfig::default()
+                },
+            )).unwrap();
+            sim.run_to_completion();
+            (sim.current_cycle(), sim.metrics().clone(), sim.warmup_metrics().cloned())
+        };
+        let (end, cold, none) = run(0);
+        assert!(none.is_none());
+        assert!(cold.hit_rate() < 0.995, "hit rate {}", cold.hit_rate());
+        // Warm-up long enough for the first pass to have missed and filled.
+        let (_, warm, warmup) = run(1000);
+        let warmup = warmup.unwrap();
+        assert_eq!(warm.hit_rate(), 1.0);
+        assert_eq!(warmup.cache_misses, cold.cache_misses);
+        assert_eq!(warm.total_cycles, end - 1000);
+        assert_eq!(warmup.total_cycles, 1000);
+    }
+
+    #[test]
+    fn simulator_stall_breakdown_attributes_port_conflicts() {
+        let run = |ports: usize| {
+            let instrs = (0..64u64)
+                .map(|i| Instruction::new_memory(InstructionKind::Load, i * 4096, 0))
+                .collect();
+            run_metrics(1, MemoryConfig::default(), vec![instrs], |sim| {
+                sim.set_cache_ports(ports);
+            })
+        };
+        let share = |m: &Metrics, reason: StallReason| {
+            let total: u64 = m.stall_cycles_by_reason.values().sum();
+            m.stall_cycles_by_reason.get(&reason).copied().unwrap_or(0) as f64 / total as f64
+        };
+        let wide = run(4);
+        assert_eq!(wide.stall_breakdown()[0].0, StallReason::CacheMiss);
+        assert!(share(&wide, StallReason::CacheMiss) > 0.99);
+        let per_core = &wide.per_core[&CoreId(0)].stall_cycles_by_reason;
+        assert_eq!(per_core, &wide.stall_cycles_by_reason);
+
+        let narrow = run(1);
+        assert!(share(&narrow, StallReason::Structural) > 0.0);
+        assert!(share(&narrow, StallReason::CacheMiss) < share(&wide, StallReason::CacheMiss));
+        let breakdown = narrow.stall_breakdown();
+        assert!(breakdown.windows(2).all(|w| w[0].1 >= w[1].1));
+
+        // An uncontended lock held back behind a missing load still waits on synchronization.
+        let locked = vec![
+            Instruction::new_memory(InstructionKind::Load, 0, 0),
+            Instruction::new_control(InstructionKind::LockAcquire { lock_id: 0 }),
+            Instruction::new_control(InstructionKind::LockRelease { lock_id: 0 }),
+        ];
+        let m = run_metrics(1, MemoryConfig::default(), vec![locked], |_| {});
+        let by_reason = &m.stall_cycles_by_reason;
+        assert!(by_reason[&StallReason::Synchronization] >= by_reason[&StallReason::CacheMiss]);
+    }
+
+    #[test]
+    fn simulator_cache_ports_limit_memory_throughput() {
+        let run = |ports: usize| {
+            let mut sim = Simulator::new(1, 1, CacheConfig::default(), MemoryConfig::default(), 16);
+            sim.set_fetch_width(4);
+            sim.set_issue_width(4);
+            sim.set_cache_ports(ports);
+            // Loads and stores over 8 lines: all hits after the first pass.
+            sim.load_workload(build_workload(
+                1,
+                WorkloadConfig {
+                    instructions_per_thread: 2000,
+                    memory_fraction: 1.0,
+                    working_set_lines: 8,
+                    ..WorkloadConfig::default()
+                },
+            )).unwrap();
+            sim.run_to_completion();
+            sim.metrics().clone()
+        };
+        let one_port = run(1);
+        let four_ports = run(4);
+        assert!(one_port.ipc() <= 1.0, "ipc {}", one_port.ipc());
+        assert!(one_port.structural_stall_cycles > 0);
+        assert!(four_ports.ipc() > one_port.ipc() * 1.5, "ipc {}", four_ports.ipc());
+    }
+
+    #[test]
+    fn simulator_lsq_forwards_stores_to_loads() {
+        let run = |lsq: Option<usize>| {
+            // Producer-consumer within one thread: store a value, read it straight back.
+            let instrs = (0..50u64)
+                .flat_map(|i| {
+                    [
+                        Instruction::new_memory(InstructionKind::Store, i * 64, 0),
+                        Instruction::new_memory(InstructionKind::Load, i * 64, 0),
+                    ]
+                })
+                .collect();
+            let metrics = run_metrics(1, MemoryConfig::default(), vec![instrs], |sim| {
+                if let Some(depth) = lsq {
+                    sim.set_load_store_queue(LoadStoreQueueConfig { depth });
+                }
+            });
+            assert_eq!(metrics.per_thread[&ThreadId(0)].instructions_committed, 100);
+            metrics
+        };
+        let without = run(None);
+        assert_eq!(without.total_memory_accesses, 100);
+        assert_eq!(without.store_forwards, 0);
+        let with = run(Some(16));
+        assert_eq!(with.store_forwards, 50);
+        assert_eq!(with.total_memory_accesses, 50);
+        let tiny = run(Some(1));
+        assert!(tiny.lsq_full_stalls > 0);
+    }
+
+    #[test]
+    fn simulator_shared_atomics_ping_pong() {
+        let run = |shared: bool| {
+            let workload = (0..2u64)
+                .flat_map(|t| {
+                    let config = WorkloadConfig {
+                        instructions_per_thread: 200,
+                        memory_fraction: 0.0,
+                        atomic_fraction: 0.5,
+                        atomic_address: shared.then_some(0x8000),
+                        working_set_lines: 1,
+                        base_address: t * 0x10_0000,
+                        ..WorkloadConfig::default()
+                    };
+                    build_workload(1, config)
+                })
+                .collect();
+            run_metrics(2, MemoryConfig::default(), workload, |_| {})
+        };
+        let private = run(false);
+        let shared = run(true);
+        assert_eq!(private.atomics, 200);
+        assert_eq!(shared.atomics, 200);
+        assert!(private.cache_misses <= 2, "private misses {}", private.cache_misses);
+        // Each core's atomic steals the line from the other, so most atomics miss.
+        assert!(shared.cache_misses > 100, "shared misses {}", shared.cache_misses);
+        assert!(
+            shared.total_cycles > 5 * private.total_cycles,
+            "shared {} vs private {}",
+            shared.total_cycles,
+            private.total_cycles
+        );
+        assert!(private.atomic_serialization_stalls > 0);
+    }
+
+    #[test]
+    fn simulator_barrier_holds_fast_thread() {
+        let computes = |n: u64| (0..n).map(Instruction::new_compute);
+        let barrier = || {
+            Instruction::new_control(InstructionKind::Barrier {
+                id: 7,
+                participants: 2,
+            })
+        };
+        // Cycles per compute on one core running alone.
+        let mut solo = Simulator::new(1, 1, CacheConfig::default(), MemoryConfig::default(), 4);
+        solo.load_workload(vec![computes(1000).collect()]).unwrap();
+        solo.run_to_completion();
+        let cpi = solo.metrics().total_cycles as f64 / 1000.0;
+
+        let mut sim = Simulator::new(2, 2, CacheConfig::default(), MemoryConfig::default(), 4);
+        let fast = computes(100).chain([barrier()]).chain(computes(10)).collect();
+        let slow = computes(500).chain([barrier()]).chain(computes(10)).collect();
+        sim.load_workload(vec![fast, slow]).unwrap();
+        sim.run_to_completion();
+        let m = sim.metrics();
+        let fast_wait = m.per_thread[&ThreadId(0)].barrier_wait_cycles as f64;
+        let expected = 400.0 * cpi;
+        assert!(
+            (fast_wait - expected).abs() < 0.1 * expected,
+            "fast thread waited {fast_wait}, expected ~{expected}"
+        );
+        assert!(m.per_thread[&ThreadId(1)].barrier_wait_cycles <= 2);
+        let imbalance = m.barrier_imbalance_cycles[&7] as f64;
+        assert!((imbalance - expected).abs() < 0.1 * expected, "imbalance {imbalance}");
+        // Both threads leave the barrier together, so they finish at about the same time.
+        let done = |t| m.thread_completion_cycles[&ThreadId(t)] as i64;
+        assert!((done(0) - done(1)).abs() <= 2);
+    }
+
+    #[test]
+    fn simulator_shared_lock_serializes_threads() {
+        let run = |threads: usize, lock_fraction: f64| {
+            let mut sim = Simulator::new(
+                threads,
+                threads,
+                CacheConfig::default(),
+                MemoryConfig::default(),
+                4,
+            );
+            let workload = (0..threads as u64)
+                .flat_map(|t| {
+                    let config = WorkloadConfig {
+                        instructions_per_thread: 200,
+                        // Every access misses, so the critical sections are long.
+                        memory_fraction: 1.0,
+                        base_address: t * 0x10_0000,
+                        lock_fraction,
+                        ..WorkloadConfig::default()
+                    };
+                    build_workload(1, config)
+                })
+                .collect();
+            sim.load_workload(workload).unwrap();
+            sim.run_to_completion();
+            sim.metrics().clone()
+        };
+        let lock_free = run(2, 0.0);
+        let alone = run(1, 1.0);
+        let locked = run(2, 1.0);
+        assert_eq!(lock_free.lock_acquisitions, 0);
+        assert_eq!(locked.lock_acquisitions, 400);
+        assert!(locked.contended_lock_acquisitions > 100);
+        assert_eq!(alone.contended_lock_acquisitions, 0);
+        // Two threads on two cores take about as long as one doing both sections in turn.
+        let serialized = locked.total_cycles as f64 / alone.total_cycles as f64;
+        assert!(serialized > 1.7, "2 locked threads / 1 = {serialized}");
+        assert!(locked.total_cycles > 2 * lock_free.total_cycles);
+        let waited: u64 = locked.per_thread.values().map(|t| t.lock_wait_cycles).sum();
+        assert!(waited > 0);
+    }
+
+    #[test]
+    fn simulator_padding_removes_false_sharing() {
+        let run = |padded: bool| {
+            let config = WorkloadConfig {
+                instructions_per_thread: 400,
+                memory_fraction: 0.5,
+                false_sharing: Some(FalseSharing {
+                    num_vars: 2,
+                    line_size: 64,
+                    padded,
+                }),
+                ..WorkloadConfig::default()
+            };
+            run_metrics(2, MemoryConfig::default(), build_workload(2, config), |sim| {
+                sim.enable_coherence();
+            })
+        };
+        let packed = run(false);
+        let padded = run(true);
+        assert_eq!(padded.coherence_misses, 0);
+        assert_eq!(padded.cache_misses, 2);
+        assert!(packed.coherence_misses > 100, "coherence misses {}", packed.coherence_misses);
+        assert!(
+            packed.total_cycles > 3 * padded.total_cycles,
+            "packed {} vs padded {}",
+            packed.total_cycles,
+            padded.total_cycles
+        );
+    }
+
+    #[test]
+    fn simulator_fr_fcfs_favors_row_hits() {
+        let run = |policy: SchedulingPolicy| {
+            let memory_config = MemoryConfig {
+                dram: Some(DramConfig {
+                    num_banks: 1,
+                    ..DramConfig::default()
+                }),
+                scheduling_policy: Some(policy),
+                ..MemoryConfig::default()
+            };
+            // Core 0 streams through rows; core 1 jumps between rows at random.
+            let sequential = (0..200u64)
+                .map(|i| Instruction::new_memory(InstructionKind::Load, i * 64, i))
+                .collect();
+            let mut rng = Rng::new(11);
+            let random = (0..200u64)
+                .map(|i| {
+                    let address = (1 << 30) + rng.range_inclusive(0, 1 << 16) * 4096;
+                    Instruction::new_memory(InstructionKind::Load, address, i)
+                })
+                .collect();
+            run_metrics(2, memory_config, vec![sequential, random], |_| {})
+        };
+        let fcfs = run(SchedulingPolicy::Fcfs);
+        let fr_fcfs = run(SchedulingPolicy::FrFcfs);
+        let stall = |m: &Metrics, core| m.per_core[&CoreId(core)].memory_stall_cycles;
+        assert!(fr_fcfs.row_hit_rate() > fcfs.row_hit_rate());
+        // FCFS treats both cores alike; FR-FCFS lets the streaming core run ahead.
+        let skew = |m: &Metrics| stall(m, 1) as f64 / stall(m, 0) as f64;
+        assert!(skew(&fcfs) < 1.2, "fcfs skew {}", skew(&fcfs));
+        assert!(skew(&fr_fcfs) > 2.0, "fr-fcfs skew {}", skew(&fr_fcfs));
+        assert!(stall(&fr_fcfs, 0) < stall(&fcfs, 0));
+    }
+
+    #[test]
+    fn simulator_conflict_pattern_hammers_one_bank() {
+        let run = |access_pattern: AccessPattern| {
+            let memory_config = MemoryConfig {
+                dram: Some(DramConfig::default()),
+                ..MemoryConfig::default()
+            };
+            // 256 sets: the conflict stride (256 lines = 16 KiB) is a multiple of 8 banks' rows.
+            let cache_config = CacheConfig {
+                size_bytes: 256 * 64 * 2,
+                ..CacheConfig::default()
+            };
+            let mut sim = Simulator::new(1, 1, cache_config, memory_config, 4);
+            sim.load_workload(build_workload(
+                1,
+                WorkloadConfig {
+                    instructions_per_thread: 2000,
+                    memory_fraction: 1.0,
+                    access_pattern,
+                    cache_num_sets: 256,
+                    ..WorkloadConfig::default()
+                },
+            )).unwrap();
+            sim.run_to_completion();
+            let stats = sim.memory().stats().clone();
+            let mirrored: u64 = sim.metrics().per_bank.values().map(|b| b.accesses).sum();
+            let total: u64 = stats.per_bank.iter().map(|b| b.accesses).sum();
+            assert_eq!(mirrored, total);
+            stats
+        };
+        let conflict = run(AccessPattern::ConflictHeavy);
+        assert!(conflict.hottest_bank_share() > 0.8);
+        let sequential = run(AccessPattern::Sequential);
+        let uniform = 1.0 / sequential.per_bank.len() as f64;
+        assert!(sequential.hottest_bank_share() < 2.0 * uniform);
+    }
+
+    #[test]
+    fn simulator_energy_adds_up() {
+        let memory_config = MemoryConfig {
+            read_energy_pj: 1000.0,
+            write_energy_pj: 1500.0,
+            background_power_pw_per_cycle: 2.0,
+            ..MemoryConfig::default()
+        };
+        let cache_config = CacheConfig {
+            access_energy_pj: 5.0,
+            ..CacheConfig::default()
+        };
         let mut sim = Simulator::new(1, 1, cache_config, memory_config, 4);
-        let workload = build_workload(
+        // 10 load misses, 10 store misses, then 10 load hits on the stored lines.
+        let mut instrs: Vec<Instruction> = (0..10u64)
+            .map(|i| Instruction::new_memory(InstructionKind::Load, i * 64, i))
+            .collect();
+        instrs.extend((16..26u64).map(|i| Instruction::new_memory(InstructionKind::Store, i * 64, i)));
+        instrs.extend((16..26u64).map(|i| Instruction::new_memory(InstructionKind::Load, i * 64, i)));
+        sim.load_workload(vec![instrs]).unwrap();
+        sim.run_to_completion();
+        let m = sim.metrics();
+        assert_eq!(m.cache_misses, 20);
+        assert_eq!(m.cache_energy_pj, 30.0 * 5.0);
+        let expected_memory = 10.0 * 1000.0 + 10.0 * 1500.0 + 2.0 * m.total_cycles as f64;
+        assert_eq!(m.memory_energy_pj, expected_memory);
+        assert_eq!(m.total_energy_pj(), 150.0 + expected_memory);
+    }
+
+    #[test]
+    fn simulator_dirty_evictions_pay_the_writeback_latency() {
+        let run = |kind: InstructionKind, writeback_latency_cycles: u32| {
+            let cache_config = CacheConfig {
+                writeback_latency_cycles,
+                ..CacheConfig::default()
+            };
+            let mut sim = Simulator::new(1, 1, cache_config, MemoryConfig::default(), 1);
+            // Eight lines of the same set of the 2-way cache: every miss from the third on
+            // evicts one.
+            let instrs = (0..8u64).map(|i| Instruction::new_memory(kind, i * 2048, 0)).collect();
+            sim.load_workload(vec![instrs]).unwrap();
+            sim.run_to_completion();
+            sim.metrics().clone()
+        };
+        let free = run(InstructionKind::Store, 0);
+        let charged = run(InstructionKind::Store, 20);
+        assert_eq!(charged.writebacks, 6);
+        assert_eq!(charged.memory_stall_cycles, free.memory_stall_cycles + 6 * 20);
+        assert_eq!(charged.miss_latency_cycles, free.miss_latency_cycles + 6 * 20);
+        assert_eq!(charged.total_cycles, free.total_cycles + 6 * 20);
+
+        let clean = run(InstructionKind::Load, 20);
+        assert_eq!(clean.writebacks, 0);
+        assert_eq!(clean.total_cycles, run(InstructionKind::Load, 0).total_cycles);
+    }
+
+    #[test]
+    fn simulator_shorter_quantum_costs_more_switches() {
+        let run = |quantum_cycles: u64| {
+            let workload = build_workload(
+                4,
+                WorkloadConfig {
+                    instructions_per_thread: 2000,
+                    working_set_lines: 32,
+                    ..WorkloadConfig::default()
+                },
+            );
+            run_metrics(2, MemoryConfig::default(), workload, |sim| {
+                sim.set_time_slice(TimeSliceConfig {
+                    quantum_cycles,
+                    context_switch_cycles: 20,
+                    flush_cache_on_switch: true,
+                    ..TimeSliceConfig::default()
+                });
+            })
+        };
+        let long = run(5000);
+        let short = run(100);
+        assert!(short.context_switches > long.context_switches);
+        assert!(short.total_cycles > long.total_cycles);
+        // A zero quantum switches as often as it can but still finishes.
+        let zero = run(0);
+        assert_eq!(zero.instructions_committed, 8000);
+        assert!(zero.context_switches > short.context_switches);
+        assert_eq!(short.per_thread.len(), 4);
+        assert!(short.per_thread.values().all(|t| t.run_cycles > 0));
+    }
+
+    #[test]
+    fn simulator_long_quantum_thread_keeps_its_cache() {
+        let mut sim = Simulator::new(1, 3, CacheConfig::default(), MemoryConfig::default(), 4);
+        sim.set_time_slice(TimeSliceConfig {
+            quantum_cycles: 100,
+            ..TimeSliceConfig::default()
+        });
+        sim.scheduler_mut().set_quantum(ThreadId(0), 5000);
+        // Any two of the 48-line working sets overflow the 64-line L1.
+        let workload = (0..3u64)
+            .flat_map(|t| {
+                build_workload(
+                    1,
+                    WorkloadConfig {
+                        instructions_per_thread: if t == 0 { 20_000 } else { 4000 },
+                        working_set_lines: 48,
+                        base_address: t << 20,
+                        ..WorkloadConfig::default()
+                    },
+                )
+            })
+            .collect();
+        sim.load_workload(workload).unwrap();
+        sim.run_to_completion();
+        let hit_rate = |t| sim.metrics().per_thread[&ThreadId(t)].hit_rate();
+        assert!(hit_rate(0) > 0.95, "long quantum hit rate {}", hit_rate(0));
+        assert!(hit_rate(1) < 0.6 && hit_rate(2) < 0.6);
+    }
+
+    #[test]
+    fn simulator_quantum_expiry_waits_for_misses() {
+        let run = |preempt_on_miss: bool| {
+            let memory_config = MemoryConfig::with_access_latency(300);
+            let workload = build_workload(
+                2,
+                WorkloadConfig {
+                    instructions_per_thread: 100,
+                    memory_fraction: 1.0,
+                    ..WorkloadConfig::default()
+                },
+            );
+            run_metrics(1, memory_config, workload, |sim| {
+                sim.set_time_slice(TimeSliceConfig {
+                    quantum_cycles: 20,
+                    context_switch_cycles: 5,
+                    preempt_on_miss,
+                    ..TimeSliceConfig::default()
+                });
+            })
+        };
+        let deferred = run(false);
+        let immediate = run(true);
+        assert!(deferred.context_switches * 4 < immediate.context_switches);
+        assert!(deferred.per_thread.values().all(|t| t.instructions_committed == 100));
+    }
+
+    #[test]
+    fn simulator_accounts_for_every_thread_on_shared_cores() {
+        let mut sim = Simulator::new(2, 4, CacheConfig::default(), MemoryConfig::default(), 4);
+        sim.set_time_slice(TimeSliceConfig {
+            quantum_cycles: 100,
+            ..TimeSliceConfig::default()
+        });
+        sim.load_workload(build_workload(4, WorkloadConfig::default())).unwrap();
+        assert_eq!(sim.threads_on_core(CoreId(0)), vec![ThreadId(0), ThreadId(2)]);
+        let mut seen = HashMap::new();
+        while sim.cores.iter().any(|c| c.has_pending_work() || !c.pipeline.is_empty()) {
+            sim.step();
+            for core in 0..2 {
+                if let Some(t) = sim.running_thread(CoreId(core)) {
+                    seen.insert(t, CoreId(core));
+                }
+            }
+        }
+        for t in 0..4 {
+            assert_eq!(seen[&ThreadId(t)], sim.scheduler().thread_to_core(ThreadId(t)));
+            assert_eq!(sim.metrics().per_thread[&ThreadId(t)].instructions_committed, 1000);
+        }
+        assert_eq!(sim.running_thread(CoreId(0)), None);
+    }
+
+    #[test]
+    fn simulator_reports_thread_completion_once() {
+        let mut sim = Simulator::new(2, 2, CacheConfig::default(), MemoryConfig::default(), 4);
+        let finished = Rc::new(RefCell::new(Vec::new()));
+        let log = Rc::clone(&finished);
+        sim.on_thread_complete(Box::new(move |t, cycle| log.borrow_mut().push((t, cycle))));
+        let sized = |instructions_per_thread| WorkloadConfig {
+            instructions_per_thread,
+            ..WorkloadConfig::default()
+        };
+        let mut workload = build_workload(1, sized(1000));
+        workload.extend(build_workload(1, sized(100)));
+        sim.load_workload(workload).unwrap();
+        sim.run_to_completion();
+        let done = &sim.metrics().thread_completion_cycles;
+        assert!(done[&ThreadId(1)] < done[&ThreadId(0)]);
+        let mut finished = finished.borrow().clone();
+        finished.sort_by_key(|&(t, _)| t.0);
+        assert_eq!(
+            finished,
+            vec![(ThreadId(0), done[&ThreadId(0)]), (ThreadId(1), done[&ThreadId(1)])]
+        );
+    }
+
+    #[test]
+    fn simulator_gang_runs_together_or_not_at_all() {
+        let run = |pin_gang_together: bool| {
+            let mut sim = Simulator::new(2, 3, CacheConfig::default(), MemoryConfig::default(), 4);
+            sim.set_time_slice(TimeSliceConfig {
+                quantum_cycles: 200,
+                ..TimeSliceConfig::default()
+            });
+            sim.scheduler_mut().set_gang(&[ThreadId(0), ThreadId(1)]);
+            if pin_gang_together {
+                sim.scheduler_mut().pin(ThreadId(1), CoreId(0)).unwrap();
+            }
+            sim.load_workload(build_workload(3, WorkloadConfig::default())).unwrap();
+            let run_cycles = |sim: &Simulator, t| sim.metrics().per_thread[&ThreadId(t)].run_cycles;
+            let mut overlaps = 0;
+            while sim.cores.iter().any(|c| c.has_pending_work() || !c.pipeline.is_empty()) {
+                let before: Vec<u64> = (0..3).map(|t| run_cycles(&sim, t)).collect();
+                sim.step();
+                let ran: Vec<bool> = (0..3).map(|t| run_cycles(&sim, t) > before[t]).collect();
+                if ran[2] && (ran[0] || ran[1]) {
+                    overlaps += 1;
+                }
+            }
+            assert!(sim.metrics().per_thread.values().all(|t| t.instructions_committed == 1000));
+            (overlaps, sim.metrics().gang_blocked_quanta)
+        };
+        assert_eq!(run(false), (0, 0));
+        let (_, blocked) = run(true);
+        assert!(blocked > 0);
+    }
+
+    #[test]
+    fn simulator_join_waits_for_spawned_children() {
+        let run = |num_cores: usize| {
+            let mut sim = Simulator::new(
+                num_cores,
+                3,
+                CacheConfig::default(),
+                MemoryConfig::default(),
+                4,
+            );
+            let child_work = || {
+                build_workload(
+                    1,
+                    WorkloadConfig {
+                        instructions_per_thread: 2000,
+                        working_set_lines: 32,
+                        ..WorkloadConfig::default()
+                    },
+                )
+                .remove(0)
+            };
+            let mut graph = WorkloadGraph::new();
+            let parent = graph.add_thread(Vec::new());
+            let children = [graph.add_child(child_work()), graph.add_child(child_work())];
+            for &child in &children {
+                graph.spawn(parent, child);
+            }
+            for &child in &children {
+                graph.join(parent, child);
+            }
+            graph.extend(parent, vec![Instruction::new_compute(0); 10]);
+            sim.load_graph(graph).unwrap();
+            sim.run_to_completion();
+            sim.metrics().clone()
+        };
+        let parallel = run(3);
+        let done = &parallel.thread_completion_cycles;
+        assert!(done[&ThreadId(0)] > done[&ThreadId(1)]);
+        assert!(done[&ThreadId(0)] > done[&ThreadId(2)]);
+        assert!(parallel.join_stall_cycles > 0);
+        assert_eq!(parallel.per_thread[&ThreadId(0)].instructions_committed, 14);
+        // One core runs the children back to back.
+        let serial = run(1);
+        assert!(parallel.total_cycles * 3 < serial.total_cycles * 2);
+    }
+
+    #[test]
+    fn simulator_join_on_an_empty_child_completes() {
+        let mut sim = Simulator::new(1, 3, CacheConfig::default(), MemoryConfig::default(), 4);
+        let mut graph = WorkloadGraph::new();
+        let parent = graph.add_thread(vec![Instruction::new_compute(0)]);
+        let idle = graph.add_thread(Vec::new());
+        let empty = graph.add_child(Vec::new());
+        graph.spawn(parent, empty);
+        graph.join(parent, empty);
+        graph.join(parent, idle);
+        sim.load_graph(graph).unwrap();
+        sim.run_to_completion();
+        let done = &sim.metrics().thread_completion_cycles;
+        assert_eq!(done[&idle], 0);
+        assert!(done[&empty] < done[&parent]);
+
+        // Joining a thread that is not in the graph, or a child nothing spawns, fails up front.
+        let mut graph = WorkloadGraph::new();
+        let parent = graph.add_thread(Vec::new());
+        let orphan = graph.add_child(vec![Instruction::new_compute(0)]);
+        graph.join(parent, orphan);
+        let err = sim.load_graph(graph.clone()).unwrap_err();
+        assert_eq!(err, WorkloadError::UnknownJoin { thread: parent, child: orphan });
+        graph.spawn(parent, orphan);
+        graph.join(parent, ThreadId(7));
+        let err = sim.load_graph(graph).unwrap_err();
+        assert_eq!(err.to_string(), "thread 0 joins thread 7, which never runs");
+
+        // As with `load_workload`, the graph must have the configured number of threads.
+        let mut graph = WorkloadGraph::new();
+        graph.add_thread(vec![Instruction::new_compute(0)]);
+        let err = sim.load_graph(graph).unwrap_err();
+        assert_eq!(err, WorkloadError::ThreadCount { expected: 3, got: 1 });
+    }
+
+    #[test]
+    fn simulator_reports_hang_instead_of_spinning() {
+        let mut sim = Simulator::new(1, 2, CacheConfig::default(), MemoryConfig::default(), 4);
+        sim.set_hang_window(1000);
+        // The parent joins the child before spawning it, so the join can never complete.
+        let mut graph = WorkloadGraph::new();
+        let parent = graph.add_thread(vec![Instruction::new_compute(0); 4]);
+        let orphan = graph.add_child(vec![Instruction::new_compute(0)]);
+        graph.join(parent, orphan);
+        graph.spawn(parent, orphan);
+        sim.load_graph(graph).unwrap();
+        let err = sim.run_to_completion_with_limit(1_000_000).unwrap_err();
+        match &err {
+            SimTimeout::NoProgress {
+                cycle,
+                core_id,
+                oldest: Some(instr),
+                ..
+            } => {
+                assert!(*cycle <= 2000);
+                assert_eq!(*core_id, CoreId(0));
+                assert_eq!(instr.kind, InstructionKind::Join { child: orphan });
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        assert!(err.to_string().contains("core 0 is stuck on Join"));
+
+        // The cap counts from the call.
+        sim.set_hang_window(0);
+        let before = sim.current_cycle();
+        let err = sim.run_to_completion_with_limit(5000).unwrap_err();
+        assert!(matches!(err, SimTimeout::CycleLimit { max_cycles: 5000 }));
+        assert_eq!(sim.current_cycle(), before + 5000);
+    }
+
+    #[test]
+    fn simulator_hang_window_outlasts_the_memory_latency() {
+        let memory_config = MemoryConfig {
+            read_latency_cycles: 150_000,
+            ..MemoryConfig::default()
+        };
+        let mut sim = Simulator::new(1, 1, CacheConfig::default(), memory_config, 4);
+        assert_eq!(sim.hang_window(), 15_000_000);
+        let load = Instruction::new_memory(InstructionKind::Load, 0, 0);
+        sim.load_workload(vec![vec![load, Instruction::new_compute(0)]]).unwrap();
+        sim.run_to_completion_with_limit(DEFAULT_MAX_CYCLES).unwrap();
+        assert!(sim.current_cycle() > 150_000);
+
+        let default = Simulator::new(1, 1, CacheConfig::default(), MemoryConfig::default(), 4);
+        assert_eq!(default.hang_window(), DEFAULT_HANG_WINDOW_CYCLES);
+    }
+
+    #[test]
+    fn simulator_high_priority_thread_finishes_first() {
+        let mut sim = Simulator::new(1, 3, CacheConfig::default(), MemoryConfig::default(), 4);
+        sim.set_time_slice(TimeSliceConfig {
+            quantum_cycles: 200,
+            ..TimeSliceConfig::default()
+        });
+        // Thread 1 is latency critical but loaded after background thread 0.
+        sim.scheduler_mut().set_priority(ThreadId(1), 10);
+        sim.load_workload(build_workload(3, WorkloadConfig::default())).unwrap();
+        sim.run_to_completion();
+        let m = sim.metrics();
+        let done = |t| m.thread_completion_cycles[&ThreadId(t)];
+        assert!(done(1) < done(0) && done(1) < done(2));
+        assert!(m.per_thread.values().all(|t| t.instructions_committed == 1000));
+    }
+
+    #[test]
+    fn simulator_work_stealing_balances_skewed_threads() {
+        let run = |stealing: bool| {
+            let sized = |instructions_per_thread| WorkloadConfig {
+                instructions_per_thread,
+                working_set_lines: 32,
+                ..WorkloadConfig::default()
+            };
+            // Round-robin placement puts the four long threads on core 0.
+            let mut workload = Vec::new();
+            for _ in 0..4 {
+                workload.extend(build_workload(1, sized(2_500)));
+                workload.extend(build_workload(1, sized(100)));
+            }
+            run_metrics(2, MemoryConfig::default(), workload, |sim| {
+                if stealing {
+                    sim.set_work_stealing(WorkStealingConfig::default());
+                }
+            })
+        };
+        let alone = run(false);
+        let stolen = run(true);
+        assert!(stolen.steals > 0);
+        let ratio = stolen.total_cycles as f64 / alone.total_cycles as f64;
+        assert!((0.4..0.65).contains(&ratio), "ratio {ratio}");
+        let idle = |m: &Metrics| m.per_core[&CoreId(1)].idle_cycles;
+        assert!(idle(&stolen) < idle(&alone) / 10);
+    }
+
+    #[test]
+    fn simulator_work_stealing_never_splits_a_thread() {
+        let run = |stealing: bool| {
+            let sized = |instructions_per_thread| WorkloadConfig {
+                instructions_per_thread,
+                working_set_lines: 32,
+                ..WorkloadConfig::default()
+            };
+            let mut workload = build_workload(1, sized(2_000));
+            workload.extend(build_workload(1, sized(100)));
+            run_metrics(2, MemoryConfig::default(), workload, |sim| {
+                if stealing {
+                    sim.set_work_stealing(WorkStealingConfig::default());
+                }
+            })
+        };
+        // The long thread is running the whole time, so there is nothing whole to take.
+        let stolen = run(true);
+        assert_eq!(stolen.steals, 0);
+        assert_eq!(stolen.total_cycles, run(false).total_cycles);
+    }
+
+    #[test]
+    fn simulator_global_queue_keeps_cores_busy() {
+        let run = |global_queue: bool| {
+            let sized = |instructions_per_thread| WorkloadConfig {
+                instructions_per_thread,
+                working_set_lines: 32,
+                ..WorkloadConfig::default()
+            };
+            let mut workload = build_workload(1, sized(10_000));
+            workload.extend(build_workload(1, sized(100)));
+            run_metrics(2, MemoryConfig::default(), workload, |sim| {
+                if global_queue {
+                    sim.set_global_queue(GlobalQueueConfig::default());
+                }
+            })
+        };
+        let static_placement = run(false);
+        let dynamic = run(true);
+        let ratio = dynamic.total_cycles as f64 / static_placement.total_cycles as f64;
+        assert!((0.4..0.65).contains(&ratio), "ratio {ratio}");
+        for core in 0..2 {
+            let idle = dynamic.per_core[&CoreId(core)].idle_cycles;
+            assert!(idle * 100 < dynamic.total_cycles, "core {core} idle {idle}");
+        }
+        assert_eq!(dynamic.per_thread[&ThreadId(0)].instructions_committed, 10_000);
+    }
+
+    #[test]
+    fn simulator_empty_core_shows_no_utilization() {
+        let mut sim = Simulator::new(2, 1, CacheConfig::default(), MemoryConfig::default(), 4);
+        sim.load_workload(build_workload(
             1,
             WorkloadConfig {
-                instructions_per_thread: 100,
-                memory_fraction: 0.5,
-                access_pattern: AccessPattern::Sequential,
-                line_size: 64,
-                cache_num_sets: 64,
-                working_set_lines: 0,
+                memory_fraction: 1.0,
+                ..WorkloadConfig::default()
+            },
+        )).unwrap();
+        sim.run_to_completion();
+        let m = sim.metrics();
+        assert!(m.core_utilization(CoreId(0)) > 0.99);
+        assert_eq!(m.core_utilization(CoreId(1)), 0.0);
+        assert_eq!(m.per_core[&CoreId(1)].idle_cycles, m.total_cycles);
+        // Every access misses: the loaded core spends its time waiting on memory.
+        assert!(m.per_core[&CoreId(0)].stall_dominated_cycles * 10 > m.total_cycles * 9);
+    }
+
+    #[test]
+    fn simulator_lpt_placement_beats_modulo() {
+        let sizes = [9000, 8000, 100, 50];
+        let run = |lpt: bool| {
+            let mut sim = Simulator::new(2, 4, CacheConfig::default(), MemoryConfig::default(), 4);
+            if lpt {
+                sim.scheduler_mut().assign_lpt(&sizes);
+            }
+            let workload = sizes
+                .iter()
+                .flat_map(|&instructions_per_thread| {
+                    build_workload(
+                        1,
+                        WorkloadConfig {
+                            instructions_per_thread,
+                            working_set_lines: 32,
+                            ..WorkloadConfig::default()
+                        },
+                    )
+                })
+                .collect();
+            sim.load_workload(workload).unwrap();
+            sim.run_to_completion();
+            sim.metrics().total_cycles
+        };
+        assert!(run(true) < run(false));
+    }
+
+    #[test]
+    fn simulator_samples_thread_progress() {
+        let mut sim = Simulator::new(1, 2, CacheConfig::default(), MemoryConfig::default(), 4);
+        sim.set_progress_interval(100);
+        sim.load_workload(build_workload(2, WorkloadConfig::default())).unwrap();
+        sim.run_to_completion();
+        let progress = &sim.metrics().per_thread[&ThreadId(1)].progress;
+        assert_eq!(progress.len() as u64, sim.metrics().total_cycles / 100);
+        assert!(progress.windows(2).all(|w| w[0].1 <= w[1].1));
+        // Thread 1 waits for thread 0, then makes progress.
+        assert_eq!(progress[0].1, 0);
+        assert!(progress.last().unwrap().1 > 500);
+    }
+
+    #[test]
+    fn simulator_fairness_drops_under_bus_contention() {
+        let fixed = WorkloadConfig {
+            instructions_per_thread: 400,
+            memory_fraction: 0.5,
+            ..WorkloadConfig::default()
+        };
+        let identical = run_fairness_study(
+            2,
+            CacheConfig::default(),
+            MemoryConfig::default(),
+            4,
+            build_workload(2, fixed.clone()),
+        );
+        assert!((identical.fairness_index - 1.0).abs() < 1e-9);
+        assert_eq!(identical.slowdowns.len(), 2);
+
+        let mut workload = build_workload(1, fixed.clone());
+        workload.extend(build_workload(
+            1,
+            WorkloadConfig {
+                instructions_per_thread: 4000,
+                memory_fraction: 1.0,
+                ..fixed
             },
+        ));
+        let memory_config = MemoryConfig {
+            bytes_per_cycle: 8,
+            ..MemoryConfig::with_access_latency(20)
+        };
+        let contended = run_fairness_study(2, CacheConfig::default(), memory_config, 4, workload);
+        assert!(contended.slowdowns[&ThreadId(0)] > 1.0);
+        assert!(contended.fairness_index < 1.0, "fairness {}", contended.fairness_index);
+    }
+
+    /// Hit rate of two time-sliced threads on two cores that both read the same 32 lines,
+    /// placed by `place` before they load.
+    fn sharers_hit_rate(place: impl FnOnce(&mut Simulator, &[Vec<Instruction>])) -> f64 {
+        let mut sim = Simulator::new(2, 2, CacheConfig::default(), MemoryConfig::default(), 4);
+        sim.set_time_slice(TimeSliceConfig::default());
+        let reader = || {
+            (0..320u64)
+                .map(|i| Instruction::new_memory(InstructionKind::Load, (i % 32) * 64, i))
+                .collect::<Vec<_>>()
+        };
+        let workload = vec![reader(), reader()];
+        place(&mut sim, &workload);
+        sim.load_workload(workload).unwrap();
+        sim.run_to_completion();
+        sim.metrics().hit_rate()
+    }
+
+    #[test]
+    fn simulator_co_located_sharers_hit_more() {
+        let pinned = |core| {
+            sharers_hit_rate(|sim, _| sim.scheduler_mut().pin(ThreadId(1), core).unwrap())
+        };
+        assert!(pinned(CoreId(0)) > pinned(CoreId(1)));
+    }
+
+    #[test]
+    fn simulator_affinity_placement_co_locates_sharers() {
+        let by_affinity = sharers_hit_rate(|sim, workload| {
+            sim.scheduler_mut().assign_by_affinity(&shared_footprint(workload, 64));
+        });
+        assert!(by_affinity > sharers_hit_rate(|_, _| {}));
+    }
+
+    #[test]
+    fn simulator_migrated_thread_resumes_cold() {
+        let mut sim = Simulator::new(2, 1, CacheConfig::default(), MemoryConfig::default(), 4);
+        let loads = (0..400u64)
+            .map(|i| Instruction::new_memory(InstructionKind::Load, (i % 16) * 64, i))
+            .collect();
+        sim.load_workload(vec![loads]).unwrap();
+        for _ in 0..500 {
+            sim.step();
+        }
+        let before = sim.metrics().clone();
+        assert!(before.per_core[&CoreId(0)].cache_hits > 0);
+        sim.migrate_thread(ThreadId(0), CoreId(1)).unwrap();
+        assert_eq!(
+            sim.migrate_thread(ThreadId(0), CoreId(5)),
+            Err(SchedulerError::NoSuchCore {
+                thread_id: ThreadId(0),
+                core_id: CoreId(5)
+            })
         );
-        sim.load_workload(workload);
         sim.run_to_completion();
-        assert!(sim.metrics().total_memory_accesses > 0);
-        assert!(sim.metrics().cache_hits + sim.metrics().cache_misses == sim.metrics().total_memory_accesses);
+        let after = sim.metrics();
+        assert_eq!(after.migrations, 1);
+        assert_eq!(after.per_thread[&ThreadId(0)].instructions_committed, 400);
+        // Core 1 has to refetch the whole working set.
+        assert_eq!(after.per_core[&CoreId(1)].cache_misses, 16);
+        assert!(after.per_core[&CoreId(1)].busy_cycles > 0);
+    }
+
+    #[test]
+    fn simulator_smt_helps_hits_not_misses() {
+        let run = |smt: bool, working_set_lines: usize| {
+            let workload = build_workload(
+                2,
+                WorkloadConfig {
+                    instructions_per_thread: 2000,
+                    working_set_lines,
+                    ..WorkloadConfig::default()
+                },
+            );
+            run_metrics(1, MemoryConfig::default(), workload, |sim| {
+                if smt {
+                    sim.set_smt_contexts(2);
+                } else {
+                    sim.set_time_slice(TimeSliceConfig {
+                        quantum_cycles: 100,
+                        context_switch_cycles: 20,
+                        flush_cache_on_switch: false,
+                        ..TimeSliceConfig::default()
+                    });
+                }
+            })
+        };
+        // Cache-friendly threads: SMT beats time slicing.
+        let smt_hits = run(true, 16);
+        let sliced_hits = run(false, 16);
+        assert!(smt_hits.total_cycles < sliced_hits.total_cycles);
+        let ipc = |m: &Metrics, t| m.per_thread[&ThreadId(t)].ipc();
+        assert!(ipc(&smt_hits, 0) > 0.0 && ipc(&smt_hits, 1) > 0.0);
+        // Miss-heavy threads: each gets a much smaller share of the core.
+        let smt_misses = run(true, 0);
+        assert!(ipc(&smt_misses, 0) < ipc(&smt_hits, 0) / 2.0);
+    }
+
+    #[test]
+    fn simulator_srwf_lowers_average_turnaround() {
+        let run = |policy: ThreadPolicy| {
+            let sized = |instructions_per_thread| WorkloadConfig {
+                instructions_per_thread,
+                working_set_lines: 32,
+                ..WorkloadConfig::default()
+            };
+            let mut workload = build_workload(1, sized(5000));
+            workload.extend(build_workload(1, sized(1000)));
+            workload.extend(build_workload(1, sized(100)));
+            run_metrics(1, MemoryConfig::default(), workload, |sim| {
+                sim.set_time_slice(TimeSliceConfig {
+                    quantum_cycles: 200,
+                    ..TimeSliceConfig::default()
+                });
+                sim.scheduler_mut().set_policy(policy);
+            })
+        };
+        let round_robin = run(ThreadPolicy::RoundRobin);
+        let srwf = run(ThreadPolicy::ShortestRemainingWorkFirst);
+        assert!(srwf.average_turnaround() < round_robin.average_turnaround());
+        let done = |m: &Metrics, t| m.thread_completion_cycles[&ThreadId(t)];
+        assert!(done(&srwf, 2) < done(&srwf, 1) && done(&srwf, 1) < done(&srwf, 0));
     }
 }
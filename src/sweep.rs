@@ -0,0 +1,269 @@
+//! Parameter sweeps: run one scenario over every combination of a few varied parameters.
+
+use crate::builder::{ConfigError, SimulatorBuilder};
+use crate::cache::CacheConfig;
+use crate::memory::MemoryConfig;
+use crate::metrics::Metrics;
+use crate::workload::{AccessPattern, WorkloadConfig};
+use std::fmt;
+
+/// The machine and workload every sweep point starts from.
+#[derive(Clone, Debug)]
+pub struct ScenarioConfig {
+    pub cores: usize,
+    pub threads: usize,
+    pub cache: CacheConfig,
+    pub memory: MemoryConfig,
+    pub pipeline_width: usize,
+    /// Generated for every thread; its cache geometry is matched to `cache`.
+    pub workload: WorkloadConfig,
+}
+
+impl Default for ScenarioConfig {
+    fn default() -> Self {
+        Self {
+            cores: 1,
+            threads: 1,
+            cache: CacheConfig::default(),
+            memory: MemoryConfig::default(),
+            pipeline_width: 4,
+            workload: WorkloadConfig::default(),
+        }
+    }
+}
+
+/// One parameter setting of a sweep point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParamValue {
+    /// L1 size in bytes (associativity and line size stay).
+    CacheSize(usize),
+    /// L1 ways (the size stays, so the number of sets changes).
+    Associativity(usize),
+    /// Memory read and write latency in cycles.
+    MemoryLatency(u32),
+    Threads(usize),
+    AccessPattern(AccessPattern),
+}
+
+impl ParamValue {
+    /// Column name of the parameter.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ParamValue::CacheSize(_) => "cache_size",
+            ParamValue::Associativity(_) => "associativity",
+            ParamValue::MemoryLatency(_) => "memory_latency",
+            ParamValue::Threads(_) => "threads",
+            ParamValue::AccessPattern(_) => "access_pattern",
+        }
+    }
+
+    fn apply(&self, scenario: &mut ScenarioConfig) {
+        match *self {
+            ParamValue::CacheSize(bytes) => scenario.cache.size_bytes = bytes,
+            ParamValue::Associativity(ways) => scenario.cache.associativity = ways,
+            ParamValue::MemoryLatency(cycles) => {
+                scenario.memory.read_latency_cycles = cycles;
+                scenario.memory.write_latency_cycles = cycles;
+            }
+            ParamValue::Threads(threads) => scenario.threads = threads,
+            ParamValue::AccessPattern(pattern) => scenario.workload.access_pattern = pattern,
+        }
+    }
+}
+
+impl fmt::Display for ParamValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamValue::CacheSize(v) | ParamValue::Associativity(v) | ParamValue::Threads(v) => {
+                write!(f, "{v}")
+            }
+            ParamValue::MemoryLatency(v) => write!(f, "{v}"),
+            // No commas, so the value stays one CSV field.
+            ParamValue::AccessPattern(AccessPattern::Random {
+                footprint_lines,
+                seed,
+            }) => write!(f, "Random({footprint_lines} lines; seed {seed})"),
+            ParamValue::AccessPattern(AccessPattern::PointerChase {
+                footprint_lines,
+                seed,
+            }) => write!(f, "PointerChase({footprint_lines} lines; seed {seed})"),
+            ParamValue::AccessPattern(AccessPattern::Strided {
+                stride_bytes,
+                footprint_bytes,
+            }) => write!(f, "Strided({stride_bytes} B; footprint {footprint_bytes} B)"),
+            ParamValue::AccessPattern(AccessPattern::Zipf {
+                num_lines,
+                exponent,
+                seed,
+            }) => write!(f, "Zipf({num_lines} lines; exponent {exponent}; seed {seed})"),
+            ParamValue::AccessPattern(p) => write!(f, "{p:?}"),
+        }
+    }
+}
+
+/// The parameters to vary, each over a list of values; the sweep runs every combination.
+#[derive(Clone, Debug, Default)]
+pub struct SweepGrid {
+    axes: Vec<Vec<ParamValue>>,
+}
+
+impl SweepGrid {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cache_sizes(self, bytes: Vec<usize>) -> Self {
+        self.axis(bytes.into_iter().map(ParamValue::CacheSize))
+    }
+
+    pub fn associativities(self, ways: Vec<usize>) -> Self {
+        self.axis(ways.into_iter().map(ParamValue::Associativity))
+    }
+
+    pub fn memory_latencies(self, cycles: Vec<u32>) -> Self {
+        self.axis(cycles.into_iter().map(ParamValue::MemoryLatency))
+    }
+
+    pub fn thread_counts(self, threads: Vec<usize>) -> Self {
+        self.axis(threads.into_iter().map(ParamValue::Threads))
+    }
+
+    pub fn access_patterns(self, patterns: Vec<AccessPattern>) -> Self {
+        self.axis(patterns.into_iter().map(ParamValue::AccessPattern))
+    }
+
+    fn axis(mut self, values: impl Iterator<Item = ParamValue>) -> Self {
+        self.axes.push(values.collect());
+        self
+    }
+
+    /// Every combination, one value per axis in the order the axes were added; the last axis
+    /// varies fastest.
+    pub fn points(&self) -> Vec<Vec<ParamValue>> {
+        self.axes.iter().fold(vec![Vec::new()], |points, axis| {
+            points
+                .iter()
+                .flat_map(|point| {
+                    axis.iter().map(move |&value| {
+                        let mut point = point.clone();
+                        point.push(value);
+                        point
+                    })
+                })
+                .collect()
+        })
+    }
+}
+
+/// Metrics of one sweep point.
+#[derive(Clone, Debug)]
+pub struct SweepResult {
+    /// The parameter values of the point, in axis order.
+    pub point: Vec<ParamValue>,
+    pub metrics: Metrics,
+}
+
+/// Runs `base` once per point of `grid` (in parallel with the `parallel` feature), each to
+/// completion. Results are in [`SweepGrid::points`] order; the first point whose
+/// configuration is invalid fails the sweep.
+pub fn run(grid: &SweepGrid, base: &ScenarioConfig) -> Result<Vec<SweepResult>, ConfigError> {
+    let points = grid.points();
+    #[cfg(feature = "parallel")]
+    let results = {
+        use rayon::prelude::*;
+        points.into_par_iter().map(|point| run_point(base, point)).collect()
+    };
+    #[cfg(not(feature = "parallel"))]
+    let results = points.into_iter().map(|point| run_point(base, point)).collect();
+    results
+}
+
+fn run_point(base: &ScenarioConfig, point: Vec<ParamValue>) -> Result<SweepResult, ConfigError> {
+    let mut scenario = base.clone();
+    for value in &point {
+        value.apply(&mut scenario);
+    }
+    let mut sim = SimulatorBuilder::default()
+        .cores(scenario.cores)
+        .threads(scenario.threads)
+        .cache(scenario.cache)
+        .memory(scenario.memory)
+        .pipeline_width(scenario.pipeline_width)
+        .workload(scenario.workload)
+        .build()?;
+    sim.run_to_completion();
+    Ok(SweepResult {
+        point,
+        metrics: sim.metrics().clone(),
+    })
+}
+
+/// The results as CSV: a column per parameter, then the headline metrics.
+pub fn to_csv(results: &[SweepResult]) -> String {
+    let mut csv = String::new();
+    if let Some(first) = results.first() {
+        for value in &first.point {
+            csv.push_str(value.name());
+            csv.push(',');
+        }
+    }
+    csv.push_str("total_cycles,instructions_committed,ipc,hit_rate,memory_stall_cycles\n");
+    for result in results {
+        for value in &result.point {
+            csv.push_str(&format!("{value},"));
+        }
+        let m = &result.metrics;
+        csv.push_str(&format!(
+            "{},{},{:.4},{:.4},{}\n",
+            m.total_cycles,
+            m.instructions_committed,
+            m.ipc(),
+            m.hit_rate(),
+            m.memory_stall_cycles
+        ));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweep_miss_rate_falls_as_the_cache_grows() {
+        let base = ScenarioConfig {
+            workload: WorkloadConfig {
+                instructions_per_thread: 4000,
+                working_set_lines: 128,
+                ..WorkloadConfig::default()
+            },
+            ..ScenarioConfig::default()
+        };
+        let grid = SweepGrid::new().cache_sizes(vec![2 * 1024, 4 * 1024, 8 * 1024]);
+        let results = run(&grid, &base).unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[1].point, [ParamValue::CacheSize(4 * 1024)]);
+        let miss_rates: Vec<f64> = results.iter().map(|r| r.metrics.miss_rate()).collect();
+        assert!(miss_rates.windows(2).all(|w| w[1] <= w[0]), "{miss_rates:?}");
+        assert!(miss_rates[2] < miss_rates[0]);
+
+        let csv = to_csv(&results);
+        assert_eq!(csv.lines().count(), 4);
+        assert!(csv.starts_with("cache_size,total_cycles,"));
+        assert!(csv.lines().nth(1).unwrap().starts_with("2048,"));
+    }
+
+    #[test]
+    fn sweep_grid_crosses_every_axis() {
+        let grid = SweepGrid::new()
+            .thread_counts(vec![1, 2])
+            .access_patterns(vec![AccessPattern::Sequential, AccessPattern::ConflictHeavy]);
+        let points = grid.points();
+        assert_eq!(points.len(), 4);
+        assert_eq!(
+            points[1],
+            [ParamValue::Threads(1), ParamValue::AccessPattern(AccessPattern::ConflictHeavy)]
+        );
+        assert_eq!(SweepGrid::new().points(), vec![Vec::new()]);
+    }
+}
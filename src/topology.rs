@@ -0,0 +1,96 @@
+//! System topology: which memory node each core is near.
+
+use crate::core::CoreId;
+
+/// How cores reach the shared L2 and memory.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Interconnect {
+    /// Free: every core is next to the L2.
+    #[default]
+    Direct,
+    /// Bidirectional ring with one stop per core and the L2 at core 0's stop.
+    Ring { hop_cycles: u32 },
+    /// Crossbar: one hop from every core.
+    Crossbar { hop_cycles: u32 },
+}
+
+/// Placement of cores relative to memory nodes.
+#[derive(Clone, Debug, Default)]
+pub struct Topology {
+    /// Home memory node of each core (indexed by core id). Cores not listed live on node 0.
+    pub core_nodes: Vec<usize>,
+    pub interconnect: Interconnect,
+}
+
+impl Topology {
+    pub fn new(core_nodes: Vec<usize>) -> Self {
+        Self {
+            core_nodes,
+            ..Self::default()
+        }
+    }
+
+    /// Single-node system: every core is local to node 0.
+    pub fn uniform() -> Self {
+        Self::default()
+    }
+
+    /// Returns the memory node the given core is local to.
+    pub fn home_node(&self, core_id: CoreId) -> usize {
+        self.core_nodes.get(core_id.0).copied().unwrap_or(0)
+    }
+
+    /// Interconnect hops between `core_id` and the L2, with `num_cores` cores on it.
+    pub fn hops(&self, core_id: CoreId, num_cores: usize) -> u32 {
+        match self.interconnect {
+            Interconnect::Direct => 0,
+            Interconnect::Ring { .. } => {
+                let stop = core_id.0 % num_cores.max(1);
+                stop.min(num_cores - stop) as u32
+            }
+            Interconnect::Crossbar { .. } => 1,
+        }
+    }
+
+    /// Extra cycles each of `core_id`'s L2/memory requests pays crossing the interconnect.
+    pub fn interconnect_cycles(&self, core_id: CoreId, num_cores: usize) -> u32 {
+        let hop_cycles = match self.interconnect {
+            Interconnect::Direct => 0,
+            Interconnect::Ring { hop_cycles } | Interconnect::Crossbar { hop_cycles } => {
+                hop_cycles
+            }
+        };
+        self.hops(core_id, num_cores) * hop_cycles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn topology_home_nodes() {
+        let t = Topology::new(vec![0, 1]);
+        assert_eq!(t.home_node(CoreId(0)), 0);
+        assert_eq!(t.home_node(CoreId(1)), 1);
+        assert_eq!(t.home_node(CoreId(5)), 0);
+        assert_eq!(Topology::uniform().home_node(CoreId(3)), 0);
+    }
+
+    #[test]
+    fn topology_ring_hops_to_the_l2() {
+        let ring = Topology {
+            interconnect: Interconnect::Ring { hop_cycles: 3 },
+            ..Topology::uniform()
+        };
+        let hops: Vec<u32> = (0..8).map(|c| ring.hops(CoreId(c), 8)).collect();
+        assert_eq!(hops, [0, 1, 2, 3, 4, 3, 2, 1]);
+        assert_eq!(ring.interconnect_cycles(CoreId(4), 8), 12);
+        let crossbar = Topology {
+            interconnect: Interconnect::Crossbar { hop_cycles: 3 },
+            ..Topology::uniform()
+        };
+        assert_eq!(crossbar.interconnect_cycles(CoreId(4), 8), 3);
+        assert_eq!(Topology::uniform().interconnect_cycles(CoreId(4), 8), 0);
+    }
+}
@@ -0,0 +1,333 @@
+//! Per-instruction lifetime trace: when each committed instruction entered each stage.
+
+use crate::core::{CoreId, Cycle, Instruction, InstructionKind, PipelineStage, ThreadId};
+use crate::observer::SimObserver;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::{self, Write as _};
+use std::io::{self, Write};
+use std::ops::Range;
+
+/// Why an instruction stalled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum StallReason {
+    /// Waited on memory after missing its L1.
+    CacheMiss,
+    /// Its miss waited in the memory controller's queue for a bank.
+    MemoryQueue,
+    /// A shared resource was taken: cache port, miss slot, write buffer, LSQ entry, execution
+    /// or matrix unit, commit width.
+    Structural,
+    /// Waited for a source register or an older store's address (data dependency).
+    Dependency,
+    /// Finished, but an older instruction had not committed yet.
+    CommitBlocked,
+    /// Held by a fence or by memory ordering.
+    Fence,
+    /// Waited at a lock, barrier, join or DMA wait.
+    Synchronization,
+}
+
+impl fmt::Display for StallReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            StallReason::CacheMiss => "cache_miss",
+            StallReason::MemoryQueue => "memory_queue",
+            StallReason::Structural => "structural",
+            StallReason::Dependency => "dependency",
+            StallReason::CommitBlocked => "commit_blocked",
+            StallReason::Fence => "fence",
+            StallReason::Synchronization => "synchronization",
+        };
+        f.pad(name)
+    }
+}
+
+/// Lifetime of one committed instruction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InstructionRecord {
+    /// Instruction id (see `Instruction::id`).
+    pub id: u64,
+    /// Position in its thread's program (see `Instruction::seq`).
+    pub seq: u64,
+    pub core_id: CoreId,
+    pub thread: ThreadId,
+    pub kind: InstructionKind,
+    pub address: u64,
+    /// Cycle it was fetched (entered Fetch).
+    pub issue_cycle: Cycle,
+    /// Cycle it entered Decode (only with a decode latency).
+    pub decode_cycle: Option<Cycle>,
+    pub execute_cycle: Cycle,
+    /// Cycle it entered Memory (memory operations only).
+    pub memory_cycle: Option<Cycle>,
+    /// Cycle it entered Commit.
+    pub commit_stage_cycle: Cycle,
+    /// Cycle it left the pipeline.
+    pub commit_cycle: Cycle,
+    pub stall_cycles: u64,
+    pub stall_reason: Option<StallReason>,
+    /// Cycles it sat in each stage with its work there done, held back (a stall), in pipeline
+    /// order; stages it never waited in are left out.
+    pub waits: Vec<(PipelineStage, u64)>,
+}
+
+impl InstructionRecord {
+    fn fetched(cycle: Cycle, core_id: CoreId, instr: &Instruction) -> Self {
+        Self {
+            id: instr.id,
+            seq: instr.seq,
+            core_id,
+            thread: instr.thread,
+            kind: instr.kind,
+            address: instr.address,
+            issue_cycle: cycle,
+            decode_cycle: None,
+            execute_cycle: cycle,
+            memory_cycle: None,
+            commit_stage_cycle: cycle,
+            commit_cycle: cycle,
+            stall_cycles: 0,
+            stall_reason: None,
+            waits: Vec::new(),
+        }
+    }
+
+    /// Cycles spent in each stage it went through, in pipeline order. They add up to
+    /// `commit_cycle - issue_cycle`.
+    pub fn stage_cycles(&self) -> Vec<(PipelineStage, u64)> {
+        let mut stages = match self.decode_cycle {
+            Some(decode) => vec![
+                (PipelineStage::Fetch, decode - self.issue_cycle),
+                (PipelineStage::Decode, self.execute_cycle - decode),
+            ],
+            None => vec![(PipelineStage::Fetch, self.execute_cycle - self.issue_cycle)],
+        };
+        match self.memory_cycle {
+            Some(memory) => {
+                stages.push((PipelineStage::Execute, memory - self.execute_cycle));
+                stages.push((PipelineStage::Memory, self.commit_stage_cycle - memory));
+            }
+            None => stages.push((
+                PipelineStage::Execute,
+                self.commit_stage_cycle - self.execute_cycle,
+            )),
+        }
+        stages.push((PipelineStage::Commit, self.commit_cycle - self.commit_stage_cycle));
+        stages
+    }
+}
+
+/// Collects an [`InstructionRecord`] per committed instruction (see
+/// `Simulator::enable_instruction_trace`).
+#[derive(Clone, Debug, Default)]
+pub struct InstructionTrace {
+    /// Records of instructions still in flight, by id.
+    in_flight: HashMap<u64, InstructionRecord>,
+    records: Vec<InstructionRecord>,
+    /// Keep only the most recent this many records (None = all).
+    capacity: Option<usize>,
+    /// Once a ring is full: where the oldest record is, and so where the next one goes.
+    head: usize,
+    /// Cycle each in-flight instruction's current stall began.
+    stalled_since: HashMap<u64, Cycle>,
+    /// Cycle from which each in-flight instruction could leave its current stage.
+    ready_at: HashMap<u64, Cycle>,
+}
+
+impl InstructionTrace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A trace that keeps only the last `capacity` records.
+    pub fn ring(capacity: usize) -> Self {
+        Self {
+            capacity: Some(capacity),
+            ..Self::default()
+        }
+    }
+
+    /// Committed instructions in commit order (the most recent ones in ring mode). Borrowed
+    /// unless a full ring has wrapped.
+    pub fn records(&self) -> Cow<'_, [InstructionRecord]> {
+        if self.head == 0 {
+            return Cow::Borrowed(&self.records);
+        }
+        let (newer, older) = self.records.split_at(self.head);
+        Cow::Owned([older, newer].concat())
+    }
+
+    /// Drops every record, committed or in flight; the capacity stays.
+    pub fn clear(&mut self) {
+        self.in_flight.clear();
+        self.records.clear();
+        self.head = 0;
+        self.stalled_since.clear();
+        self.ready_at.clear();
+    }
+
+    /// `instr` starts a stage (or its miss returned) at `cycle`: it can move on once the
+    /// stage's remaining cycles have been counted down.
+    fn start_work(&mut self, cycle: Cycle, instr: &Instruction) {
+        let ready = cycle + u64::from(instr.stage_cycles_left) + 1;
+        self.ready_at.insert(instr.id, ready);
+    }
+
+    /// `instr` leaves `stage` at `cycle`: any cycles since it was ready were a wait.
+    fn leave_stage(&mut self, cycle: Cycle, instr: &Instruction, stage: PipelineStage) {
+        let ready = self.ready_at.remove(&instr.id).unwrap_or(cycle);
+        let waited = cycle.saturating_sub(ready);
+        if let (true, Some(record)) = (waited > 0, self.in_flight.get_mut(&instr.id)) {
+            record.waits.push((stage, waited));
+        }
+    }
+
+    fn push(&mut self, record: InstructionRecord) {
+        match self.capacity {
+            Some(0) => {}
+            Some(n) if self.records.len() == n => {
+                self.records[self.head] = record;
+                self.head = (self.head + 1) % n;
+            }
+            _ => self.records.push(record),
+        }
+    }
+}
+
+impl SimObserver for InstructionTrace {
+    fn on_fetch(&mut self, cycle: Cycle, core_id: CoreId, instr: &Instruction) {
+        let record = InstructionRecord::fetched(cycle, core_id, instr);
+        self.in_flight.insert(instr.id, record);
+        self.start_work(cycle, instr);
+    }
+
+    fn on_stage_change(
+        &mut self,
+        cycle: Cycle,
+        _core_id: CoreId,
+        instr: &Instruction,
+        old: PipelineStage,
+        new: PipelineStage,
+    ) {
+        self.leave_stage(cycle, instr, old);
+        self.start_work(cycle, instr);
+        let Some(record) = self.in_flight.get_mut(&instr.id) else {
+            return;
+        };
+        match new {
+            PipelineStage::Fetch => record.issue_cycle = cycle,
+            PipelineStage::Decode => record.decode_cycle = Some(cycle),
+            PipelineStage::Execute => record.execute_cycle = cycle,
+            PipelineStage::Memory => record.memory_cycle = Some(cycle),
+            PipelineStage::Commit => record.commit_stage_cycle = cycle,
+        }
+    }
+
+    fn on_stall_begin(&mut self, cycle: Cycle, _core_id: CoreId, instr: &Instruction) {
+        self.stalled_since.insert(instr.id, cycle);
+        if let Some(record) = self.in_flight.get_mut(&instr.id) {
+            record.stall_reason = Some(StallReason::CacheMiss);
+        }
+    }
+
+    fn on_stall_end(&mut self, cycle: Cycle, _core_id: CoreId, instr: &Instruction) {
+        let since = self.stalled_since.remove(&instr.id);
+        if let (Some(since), Some(record)) = (since, self.in_flight.get_mut(&instr.id)) {
+            record.stall_cycles += cycle - since;
+        }
+        self.start_work(cycle, instr);
+    }
+
+    fn on_commit(&mut self, cycle: Cycle, _core_id: CoreId, instr: &Instruction) {
+        self.leave_stage(cycle, instr, PipelineStage::Commit);
+        if let Some(mut record) = self.in_flight.remove(&instr.id) {
+            record.commit_cycle = cycle;
+            self.push(record);
+        }
+    }
+
+    fn on_squash(&mut self, _cycle: Cycle, _core_id: CoreId, instr: &Instruction) {
+        self.in_flight.remove(&instr.id);
+        self.stalled_since.remove(&instr.id);
+        self.ready_at.remove(&instr.id);
+    }
+}
+
+/// Draws `core`'s instructions over `cycles` as a classic pipeline diagram: a row per
+/// instruction (oldest first), a column per cycle. A stage's first cycle shows its letter
+/// (F, D, E, M, C); later cycles show `M` in Memory (the access, misses included) and `=` in
+/// any other stage while the instruction works there, and `-` once it is done but held back
+/// (a stall; see [`InstructionRecord::waits`]). The header shows each cycle's last digit; the
+/// label column fits the longest label.
+pub fn render_pipeline_diagram(
+    records: &[InstructionRecord],
+    core: CoreId,
+    cycles: Range<Cycle>,
+) -> String {
+    let mut rows: Vec<&InstructionRecord> = records
+        .iter()
+        .filter(|r| r.core_id == core)
+        .filter(|r| r.issue_cycle < cycles.end && r.commit_cycle > cycles.start)
+        .collect();
+    rows.sort_by_key(|r| r.id);
+    let digits: String = cycles.clone().map(|c| char::from(b'0' + (c % 10) as u8)).collect();
+    let label = |r: &InstructionRecord| format!("{:>4} {}", r.id, r.kind.name());
+    let width = rows.iter().map(|r| label(r).len() + 1).fold(16, usize::max);
+    let mut out = String::new();
+    let _ = writeln!(out, "{:<width$}{digits}", "cycle");
+    for r in rows {
+        let mut cells = vec![' '; digits.len()];
+        let mut start = r.issue_cycle;
+        for (stage, n) in r.stage_cycles() {
+            let letter = stage.to_string().chars().next().unwrap_or('?');
+            let waited = r.waits.iter().filter(|w| w.0 == stage).map(|w| w.1).sum::<u64>();
+            let ready = start + n - waited.min(n);
+            for c in (start..start + n).filter(|c| cycles.contains(c)) {
+                cells[(c - cycles.start) as usize] = match stage {
+                    _ if c == start => letter,
+                    _ if c >= ready => '-',
+                    PipelineStage::Memory => 'M',
+                    _ => '=',
+                };
+            }
+            start += n;
+        }
+        let cells: String = cells.into_iter().collect();
+        let _ = writeln!(out, "{}", format!("{:<width$}{cells}", label(r)).trim_end());
+    }
+    out
+}
+
+/// Writes `records` as CSV with a header row.
+pub fn write_csv<W: Write>(records: &[InstructionRecord], mut w: W) -> io::Result<()> {
+    writeln!(
+        w,
+        "id,core,thread,kind,address,issue,decode,execute,memory,commit_stage,commit,\
+         stall_cycles,stall_reason,seq"
+    )?;
+    for r in records {
+        let decode = r.decode_cycle.map(|c| c.to_string()).unwrap_or_default();
+        let memory = r.memory_cycle.map(|c| c.to_string()).unwrap_or_default();
+        let reason = r.stall_reason.map(|s| s.to_string()).unwrap_or_default();
+        writeln!(
+            w,
+            "{},{},{},\"{:?}\",{:#x},{},{},{},{},{},{},{},{},{}",
+            r.id,
+            r.core_id.0,
+            r.thread.0,
+            r.kind,
+            r.address,
+            r.issue_cycle,
+            decode,
+            r.execute_cycle,
+            memory,
+            r.commit_stage_cycle,
+            r.commit_cycle,
+            r.stall_cycles,
+            reason,
+            r.seq
+        )?;
+    }
+    Ok(())
+}
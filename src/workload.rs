@@ -1,17 +1,163 @@
-//! Configurable workload generator: sequential and conflict-heavy access patterns.
+//! Configurable workload generator: sequential, conflict-heavy, strided, random, Zipf and
+//! pointer-chasing access patterns.
 
-use crate::core::{Instruction, InstructionKind};
-use std::iter;
+use crate::core::{Instruction, InstructionKind, RegId, ThreadId};
+use crate::rng::{Rng, Zipf};
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 
 /// Access pattern for memory instructions.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum AccessPattern {
     /// Sequential: addresses 0, line_size, 2*line_size, ... (good locality).
     Sequential,
     /// Conflict-heavy: addresses chosen to map to the same cache set(s), causing evictions.
     ConflictHeavy,
+    /// Uniformly random lines among the first `footprint_lines`, drawn from a generator
+    /// seeded with `seed` (the same seed gives the same addresses).
+    Random { footprint_lines: usize, seed: u64 },
+    /// Addresses 0, s, 2s, ... for stride s = `stride_bytes`, wrapping at `footprint_bytes`
+    /// (0 = no wrap). A stride of a multiple of sets × line size aliases like ConflictHeavy;
+    /// one under a line size hits the same line repeatedly.
+    Strided {
+        stride_bytes: usize,
+        footprint_bytes: usize,
+    },
+    /// Lines among the first `num_lines`, line k drawn with probability proportional to
+    /// `1 / (k + 1)^exponent` (seeded with `seed`): a hot head and a long cold tail.
+    Zipf {
+        num_lines: usize,
+        exponent: f64,
+        seed: u64,
+    },
+    /// Loads walking a random cyclic permutation of the first `footprint_lines` lines (seeded
+    /// with `seed`), each reading the register the previous one wrote, so no two overlap:
+    /// every access pays the full latency.
+    PointerChase { footprint_lines: usize, seed: u64 },
+}
+
+/// Why `Simulator::load_workload` or `append_workload` rejected a workload, or
+/// `Simulator::save_checkpoint` could not capture one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WorkloadError {
+    /// The workload has a different number of threads than the simulator.
+    ThreadCount { expected: usize, got: usize },
+    /// Instructions from an earlier workload are still in a pipeline.
+    InFlight,
+    /// `thread` joins `child`, which never runs: no such thread, or a child nothing spawns.
+    UnknownJoin { thread: ThreadId, child: ThreadId },
+    /// Threads are still read from streams, which a checkpoint cannot copy.
+    Streamed,
+}
+
+impl fmt::Display for WorkloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorkloadError::ThreadCount { expected, got } => {
+                write!(f, "workload has {got} threads, simulator has {expected}")
+            }
+            WorkloadError::InFlight => {
+                write!(f, "instructions are still in flight; use append_workload to stack work")
+            }
+            WorkloadError::UnknownJoin { thread, child } => {
+                write!(f, "thread {} joins thread {}, which never runs", thread.0, child.0)
+            }
+            WorkloadError::Streamed => write!(f, "streamed threads cannot be checkpointed"),
+        }
+    }
+}
+
+impl std::error::Error for WorkloadError {}
+
+/// Why a trace could not be read (see [`from_csv_reader`] and [`BinaryTraceReader`]).
+#[derive(Debug)]
+pub enum TraceError {
+    Io(io::Error),
+    /// Row `line` (1-based) is malformed.
+    Parse { line: usize, message: String },
+    /// The binary trace's header or a record is malformed.
+    Format(String),
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceError::Io(e) => write!(f, "reading trace: {e}"),
+            TraceError::Parse { line, message } => write!(f, "trace line {line}: {message}"),
+            TraceError::Format(message) => write!(f, "bad binary trace: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for TraceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TraceError::Io(e) => Some(e),
+            TraceError::Parse { .. } | TraceError::Format(_) => None,
+        }
+    }
 }
 
+impl From<io::Error> for TraceError {
+    fn from(e: io::Error) -> Self {
+        TraceError::Io(e)
+    }
+}
+
+/// Outcomes of generated branches.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BranchPattern {
+    /// Each branch is taken with `taken_probability`, drawn from a seeded generator.
+    Biased { taken_probability: f64, seed: u64 },
+    /// A loop branch: taken `trip_count - 1` times, then not taken once, repeating.
+    Loop { trip_count: usize },
+}
+
+/// Per-thread variables packed next to each other (see `WorkloadConfig::false_sharing`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FalseSharing {
+    /// Variables; thread `t` uses variable `t % num_vars`.
+    pub num_vars: usize,
+    /// Cache line size in bytes (the padding stride).
+    pub line_size: usize,
+    /// Give each variable its own line instead of packing them 8 bytes apart.
+    pub padded: bool,
+}
+
+/// Shares of the generated computes that are multiplies, divides and FMAs (the rest stay
+/// plain `Compute`).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InstructionMix {
+    pub mul: f64,
+    pub div: f64,
+    pub fma: f64,
+}
+
+/// One phase of a multi-phase workload (see `WorkloadConfig::phases`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PhaseConfig {
+    /// Instructions the phase generates per thread.
+    pub instructions: usize,
+    pub memory_fraction: f64,
+    pub access_pattern: AccessPattern,
+    /// Start the phase's addresses over from the beginning of its pattern, rather than carry
+    /// on from where the previous phase left off.
+    pub reset_addresses: bool,
+}
+
+/// Bytes per shared variable when packed.
+const VAR_BYTES: u64 = 8;
+
+/// Program address of generated branches.
+const BRANCH_ADDRESS: u64 = 0x40_0000;
+
+/// Register each pointer-chasing load writes and the next one reads.
+const CHASE_REG: RegId = RegId(1);
+
 /// Workload configuration.
 #[derive(Clone, Debug)]
 pub struct WorkloadConfig {
@@ -27,6 +173,43 @@ pub struct WorkloadConfig {
     pub cache_num_sets: usize,
     /// For Sequential: cap unique lines to this many (reuse = cache hits). 0 = no cap.
     pub working_set_lines: usize,
+    /// Offset added to every generated address (e.g. to place the working set in a fast region).
+    pub base_address: u64,
+    /// Computes come in chains of this many, each reading the previous one's result.
+    /// 0 or 1 = independent computes.
+    pub dependency_chain_length: usize,
+    /// Kinds of the computes.
+    pub instruction_mix: InstructionMix,
+    /// Fraction of instructions that are branches (taken from the non-memory ones).
+    pub branch_fraction: f64,
+    /// Taken/not-taken sequence of the branches.
+    pub branch_pattern: BranchPattern,
+    /// Fraction of instructions that are atomic read-modify-writes (taken from the non-memory,
+    /// non-branch ones).
+    pub atomic_fraction: f64,
+    /// Address every atomic targets (shared between threads), or None for the pattern's next
+    /// address (private as long as threads use distinct `base_address`es).
+    pub atomic_address: Option<u64>,
+    /// Fraction of instructions that are yields (taken from the non-memory, non-branch,
+    /// non-atomic ones), for cooperative multitasking.
+    pub yield_fraction: f64,
+    /// `build_workload` puts a barrier across all its threads after every this many
+    /// instructions (on top of `instructions_per_thread`). 0 = no barriers.
+    pub barrier_interval: usize,
+    /// Fraction of memory instructions wrapped in their own critical section on lock
+    /// `lock_id` (an acquire before, a release after; on top of `instructions_per_thread`).
+    pub lock_fraction: f64,
+    pub lock_id: u64,
+    /// A fence follows every this many memory instructions (on top of
+    /// `instructions_per_thread`). 0 = no fences.
+    pub fence_interval: usize,
+    /// Every memory instruction of a thread accesses that thread's own variable, placed after
+    /// `base_address` as configured (all in one line unless padded).
+    pub false_sharing: Option<FalseSharing>,
+    /// Phases generated one after another, each with its own length, memory fraction and
+    /// access pattern. When non-empty they replace `instructions_per_thread`,
+    /// `memory_fraction` and `access_pattern`.
+    pub phases: Vec<PhaseConfig>,
 }
 
 impl Default for WorkloadConfig {
@@ -38,6 +221,31 @@ impl Default for WorkloadConfig {
             line_size: 64,
             cache_num_sets: 64,
             working_set_lines: 0,
+            base_address: 0,
+            dependency_chain_length: 0,
+            instruction_mix: InstructionMix::default(),
+            branch_fraction: 0.0,
+            branch_pattern: BranchPattern::Loop { trip_count: 16 },
+            atomic_fraction: 0.0,
+            atomic_address: None,
+            yield_fraction: 0.0,
+            barrier_interval: 0,
+            lock_fraction: 0.0,
+            lock_id: 0,
+            fence_interval: 0,
+            false_sharing: None,
+            phases: Vec::new(),
+        }
+    }
+}
+
+impl WorkloadConfig {
+    /// Instructions generated per thread, not counting barriers, locks and fences.
+    pub fn total_instructions(&self) -> usize {
+        if self.phases.is_empty() {
+            self.instructions_per_thread
+        } else {
+            self.phases.iter().map(|p| p.instructions).sum()
         }
     }
 }
@@ -47,39 +255,259 @@ pub struct WorkloadGenerator {
     config: WorkloadConfig,
     /// Next instruction index (for sequential or conflict address generation).
     index: usize,
+    /// Computes generated so far (for dependency chains).
+    computes: usize,
+    /// Branches generated so far (for the loop pattern).
+    branches: usize,
+    /// Draws biased branch outcomes.
+    rng: Rng,
+    /// Draws random line addresses.
+    address_rng: Rng,
+    /// Strided addresses generated so far.
+    strides: usize,
+    /// Line sampler of the Zipf pattern.
+    zipf: Option<Zipf>,
+    /// Next line after each line of the pointer-chasing permutation.
+    chase: Vec<u64>,
+    /// Line the pointer chase visits next.
+    chase_line: u64,
+    /// Memory instructions generated so far (for critical sections).
+    memory_ops: usize,
+    /// Rest of a critical section, handed out before the next new instruction.
+    pending: VecDeque<Instruction>,
+    /// Index of the thread this stream is for (selects its false-sharing variable).
+    thread: usize,
+    /// Current entry of `config.phases`.
+    phase: usize,
+    /// Index of the current phase's first instruction.
+    phase_start: usize,
+    /// Index the sequential and conflict-heavy addresses count from.
+    cursor_start: usize,
 }
 
 impl WorkloadGenerator {
-    pub fn new(config: WorkloadConfig) -> Self {
-        Self { config, index: 0 }
+    pub fn new(mut config: WorkloadConfig) -> Self {
+        if let Some(first) = config.phases.first() {
+            config.memory_fraction = first.memory_fraction;
+            config.access_pattern = first.access_pattern;
+            config.instructions_per_thread = config.total_instructions();
+        }
+        let seed = match config.branch_pattern {
+            BranchPattern::Biased { seed, .. } => seed,
+            BranchPattern::Loop { .. } => 0,
+        };
+        let mut gen = Self {
+            config,
+            index: 0,
+            computes: 0,
+            branches: 0,
+            rng: Rng::new(seed),
+            address_rng: Rng::new(0),
+            strides: 0,
+            zipf: None,
+            chase: Vec::new(),
+            chase_line: 0,
+            memory_ops: 0,
+            pending: VecDeque::new(),
+            thread: 0,
+            phase: 0,
+            phase_start: 0,
+            cursor_start: 0,
+        };
+        gen.start_pattern();
+        gen
+    }
+
+    /// Seeds the address generator and builds the tables of the current access pattern. The
+    /// thread index is mixed into the seed, so threads of one workload draw different lines.
+    fn start_pattern(&mut self) {
+        let address_seed = match self.config.access_pattern {
+            AccessPattern::Random { seed, .. }
+            | AccessPattern::Zipf { seed, .. }
+            | AccessPattern::PointerChase { seed, .. } => seed,
+            _ => 0,
+        };
+        let thread_mix = (self.thread as u64).wrapping_mul(0xD6E8_FEB8_6659_FD93);
+        self.address_rng = Rng::new(address_seed ^ thread_mix);
+        self.chase = match self.config.access_pattern {
+            AccessPattern::PointerChase {
+                footprint_lines, ..
+            } => cyclic_permutation(footprint_lines.max(1), &mut self.address_rng),
+            _ => Vec::new(),
+        };
+        self.zipf = match self.config.access_pattern {
+            AccessPattern::Zipf {
+                num_lines,
+                exponent,
+                ..
+            } => Some(Zipf::new(num_lines, exponent)),
+            _ => None,
+        };
+    }
+
+    /// Moves on to the next phase once the current one has generated all its instructions.
+    /// A phase whose pattern changes starts that pattern afresh; its cursor (the position in
+    /// a sequential, conflict-heavy, strided or pointer-chasing walk) carries on unless the
+    /// phase resets it.
+    fn advance_phase(&mut self) {
+        while let Some(current) = self.config.phases.get(self.phase) {
+            if self.index < self.phase_start + current.instructions {
+                return;
+            }
+            self.phase_start += current.instructions;
+            self.phase += 1;
+            let Some(&next) = self.config.phases.get(self.phase) else {
+                return;
+            };
+            let pattern_changed = next.access_pattern != self.config.access_pattern;
+            self.config.memory_fraction = next.memory_fraction;
+            self.config.access_pattern = next.access_pattern;
+            if pattern_changed || next.reset_addresses {
+                self.start_pattern();
+            }
+            if next.reset_addresses {
+                self.cursor_start = self.index;
+                self.strides = 0;
+                self.chase_line = 0;
+            }
+        }
+    }
+
+    /// Generates the stream of thread number `thread` (`build_workload` sets this). Call it
+    /// before drawing any instruction: it restarts the address pattern with the thread's seed.
+    pub fn with_thread(mut self, thread: usize) -> Self {
+        self.thread = thread;
+        self.start_pattern();
+        self
     }
 
     /// Generates the next instruction at the given logical "issue" cycle (for logging).
     pub fn next_instruction(&mut self, issue_cycle: u64) -> Option<Instruction> {
+        if let Some(instr) = self.pending.pop_front() {
+            return Some(instr);
+        }
         if self.index >= self.config.instructions_per_thread {
             return None;
         }
+        self.advance_phase();
         let frac = (self.config.memory_fraction * 100.0).round() as usize;
-        let use_memory = (self.index % 100) < frac.min(100) || self.config.memory_fraction >= 1.0;
+        let phase_index = self.index - self.phase_start;
+        let use_memory =
+            (phase_index % 100) < frac.min(100) || self.config.memory_fraction >= 1.0;
+        // Branches and atomics are spread evenly rather than bunched like memory ops.
+        let spread = |fraction: f64, index: usize| {
+            ((index + 1) as f64 * fraction).floor() > (index as f64 * fraction).floor()
+        };
+        let use_branch = !use_memory && spread(self.config.branch_fraction, self.index);
+        let use_atomic =
+            !use_memory && !use_branch && spread(self.config.atomic_fraction, self.index);
+        let use_yield = !use_memory
+            && !use_branch
+            && !use_atomic
+            && spread(self.config.yield_fraction, self.index);
         self.index += 1;
 
         let instr = if use_memory {
             let address = self.next_address();
-            let kind = if self.index % 2 == 0 {
+            let chasing = matches!(self.config.access_pattern, AccessPattern::PointerChase { .. });
+            let kind = if chasing || self.index.is_multiple_of(2) {
                 InstructionKind::Load
             } else {
                 InstructionKind::Store
             };
-            Instruction::new_memory(kind, address, issue_cycle)
+            let mut instr = Instruction::new_memory(kind, address, issue_cycle);
+            if chasing {
+                // Its address comes from the previous load's data.
+                instr = instr.with_dest(CHASE_REG);
+                if self.memory_ops > 0 {
+                    instr = instr.with_srcs(vec![CHASE_REG]);
+                }
+            }
+            let locked = spread(self.config.lock_fraction, self.memory_ops);
+            self.memory_ops += 1;
+            let fenced = self.config.fence_interval > 0
+                && self.memory_ops.is_multiple_of(self.config.fence_interval);
+            let instr = if locked {
+                let lock_id = self.config.lock_id;
+                self.pending.push_back(instr);
+                let release = Instruction::new_control(InstructionKind::LockRelease { lock_id });
+                self.pending.push_back(release);
+                Instruction::new_control(InstructionKind::LockAcquire { lock_id })
+            } else {
+                instr
+            };
+            if fenced {
+                self.pending.push_back(Instruction::new_control(InstructionKind::Fence));
+            }
+            instr
+        } else if use_atomic {
+            let address = match self.config.atomic_address {
+                Some(address) => address,
+                None => self.next_address(),
+            };
+            Instruction::new_memory(InstructionKind::AtomicRmw, address, issue_cycle)
+        } else if use_branch {
+            let taken = self.next_branch_outcome();
+            Instruction::new_branch(BRANCH_ADDRESS, taken)
+        } else if use_yield {
+            Instruction::new_control(InstructionKind::Yield)
         } else {
-            Instruction::new_compute(issue_cycle)
+            self.next_compute(issue_cycle)
         };
         Some(instr)
     }
 
+    /// A compute of the configured mix that continues the current dependency chain (if
+    /// chains are on).
+    fn next_compute(&mut self, issue_cycle: u64) -> Instruction {
+        let mix = self.config.instruction_mix;
+        let slot = (self.computes % 100) as f64;
+        let kind = if slot < mix.mul * 100.0 {
+            InstructionKind::Mul
+        } else if slot < (mix.mul + mix.div) * 100.0 {
+            InstructionKind::Div
+        } else if slot < (mix.mul + mix.div + mix.fma) * 100.0 {
+            InstructionKind::Fma
+        } else {
+            InstructionKind::Compute
+        };
+        let instr = Instruction::new_op(kind, issue_cycle);
+        let chain = self.config.dependency_chain_length;
+        let position = self.computes % chain.max(1);
+        self.computes += 1;
+        if chain < 2 {
+            return instr;
+        }
+        let acc = RegId(0);
+        match position {
+            0 => instr.with_dest(acc),
+            _ => instr.with_dest(acc).with_srcs(vec![acc]),
+        }
+    }
+
+    fn next_branch_outcome(&mut self) -> bool {
+        let n = self.branches;
+        self.branches += 1;
+        match self.config.branch_pattern {
+            BranchPattern::Biased {
+                taken_probability, ..
+            } => self.rng.next_f64() < taken_probability,
+            BranchPattern::Loop { trip_count } => !(n + 1).is_multiple_of(trip_count.max(1)),
+        }
+    }
+
     fn next_address(&mut self) -> u64 {
-        let idx = self.index - 1;
-        let addr = match self.config.access_pattern {
+        if let Some(sharing) = self.config.false_sharing {
+            let var = (self.thread % sharing.num_vars.max(1)) as u64;
+            let stride = if sharing.padded {
+                sharing.line_size as u64
+            } else {
+                VAR_BYTES
+            };
+            return self.config.base_address.wrapping_add(var * stride);
+        }
+        let idx = self.index - 1 - self.cursor_start;
+        let offset = match self.config.access_pattern {
             AccessPattern::Sequential => {
                 let line_idx = if self.config.working_set_lines > 0 {
                     idx % self.config.working_set_lines
@@ -93,12 +521,40 @@ impl WorkloadGenerator {
                 let line_addr = (idx as u64).wrapping_mul(self.config.cache_num_sets as u64);
                 line_addr * self.config.line_size as u64
             }
+            AccessPattern::Random {
+                footprint_lines, ..
+            } => {
+                let line = self.address_rng.range_inclusive(0, footprint_lines.max(1) as u64 - 1);
+                line.wrapping_mul(self.config.line_size as u64)
+            }
+            AccessPattern::Strided {
+                stride_bytes,
+                footprint_bytes,
+            } => {
+                let offset = (self.strides as u64).wrapping_mul(stride_bytes as u64);
+                self.strides += 1;
+                match footprint_bytes {
+                    0 => offset,
+                    footprint => offset % footprint as u64,
+                }
+            }
+            AccessPattern::Zipf { .. } => {
+                let zipf = self.zipf.as_ref().expect("built for the Zipf pattern");
+                let line = zipf.sample(&mut self.address_rng) as u64;
+                line.wrapping_mul(self.config.line_size as u64)
+            }
+            AccessPattern::PointerChase { .. } => {
+                // A cursor carried over from a larger permutation wraps into this one.
+                let line = self.chase_line % self.chase.len() as u64;
+                self.chase_line = self.chase[line as usize];
+                line.wrapping_mul(self.config.line_size as u64)
+            }
         };
-        addr
+        self.config.base_address.wrapping_add(offset)
     }
 
     pub fn remaining(&self) -> usize {
-        self.config.instructions_per_thread.saturating_sub(self.index)
+        self.config.instructions_per_thread.saturating_sub(self.index) + self.pending.len()
     }
 
     pub fn config(&self) -> &WorkloadConfig {
@@ -106,28 +562,954 @@ impl WorkloadGenerator {
     }
 }
 
+/// Successor table of a random permutation of `0..n` forming a single cycle (Sattolo's
+/// algorithm), so a walk from any element visits all `n` before repeating.
+fn cyclic_permutation(n: usize, rng: &mut Rng) -> Vec<u64> {
+    let mut next: Vec<u64> = (0..n as u64).collect();
+    for i in (1..n).rev() {
+        let j = rng.range_inclusive(0, i as u64 - 1) as usize;
+        next.swap(i, j);
+    }
+    next
+}
+
 /// Build a full workload: list of instruction streams, one per thread.
 pub fn build_workload(
     num_threads: usize,
     config: WorkloadConfig,
 ) -> Vec<Vec<Instruction>> {
     (0..num_threads)
-        .map(|_| {
-            let mut gen = WorkloadGenerator::new(config.clone());
-            let mut list = Vec::with_capacity(config.instructions_per_thread);
+        .map(|thread| {
+            let mut gen = WorkloadGenerator::new(config.clone()).with_thread(thread);
+            let mut list = Vec::with_capacity(config.total_instructions());
             let mut cycle = 0u64;
             while let Some(instr) = gen.next_instruction(cycle) {
                 list.push(instr);
                 cycle += 1;
+                let interval = config.barrier_interval;
+                if interval > 0 && (cycle as usize).is_multiple_of(interval) {
+                    let id = cycle / interval as u64 - 1;
+                    let participants = num_threads;
+                    let kind = InstructionKind::Barrier { id, participants };
+                    list.push(Instruction::new_control(kind));
+                }
             }
             list
         })
         .collect()
 }
 
+/// Largest number of threads a trace may hold; thread ids are indices, so a trace naming a
+/// thread at or past this is rejected rather than allocating a thread for every id below it.
+pub const MAX_TRACE_THREADS: usize = 1 << 16;
+
+/// Kinds a trace can hold, in the order of their binary codes, with the operands each takes.
+const TRACE_KINDS: [(&str, usize); 19] = [
+    ("compute", 0),
+    ("load", 0),
+    ("store", 0),
+    ("mul", 0),
+    ("div", 0),
+    ("fma", 0),
+    ("spawn", 1),
+    ("join", 1),
+    ("barrier", 2),
+    ("lock_acquire", 1),
+    ("lock_release", 1),
+    ("atomic_rmw", 0),
+    ("branch", 1),
+    ("fence", 0),
+    ("dma_start", 3),
+    ("dma_wait", 1),
+    ("matrix_op", 3),
+    ("yield", 0),
+    ("sleep", 1),
+];
+
+/// A kind's operands, in the order [`trace_kind`] takes them back.
+fn kind_operands(kind: InstructionKind) -> Vec<u64> {
+    match kind {
+        InstructionKind::Spawn { child } | InstructionKind::Join { child } => {
+            vec![child.0 as u64]
+        }
+        InstructionKind::Barrier { id, participants } => vec![id, participants as u64],
+        InstructionKind::LockAcquire { lock_id } | InstructionKind::LockRelease { lock_id } => {
+            vec![lock_id]
+        }
+        InstructionKind::Branch { taken } => vec![u64::from(taken)],
+        InstructionKind::DmaStart { src, dst, bytes } => vec![src, dst, bytes],
+        InstructionKind::DmaWait { id } => vec![id],
+        InstructionKind::MatrixOp { m, n, k } => vec![m.into(), n.into(), k.into()],
+        InstructionKind::Sleep { cycles } => vec![cycles],
+        _ => Vec::new(),
+    }
+}
+
+/// The kind named `name` (see [`InstructionKind::name`]) with `operands`.
+fn trace_kind(name: &str, operands: &[u64]) -> Result<InstructionKind, String> {
+    let Some(&(_, arity)) = TRACE_KINDS.iter().find(|(n, _)| *n == name) else {
+        return Err(format!("unknown kind {name:?}"));
+    };
+    if operands.len() != arity {
+        return Err(format!("{name} takes {arity} operands, got {}", operands.len()));
+    }
+    let too_big = |value: u64| format!("{name} operand {value} is too large");
+    let small = |value: u64| u32::try_from(value).map_err(|_| too_big(value));
+    let index = |value: u64| usize::try_from(value).map_err(|_| too_big(value));
+    let kind = match (name, operands) {
+        ("compute", _) => InstructionKind::Compute,
+        ("load", _) => InstructionKind::Load,
+        ("store", _) => InstructionKind::Store,
+        ("mul", _) => InstructionKind::Mul,
+        ("div", _) => InstructionKind::Div,
+        ("fma", _) => InstructionKind::Fma,
+        ("spawn", &[child]) => InstructionKind::Spawn {
+            child: ThreadId(index(child)?),
+        },
+        ("join", &[child]) => InstructionKind::Join {
+            child: ThreadId(index(child)?),
+        },
+        ("barrier", &[id, participants]) => InstructionKind::Barrier {
+            id,
+            participants: index(participants)?,
+        },
+        ("lock_acquire", &[lock_id]) => InstructionKind::LockAcquire { lock_id },
+        ("lock_release", &[lock_id]) => InstructionKind::LockRelease { lock_id },
+        ("atomic_rmw", _) => InstructionKind::AtomicRmw,
+        ("branch", &[taken]) => InstructionKind::Branch { taken: taken != 0 },
+        ("fence", _) => InstructionKind::Fence,
+        ("dma_start", &[src, dst, bytes]) => InstructionKind::DmaStart { src, dst, bytes },
+        ("dma_wait", &[id]) => InstructionKind::DmaWait { id },
+        ("matrix_op", &[m, n, k]) => InstructionKind::MatrixOp {
+            m: small(m)?,
+            n: small(n)?,
+            k: small(k)?,
+        },
+        ("yield", _) => InstructionKind::Yield,
+        ("sleep", &[cycles]) => InstructionKind::Sleep { cycles },
+        _ => unreachable!("arity checked against TRACE_KINDS"),
+    };
+    Ok(kind)
+}
+
+/// Reads a workload from CSV rows `thread_id,kind,address,dest,srcs,value`, one instruction
+/// each, in program order per thread. `kind` is an [`InstructionKind::name`], followed by
+/// its operands separated by `:` (`barrier:3:4`, `branch:1`, `sleep:100`). The address is
+/// decimal or `0x` hex and is required only by loads, stores and atomics. `dest` is the
+/// register written, `srcs` the registers read separated by spaces, and `value` the data a
+/// store writes; the trailing columns may be left out. Blank lines, `#` comments and a
+/// `thread_id,...` header are skipped. Thread `t`'s instructions are element `t` of the
+/// result (threads without rows get none).
+pub fn from_csv_reader(r: impl BufRead) -> Result<Vec<Vec<Instruction>>, TraceError> {
+    let mut threads: Vec<Vec<Instruction>> = Vec::new();
+    for (idx, line) in r.lines().enumerate() {
+        let line = line?;
+        let row = line.trim();
+        if row.is_empty() || row.starts_with('#') || (idx == 0 && row.starts_with("thread")) {
+            continue;
+        }
+        let parse_error = |message: String| TraceError::Parse {
+            line: idx + 1,
+            message,
+        };
+        let fields: Vec<&str> = row.split(',').map(str::trim).collect();
+        if !(2..=6).contains(&fields.len()) {
+            let message = format!("expected thread_id,kind,address,dest,srcs,value, got {row:?}");
+            return Err(parse_error(message));
+        }
+        let field = |i: usize| fields.get(i).copied().filter(|f| !f.is_empty());
+        let thread = fields[0];
+        let thread: usize = thread
+            .parse()
+            .map_err(|_| parse_error(format!("bad thread id {thread:?}")))?;
+        if thread >= MAX_TRACE_THREADS {
+            let message = format!("thread id {thread} is not below {MAX_TRACE_THREADS}");
+            return Err(parse_error(message));
+        }
+        let number = |what: &str, text: &str| {
+            let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+                Some(hex) => u64::from_str_radix(hex, 16),
+                None => text.parse(),
+            };
+            parsed.map_err(|_| parse_error(format!("bad {what} {text:?}")))
+        };
+        let mut parts = fields[1].split(':');
+        let name = parts.next().unwrap_or_default().to_ascii_lowercase();
+        let operands = parts
+            .map(|op| number("operand", op))
+            .collect::<Result<Vec<u64>, _>>()?;
+        let kind = trace_kind(&name, &operands).map_err(parse_error)?;
+        let address = field(2).map(|a| number("address", a)).transpose()?;
+        let mut instr = Instruction::new_control(kind);
+        match address {
+            Some(address) => instr.address = address,
+            None if instr.is_memory_op() => {
+                return Err(parse_error(format!("{name} needs an address")));
+            }
+            None => {}
+        }
+        let register = |r: &str| number("register", r).map(|r| RegId(r as usize));
+        instr.dest = field(3).map(register).transpose()?;
+        instr.srcs = match field(4) {
+            Some(srcs) => srcs.split_whitespace().map(register).collect::<Result<_, _>>()?,
+            None => Vec::new(),
+        };
+        instr.value = field(5).map(|v| number("value", v)).transpose()?;
+        if threads.len() <= thread {
+            threads.resize_with(thread + 1, Vec::new);
+        }
+        threads[thread].push(instr);
+    }
+    Ok(threads)
+}
+
+/// [`from_csv_reader`] over the file at `path`.
+pub fn from_csv_path(path: impl AsRef<Path>) -> Result<Vec<Vec<Instruction>>, TraceError> {
+    from_csv_reader(BufReader::new(File::open(path)?))
+}
+
+/// Writes `workload` (element `t` = thread `t`'s instructions) as a CSV trace that
+/// [`from_csv_reader`] reads back exactly: a header, then thread 0's rows, thread 1's, and so
+/// on, addresses in hex, empty trailing columns left out.
+pub fn to_csv(workload: &[Vec<Instruction>], mut w: impl Write) -> io::Result<()> {
+    writeln!(w, "thread_id,kind,address,dest,srcs,value")?;
+    for (thread, instrs) in workload.iter().enumerate() {
+        for instr in instrs {
+            let mut kind = instr.kind.name().to_string();
+            for operand in kind_operands(instr.kind) {
+                kind.push_str(&format!(":{operand}"));
+            }
+            let address = match instr.address {
+                0 if !instr.is_memory_op() => String::new(),
+                address => format!("{address:#x}"),
+            };
+            let srcs: Vec<String> = instr.srcs.iter().map(|r| r.0.to_string()).collect();
+            let mut fields = vec![
+                thread.to_string(),
+                kind,
+                address,
+                instr.dest.map(|r| r.0.to_string()).unwrap_or_default(),
+                srcs.join(" "),
+                instr.value.map(|v| v.to_string()).unwrap_or_default(),
+            ];
+            while fields.len() > 3 && fields.last().is_some_and(String::is_empty) {
+                fields.pop();
+            }
+            writeln!(w, "{}", fields.join(","))?;
+        }
+    }
+    Ok(())
+}
+
+/// First bytes of a binary trace.
+pub const BINARY_TRACE_MAGIC: [u8; 4] = *b"MCTR";
+/// Binary trace format version [`write_binary`] writes. [`BinaryTraceReader`] also reads
+/// version 1 traces, which only hold loads, stores and computes.
+pub const BINARY_TRACE_VERSION: u8 = 2;
+
+/// Low bits of a record's kind byte: the kind's index in [`TRACE_KINDS`].
+const KIND_CODE_MASK: u8 = 0x1f;
+/// Kind byte flag: the destination and source registers follow.
+const KIND_HAS_REGISTERS: u8 = 0x20;
+/// Kind byte flag: the store's data value follows.
+const KIND_HAS_VALUE: u8 = 0x40;
+/// Kind byte flag: an address follows for a kind that is not a memory op.
+const KIND_HAS_ADDRESS: u8 = 0x80;
+
+/// Writes `workload` (element `t` = thread `t`'s instructions) as a binary trace: the magic,
+/// a version byte and the thread count (varint), then one record per instruction, threads
+/// interleaved in turn. A record is the thread id (varint) and a kind byte (the kind's code,
+/// 0 compute, 1 load, 2 store and so on, plus flags), then as the flags say: the address as
+/// a zigzag varint delta from the thread's previous address (always, for a memory op), the
+/// destination register plus one (0 = none), the source count and the sources, the value,
+/// and last the kind's operands, all varints.
+pub fn write_binary(mut w: impl Write, workload: &[Vec<Instruction>]) -> io::Result<()> {
+    w.write_all(&BINARY_TRACE_MAGIC)?;
+    w.write_all(&[BINARY_TRACE_VERSION])?;
+    write_varint(&mut w, workload.len() as u64)?;
+    let mut last_address = vec![0u64; workload.len()];
+    let longest = workload.iter().map(Vec::len).max().unwrap_or(0);
+    for idx in 0..longest {
+        for (thread, instrs) in workload.iter().enumerate() {
+            let Some(instr) = instrs.get(idx) else {
+                continue;
+            };
+            let name = instr.kind.name();
+            let code = TRACE_KINDS.iter().position(|(n, _)| *n == name);
+            let mut kind = code.expect("every kind is in TRACE_KINDS") as u8;
+            let has_address = instr.is_memory_op() || instr.address != 0;
+            if has_address && !instr.is_memory_op() {
+                kind |= KIND_HAS_ADDRESS;
+            }
+            if instr.dest.is_some() || !instr.srcs.is_empty() {
+                kind |= KIND_HAS_REGISTERS;
+            }
+            if instr.value.is_some() {
+                kind |= KIND_HAS_VALUE;
+            }
+            write_varint(&mut w, thread as u64)?;
+            w.write_all(&[kind])?;
+            if has_address {
+                let delta = instr.address.wrapping_sub(last_address[thread]) as i64;
+                write_varint(&mut w, ((delta << 1) ^ (delta >> 63)) as u64)?;
+                last_address[thread] = instr.address;
+            }
+            if kind & KIND_HAS_REGISTERS != 0 {
+                write_varint(&mut w, instr.dest.map_or(0, |r| r.0 as u64 + 1))?;
+                write_varint(&mut w, instr.srcs.len() as u64)?;
+                for src in &instr.srcs {
+                    write_varint(&mut w, src.0 as u64)?;
+                }
+            }
+            if let Some(value) = instr.value {
+                write_varint(&mut w, value)?;
+            }
+            for operand in kind_operands(instr.kind) {
+                write_varint(&mut w, operand)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_varint(w: &mut impl Write, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return w.write_all(&[byte]);
+        }
+        w.write_all(&[byte | 0x80])?;
+    }
+}
+
+/// Reads a trace written by [`write_binary`] a record at a time, so traces larger than memory
+/// can be replayed: iterate it for `(thread, instruction)` pairs, or split it into per-thread
+/// streams for `Simulator::load_streams`.
+pub struct BinaryTraceReader<R> {
+    r: R,
+    num_threads: usize,
+    last_address: Vec<u64>,
+    /// Set after the end of the trace or an error.
+    done: bool,
+}
+
+impl<R: Read> BinaryTraceReader<R> {
+    /// Reads and checks the header.
+    pub fn new(mut r: R) -> Result<Self, TraceError> {
+        let mut header = [0u8; 5];
+        r.read_exact(&mut header).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => TraceError::Format("truncated header".to_string()),
+            _ => TraceError::Io(e),
+        })?;
+        if header[..4] != BINARY_TRACE_MAGIC {
+            return Err(TraceError::Format(format!("bad magic {:?}", &header[..4])));
+        }
+        if !(1..=BINARY_TRACE_VERSION).contains(&header[4]) {
+            return Err(TraceError::Format(format!("unsupported version {}", header[4])));
+        }
+        let num_threads = read_varint(&mut r)?
+            .ok_or_else(|| TraceError::Format("truncated header".to_string()))?;
+        let num_threads = usize::try_from(num_threads)
+            .ok()
+            .filter(|&n| n <= MAX_TRACE_THREADS)
+            .ok_or_else(|| TraceError::Format(format!("thread count {num_threads}")))?;
+        Ok(Self {
+            r,
+            num_threads,
+            last_address: vec![0; num_threads],
+            done: false,
+        })
+    }
+
+    pub fn num_threads(&self) -> usize {
+        self.num_threads
+    }
+
+    fn next_record(&mut self) -> Result<Option<(usize, Instruction)>, TraceError> {
+        let Some(thread) = read_varint(&mut self.r)? else {
+            return Ok(None);
+        };
+        let truncated = || TraceError::Format("truncated record".to_string());
+        let thread = usize::try_from(thread)
+            .ok()
+            .filter(|&t| t < self.num_threads)
+            .ok_or_else(|| TraceError::Format(format!("thread {thread} out of range")))?;
+        let byte = read_byte(&mut self.r)?.ok_or_else(truncated)?;
+        let Some(&(name, arity)) = TRACE_KINDS.get(usize::from(byte & KIND_CODE_MASK)) else {
+            return Err(TraceError::Format(format!("unknown kind byte {byte}")));
+        };
+        let mut varint = || read_varint(&mut self.r)?.ok_or_else(truncated);
+        let mut instr = Instruction::new_control(InstructionKind::Compute);
+        let address = match name {
+            "load" | "store" | "atomic_rmw" => Some(varint()?),
+            _ if byte & KIND_HAS_ADDRESS != 0 => Some(varint()?),
+            _ => None,
+        };
+        if byte & KIND_HAS_REGISTERS != 0 {
+            instr.dest = varint()?.checked_sub(1).map(|r| RegId(r as usize));
+            let count = varint()?;
+            instr.srcs = (0..count)
+                .map(|_| varint().map(|r| RegId(r as usize)))
+                .collect::<Result<_, _>>()?;
+        }
+        if byte & KIND_HAS_VALUE != 0 {
+            instr.value = Some(varint()?);
+        }
+        let operands = (0..arity).map(|_| varint()).collect::<Result<Vec<u64>, _>>()?;
+        instr.kind = trace_kind(name, &operands).map_err(TraceError::Format)?;
+        if let Some(zigzag) = address {
+            let delta = ((zigzag >> 1) as i64) ^ -((zigzag & 1) as i64);
+            instr.address = self.last_address[thread].wrapping_add(delta as u64);
+            self.last_address[thread] = instr.address;
+        }
+        Ok(Some((thread, instr)))
+    }
+
+    /// One stream per thread of the trace, sharing this reader: a stream reading ahead keeps
+    /// the other threads' records it passes until their streams ask for them. A malformed
+    /// record ends every stream; take a [`ThreadStream::status`] handle before handing the
+    /// streams off to learn whether the trace ended early.
+    pub fn into_thread_streams(self) -> Vec<ThreadStream<R>> {
+        let num_threads = self.num_threads;
+        let shared = Arc::new(Mutex::new(Demux {
+            reader: self,
+            pending: vec![VecDeque::new(); num_threads],
+        }));
+        let status = TraceStatus::default();
+        (0..num_threads)
+            .map(|thread| ThreadStream {
+                thread,
+                shared: Arc::clone(&shared),
+                status: status.clone(),
+            })
+            .collect()
+    }
+}
+
+impl<R: Read> Iterator for BinaryTraceReader<R> {
+    type Item = Result<(ThreadId, Instruction), TraceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let record = self.next_record();
+        self.done = !matches!(record, Ok(Some(_)));
+        record.transpose().map(|r| r.map(|(thread, instr)| (ThreadId(thread), instr)))
+    }
+}
+
+/// Reads a whole binary trace into memory (element `t` = thread `t`'s instructions).
+pub fn read_binary(r: impl Read) -> Result<Vec<Vec<Instruction>>, TraceError> {
+    let reader = BinaryTraceReader::new(r)?;
+    let mut threads = vec![Vec::new(); reader.num_threads()];
+    for record in reader {
+        let (thread, instr) = record?;
+        threads[thread.0].push(instr);
+    }
+    Ok(threads)
+}
+
+struct Demux<R> {
+    reader: BinaryTraceReader<R>,
+    /// Records read ahead, per thread.
+    pending: Vec<VecDeque<Instruction>>,
+}
+
+/// One thread's instructions from a shared [`BinaryTraceReader`].
+pub struct ThreadStream<R> {
+    thread: usize,
+    shared: Arc<Mutex<Demux<R>>>,
+    status: TraceStatus,
+}
+
+impl<R> ThreadStream<R> {
+    /// What ended the streams early, if the trace turned out malformed.
+    pub fn error(&self) -> Option<String> {
+        self.status.error()
+    }
+
+    /// A handle on the streams' shared status that stays valid after the streams are moved
+    /// (e.g. into `Simulator::load_streams`).
+    pub fn status(&self) -> TraceStatus {
+        self.status.clone()
+    }
+}
+
+/// Whether the streams of one binary trace hit a malformed record (see
+/// [`ThreadStream::status`]).
+#[derive(Clone, Debug, Default)]
+pub struct TraceStatus(Arc<Mutex<Option<String>>>);
+
+impl TraceStatus {
+    /// What ended the streams early, if the trace turned out malformed.
+    pub fn error(&self) -> Option<String> {
+        self.0.lock().expect("trace status lock").clone()
+    }
+}
+
+impl<R: Read> Iterator for ThreadStream<R> {
+    type Item = Instruction;
+
+    fn next(&mut self) -> Option<Instruction> {
+        let mut demux = self.shared.lock().expect("trace lock");
+        if let Some(instr) = demux.pending[self.thread].pop_front() {
+            return Some(instr);
+        }
+        loop {
+            match demux.reader.next()? {
+                Ok((thread, instr)) if thread.0 == self.thread => return Some(instr),
+                Ok((thread, instr)) => demux.pending[thread.0].push_back(instr),
+                Err(e) => {
+                    *self.status.0.lock().expect("trace status lock") = Some(e.to_string());
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+fn read_byte(r: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut byte = [0u8; 1];
+    loop {
+        match r.read(&mut byte) {
+            Ok(0) => return Ok(None),
+            Ok(_) => return Ok(Some(byte[0])),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// A LEB128 varint, or None at a clean end of input.
+fn read_varint(r: &mut impl Read) -> Result<Option<u64>, TraceError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let Some(byte) = read_byte(r)? else {
+            if shift == 0 {
+                return Ok(None);
+            }
+            return Err(TraceError::Format("truncated varint".to_string()));
+        };
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+    Err(TraceError::Format("varint too long".to_string()))
+}
+
+/// Fork/join workload: threads plus spawn and join edges between them.
+/// Load it with `Simulator::load_graph`.
+#[derive(Clone, Debug, Default)]
+pub struct WorkloadGraph {
+    /// Instructions per thread (index = thread id).
+    threads: Vec<Vec<Instruction>>,
+    /// Threads that only become runnable when spawned.
+    spawned: Vec<bool>,
+}
+
+impl WorkloadGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a thread that is runnable from the start; returns its id.
+    pub fn add_thread(&mut self, instrs: Vec<Instruction>) -> ThreadId {
+        self.threads.push(instrs);
+        self.spawned.push(false);
+        ThreadId(self.threads.len() - 1)
+    }
+
+    /// Adds a thread that stays dormant until another thread spawns it; returns its id.
+    pub fn add_child(&mut self, instrs: Vec<Instruction>) -> ThreadId {
+        self.threads.push(instrs);
+        self.spawned.push(true);
+        ThreadId(self.threads.len() - 1)
+    }
+
+    /// Appends a Spawn of `child` to `parent`'s instructions.
+    pub fn spawn(&mut self, parent: ThreadId, child: ThreadId) {
+        self.threads[parent.0].push(Instruction::new_control(InstructionKind::Spawn { child }));
+    }
+
+    /// Appends a Join on `child` to `parent`'s instructions.
+    pub fn join(&mut self, parent: ThreadId, child: ThreadId) {
+        self.threads[parent.0].push(Instruction::new_control(InstructionKind::Join { child }));
+    }
+
+    /// Appends ordinary instructions to `thread`.
+    pub fn extend(&mut self, thread: ThreadId, instrs: Vec<Instruction>) {
+        self.threads[thread.0].extend(instrs);
+    }
+
+    /// Checks that every Join names a thread that will run: one added with `add_thread`, or a
+    /// child some thread spawns.
+    pub fn check_joins(&self) -> Result<(), WorkloadError> {
+        let spawned: HashSet<ThreadId> = self
+            .threads
+            .iter()
+            .flatten()
+            .filter_map(|i| match i.kind {
+                InstructionKind::Spawn { child } => Some(child),
+                _ => None,
+            })
+            .collect();
+        let runs = |child: ThreadId| match self.spawned.get(child.0) {
+            Some(&waits) => !waits || spawned.contains(&child),
+            None => false,
+        };
+        check_joins(&self.threads, runs)
+    }
+
+    /// Each thread's instructions and whether it waits to be spawned, in thread order.
+    pub fn into_threads(self) -> Vec<(Vec<Instruction>, bool)> {
+        self.threads.into_iter().zip(self.spawned).collect()
+    }
+}
+
+/// Checks that every Join in `thread_workloads` (index = thread id) names a thread for which
+/// `runs` holds.
+pub(crate) fn check_joins(
+    thread_workloads: &[Vec<Instruction>],
+    runs: impl Fn(ThreadId) -> bool,
+) -> Result<(), WorkloadError> {
+    for (thread, instrs) in thread_workloads.iter().enumerate() {
+        for instr in instrs {
+            if let InstructionKind::Join { child } = instr.kind {
+                if !runs(child) {
+                    let thread = ThreadId(thread);
+                    return Err(WorkloadError::UnknownJoin { thread, child });
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Pairwise sharing between threads: `matrix[a][b]` = lines both thread a and thread b touch
+/// (the diagonal holds each thread's own footprint).
+pub type SharingMatrix = Vec<Vec<usize>>;
+
+/// Counts the cache lines (of `line_size` bytes) each pair of threads both access, for
+/// locality-aware placement before simulation (see `Scheduler::assign_by_affinity`).
+pub fn shared_footprint(thread_workloads: &[Vec<Instruction>], line_size: usize) -> SharingMatrix {
+    let line_size = line_size.max(1) as u64;
+    let footprints: Vec<HashSet<u64>> = thread_workloads
+        .iter()
+        .map(|instrs| {
+            instrs
+                .iter()
+                .filter(|i| i.is_memory_op())
+                .map(|i| i.address / line_size)
+                .collect()
+        })
+        .collect();
+    footprints
+        .iter()
+        .map(|a| footprints.iter().map(|b| a.intersection(b).count()).collect())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cache::{Cache, CacheAccessResult, CacheConfig};
+
+    fn pattern_addresses(access_pattern: AccessPattern) -> Vec<u64> {
+        let config = WorkloadConfig {
+            instructions_per_thread: 4000,
+            memory_fraction: 1.0,
+            access_pattern,
+            cache_num_sets: 32,
+            ..WorkloadConfig::default()
+        };
+        build_workload(1, config)[0].iter().map(|i| i.address).collect()
+    }
+
+    fn random_addresses(footprint_lines: usize, seed: u64) -> Vec<u64> {
+        pattern_addresses(AccessPattern::Random {
+            footprint_lines,
+            seed,
+        })
+    }
+
+    /// Hit rate of the default L1 (32 sets, 2 ways, 64 lines) over `addresses`.
+    fn hit_rate(addresses: &[u64]) -> f64 {
+        let mut cache = Cache::new(CacheConfig::default());
+        let hits = addresses
+            .iter()
+            .filter(|&&a| cache.access(a) == CacheAccessResult::Hit)
+            .count();
+        hits as f64 / addresses.len() as f64
+    }
+
+    #[test]
+    fn workload_random_is_reproducible_per_seed() {
+        let addresses = random_addresses(256, 7);
+        assert_eq!(addresses, random_addresses(256, 7));
+        assert_ne!(addresses, random_addresses(256, 8));
+        assert!(addresses.iter().all(|&a| a % 64 == 0 && a < 256 * 64));
+        let lines: HashSet<u64> = addresses.iter().copied().collect();
+        assert!(lines.len() > 250);
+    }
+
+    #[test]
+    fn workload_threads_draw_their_own_random_lines() {
+        let config = WorkloadConfig {
+            instructions_per_thread: 200,
+            memory_fraction: 1.0,
+            access_pattern: AccessPattern::Zipf {
+                num_lines: 4096,
+                exponent: 0.5,
+                seed: 5,
+            },
+            ..WorkloadConfig::default()
+        };
+        let lines = |t: usize| {
+            let threads = build_workload(2, config.clone());
+            threads[t].iter().map(|i| i.address).collect::<Vec<_>>()
+        };
+        assert_ne!(lines(0), lines(1));
+        assert_eq!(lines(1), lines(1));
+    }
+
+    #[test]
+    fn workload_random_hit_rate_tracks_the_footprint() {
+        let hit_rate = |footprint_lines: usize| hit_rate(&random_addresses(footprint_lines, 1));
+        assert!(hit_rate(32) > 0.95);
+        assert!(hit_rate(4096) < 0.05);
+        assert!(hit_rate(64) > hit_rate(128) && hit_rate(128) > hit_rate(256));
+    }
+
+    #[test]
+    fn workload_strided_hits_within_a_line_and_aliases_across_sets() {
+        let strided = |stride_bytes: usize| {
+            pattern_addresses(AccessPattern::Strided {
+                stride_bytes,
+                footprint_bytes: 1 << 20,
+            })
+        };
+        let sub_line = strided(16);
+        assert_eq!(&sub_line[..5], [0, 16, 32, 48, 64]);
+        assert_eq!(hit_rate(&sub_line), 0.75);
+
+        let aliasing = strided(32 * 64);
+        let conflict = pattern_addresses(AccessPattern::ConflictHeavy);
+        assert_eq!(hit_rate(&aliasing), hit_rate(&conflict));
+        assert_eq!(hit_rate(&aliasing), 0.0);
+
+        let wrapped = pattern_addresses(AccessPattern::Strided {
+            stride_bytes: 64,
+            footprint_bytes: 16 * 64,
+        });
+        assert_eq!(wrapped[16], 0);
+        assert!(hit_rate(&wrapped) > 0.99);
+    }
+
+    #[test]
+    fn workload_zipf_skew_raises_the_hit_rate() {
+        let zipf = |exponent: f64| {
+            hit_rate(&pattern_addresses(AccessPattern::Zipf {
+                num_lines: 4096,
+                exponent,
+                seed: 5,
+            }))
+        };
+        let rates: Vec<f64> = [0.6, 0.8, 1.0, 1.2].into_iter().map(zipf).collect();
+        assert!(rates.windows(2).all(|w| w[1] > w[0]), "{rates:?}");
+        assert!(rates[2] > 2.0 * hit_rate(&random_addresses(4096, 5)));
+    }
+
+    #[test]
+    fn workload_pointer_chase_visits_every_line_once_per_lap() {
+        let config = WorkloadConfig {
+            instructions_per_thread: 130,
+            memory_fraction: 1.0,
+            access_pattern: AccessPattern::PointerChase {
+                footprint_lines: 64,
+                seed: 9,
+            },
+            ..WorkloadConfig::default()
+        };
+        let loads = &build_workload(1, config)[0];
+        let lap: HashSet<u64> = loads[..64].iter().map(|i| i.address).collect();
+        assert_eq!(lap.len(), 64);
+        assert_eq!(loads[64].address, loads[0].address);
+        assert!(loads.iter().all(|i| i.kind == InstructionKind::Load));
+        assert!(loads[0].srcs.is_empty());
+        assert!(loads[1..].iter().all(|i| i.srcs == [CHASE_REG] && i.dest == Some(CHASE_REG)));
+    }
+
+    #[test]
+    fn workload_reads_a_csv_trace() {
+        let trace = "thread_id,kind,address\n\
+                     0,load,0x1000\n\
+                     # a comment\n\
+                     1,store,4096\n\
+                     0,compute,\n\
+                     \n\
+                     1,Compute\n\
+                     0,store,0X40\n";
+        let threads = from_csv_reader(trace.as_bytes()).unwrap();
+        let kinds: Vec<Vec<(InstructionKind, u64)>> = threads
+            .iter()
+            .map(|t| t.iter().map(|i| (i.kind, i.address)).collect())
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                vec![
+                    (InstructionKind::Load, 0x1000),
+                    (InstructionKind::Compute, 0),
+                    (InstructionKind::Store, 0x40),
+                ],
+                vec![(InstructionKind::Store, 4096), (InstructionKind::Compute, 0)],
+            ]
+        );
+    }
+
+    #[test]
+    fn workload_csv_errors_name_the_line() {
+        let error = |trace: &str| from_csv_reader(trace.as_bytes()).unwrap_err().to_string();
+        assert_eq!(
+            error("0,load,0x10\n0,jump,0x20\n"),
+            "trace line 2: unknown kind \"jump\""
+        );
+        assert_eq!(error("0,barrier:1\n"), "trace line 1: barrier takes 2 operands, got 1");
+        assert_eq!(error("0,compute,,x\n"), "trace line 1: bad register \"x\"");
+        assert_eq!(error("0,load,0x10\n\n1,store,abc\n"), "trace line 3: bad address \"abc\"");
+        assert_eq!(error("0,load\n"), "trace line 1: load needs an address");
+        assert_eq!(error("x,load,1\n"), "trace line 1: bad thread id \"x\"");
+        assert_eq!(
+            error("18446744073709551615,load,0\n"),
+            "trace line 1: thread id 18446744073709551615 is not below 65536"
+        );
+        let too_many = error("0,load,1,2,3,4,5\n");
+        assert!(too_many.starts_with("trace line 1: expected thread_id,kind,address"));
+        let missing = from_csv_path("/nonexistent/trace.csv").unwrap_err();
+        assert!(matches!(missing, TraceError::Io(_)));
+    }
+
+    #[test]
+    fn workload_binary_trace_round_trips() {
+        let config = WorkloadConfig {
+            instructions_per_thread: 500,
+            access_pattern: AccessPattern::Random {
+                footprint_lines: 1 << 20,
+                seed: 4,
+            },
+            ..WorkloadConfig::default()
+        };
+        let mut workload = build_workload(3, config);
+        workload[1].truncate(7);
+        let mut bytes = Vec::new();
+        write_binary(&mut bytes, &workload).unwrap();
+        let summary = |w: &[Vec<Instruction>]| -> Vec<Vec<(InstructionKind, u64)>> {
+            w.iter().map(|t| t.iter().map(|i| (i.kind, i.address)).collect()).collect()
+        };
+        assert_eq!(summary(&read_binary(bytes.as_slice()).unwrap()), summary(&workload));
+
+        let streams = BinaryTraceReader::new(bytes.as_slice()).unwrap().into_thread_streams();
+        // Drained last thread first, so the reader has to hold the others' records.
+        let mut streamed: Vec<Vec<Instruction>> =
+            streams.into_iter().rev().map(|s| s.collect()).collect();
+        streamed.reverse();
+        assert_eq!(summary(&streamed), summary(&workload));
+    }
+
+    #[test]
+    fn workload_traces_keep_registers_and_every_kind() {
+        let config = WorkloadConfig {
+            instructions_per_thread: 300,
+            memory_fraction: 0.5,
+            access_pattern: AccessPattern::PointerChase {
+                footprint_lines: 64,
+                seed: 3,
+            },
+            ..WorkloadConfig::default()
+        };
+        let mut workload = build_workload(2, config);
+        workload[1].extend([
+            Instruction::new_op(InstructionKind::Fma, 0).with_dest(RegId(2)).with_srcs(vec![
+                RegId(0),
+                RegId(1),
+            ]),
+            Instruction::new_memory(InstructionKind::Store, 0x80, 0).with_value(7),
+            Instruction::new_memory(InstructionKind::AtomicRmw, 0x40, 0),
+            Instruction::new_branch(0x400, true),
+            Instruction::new_control(InstructionKind::Barrier {
+                id: 1,
+                participants: 2,
+            }),
+            Instruction::new_control(InstructionKind::Spawn { child: ThreadId(3) }),
+            Instruction::new_control(InstructionKind::LockRelease { lock_id: 9 }),
+            Instruction::new_control(InstructionKind::DmaStart {
+                src: 0x1000,
+                dst: 0x2000,
+                bytes: 256,
+            }),
+            Instruction::new_control(InstructionKind::MatrixOp { m: 8, n: 4, k: 2 }),
+            Instruction::new_control(InstructionKind::Sleep { cycles: 50 }),
+            Instruction::new_control(InstructionKind::Fence),
+        ]);
+        type Summary = (InstructionKind, u64, Option<RegId>, Vec<RegId>, Option<u64>);
+        let summary = |w: &[Vec<Instruction>]| -> Vec<Vec<Summary>> {
+            w.iter()
+                .map(|t| {
+                    t.iter()
+                        .map(|i| (i.kind, i.address, i.dest, i.srcs.clone(), i.value))
+                        .collect()
+                })
+                .collect()
+        };
+        // The chase's loads each read the register the previous one wrote.
+        assert!(workload[0].iter().filter(|i| i.srcs == [CHASE_REG]).count() > 100);
+
+        let mut csv = Vec::new();
+        to_csv(&workload, &mut csv).unwrap();
+        assert_eq!(summary(&from_csv_reader(csv.as_slice()).unwrap()), summary(&workload));
+        let mut bytes = Vec::new();
+        write_binary(&mut bytes, &workload).unwrap();
+        assert_eq!(summary(&read_binary(bytes.as_slice()).unwrap()), summary(&workload));
+    }
+
+    #[test]
+    fn workload_binary_trace_rejects_a_corrupted_header() {
+        let mut bytes = Vec::new();
+        write_binary(&mut bytes, &[vec![Instruction::new_compute(0)]]).unwrap();
+        let error = |bytes: &[u8]| BinaryTraceReader::new(bytes).err().unwrap().to_string();
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert!(error(&bad_magic).starts_with("bad binary trace: bad magic"));
+        let mut bad_version = bytes.clone();
+        bad_version[4] = 9;
+        assert_eq!(error(&bad_version), "bad binary trace: unsupported version 9");
+        // Version 1 records are the same for the loads, stores and computes it could hold.
+        let mut version_1 = bytes.clone();
+        version_1[4] = 1;
+        assert_eq!(read_binary(version_1.as_slice()).unwrap()[0].len(), 1);
+        assert_eq!(error(&bytes[..3]), "bad binary trace: truncated header");
+        // A thread count far past MAX_TRACE_THREADS: rejected, not allocated.
+        let mut huge = bytes[..5].to_vec();
+        write_varint(&mut huge, u64::MAX).unwrap();
+        assert_eq!(error(&huge), format!("bad binary trace: thread count {}", u64::MAX));
+        assert!(read_binary(huge.as_slice()).is_err());
+
+        // A record naming a thread the header does not have.
+        let mut bad_record = bytes.clone();
+        bad_record[6] = 5;
+        let records: Vec<_> = BinaryTraceReader::new(bad_record.as_slice()).unwrap().collect();
+        assert_eq!(records.len(), 1);
+        assert!(records[0].is_err());
+
+        let mut bad_kind = bytes.clone();
+        bad_kind[7] = KIND_CODE_MASK;
+        let records: Vec<_> = BinaryTraceReader::new(bad_kind.as_slice()).unwrap().collect();
+        let message = records[0].as_ref().unwrap_err().to_string();
+        assert_eq!(message, "bad binary trace: unknown kind byte 31");
+    }
 
     #[test]
     fn workload_sequential_count() {
@@ -138,6 +1520,7 @@ mod tests {
             line_size: 64,
             cache_num_sets: 64,
             working_set_lines: 0,
+            ..WorkloadConfig::default()
         };
         let mut gen = WorkloadGenerator::new(config);
         let mut count = 0;
@@ -147,6 +1530,108 @@ mod tests {
         assert_eq!(count, 10);
     }
 
+    #[test]
+    fn workload_chains_read_the_previous_compute() {
+        let config = WorkloadConfig {
+            instructions_per_thread: 6,
+            memory_fraction: 0.0,
+            dependency_chain_length: 3,
+            ..WorkloadConfig::default()
+        };
+        let instrs = build_workload(1, config).remove(0);
+        let reads: Vec<usize> = instrs.iter().map(|i| i.srcs.len()).collect();
+        assert_eq!(reads, vec![0, 1, 1, 0, 1, 1]);
+        assert!(instrs.iter().all(|i| i.dest == Some(RegId(0))));
+    }
+
+    #[test]
+    fn workload_fences_every_n_memory_ops() {
+        let config = WorkloadConfig {
+            instructions_per_thread: 10,
+            memory_fraction: 1.0,
+            fence_interval: 3,
+            ..WorkloadConfig::default()
+        };
+        let workload = build_workload(1, config);
+        let fences: Vec<usize> = workload[0]
+            .iter()
+            .enumerate()
+            .filter(|(_, i)| i.kind == InstructionKind::Fence)
+            .map(|(pos, _)| pos)
+            .collect();
+        assert_eq!(fences, vec![3, 7, 11]);
+        assert_eq!(workload[0].len(), 13);
+    }
+
+    #[test]
+    fn workload_phases_generate_their_own_counts_and_patterns() {
+        let phase = |instructions, memory_fraction, access_pattern| PhaseConfig {
+            instructions,
+            memory_fraction,
+            access_pattern,
+            reset_addresses: true,
+        };
+        let strided = AccessPattern::Strided {
+            stride_bytes: 256,
+            footprint_bytes: 0,
+        };
+        let config = WorkloadConfig {
+            phases: vec![
+                phase(1000, 0.2, AccessPattern::Sequential),
+                phase(600, 0.5, strided),
+            ],
+            ..WorkloadConfig::default()
+        };
+        let instrs = build_workload(1, config).remove(0);
+        assert_eq!(instrs.len(), 1600);
+        let memory = |instrs: &[Instruction]| -> Vec<u64> {
+            instrs.iter().filter(|i| i.is_memory_op()).map(|i| i.address).collect()
+        };
+        let (first, second) = instrs.split_at(1000);
+        assert_eq!(memory(first).len(), 200);
+        assert_eq!(memory(second).len(), 300);
+        let expected: Vec<u64> = (0..300).map(|k| k * 256).collect();
+        assert_eq!(memory(second), expected);
+    }
+
+    #[test]
+    fn workload_phases_continue_or_reset_the_address_cursor() {
+        let addresses = |reset_addresses| {
+            let phase = PhaseConfig {
+                instructions: 4,
+                memory_fraction: 1.0,
+                access_pattern: AccessPattern::Sequential,
+                reset_addresses,
+            };
+            let config = WorkloadConfig {
+                phases: vec![phase; 2],
+                ..WorkloadConfig::default()
+            };
+            let instrs = build_workload(1, config).remove(0);
+            instrs.iter().map(|i| i.address / 64).collect::<Vec<_>>()
+        };
+        assert_eq!(addresses(false), vec![0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(addresses(true), vec![0, 1, 2, 3, 0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn workload_inserts_numbered_barriers() {
+        let config = WorkloadConfig {
+            instructions_per_thread: 10,
+            barrier_interval: 4,
+            ..WorkloadConfig::default()
+        };
+        let workload = build_workload(3, config);
+        let barriers: Vec<(usize, InstructionKind)> = workload[0]
+            .iter()
+            .enumerate()
+            .filter(|(_, i)| matches!(i.kind, InstructionKind::Barrier { .. }))
+            .map(|(pos, i)| (pos, i.kind))
+            .collect();
+        let barrier = |id| InstructionKind::Barrier { id, participants: 3 };
+        assert_eq!(barriers, vec![(4, barrier(0)), (9, barrier(1))]);
+    }
+
     #[test]
     fn workload_conflict_addresses_same_set() {
         let config = WorkloadConfig {
@@ -156,6 +1641,7 @@ mod tests {
             line_size: 64,
             cache_num_sets: 4,
             working_set_lines: 0,
+            ..WorkloadConfig::default()
         };
         let mut gen = WorkloadGenerator::new(config);
         let mut addrs = Vec::new();
@@ -169,4 +1655,28 @@ mod tests {
         // With conflict-heavy, addresses should repeat set indices (many map to set 0,1,2,3).
         assert!(!addrs.is_empty());
     }
+
+    #[test]
+    fn workload_shared_footprint_counts_common_lines() {
+        let config = WorkloadConfig {
+            memory_fraction: 1.0,
+            working_set_lines: 32,
+            ..WorkloadConfig::default()
+        };
+        let mut workloads = build_workload(2, config.clone());
+        workloads.extend(build_workload(
+            1,
+            WorkloadConfig {
+                base_address: 1 << 20,
+                ..config
+            },
+        ));
+        let matrix = shared_footprint(&workloads, 64);
+        assert_eq!(matrix[0][1], 32);
+        assert_eq!(matrix[1][0], 32);
+        assert_eq!(matrix[0][2], 0);
+        assert_eq!(matrix[2][2], 32);
+        // Lines need not be a power of two: 0..=1984 spans 21 lines of 96 bytes.
+        assert_eq!(shared_footprint(&workloads, 96)[0][0], 21);
+    }
 }
@@ -0,0 +1,137 @@
+//! Per-core store write buffer: store misses retire into the buffer and drain to memory later.
+
+use std::collections::VecDeque;
+
+/// Memory ordering a core enforces (see `Simulator::set_consistency`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConsistencyModel {
+    /// No ordering between a thread's memory operations; a write buffer, if set, is used.
+    #[default]
+    Relaxed,
+    /// Sequential consistency: no write buffer, and a memory operation waits for the thread's
+    /// older stores to complete.
+    Sc,
+    /// Total store order: stores retire into the write buffer and drain in order while younger
+    /// loads go ahead; `InstructionKind::Fence` waits for the buffer to drain.
+    Tso,
+}
+
+/// Configuration for a core's write buffer.
+#[derive(Clone, Debug)]
+pub struct WriteBufferConfig {
+    /// Maximum number of buffered store misses.
+    pub depth: usize,
+    /// One entry drains to memory every `drain_interval` cycles while the buffer is non-empty.
+    pub drain_interval: u32,
+}
+
+impl Default for WriteBufferConfig {
+    fn default() -> Self {
+        Self {
+            depth: 8,
+            drain_interval: 1,
+        }
+    }
+}
+
+/// FIFO of line addresses waiting to be written to memory.
+#[derive(Clone, Debug)]
+pub struct WriteBuffer {
+    config: WriteBufferConfig,
+    entries: VecDeque<u64>,
+    /// Cycles until the head entry drains.
+    drain_countdown: u32,
+}
+
+impl WriteBuffer {
+    pub fn new(config: WriteBufferConfig) -> Self {
+        let drain_countdown = config.drain_interval;
+        Self {
+            config,
+            entries: VecDeque::new(),
+            drain_countdown,
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.entries.len() >= self.config.depth
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Whether a store to `line_addr` is still waiting in the buffer.
+    pub fn contains(&self, line_addr: u64) -> bool {
+        self.entries.contains(&line_addr)
+    }
+
+    /// Buffers a store miss. Returns false (and buffers nothing) if the buffer is full.
+    pub fn push(&mut self, line_addr: u64) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        if self.entries.is_empty() {
+            self.drain_countdown = self.config.drain_interval;
+        }
+        self.entries.push_back(line_addr);
+        true
+    }
+
+    /// Advances one cycle. Returns the line address written to memory this cycle, if any.
+    pub fn tick(&mut self) -> Option<u64> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        self.drain_countdown = self.drain_countdown.saturating_sub(1);
+        if self.drain_countdown > 0 {
+            return None;
+        }
+        self.drain_countdown = self.config.drain_interval;
+        self.entries.pop_front()
+    }
+
+    pub fn config(&self) -> &WriteBufferConfig {
+        &self.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_buffer_fills_and_forwards() {
+        let mut wb = WriteBuffer::new(WriteBufferConfig {
+            depth: 2,
+            drain_interval: 1,
+        });
+        assert!(wb.push(10));
+        assert!(wb.push(11));
+        assert!(wb.is_full());
+        assert!(!wb.push(12));
+        assert!(wb.contains(11));
+        assert!(!wb.contains(12));
+    }
+
+    #[test]
+    fn write_buffer_drains_every_interval() {
+        let mut wb = WriteBuffer::new(WriteBufferConfig {
+            depth: 4,
+            drain_interval: 3,
+        });
+        wb.push(1);
+        wb.push(2);
+        assert_eq!(wb.tick(), None);
+        assert_eq!(wb.tick(), None);
+        assert_eq!(wb.tick(), Some(1));
+        assert_eq!(wb.tick(), None);
+        assert_eq!(wb.tick(), None);
+        assert_eq!(wb.tick(), Some(2));
+        assert!(wb.is_empty());
+    }
+}